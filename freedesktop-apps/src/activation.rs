@@ -0,0 +1,156 @@
+//! D-Bus activation of entries with DBusActivatable=true
+//!
+//! Such applications are started by calling `org.freedesktop.Application` on the
+//! session bus, using the desktop file ID as the bus name. The bus starts the
+//! application if it isn't running yet.
+
+use std::collections::HashMap;
+
+use freedesktop_core::call::{CallError, CallOptions};
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+use zbus::Proxy;
+
+use crate::{ApplicationEntry, ExecuteError, LaunchOptions, Launched, SpawnLauncher};
+
+const INTERFACE: &str = "org.freedesktop.Application";
+
+impl ApplicationEntry {
+    /// Start the application, opening the given URIs
+    ///
+    /// DBusActivatable entries are activated over D-Bus first; if that fails, or the
+    /// entry isn't DBusActivatable, its Exec line is run with the URIs as `%u`/`%U`.
+    /// Returns `None` when the application was activated over D-Bus, since no
+    /// process was spawned. An activation token from the options, or set as
+    /// `XDG_ACTIVATION_TOKEN` or `DESKTOP_STARTUP_ID` in their environment, is
+    /// passed along in either case. Cancelling the options'
+    /// [`call_options`](LaunchOptions::call_options) stops without running Exec.
+    pub fn activate_with(&self, uris: &[&str], options: &LaunchOptions) -> Result<Option<Launched>, ExecuteError> {
+        match options.session_bus_connection() {
+            Ok(connection) => self.activate_with_connection(&connection, uris, options),
+            Err(_) => self.launch_using(&[], uris, options, &SpawnLauncher).map(Some),
+        }
+    }
+
+    /// Like [`ApplicationEntry::activate_with`], using an existing session bus connection
+    pub fn activate_with_connection(
+        &self,
+        connection: &Connection,
+        uris: &[&str],
+        options: &LaunchOptions,
+    ) -> Result<Option<Launched>, ExecuteError> {
+        let options = &*options.with_activation_token(self);
+        match self.dbus_activate(connection, uris, options) {
+            Ok(()) => return Ok(None),
+            Err(ExecuteError::Cancelled) => return Err(ExecuteError::Cancelled),
+            Err(_) => {}
+        }
+
+        self.launch_using(&[], uris, options, &SpawnLauncher).map(Some)
+    }
+
+    /// Activate one of the entry's desktop actions over D-Bus
+    ///
+    /// Only works for DBusActivatable entries; there is no Exec fallback.
+    pub fn activate_action(&self, action: &str, options: &LaunchOptions) -> Result<(), ExecuteError> {
+        if self.dbus_activation_id().is_none() {
+            return Err(not_activatable());
+        }
+        let connection = options.session_bus_connection().map_err(|e| ExecuteError::ActivationFailed(e.to_string()))?;
+        self.activate_action_with_connection(&connection, action, options)
+    }
+
+    /// Like [`ApplicationEntry::activate_action`], using an existing session bus connection
+    pub fn activate_action_with_connection(
+        &self,
+        connection: &Connection,
+        action: &str,
+        options: &LaunchOptions,
+    ) -> Result<(), ExecuteError> {
+        let id = self.dbus_activation_id().ok_or_else(not_activatable)?;
+        let options = &*options.with_activation_token(self);
+        let parameters: Vec<Value> = Vec::new();
+
+        call(
+            connection,
+            options.call_options_ref(),
+            &id,
+            "ActivateAction",
+            &(action, parameters, platform_data(options)),
+        )
+    }
+
+    /// Call `Open` with the URIs, or `Activate` without any
+    pub(crate) fn dbus_activate(
+        &self,
+        connection: &Connection,
+        uris: &[&str],
+        options: &LaunchOptions,
+    ) -> Result<(), ExecuteError> {
+        let id = self.dbus_activation_id().ok_or_else(not_activatable)?;
+        let platform_data = platform_data(options);
+        let call_options = options.call_options_ref();
+        if uris.is_empty() {
+            call(connection, call_options, &id, "Activate", &(platform_data,))
+        } else {
+            call(connection, call_options, &id, "Open", &(uris, platform_data))
+        }
+    }
+
+    fn dbus_activation_id(&self) -> Option<String> {
+        if self.dbus_activatable() {
+            self.id()
+        } else {
+            None
+        }
+    }
+}
+
+impl From<CallError> for ExecuteError {
+    fn from(e: CallError) -> Self {
+        match e {
+            CallError::DBus(e) => ExecuteError::ActivationFailed(e.to_string()),
+            CallError::Timeout(timeout) => ExecuteError::Timeout(timeout),
+            CallError::Cancelled => ExecuteError::Cancelled,
+        }
+    }
+}
+
+impl From<zbus::Error> for ExecuteError {
+    fn from(e: zbus::Error) -> Self {
+        ExecuteError::ActivationFailed(e.to_string())
+    }
+}
+
+fn not_activatable() -> ExecuteError {
+    ExecuteError::NotExecutable("Entry is not D-Bus activatable".to_string())
+}
+
+/// Call a method on the application's `org.freedesktop.Application` object
+fn call<B>(connection: &Connection, options: &CallOptions, id: &str, method: &str, body: &B) -> Result<(), ExecuteError>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    let path = object_path(id);
+    options.block_on(async {
+        let proxy = Proxy::new(connection.inner(), id, path.as_str(), INTERFACE).await?;
+        proxy.call_method(method, body).await?;
+        Ok(())
+    })
+}
+
+/// Build the object path for a desktop file ID, `org.example.App` → `/org/example/App`
+fn object_path(id: &str) -> String {
+    format!("/{}", id.replace('.', "/").replace('-', "_"))
+}
+
+fn platform_data(options: &LaunchOptions) -> HashMap<&'static str, Value<'_>> {
+    let mut data = HashMap::new();
+    if let Some(token) = options.env_value("XDG_ACTIVATION_TOKEN") {
+        data.insert("activation-token", Value::from(token));
+    }
+    if let Some(id) = options.env_value("DESKTOP_STARTUP_ID") {
+        data.insert("desktop-startup-id", Value::from(id));
+    }
+    data
+}
@@ -0,0 +1,110 @@
+//! Mapping between AppStream component IDs and desktop file IDs
+//!
+//! AppStream `desktop-application` components reference their desktop file through
+//! a `<launchable type="desktop-id">` element. Older metadata instead used the desktop
+//! file name (including the `.desktop` suffix) as the component ID itself. The helpers
+//! here implement both conventions so stores and launchers can agree on identities.
+//!
+//! Desktop file IDs are handled in the same form as [`ApplicationEntry::id`], without
+//! the `.desktop` suffix. Inputs are accepted with or without it.
+
+use std::path::Path;
+
+use crate::ApplicationEntry;
+
+const DESKTOP_SUFFIX: &str = ".desktop";
+
+/// Strip a trailing `.desktop` from an identifier, if present
+fn strip_desktop_suffix(id: &str) -> &str {
+    id.strip_suffix(DESKTOP_SUFFIX).unwrap_or(id)
+}
+
+/// Get the desktop file ID a component launches when no explicit launchable is given
+///
+/// Legacy component IDs such as `firefox.desktop` map to `firefox`, and modern
+/// reverse-DNS IDs such as `org.gnome.Nautilus` map to the desktop file of the same name.
+pub fn desktop_id_for_component(component_id: &str) -> String {
+    strip_desktop_suffix(component_id.trim()).to_string()
+}
+
+/// Get the component IDs that may describe the given desktop file ID
+///
+/// The modern form (the desktop ID itself) comes first, followed by the legacy
+/// form carrying the `.desktop` suffix.
+pub fn component_id_candidates(desktop_id: &str) -> Vec<String> {
+    let base = strip_desktop_suffix(desktop_id.trim());
+    vec![base.to_string(), format!("{}{}", base, DESKTOP_SUFFIX)]
+}
+
+/// Check whether a component ID and a desktop file ID refer to the same application
+pub fn component_matches_desktop_id(component_id: &str, desktop_id: &str) -> bool {
+    desktop_id_for_component(component_id) == strip_desktop_suffix(desktop_id.trim())
+}
+
+/// Extract the desktop IDs declared as `<launchable type="desktop-id">` in metainfo XML
+///
+/// This is a lightweight scan rather than a full XML parser, which is sufficient for
+/// the simple, attribute-only structure of launchable elements.
+pub fn launchable_desktop_ids(metainfo: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = metainfo;
+
+    while let Some(start) = rest.find("<launchable") {
+        rest = &rest[start + "<launchable".len()..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let attributes = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+
+        let is_desktop_id = attributes.contains("type=\"desktop-id\"")
+            || attributes.contains("type='desktop-id'");
+        let Some(close) = rest.find("</launchable>") else {
+            break;
+        };
+        if is_desktop_id {
+            let value = rest[..close].trim();
+            if !value.is_empty() {
+                ids.push(strip_desktop_suffix(value).to_string());
+            }
+        }
+        rest = &rest[close..];
+    }
+
+    ids
+}
+
+/// Read a metainfo file and return the desktop IDs it declares as launchables
+///
+/// Falls back to the component ID mapping when the file has no launchable elements.
+pub fn desktop_ids_from_metainfo<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)?;
+    let launchables = launchable_desktop_ids(&content);
+    if !launchables.is_empty() {
+        return Ok(launchables);
+    }
+
+    let component_id = content.find("<id>").and_then(|start| {
+        let after = &content[start + "<id>".len()..];
+        after.find("</id>").map(|end| after[..end].trim().to_string())
+    });
+
+    Ok(component_id
+        .map(|id| vec![desktop_id_for_component(&id)])
+        .unwrap_or_default())
+}
+
+impl ApplicationEntry {
+    /// Get the AppStream component IDs that may describe this entry
+    pub fn appstream_component_ids(&self) -> Vec<String> {
+        self.id()
+            .map(|id| component_id_candidates(&id))
+            .unwrap_or_default()
+    }
+
+    /// Check whether this entry is the launchable of the given AppStream component
+    pub fn matches_component_id(&self, component_id: &str) -> bool {
+        self.id()
+            .is_some_and(|id| component_matches_desktop_id(component_id, &id))
+    }
+}
@@ -0,0 +1,133 @@
+//! Opt-in log of every launch performed through the crate
+//!
+//! Once enabled with [`AuditLog::enable`], each launch appends one tab-separated line:
+//! the RFC 3339 timestamp, the desktop file ID, `ok` or the error, the shell-escaped
+//! command line and the launch ID from [`LaunchOptions::launch_id`](crate::LaunchOptions::launch_id),
+//! empty if there is none. The file is rotated to `<name>.1`, `<name>.2`, ... once it
+//! grows past the configured size.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use freedesktop_core::time::Timestamp;
+
+use crate::{shell_escape, ApplicationEntry, ExecuteError};
+
+static AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+/// Configuration of the launch audit log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl AuditLog {
+    /// Log to the given file, rotating at 1 MiB and keeping 5 old files
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        AuditLog {
+            path: path.into(),
+            max_size: 1024 * 1024,
+            max_files: 5,
+        }
+    }
+
+    /// Default location of the log, `$XDG_STATE_HOME/freedesktop/launch.log`
+    pub fn default_path() -> Option<PathBuf> {
+        freedesktop_core::state_home().map(|state| state.join("freedesktop").join("launch.log"))
+    }
+
+    /// Size in bytes after which the log is rotated
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Number of rotated files to keep; 0 discards the log on rotation
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Get the path of the current log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start logging every launch to this log, replacing any previously enabled one
+    pub fn enable(self) {
+        *AUDIT_LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(self);
+    }
+
+    /// Stop logging launches
+    pub fn disable() {
+        *AUDIT_LOG.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Get the currently enabled log, if any
+    pub fn current() -> Option<AuditLog> {
+        AUDIT_LOG.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Append a line to the log, rotating first if it is too large
+    fn append(&self, line: &str) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if std::fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_size) {
+            self.rotate()?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", line)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+
+        let _ = std::fs::remove_file(self.rotated_path(self.max_files));
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                std::fs::rename(from, self.rotated_path(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+}
+
+/// Record a launch in the enabled audit log, if any
+///
+/// Failing to write the log never fails the launch itself.
+pub(crate) fn record(entry: &ApplicationEntry, argv: &[String], launch_id: Option<&str>, result: &Result<(), ExecuteError>) {
+    let Some(log) = AuditLog::current() else {
+        return;
+    };
+
+    let id = entry
+        .id()
+        .unwrap_or_else(|| entry.path().to_string_lossy().to_string());
+    let outcome = match result {
+        Ok(()) => "ok".to_string(),
+        Err(e) => format!("error: {}", e),
+    };
+    let command: Vec<String> = argv.iter().map(|arg| shell_escape(arg)).collect();
+
+    let launch_id = launch_id.unwrap_or_default().to_string();
+    let line = [Timestamp::now().to_string(), id, outcome, command.join(" "), launch_id]
+        .map(|field| field.replace(['\t', '\n'], " "))
+        .join("\t");
+    let _ = log.append(&line);
+}
@@ -0,0 +1,95 @@
+//! Autostart entries and systemd's xdg-autostart-generator
+//!
+//! On systemd-managed sessions, `systemd-xdg-autostart-generator` turns autostart
+//! entries into `app-<id>@autostart.service` units. A session manager that also
+//! launches autostart entries itself has to leave those alone, or the
+//! applications start twice. [`AutostartHandling`] mirrors the generator's rules
+//! so both sides agree on who starts what.
+
+use std::path::PathBuf;
+
+use crate::{which, ApplicationEntry, EntryType};
+
+/// Who starts an autostart entry in a systemd-managed session
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AutostartHandling {
+    /// The generator creates a service for it
+    ///
+    /// OnlyShowIn, NotShowIn and the autostart condition keys are checked by the
+    /// service when it starts, so the entry may still not run in every session.
+    Generated,
+    /// The generator skips it because of X-systemd-skip=true; the session starts it itself
+    SystemdSkip,
+    /// gnome-session starts it in this X-GNOME-Autostart-Phase
+    SessionPhase(String),
+    /// A systemd user unit replaces it (X-GNOME-HiddenUnderSystemd=true)
+    ReplacedByUnit,
+    /// Nobody starts it: it is hidden, disabled, not an application, has no Exec,
+    /// or its TryExec program is missing
+    Disabled,
+}
+
+impl AutostartHandling {
+    /// Check if the session has to launch the entry because systemd won't
+    pub fn needs_manual_launch(&self) -> bool {
+        matches!(self, AutostartHandling::SystemdSkip | AutostartHandling::SessionPhase(_))
+    }
+}
+
+/// Get the autostart directories, most important first
+///
+/// `$XDG_CONFIG_HOME/autostart` followed by `autostart` in each XDG_CONFIG_DIRS entry.
+pub fn autostart_dirs() -> Vec<PathBuf> {
+    freedesktop_core::config_home()
+        .into_iter()
+        .chain(freedesktop_core::config_dirs())
+        .map(|dir| dir.join("autostart"))
+        .collect()
+}
+
+impl ApplicationEntry {
+    /// Get the autostart entries of the session
+    ///
+    /// A file in a more important directory overrides files with the same name in
+    /// the others, so users disable an entry with a Hidden=true copy in their
+    /// config directory. Hidden entries are left out.
+    pub fn autostart() -> Vec<ApplicationEntry> {
+        Self::all_in(&autostart_dirs())
+    }
+
+    /// Get how systemd's xdg-autostart-generator treats this autostart entry
+    pub fn autostart_handling(&self) -> AutostartHandling {
+        let disabled = self.is_hidden()
+            || self.entry_type() != Some(EntryType::Application)
+            || self.exec().is_none_or(|exec| exec.trim().is_empty())
+            || self.get_bool("X-GNOME-Autostart-enabled") == Some(false);
+        if disabled {
+            return AutostartHandling::Disabled;
+        }
+        if self.get_bool("X-systemd-skip").unwrap_or(false) {
+            return AutostartHandling::SystemdSkip;
+        }
+        if self.get_bool("X-GNOME-HiddenUnderSystemd").unwrap_or(false) {
+            return AutostartHandling::ReplacedByUnit;
+        }
+        if let Some(phase) = self.get_string("X-GNOME-Autostart-Phase").filter(|phase| !phase.is_empty()) {
+            return AutostartHandling::SessionPhase(phase);
+        }
+        // The generator checks TryExec when it runs, not when the service starts
+        if self.get_string("TryExec").is_some_and(|try_exec| which(&try_exec).is_none()) {
+            return AutostartHandling::Disabled;
+        }
+        AutostartHandling::Generated
+    }
+}
+
+/// Get the session's autostart entries with how systemd handles each
+pub fn autostart_report() -> Vec<(ApplicationEntry, AutostartHandling)> {
+    ApplicationEntry::autostart()
+        .into_iter()
+        .map(|entry| {
+            let handling = entry.autostart_handling();
+            (entry, handling)
+        })
+        .collect()
+}
@@ -0,0 +1,496 @@
+use std::fmt;
+
+use crate::{data_dirs_by_precedence, ApplicationEntry, DirectoryEntry};
+
+macro_rules! categories {
+    (
+        main { $($main:ident = $main_name:literal,)* }
+        additional { $($extra:ident = $extra_name:literal,)* }
+    ) => {
+        /// A category from the Desktop Menu Specification's registry
+        ///
+        /// Values not in the registry (including `X-` extensions) are kept as [`Category::Other`].
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum Category {
+            $($main,)*
+            $($extra,)*
+            Other(String),
+        }
+
+        impl Category {
+            /// Get the category as it appears in the Categories key
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Category::$main => $main_name,)*
+                    $(Category::$extra => $extra_name,)*
+                    Category::Other(s) => s,
+                }
+            }
+
+            /// Check whether this is one of the registered Main Categories
+            pub fn is_main(&self) -> bool {
+                matches!(self, $(Category::$main)|*)
+            }
+        }
+
+        impl From<&str> for Category {
+            fn from(value: &str) -> Self {
+                match value {
+                    $($main_name => Category::$main,)*
+                    $($extra_name => Category::$extra,)*
+                    other => Category::Other(other.to_string()),
+                }
+            }
+        }
+    };
+}
+
+categories! {
+    main {
+        AudioVideo = "AudioVideo",
+        Audio = "Audio",
+        Video = "Video",
+        Development = "Development",
+        Education = "Education",
+        Game = "Game",
+        Graphics = "Graphics",
+        Network = "Network",
+        Office = "Office",
+        Science = "Science",
+        Settings = "Settings",
+        System = "System",
+        Utility = "Utility",
+    }
+    additional {
+        Building = "Building",
+        Debugger = "Debugger",
+        IDE = "IDE",
+        GUIDesigner = "GUIDesigner",
+        Profiling = "Profiling",
+        RevisionControl = "RevisionControl",
+        Translation = "Translation",
+        Calendar = "Calendar",
+        ContactManagement = "ContactManagement",
+        Database = "Database",
+        Dictionary = "Dictionary",
+        Chart = "Chart",
+        Email = "Email",
+        Finance = "Finance",
+        FlowChart = "FlowChart",
+        PDA = "PDA",
+        ProjectManagement = "ProjectManagement",
+        Presentation = "Presentation",
+        Spreadsheet = "Spreadsheet",
+        WordProcessor = "WordProcessor",
+        Graphics2D = "2DGraphics",
+        VectorGraphics = "VectorGraphics",
+        RasterGraphics = "RasterGraphics",
+        Graphics3D = "3DGraphics",
+        Scanning = "Scanning",
+        OCR = "OCR",
+        Photography = "Photography",
+        Publishing = "Publishing",
+        Viewer = "Viewer",
+        TextTools = "TextTools",
+        DesktopSettings = "DesktopSettings",
+        HardwareSettings = "HardwareSettings",
+        Printing = "Printing",
+        PackageManager = "PackageManager",
+        Dialup = "Dialup",
+        InstantMessaging = "InstantMessaging",
+        Chat = "Chat",
+        IRCClient = "IRCClient",
+        Feed = "Feed",
+        FileTransfer = "FileTransfer",
+        HamRadio = "HamRadio",
+        News = "News",
+        P2P = "P2P",
+        RemoteAccess = "RemoteAccess",
+        Telephony = "Telephony",
+        TelephonyTools = "TelephonyTools",
+        VideoConference = "VideoConference",
+        WebBrowser = "WebBrowser",
+        WebDevelopment = "WebDevelopment",
+        Midi = "Midi",
+        Mixer = "Mixer",
+        Sequencer = "Sequencer",
+        Tuner = "Tuner",
+        TV = "TV",
+        AudioVideoEditing = "AudioVideoEditing",
+        Player = "Player",
+        Recorder = "Recorder",
+        DiscBurning = "DiscBurning",
+        ActionGame = "ActionGame",
+        AdventureGame = "AdventureGame",
+        ArcadeGame = "ArcadeGame",
+        BoardGame = "BoardGame",
+        BlocksGame = "BlocksGame",
+        CardGame = "CardGame",
+        KidsGame = "KidsGame",
+        LogicGame = "LogicGame",
+        RolePlaying = "RolePlaying",
+        Shooter = "Shooter",
+        Simulation = "Simulation",
+        SportsGame = "SportsGame",
+        StrategyGame = "StrategyGame",
+        Art = "Art",
+        Construction = "Construction",
+        Music = "Music",
+        Languages = "Languages",
+        ArtificialIntelligence = "ArtificialIntelligence",
+        Astronomy = "Astronomy",
+        Biology = "Biology",
+        Chemistry = "Chemistry",
+        ComputerScience = "ComputerScience",
+        DataVisualization = "DataVisualization",
+        Economy = "Economy",
+        Electricity = "Electricity",
+        Geography = "Geography",
+        Geology = "Geology",
+        Geoscience = "Geoscience",
+        History = "History",
+        Humanities = "Humanities",
+        ImageProcessing = "ImageProcessing",
+        Literature = "Literature",
+        Maps = "Maps",
+        Math = "Math",
+        NumericalAnalysis = "NumericalAnalysis",
+        MedicalSoftware = "MedicalSoftware",
+        Physics = "Physics",
+        Robotics = "Robotics",
+        Spirituality = "Spirituality",
+        Sports = "Sports",
+        ParallelComputing = "ParallelComputing",
+        Amusement = "Amusement",
+        Archiving = "Archiving",
+        Compression = "Compression",
+        Electronics = "Electronics",
+        Emulator = "Emulator",
+        Engineering = "Engineering",
+        FileTools = "FileTools",
+        FileManager = "FileManager",
+        TerminalEmulator = "TerminalEmulator",
+        Filesystem = "Filesystem",
+        Monitor = "Monitor",
+        Security = "Security",
+        Accessibility = "Accessibility",
+        Calculator = "Calculator",
+        Clock = "Clock",
+        TextEditor = "TextEditor",
+        Documentation = "Documentation",
+        Adult = "Adult",
+        Core = "Core",
+        KDE = "KDE",
+        GNOME = "GNOME",
+        XFCE = "XFCE",
+        DDE = "DDE",
+        GTK = "GTK",
+        Qt = "Qt",
+        Motif = "Motif",
+        Java = "Java",
+        ConsoleOnly = "ConsoleOnly",
+        Screensaver = "Screensaver",
+        TrayIcon = "TrayIcon",
+        Applet = "Applet",
+        Shell = "Shell",
+    }
+}
+
+impl Category {
+    /// Check whether this is a Reserved Category, which may only be used together with OnlyShowIn
+    pub fn is_reserved(&self) -> bool {
+        matches!(
+            self,
+            Category::Screensaver | Category::TrayIcon | Category::Applet | Category::Shell
+        )
+    }
+}
+
+/// A top-level section of an application menu, as laid out by the menu spec's default menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MenuSection {
+    Accessories,
+    Development,
+    Education,
+    Games,
+    Graphics,
+    Internet,
+    Multimedia,
+    Office,
+    Science,
+    Settings,
+    System,
+    Other,
+}
+
+impl MenuSection {
+    /// Get the section's (untranslated) display name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MenuSection::Accessories => "Accessories",
+            MenuSection::Development => "Development",
+            MenuSection::Education => "Education",
+            MenuSection::Games => "Games",
+            MenuSection::Graphics => "Graphics",
+            MenuSection::Internet => "Internet",
+            MenuSection::Multimedia => "Multimedia",
+            MenuSection::Office => "Office",
+            MenuSection::Science => "Science",
+            MenuSection::Settings => "Settings",
+            MenuSection::System => "System",
+            MenuSection::Other => "Other",
+        }
+    }
+
+    /// Get the `.directory` file the default menu layout uses for this section
+    pub fn directory_file(&self) -> Option<&'static str> {
+        let file = match self {
+            MenuSection::Accessories => "Utility.directory",
+            MenuSection::Development => "Development.directory",
+            MenuSection::Education => "Education.directory",
+            MenuSection::Games => "Game.directory",
+            MenuSection::Graphics => "Graphics.directory",
+            MenuSection::Internet => "Network.directory",
+            MenuSection::Multimedia => "AudioVideo.directory",
+            MenuSection::Office => "Office.directory",
+            MenuSection::Science => "Science.directory",
+            MenuSection::Settings => "Settings.directory",
+            MenuSection::System => "System-Tools.directory",
+            MenuSection::Other => return None,
+        };
+        Some(file)
+    }
+
+    /// Get the section's display name translated for `locale`, or the current locale if None
+    ///
+    /// The Name of the section's installed `.directory` file is preferred. Without one,
+    /// a built-in table covering common languages is used, falling back to [`MenuSection::as_str`].
+    pub fn localized_name(&self, locale: Option<&str>) -> String {
+        let locale = locale.map(str::to_string).or_else(freedesktop_core::message_locale);
+
+        let from_directory = self.directory_file().and_then(|file| {
+            data_dirs_by_precedence()
+                .iter()
+                .map(|dir| dir.join("desktop-directories").join(file))
+                .find(|path| path.is_file())
+                .and_then(|path| DirectoryEntry::try_from_path(path).ok())
+                .and_then(|entry| entry.get_localized_string("Name", locale.as_deref()))
+        });
+
+        from_directory
+            .or_else(|| locale.and_then(|locale| self.builtin_name(&locale)))
+            .unwrap_or_else(|| self.as_str().to_string())
+    }
+
+    /// Look up the built-in translation, trying `lang_COUNTRY` before `lang`
+    fn builtin_name(&self, locale: &str) -> Option<String> {
+        let locale = locale.split(['.', '@']).next().unwrap_or(locale);
+        let lang = locale.split('_').next().unwrap_or(locale);
+        let index = *self as usize;
+
+        [locale, lang].iter().find_map(|candidate| {
+            SECTION_NAMES
+                .iter()
+                .find(|(lang, _)| lang == candidate)
+                .map(|(_, names)| names[index].to_string())
+        })
+    }
+}
+
+impl fmt::Display for MenuSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Category {
+    /// Get the menu section a Main Category is shown in
+    pub fn menu_section(&self) -> Option<MenuSection> {
+        let section = match self {
+            Category::AudioVideo | Category::Audio | Category::Video => MenuSection::Multimedia,
+            Category::Development => MenuSection::Development,
+            Category::Education => MenuSection::Education,
+            Category::Game => MenuSection::Games,
+            Category::Graphics => MenuSection::Graphics,
+            Category::Network => MenuSection::Internet,
+            Category::Office => MenuSection::Office,
+            Category::Science => MenuSection::Science,
+            Category::Settings => MenuSection::Settings,
+            Category::System => MenuSection::System,
+            Category::Utility => MenuSection::Accessories,
+            _ => return None,
+        };
+        Some(section)
+    }
+
+    /// Get the Main Category an Additional Category is related to in the registry
+    pub fn related_main(&self) -> Option<Category> {
+        let main = match self {
+            Category::Building
+            | Category::Debugger
+            | Category::IDE
+            | Category::GUIDesigner
+            | Category::Profiling
+            | Category::RevisionControl
+            | Category::Translation
+            | Category::WebDevelopment => Category::Development,
+            Category::Calendar
+            | Category::ContactManagement
+            | Category::Database
+            | Category::Dictionary
+            | Category::Chart
+            | Category::Email
+            | Category::Finance
+            | Category::FlowChart
+            | Category::PDA
+            | Category::ProjectManagement
+            | Category::Presentation
+            | Category::Spreadsheet
+            | Category::WordProcessor
+            | Category::Publishing
+            | Category::Viewer => Category::Office,
+            Category::Graphics2D
+            | Category::VectorGraphics
+            | Category::RasterGraphics
+            | Category::Graphics3D
+            | Category::Scanning
+            | Category::OCR
+            | Category::Photography => Category::Graphics,
+            Category::DesktopSettings | Category::HardwareSettings | Category::Printing => Category::Settings,
+            Category::Dialup
+            | Category::InstantMessaging
+            | Category::Chat
+            | Category::IRCClient
+            | Category::Feed
+            | Category::FileTransfer
+            | Category::HamRadio
+            | Category::News
+            | Category::P2P
+            | Category::RemoteAccess
+            | Category::Telephony
+            | Category::VideoConference
+            | Category::WebBrowser => Category::Network,
+            Category::Midi
+            | Category::Mixer
+            | Category::Sequencer
+            | Category::Tuner
+            | Category::TV
+            | Category::AudioVideoEditing
+            | Category::Player
+            | Category::Recorder
+            | Category::DiscBurning
+            | Category::Music => Category::AudioVideo,
+            Category::ActionGame
+            | Category::AdventureGame
+            | Category::ArcadeGame
+            | Category::BoardGame
+            | Category::BlocksGame
+            | Category::CardGame
+            | Category::KidsGame
+            | Category::LogicGame
+            | Category::RolePlaying
+            | Category::Shooter
+            | Category::Simulation
+            | Category::SportsGame
+            | Category::StrategyGame => Category::Game,
+            Category::Art
+            | Category::Construction
+            | Category::Languages
+            | Category::History
+            | Category::Humanities
+            | Category::Literature
+            | Category::Spirituality
+            | Category::Sports => Category::Education,
+            Category::ArtificialIntelligence
+            | Category::Astronomy
+            | Category::Biology
+            | Category::Chemistry
+            | Category::ComputerScience
+            | Category::DataVisualization
+            | Category::Economy
+            | Category::Electricity
+            | Category::Geography
+            | Category::Geology
+            | Category::Geoscience
+            | Category::ImageProcessing
+            | Category::Math
+            | Category::NumericalAnalysis
+            | Category::MedicalSoftware
+            | Category::Physics
+            | Category::Robotics
+            | Category::ParallelComputing
+            | Category::Electronics
+            | Category::Engineering => Category::Science,
+            Category::PackageManager
+            | Category::Emulator
+            | Category::FileManager
+            | Category::TerminalEmulator
+            | Category::Filesystem
+            | Category::Monitor
+            | Category::Security => Category::System,
+            Category::TelephonyTools
+            | Category::TextTools
+            | Category::Archiving
+            | Category::Compression
+            | Category::FileTools
+            | Category::Accessibility
+            | Category::Calculator
+            | Category::Clock
+            | Category::TextEditor
+            | Category::Amusement => Category::Utility,
+            _ => return None,
+        };
+        Some(main)
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ApplicationEntry {
+    /// Get the categories as typed values
+    pub fn categories_typed(&self) -> Vec<Category> {
+        self.categories()
+            .unwrap_or_default()
+            .iter()
+            .map(|c| Category::from(c.as_str()))
+            .collect()
+    }
+
+    /// Get the top-level menu section this entry belongs in
+    ///
+    /// The first Main Category decides the section. Entries that only list Additional
+    /// Categories are placed by the first one with a related Main Category.
+    pub fn menu_section(&self) -> MenuSection {
+        let categories = self.categories_typed();
+
+        categories
+            .iter()
+            .find_map(Category::menu_section)
+            .or_else(|| {
+                categories
+                    .iter()
+                    .filter_map(Category::related_main)
+                    .find_map(|main| main.menu_section())
+            })
+            .unwrap_or(MenuSection::Other)
+    }
+}
+
+/// Built-in section names, in [`MenuSection`] declaration order
+const SECTION_NAMES: &[(&str, [&str; 12])] = &[
+    ("de", ["Zubehör", "Entwicklung", "Bildung", "Spiele", "Grafik", "Internet", "Multimedia", "Büro", "Wissenschaft", "Einstellungen", "System", "Sonstige"]),
+    ("es", ["Accesorios", "Desarrollo", "Educación", "Juegos", "Gráficos", "Internet", "Multimedia", "Oficina", "Ciencia", "Configuración", "Sistema", "Otros"]),
+    ("fr", ["Accessoires", "Développement", "Éducation", "Jeux", "Graphisme", "Internet", "Multimédia", "Bureautique", "Science", "Paramètres", "Système", "Autres"]),
+    ("it", ["Accessori", "Sviluppo", "Istruzione", "Giochi", "Grafica", "Internet", "Multimedia", "Ufficio", "Scienza", "Impostazioni", "Sistema", "Altro"]),
+    ("nl", ["Hulpmiddelen", "Programmeren", "Onderwijs", "Spellen", "Grafisch", "Internet", "Multimedia", "Kantoor", "Wetenschap", "Instellingen", "Systeem", "Overige"]),
+    ("pl", ["Akcesoria", "Programowanie", "Edukacja", "Gry", "Grafika", "Internet", "Multimedia", "Biuro", "Nauka", "Ustawienia", "System", "Inne"]),
+    ("pt", ["Acessórios", "Desenvolvimento", "Educação", "Jogos", "Gráficos", "Internet", "Multimédia", "Escritório", "Ciência", "Definições", "Sistema", "Outros"]),
+    ("pt_BR", ["Acessórios", "Desenvolvimento", "Educação", "Jogos", "Gráficos", "Internet", "Multimídia", "Escritório", "Ciência", "Configurações", "Sistema", "Outros"]),
+    ("ru", ["Стандартные", "Разработка", "Образование", "Игры", "Графика", "Интернет", "Мультимедиа", "Офис", "Наука", "Настройки", "Система", "Прочие"]),
+    ("ja", ["アクセサリ", "開発", "教育", "ゲーム", "グラフィックス", "インターネット", "マルチメディア", "オフィス", "科学", "設定", "システム", "その他"]),
+    ("zh_CN", ["附件", "编程", "教育", "游戏", "图形", "互联网", "影音", "办公", "科学", "设置", "系统", "其它"]),
+];
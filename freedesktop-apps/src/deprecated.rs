@@ -0,0 +1,133 @@
+use std::fmt;
+
+use crate::{ApplicationEntry, EntryType};
+
+/// The Desktop Entry specification version this crate implements
+pub const SPEC_VERSION: &str = "1.5";
+
+/// Keys the specification lists as deprecated
+const DEPRECATED_KEYS: &[&str] = &[
+    "Encoding",
+    "MiniIcon",
+    "TerminalOptions",
+    "Protocols",
+    "Extensions",
+    "BinaryPattern",
+    "MapNotify",
+    "SwallowTitle",
+    "SwallowExec",
+    "SortOrder",
+    "FilePattern",
+    "Patterns",
+    "DefaultApp",
+    "Dev",
+    "FSType",
+    "MountPoint",
+    "ReadOnly",
+    "UnmountIcon",
+];
+
+/// Entry types the specification lists as deprecated
+const DEPRECATED_TYPES: &[&str] = &["MimeType", "FSDevice"];
+
+/// Exec field codes the specification lists as deprecated
+const DEPRECATED_FIELD_CODES: &[char] = &['d', 'D', 'n', 'N', 'v', 'm'];
+
+/// A deprecated construct found in a desktop entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Deprecation {
+    /// A deprecated key in the `[Desktop Entry]` group
+    Key(String),
+    /// A deprecated field code in the Exec key
+    FieldCode(char),
+    /// A deprecated value of the Type key
+    EntryType(String),
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deprecation::Key(key) => write!(f, "Key {} is deprecated", key),
+            Deprecation::FieldCode(code) => write!(f, "Field code %{} is deprecated", code),
+            Deprecation::EntryType(t) => write!(f, "Type={} is deprecated", t),
+        }
+    }
+}
+
+impl ApplicationEntry {
+    /// Get the version of the specification the entry conforms to
+    ///
+    /// The value is returned as written, so `1.10` isn't read as the number 1.1.
+    pub fn version(&self) -> Option<String> {
+        self.group_entries("Desktop Entry")?
+            .iter()
+            .find(|(key, _)| key == "Version")
+            .map(|(_, value)| value.trim().to_string())
+    }
+
+    /// Check whether the entry declares a newer specification version than [`SPEC_VERSION`]
+    pub fn targets_newer_spec(&self) -> bool {
+        let supported = parse_version(SPEC_VERSION);
+        self.version()
+            .and_then(|v| parse_version(&v))
+            .is_some_and(|declared| Some(declared) > supported)
+    }
+
+    /// Get the deprecated keys present in the `[Desktop Entry]` group
+    pub fn deprecated_keys(&self) -> Vec<String> {
+        let Some(group) = self.complete().get_desktop_entry_group() else {
+            return Vec::new();
+        };
+
+        let mut keys: Vec<String> = DEPRECATED_KEYS
+            .iter()
+            .filter(|key| group.fields.contains_key(**key) || group.localized_fields.contains_key(**key))
+            .map(|key| key.to_string())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Get the deprecated field codes used in the Exec key
+    pub fn deprecated_field_codes(&self) -> Vec<char> {
+        let Some(exec) = self.exec() else {
+            return Vec::new();
+        };
+
+        let mut codes = Vec::new();
+        let mut chars = exec.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '%' {
+                if let Some(code) = chars.next() {
+                    if DEPRECATED_FIELD_CODES.contains(&code) && !codes.contains(&code) {
+                        codes.push(code);
+                    }
+                }
+            }
+        }
+        codes
+    }
+
+    /// Get every deprecated construct used by this entry
+    pub fn deprecations(&self) -> Vec<Deprecation> {
+        let mut found = Vec::new();
+
+        if let Some(EntryType::Other(entry_type)) = self.entry_type() {
+            if DEPRECATED_TYPES.contains(&entry_type.as_str()) {
+                found.push(Deprecation::EntryType(entry_type));
+            }
+        }
+        found.extend(self.deprecated_keys().into_iter().map(Deprecation::Key));
+        found.extend(self.deprecated_field_codes().into_iter().map(Deprecation::FieldCode));
+
+        found
+    }
+}
+
+/// Parse a `major.minor` version string
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map(|m| m.parse().ok()).unwrap_or(Some(0))?;
+    Some((major, minor))
+}
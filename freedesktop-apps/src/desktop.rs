@@ -0,0 +1,291 @@
+//! One handle for the long-lived state of a desktop shell or launcher
+//!
+//! Watching the applications directories, caching the MIME database and keeping
+//! a bus connection each hold threads or file descriptors. A [`Desktop`] owns all
+//! of them, so a process that reconfigures or embeds the crate for a while can
+//! stop everything with [`Desktop::shutdown`] instead of tracking each piece.
+//!
+//! ```no_run
+//! use freedesktop_apps::{Desktop, RefreshPolicy};
+//!
+//! let mut desktop = Desktop::new();
+//! desktop
+//!     .watch_registry(RefreshPolicy::default(), |events| println!("{} entries changed", events.len()))
+//!     .unwrap();
+//! println!("{} applications", desktop.registry().entries().len());
+//! desktop.shutdown();
+//! ```
+
+use std::fmt;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use freedesktop_mime::MimeDatabase;
+
+use crate::{MimeAssociations, RefreshPolicy, Registry, RegistryEvent, SharedRegistry};
+
+/// Name of the task started by [`Desktop::watch_registry`]
+const REGISTRY_WATCHER: &str = "registry-watcher";
+
+/// Owns the registry, cached databases and background tasks of a desktop process
+///
+/// Dropping the handle shuts it down like [`Desktop::shutdown`].
+pub struct Desktop {
+    registry: Arc<SharedRegistry>,
+    mime: Mutex<Option<Arc<MimeDatabase>>>,
+    associations: Mutex<Option<Arc<MimeAssociations>>>,
+    #[cfg(feature = "dbus")]
+    session_bus: Mutex<Option<zbus::blocking::Connection>>,
+    tasks: Vec<Task>,
+}
+
+/// A thread started by the handle, with the signal that stops it
+struct Task {
+    name: &'static str,
+    stop: Arc<StopSignal>,
+    error: Arc<Mutex<Option<Arc<io::Error>>>>,
+    handle: JoinHandle<()>,
+}
+
+impl Task {
+    fn stop(self) {
+        self.stop.raise();
+        let _ = self.handle.join();
+    }
+}
+
+/// An eventfd that becomes readable once a task is asked to stop, so the task
+/// can wait for it alongside its own file descriptors
+struct StopSignal {
+    fd: OwnedFd,
+}
+
+impl StopSignal {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(StopSignal {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    fn raise(&self) {
+        let one: u64 = 1;
+        unsafe { libc::write(self.fd.as_raw_fd(), (&one as *const u64).cast(), 8) };
+    }
+
+    /// Block until `fd` is readable or the signal is raised, returning whether `fd` is readable
+    ///
+    /// Returns false once the signal is raised, even if `fd` is readable too.
+    fn wait_readable(&self, fd: RawFd) -> io::Result<bool> {
+        let mut fds = [
+            libc::pollfd { fd, events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+        ];
+        loop {
+            if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } >= 0 {
+                return Ok(fds[1].revents == 0 && fds[0].revents != 0);
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Desktop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Desktop").field("tasks", &self.tasks()).finish_non_exhaustive()
+    }
+}
+
+impl Default for Desktop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Desktop {
+    /// Load every installed application entry; nothing runs in the background yet
+    pub fn new() -> Self {
+        Self::with_registry(Registry::load())
+    }
+
+    /// Start from an already configured registry, such as one with a policy
+    pub fn with_registry(registry: Registry) -> Self {
+        Desktop {
+            registry: Arc::new(SharedRegistry::new(registry)),
+            mime: Mutex::new(None),
+            associations: Mutex::new(None),
+            #[cfg(feature = "dbus")]
+            session_bus: Mutex::new(None),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Get the current registry, see [`SharedRegistry::snapshot`]
+    pub fn registry(&self) -> Arc<Registry> {
+        self.registry.snapshot()
+    }
+
+    /// Get the shared registry, for threads that outlive a borrow of the handle
+    pub fn shared_registry(&self) -> &Arc<SharedRegistry> {
+        &self.registry
+    }
+
+    /// Get the MIME database, loading it on first use
+    pub fn mime_database(&self) -> Arc<MimeDatabase> {
+        cached(&self.mime, MimeDatabase::load)
+    }
+
+    /// Get the `mimeapps.list` associations, reading them on first use
+    pub fn associations(&self) -> Arc<MimeAssociations> {
+        cached(&self.associations, MimeAssociations::load)
+    }
+
+    /// Get a connection to the session bus, kept by the handle
+    ///
+    /// The connection is made on first use and closed by [`Desktop::shutdown`].
+    /// Launches only use it when it is passed on, as [`Desktop::launch_options`] does.
+    #[cfg(feature = "dbus")]
+    pub fn session_bus(&self) -> zbus::Result<zbus::blocking::Connection> {
+        let mut session_bus = self.session_bus.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(connection) = session_bus.as_ref() {
+            return Ok(connection.clone());
+        }
+        let connection = zbus::blocking::Connection::session()?;
+        *session_bus = Some(connection.clone());
+        Ok(connection)
+    }
+
+    /// Get launch options that make their D-Bus calls on [`Desktop::session_bus`]
+    ///
+    /// When the bus can't be reached the options are the defaults, and launches
+    /// that need it fail or fall back as they would without the handle.
+    #[cfg(feature = "dbus")]
+    pub fn launch_options(&self) -> crate::LaunchOptions {
+        let options = crate::LaunchOptions::new();
+        match self.session_bus() {
+            Ok(connection) => options.session_bus(connection),
+            Err(_) => options,
+        }
+    }
+
+    /// Keep the registry up to date from a background thread
+    ///
+    /// Changes are collected per `policy` and applied to the shared registry, then
+    /// `on_change` is called with the batch. Calling this again replaces the
+    /// running watcher, so changing the policy doesn't leave the old thread behind.
+    /// If reading the changes fails the watcher stops; see [`Desktop::task_error`].
+    pub fn watch_registry<F>(&mut self, policy: RefreshPolicy, mut on_change: F) -> io::Result<()>
+    where
+        F: FnMut(&[RegistryEvent]) + Send + 'static,
+    {
+        self.stop_task(REGISTRY_WATCHER);
+        let mut watcher = self.registry.snapshot().watch()?;
+        let registry = Arc::clone(&self.registry);
+        self.spawn(REGISTRY_WATCHER, move |stop| {
+            // Events about other files are read here, so next_batch never waits for long
+            while stop.wait_readable(watcher.as_raw_fd())? {
+                if !watcher.read_ready()? {
+                    continue;
+                }
+                let batch = watcher.next_batch(&policy)?;
+                registry.apply_events(&batch, |_, _| {});
+                on_change(&batch);
+            }
+            Ok(())
+        })
+    }
+
+    /// Stop watching the registry, if it is being watched
+    pub fn unwatch_registry(&mut self) {
+        self.stop_task(REGISTRY_WATCHER);
+    }
+
+    /// Get the names of the background tasks that are running
+    ///
+    /// A task whose thread ended on its own, such as a watcher that failed to read
+    /// its directories, isn't listed.
+    pub fn tasks(&self) -> Vec<&'static str> {
+        self.tasks
+            .iter()
+            .filter(|task| !task.handle.is_finished())
+            .map(|task| task.name)
+            .collect()
+    }
+
+    /// Get the error a background task stopped with, if it failed
+    ///
+    /// The error is kept until the task is started again or the handle is shut down.
+    pub fn task_error(&self, name: &str) -> Option<Arc<io::Error>> {
+        let task = self.tasks.iter().find(|task| task.name == name)?;
+        task.error.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Load the registry again and forget the cached databases
+    ///
+    /// The databases are read again on next use. Background tasks keep running.
+    pub fn reload(&self) {
+        self.registry.reload();
+        *self.mime.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.associations.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Stop every background task, waiting for its thread to finish, and close
+    /// the bus connection
+    ///
+    /// A watcher that is collecting a batch finishes it first, which takes at most
+    /// the maximum delay of its [`RefreshPolicy`]. The registry and databases stay
+    /// readable, and tasks can be started again afterwards.
+    pub fn shutdown(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.stop();
+        }
+        #[cfg(feature = "dbus")]
+        {
+            *self.session_bus.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    fn spawn<F>(&mut self, name: &'static str, run: F) -> io::Result<()>
+    where
+        F: FnOnce(&StopSignal) -> io::Result<()> + Send + 'static,
+    {
+        let stop = Arc::new(StopSignal::new()?);
+        let error = Arc::new(Mutex::new(None));
+        let (signal, slot) = (Arc::clone(&stop), Arc::clone(&error));
+        let handle = thread::Builder::new()
+            .name(format!("freedesktop-{}", name))
+            .spawn(move || {
+                if let Err(e) = run(&signal) {
+                    *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(e));
+                }
+            })?;
+        self.tasks.push(Task { name, stop, error, handle });
+        Ok(())
+    }
+
+    fn stop_task(&mut self, name: &str) {
+        if let Some(index) = self.tasks.iter().position(|task| task.name == name) {
+            self.tasks.remove(index).stop();
+        }
+    }
+}
+
+impl Drop for Desktop {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Get a cached value, loading it if there is none
+fn cached<T>(slot: &Mutex<Option<Arc<T>>>, load: impl FnOnce() -> T) -> Arc<T> {
+    let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+    Arc::clone(slot.get_or_insert_with(|| Arc::new(load())))
+}
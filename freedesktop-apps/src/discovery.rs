@@ -0,0 +1,238 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{application_entry_paths_by_precedence, export_application_paths, ApplicationEntry, ParseError};
+
+/// A lazy iterator over installed application entries
+///
+/// Directories are listed and files parsed only as the iterator advances, so
+/// stopping early skips the remaining work. Created by [`ApplicationEntry::iter`].
+#[derive(Debug)]
+pub struct ApplicationEntries {
+    roots: VecDeque<PathBuf>,
+    /// Sorted listings of the directories being walked, innermost last
+    pending: Vec<std::vec::IntoIter<PathBuf>>,
+    /// IDs already returned or deleted; None when shadowed copies are included
+    seen: Option<HashSet<String>>,
+    /// Applications directories attached to every entry for its ID, when they
+    /// aren't just the standard ones
+    custom_dirs: Option<Arc<[PathBuf]>>,
+    metadata_only: bool,
+}
+
+impl ApplicationEntries {
+    /// Walk the standard application directories, followed by the Flatpak and
+    /// Snap export directories if `include_exports` is set
+    ///
+    /// Entries found with the exports compute their IDs against them too.
+    pub(crate) fn new(include_shadowed: bool, include_exports: bool) -> Self {
+        let mut roots = application_entry_paths_by_precedence();
+        if !include_exports {
+            return Self::walk(roots, None, include_shadowed);
+        }
+
+        roots.extend(export_application_paths());
+        let custom_dirs = Some(roots.as_slice().into());
+        Self::walk(roots, custom_dirs, include_shadowed)
+    }
+
+    /// Walk the given applications directories instead of the standard ones
+    pub(crate) fn in_dirs(dirs: &[PathBuf], include_shadowed: bool) -> Self {
+        Self::walk(dirs.to_vec(), Some(dirs.into()), include_shadowed)
+    }
+
+    fn walk(roots: Vec<PathBuf>, custom_dirs: Option<Arc<[PathBuf]>>, include_shadowed: bool) -> Self {
+        ApplicationEntries {
+            roots: roots.into(),
+            pending: Vec::new(),
+            seen: (!include_shadowed).then(HashSet::new),
+            custom_dirs,
+            metadata_only: false,
+        }
+    }
+
+    /// Read only the metadata of each entry, see [`ApplicationEntry::metadata_from_path`]
+    pub fn metadata_only(mut self) -> Self {
+        self.metadata_only = true;
+        self
+    }
+
+    /// Parse a desktop file found by this walk
+    fn parse(&self, path: &Path) -> Result<ApplicationEntry, ParseError> {
+        let mut entry = match self.metadata_only {
+            true => ApplicationEntry::metadata_from_path(path)?,
+            false => ApplicationEntry::try_from_path(path)?,
+        };
+        entry.application_dirs = self.custom_dirs.clone();
+        Ok(entry)
+    }
+
+    /// Get the next desktop file path, descending into subdirectories
+    pub(crate) fn next_path(&mut self) -> Option<PathBuf> {
+        loop {
+            let Some(listing) = self.pending.last_mut() else {
+                let root = self.roots.pop_front()?;
+                self.push_listing(root);
+                continue;
+            };
+
+            match listing.next() {
+                Some(path) if path.is_dir() => self.push_listing(path),
+                Some(path) if path.extension().is_some_and(|ext| ext == "desktop") => return Some(path),
+                Some(_) => {}
+                None => {
+                    self.pending.pop();
+                }
+            }
+        }
+    }
+
+    fn push_listing(&mut self, dir: PathBuf) {
+        if let Ok(dir_entries) = std::fs::read_dir(dir) {
+            let mut paths: Vec<PathBuf> = dir_entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            paths.sort();
+            self.pending.push(paths.into_iter());
+        }
+    }
+}
+
+impl Iterator for ApplicationEntries {
+    type Item = ApplicationEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(path) = self.next_path() {
+            let Ok(entry) = self.parse(&path) else {
+                continue;
+            };
+
+            let Some(seen) = &mut self.seen else {
+                return Some(entry);
+            };
+
+            if is_visible(&entry, seen) {
+                return Some(entry);
+            }
+        }
+
+        None
+    }
+}
+
+/// Check whether an entry shows up in [`ApplicationEntry::all`], given the IDs of
+/// the higher-precedence entries already seen
+///
+/// Entries with Hidden=true count as deleted and shadow any lower-precedence entry
+/// with the same ID.
+fn is_visible(entry: &ApplicationEntry, seen: &mut HashSet<String>) -> bool {
+    let first = entry.id().is_none_or(|id| seen.insert(id));
+    first && !entry.is_hidden()
+}
+
+impl ApplicationEntry {
+    /// Lazily iterate over application entries from standard directories
+    ///
+    /// Yields the same entries as [`ApplicationEntry::all`], in the same order.
+    pub fn iter() -> ApplicationEntries {
+        ApplicationEntries::new(false, false)
+    }
+
+    /// Get all application entries from standard directories
+    ///
+    /// When several data dirs contain the same desktop file ID only the
+    /// highest-precedence copy is returned (XDG_DATA_HOME first, then
+    /// XDG_DATA_DIRS in order). Entries with Hidden=true count as deleted:
+    /// they are left out and shadow lower-precedence copies.
+    pub fn all() -> Vec<ApplicationEntry> {
+        Self::iter().collect()
+    }
+
+    /// Get the same entries as [`ApplicationEntry::all`] with only their metadata read
+    ///
+    /// Much faster for listing hundreds of entries; see [`ApplicationEntry::metadata_from_path`].
+    pub fn all_metadata() -> Vec<ApplicationEntry> {
+        Self::iter().metadata_only().collect()
+    }
+
+    /// Get the same entries as [`ApplicationEntry::all`], plus applications exported by
+    /// Flatpak and Snap whose directories are missing from XDG_DATA_DIRS
+    ///
+    /// The export directories rank below every XDG data dir, so an entry there never
+    /// shadows one found the usual way.
+    pub fn all_with_exports() -> Vec<ApplicationEntry> {
+        ApplicationEntries::new(false, true).collect()
+    }
+
+    /// Lazily iterate over application entries in the given `applications` directories
+    ///
+    /// See [`ApplicationEntry::all_in`].
+    pub fn iter_in(dirs: &[PathBuf]) -> ApplicationEntries {
+        ApplicationEntries::in_dirs(dirs, false)
+    }
+
+    /// Get all application entries from the given `applications` directories instead
+    /// of the ones in the environment
+    ///
+    /// `dirs` are ordered from highest to lowest precedence and follow the same rules
+    /// as [`ApplicationEntry::all`]. Desktop file IDs of the returned entries are
+    /// relative to these directories.
+    pub fn all_in(dirs: &[PathBuf]) -> Vec<ApplicationEntry> {
+        Self::iter_in(dirs).collect()
+    }
+
+    /// Get every copy of every application entry, including shadowed and hidden ones
+    ///
+    /// Entries are ordered from highest to lowest precedence, so the first
+    /// entry for a given ID is the one [`ApplicationEntry::all`] returns.
+    pub fn all_including_shadowed() -> Vec<ApplicationEntry> {
+        ApplicationEntries::new(true, false).collect()
+    }
+
+    /// Get the same entries as [`ApplicationEntry::all`], along with the files that failed to parse
+    ///
+    /// `all()` skips broken files silently; this lets tools tell users why an
+    /// application is missing. A file that fails to parse doesn't shadow copies
+    /// of the same ID in lower-precedence directories.
+    pub fn all_with_errors() -> (Vec<ApplicationEntry>, Vec<(PathBuf, ParseError)>) {
+        let mut walker = ApplicationEntries::new(true, false);
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+
+        while let Some(path) = walker.next_path() {
+            match walker.parse(&path) {
+                Ok(entry) => {
+                    if is_visible(&entry, &mut seen) {
+                        entries.push(entry);
+                    }
+                }
+                Err(e) => errors.push((path, e)),
+            }
+        }
+
+        (entries, errors)
+    }
+
+    /// Get the same entries as [`ApplicationEntry::all`], parsing files across threads
+    ///
+    /// Directories are scanned up front and the files parsed on the rayon thread pool.
+    /// The result keeps the precedence order, so it is identical to `all()`.
+    #[cfg(feature = "rayon")]
+    pub fn all_parallel() -> Vec<ApplicationEntry> {
+        use rayon::prelude::*;
+
+        let mut walker = ApplicationEntries::new(true, false);
+        let paths: Vec<PathBuf> = std::iter::from_fn(|| walker.next_path()).collect();
+        let parsed: Vec<Option<ApplicationEntry>> = paths
+            .par_iter()
+            .map(|path| walker.parse(path).ok())
+            .collect();
+
+        let mut seen = HashSet::new();
+        parsed
+            .into_iter()
+            .flatten()
+            .filter(|entry| is_visible(entry, &mut seen))
+            .collect()
+    }
+}
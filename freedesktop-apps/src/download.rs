@@ -0,0 +1,121 @@
+//! Downloading remote URLs for applications that only open local files
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::UrlFetcher;
+
+/// Name prefix of the private directory made for each download
+const DIR_PREFIX: &str = "freedesktop-download-";
+
+/// Downloads URLs into a temporary directory with `curl`, or `wget` if curl is missing
+///
+/// Each download gets its own directory with a random name, readable only by
+/// the user, so the file keeps the name from the URL, which is what the
+/// application shows in its title bar. The files are reported by
+/// [`Launched::downloads`](crate::Launched::downloads) for cleanup.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadFetcher {
+    dir: Option<PathBuf>,
+}
+
+impl DownloadFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Download into `dir` instead of the temporary directory
+    pub fn in_dir<P: AsRef<Path>>(dir: P) -> Self {
+        DownloadFetcher {
+            dir: Some(dir.as_ref().to_path_buf()),
+        }
+    }
+
+    fn target(&self, url: &str) -> io::Result<PathBuf> {
+        let base = match &self.dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                dir.clone()
+            }
+            None => std::env::temp_dir(),
+        };
+        Ok(make_private_dir(&base)?.join(file_name(url)))
+    }
+}
+
+impl UrlFetcher for DownloadFetcher {
+    fn fetch(&self, url: &str) -> io::Result<PathBuf> {
+        let target = self.target(url)?;
+        let run = |program: &str, args: &[&str]| {
+            Command::new(program)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+        };
+
+        let output = target.to_string_lossy();
+        let status = match run("curl", &["--fail", "--silent", "--location", "--output", &output, "--", url]) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => run("wget", &["--quiet", "--output-document", &output, "--", url])?,
+            status => status?,
+        };
+        if !status.success() {
+            remove(&target);
+            return Err(io::Error::other(format!("Download of {} failed ({})", url, status)));
+        }
+        Ok(target)
+    }
+}
+
+/// Get a file name for a URL from its last path segment
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let name = path
+        .split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..");
+    name.unwrap_or("download").to_string()
+}
+
+/// Create a new directory with a random name that only the user can access, like `mkdtemp`
+///
+/// An existing directory is never reused, as someone else may control it.
+fn make_private_dir(base: &Path) -> io::Result<PathBuf> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for _ in 0..100 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        let dir = base.join(format!("{}{:016x}", DIR_PREFIX, hasher.finish()));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("No unused download directory in {}", base.display()),
+    ))
+}
+
+/// Remove a downloaded file, along with the directory made for it
+pub(crate) fn remove(path: &Path) {
+    let _ = fs::remove_file(path);
+    if let Some(dir) = path.parent() {
+        if dir.file_name().is_some_and(|name| name.to_string_lossy().starts_with(DIR_PREFIX)) {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
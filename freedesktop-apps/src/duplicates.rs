@@ -0,0 +1,109 @@
+//! Entries that are the same application under different desktop file IDs
+//!
+//! When a distribution renames a desktop file, for example `gnome-calculator` to
+//! `org.gnome.Calculator`, the old file is often still installed by another
+//! package and menus list the application twice. Entries with the same Name and
+//! Exec are grouped here; the files themselves are never touched.
+
+use std::collections::HashMap;
+
+use crate::verify::is_bus_name;
+use crate::ApplicationEntry;
+
+/// What a [`Registry`] does with duplicate entries, see [`Registry::with_duplicate_strategy`]
+///
+/// [`Registry`]: crate::Registry
+/// [`Registry::with_duplicate_strategy`]: crate::Registry::with_duplicate_strategy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DuplicateStrategy {
+    /// Keep every entry and only report the duplicates
+    #[default]
+    Report,
+    /// Offer one entry of each group; the others are still found by their IDs
+    Merge,
+}
+
+/// Entries sharing a Name and Exec under different desktop file IDs
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    name: String,
+    exec: String,
+    kept: ApplicationEntry,
+    duplicates: Vec<ApplicationEntry>,
+}
+
+impl DuplicateGroup {
+    /// Get the Name the entries share
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the Exec the entries share
+    pub fn exec(&self) -> &str {
+        &self.exec
+    }
+
+    /// Get the entry offered for the group
+    pub fn kept(&self) -> &ApplicationEntry {
+        &self.kept
+    }
+
+    /// Get the other entries of the group
+    pub fn duplicates(&self) -> &[ApplicationEntry] {
+        &self.duplicates
+    }
+
+    /// Get the desktop file IDs of the other entries
+    pub fn duplicate_ids(&self) -> Vec<String> {
+        self.duplicates.iter().filter_map(|entry| entry.id()).collect()
+    }
+
+    /// Check whether `id` is one of the other entries' IDs
+    pub fn contains_duplicate(&self, id: &str) -> bool {
+        self.duplicates.iter().any(|entry| entry.id().as_deref() == Some(id))
+    }
+}
+
+/// Entries sharing a Name and Exec, with their IDs so each one is computed once
+type Members<'a> = Vec<(String, &'a ApplicationEntry)>;
+
+/// Group the entries that share a Name and Exec but have different IDs
+///
+/// Groups are in the order their first entry appears. The kept entry is the first
+/// one with a reverse-DNS ID, since renames move to those, or else the first one.
+/// Entries without an ID are never grouped.
+pub fn find_duplicates(entries: &[ApplicationEntry]) -> Vec<DuplicateGroup> {
+    let mut candidates: Vec<(String, String, Members)> = Vec::new();
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for entry in entries {
+        let (Some(name), Some(exec), Some(id)) = (entry.name(), entry.exec(), entry.id()) else {
+            continue;
+        };
+        match index.get(&(name.clone(), exec.clone())) {
+            Some(&i) => candidates[i].2.push((id, entry)),
+            None => {
+                index.insert((name.clone(), exec.clone()), candidates.len());
+                candidates.push((name, exec, vec![(id, entry)]));
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(_, _, members)| members.len() > 1)
+        .map(|(name, exec, members)| {
+            let kept = members.iter().position(|(id, _)| is_bus_name(id)).unwrap_or(0);
+            DuplicateGroup {
+                name,
+                exec,
+                kept: members[kept].1.clone(),
+                duplicates: members
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != kept)
+                    .map(|(_, (_, entry))| (*entry).clone())
+                    .collect(),
+            }
+        })
+        .collect()
+}
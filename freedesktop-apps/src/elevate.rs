@@ -0,0 +1,94 @@
+//! Running entries as root through polkit's `pkexec`
+//!
+//! pkexec starts the program with a minimal environment, so graphical programs
+//! can't find the display unless it is passed on explicitly. Elevated commands
+//! therefore run `pkexec env NAME=VALUE... program args`, with the variables the
+//! program needs to reach the display and those set with [`LaunchOptions::env`].
+//! Nothing else from the caller's environment reaches the program.
+
+use std::path::{Path, PathBuf};
+
+use crate::{which, ApplicationEntry, LaunchOptions};
+
+/// When a launch runs the program as root, see [`LaunchOptions::elevation`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Elevation {
+    /// Run the program as the current user
+    #[default]
+    Never,
+    /// Run entries that need root, see [`ApplicationEntry::requires_root`]
+    IfRequired,
+    /// Always run the program as root
+    Always,
+}
+
+impl Elevation {
+    /// Check whether a launch of `entry` runs as root
+    pub fn applies_to(&self, entry: &ApplicationEntry) -> bool {
+        match self {
+            Elevation::Never => false,
+            Elevation::IfRequired => entry.requires_root(),
+            Elevation::Always => true,
+        }
+    }
+}
+
+impl ApplicationEntry {
+    /// Check if the entry only works when run as root (X-KDE-RootOnly=true)
+    pub fn requires_root(&self) -> bool {
+        self.get_bool("X-KDE-RootOnly").unwrap_or(false)
+    }
+}
+
+/// Get the arguments that run a command through pkexec with the environment it needs
+///
+/// The command itself follows the returned arguments.
+pub(crate) fn pkexec_prefix(working_dir: Option<&Path>, options: &LaunchOptions) -> Vec<String> {
+    let env = which("env").unwrap_or_else(|| PathBuf::from("/usr/bin/env"));
+    let mut prefix = vec!["pkexec".to_string(), env.to_string_lossy().to_string()];
+
+    // pkexec starts in the target user's home directory
+    if let Some(dir) = working_dir {
+        prefix.push(format!("--chdir={}", dir.display()));
+    }
+
+    if let Ok(display) = std::env::var("DISPLAY") {
+        prefix.push(format!("DISPLAY={}", display));
+        if let Some(xauthority) = xauthority() {
+            prefix.push(format!("XAUTHORITY={}", xauthority.display()));
+        }
+    }
+    // Root has its own XDG_RUNTIME_DIR, so the socket is passed as an absolute path
+    if let Some(wayland) = wayland_socket() {
+        prefix.push(format!("WAYLAND_DISPLAY={}", wayland.display()));
+    }
+    for key in ["XDG_SESSION_TYPE", "XDG_CURRENT_DESKTOP"] {
+        if let Ok(value) = std::env::var(key) {
+            prefix.push(format!("{}={}", key, value));
+        }
+    }
+
+    prefix.extend(options.env_vars().map(|(key, value)| format!("{}={}", key, value)));
+    if options.env_value("XDG_LAUNCH_ID").is_none() {
+        if let Some(id) = options.launch_id_value() {
+            prefix.push(format!("XDG_LAUNCH_ID={}", id));
+        }
+    }
+    prefix
+}
+
+/// Find the X authority file, which defaults to `~/.Xauthority` when XAUTHORITY isn't set
+fn xauthority() -> Option<PathBuf> {
+    std::env::var_os("XAUTHORITY")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".Xauthority")))
+        .filter(|path| path.is_file())
+}
+
+fn wayland_socket() -> Option<PathBuf> {
+    let display = PathBuf::from(std::env::var_os("WAYLAND_DISPLAY")?);
+    if display.is_absolute() {
+        return Some(display);
+    }
+    Some(PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?).join(display))
+}
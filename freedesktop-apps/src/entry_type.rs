@@ -0,0 +1,171 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::path::Path;
+
+use crate::{ApplicationEntry, ParseError};
+
+/// The value of the `Type` key of a desktop entry
+///
+/// The types defined by the specification are recognized regardless of case, so
+/// `Type=application` is still [`EntryType::Application`]; anything else is kept
+/// as written in [`EntryType::Other`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EntryType {
+    Application,
+    Link,
+    Directory,
+    /// Any type not defined by the specification
+    Other(String),
+}
+
+impl EntryType {
+    /// Get the type as it appears in the desktop file
+    pub fn as_str(&self) -> &str {
+        match self {
+            EntryType::Application => "Application",
+            EntryType::Link => "Link",
+            EntryType::Directory => "Directory",
+            EntryType::Other(s) => s,
+        }
+    }
+
+    /// Check if the type is one the specification defines
+    pub fn is_known(&self) -> bool {
+        !matches!(self, EntryType::Other(_))
+    }
+}
+
+impl From<&str> for EntryType {
+    fn from(value: &str) -> Self {
+        match value.trim() {
+            t if t.eq_ignore_ascii_case("Application") => EntryType::Application,
+            t if t.eq_ignore_ascii_case("Link") => EntryType::Link,
+            t if t.eq_ignore_ascii_case("Directory") => EntryType::Directory,
+            other => EntryType::Other(other.to_string()),
+        }
+    }
+}
+
+impl FromStr for EntryType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(EntryType::from(s))
+    }
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A desktop entry of type `Link`
+///
+/// Dereferences to [`ApplicationEntry`] for the keys shared by all entry types.
+#[derive(Debug)]
+pub struct LinkEntry {
+    entry: ApplicationEntry,
+}
+
+impl LinkEntry {
+    /// Parse a Link entry from a path, failing if the entry has a different type
+    pub fn try_from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let entry = ApplicationEntry::try_from_path(path)?;
+        Self::try_from(entry).map_err(|entry| {
+            ParseError::invalid(
+                entry.path(),
+                None,
+                format!(
+                    "Expected Link entry, found {}",
+                    entry.entry_type().map(|t| t.to_string()).unwrap_or_default()
+                ),
+            )
+        })
+    }
+
+    /// Get the URL this link points to
+    pub fn url(&self) -> Option<String> {
+        self.entry.get_string("URL")
+    }
+
+    /// Unwrap into the underlying entry
+    pub fn into_inner(self) -> ApplicationEntry {
+        self.entry
+    }
+}
+
+impl TryFrom<ApplicationEntry> for LinkEntry {
+    type Error = ApplicationEntry;
+
+    fn try_from(entry: ApplicationEntry) -> Result<Self, Self::Error> {
+        match entry.entry_type() {
+            Some(EntryType::Link) => Ok(LinkEntry { entry }),
+            _ => Err(entry),
+        }
+    }
+}
+
+impl Deref for LinkEntry {
+    type Target = ApplicationEntry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+/// A desktop entry of type `Directory`, usually loaded from a `.directory` file
+///
+/// Dereferences to [`ApplicationEntry`] for the keys shared by all entry types.
+#[derive(Debug)]
+pub struct DirectoryEntry {
+    entry: ApplicationEntry,
+}
+
+impl DirectoryEntry {
+    /// Parse a Directory entry from a path, failing if the entry has a different type
+    pub fn try_from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let entry = ApplicationEntry::try_from_path(path)?;
+        Self::try_from(entry).map_err(|entry| {
+            ParseError::invalid(
+                entry.path(),
+                None,
+                format!(
+                    "Expected Directory entry, found {}",
+                    entry.entry_type().map(|t| t.to_string()).unwrap_or_default()
+                ),
+            )
+        })
+    }
+
+    /// Get the icon used for the menu directory
+    pub fn icon(&self) -> Option<String> {
+        self.entry.icon()
+    }
+
+    /// Unwrap into the underlying entry
+    pub fn into_inner(self) -> ApplicationEntry {
+        self.entry
+    }
+}
+
+impl TryFrom<ApplicationEntry> for DirectoryEntry {
+    type Error = ApplicationEntry;
+
+    fn try_from(entry: ApplicationEntry) -> Result<Self, Self::Error> {
+        match entry.entry_type() {
+            Some(EntryType::Directory) => Ok(DirectoryEntry { entry }),
+            _ => Err(entry),
+        }
+    }
+}
+
+impl Deref for DirectoryEntry {
+    type Target = ApplicationEntry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
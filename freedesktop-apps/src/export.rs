@@ -0,0 +1,74 @@
+//! Writing the effective set of entries into a single applications directory
+//!
+//! Containers and remote sessions often mount only one `applications` directory,
+//! which loses the precedence between the XDG data directories. Exporting a
+//! [`Registry`] resolves it up front: the target directory gets exactly the entries
+//! that win, each named after its desktop file ID.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::Registry;
+
+/// Start of the comment that marks a file as exported and names its source
+const PROVENANCE: &str = "# Exported by freedesktop-apps from ";
+
+impl Registry {
+    /// Write every entry to `dir` as `<desktop file ID>.desktop`, returning the written paths
+    ///
+    /// Each file holds the entry's groups and keys as they were parsed, preceded by
+    /// a comment naming the file it came from. An ID that appears more than once is written for its
+    /// first entry, as [`Registry::get`] would find it. Files left by an earlier
+    /// export whose entry is gone are removed; other files in `dir` are kept unless
+    /// an exported file has the same name.
+    pub fn export_to<P: AsRef<Path>>(&self, dir: P) -> io::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut written = Vec::new();
+        let mut names = HashSet::new();
+        for entry in self.entries() {
+            let Some(id) = entry.id().or_else(|| entry.path().file_stem()?.to_str().map(str::to_string)) else {
+                continue;
+            };
+            let name = format!("{}.desktop", id);
+            if !names.insert(name.clone()) {
+                continue;
+            }
+
+            let content = format!("{}{}\n{}", PROVENANCE, comment_text(entry.path()), entry.to_keyfile());
+            let target = dir.join(name);
+            fs::write(&target, content)?;
+            written.push(target);
+        }
+
+        remove_stale_exports(dir, &names)?;
+        Ok(written)
+    }
+}
+
+/// Get a path for a comment, with control characters escaped so it stays on one line
+fn comment_text(path: &Path) -> String {
+    path.display()
+        .to_string()
+        .chars()
+        .map(|ch| if ch.is_control() { ch.escape_default().to_string() } else { ch.to_string() })
+        .collect()
+}
+
+/// Remove files from an earlier export that weren't written this time
+fn remove_stale_exports(dir: &Path, written: &HashSet<String>) -> io::Result<()> {
+    for file in fs::read_dir(dir)?.flatten() {
+        let name = file.file_name().to_string_lossy().to_string();
+        if !name.ends_with(".desktop") || written.contains(&name) {
+            continue;
+        }
+        let exported = fs::read(file.path()).is_ok_and(|content| content.starts_with(PROVENANCE.as_bytes()));
+        if exported {
+            fs::remove_file(file.path())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! Tracking of entries that repeatedly fail to launch
+//!
+//! Failures are kept per desktop file ID in a small tab-separated file under
+//! `$XDG_STATE_HOME/freedesktop/launch-failures`, so launchers can warn about
+//! broken entries across restarts. A successful launch resets the count.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use freedesktop_core::time::Timestamp;
+
+use crate::ExecuteError;
+
+// Serializes read-modify-write cycles on the state file within the process
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Consecutive launch failures recorded for an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchFailure {
+    /// Number of failures since the last successful launch
+    pub count: u32,
+    /// The error of the most recent failure
+    pub last_error: String,
+    /// When the most recent failure happened
+    pub last_failed: Timestamp,
+}
+
+/// Persistent per-entry record of consecutive launch failures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchFailures {
+    path: PathBuf,
+    threshold: u32,
+}
+
+impl LaunchFailures {
+    /// Track failures in the given file; entries count as failing after 3 failures
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        LaunchFailures {
+            path: path.into(),
+            threshold: 3,
+        }
+    }
+
+    /// Track failures in `$XDG_STATE_HOME/freedesktop/launch-failures`
+    pub fn open() -> Option<Self> {
+        freedesktop_core::state_home()
+            .map(|state| Self::new(state.join("freedesktop").join("launch-failures")))
+    }
+
+    /// Number of consecutive failures after which an entry counts as failing
+    pub fn threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    /// Get the path of the state file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check whether the entry has failed to launch too many times in a row
+    pub fn is_failing(&self, id: &str) -> bool {
+        self.failure(id).is_some_and(|failure| failure.count >= self.threshold)
+    }
+
+    /// Get the recorded failures of an entry, if its last launches failed
+    pub fn failure(&self, id: &str) -> Option<LaunchFailure> {
+        self.read().remove(id)
+    }
+
+    /// Record the outcome of launching an entry
+    pub fn record<T>(&self, id: &str, result: &Result<T, ExecuteError>) -> io::Result<()> {
+        let _guard = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut failures = self.read();
+        match result {
+            Ok(_) => {
+                if failures.remove(id).is_none() {
+                    return Ok(());
+                }
+            }
+            Err(e) => {
+                let failure = failures.entry(id.to_string()).or_insert(LaunchFailure {
+                    count: 0,
+                    last_error: String::new(),
+                    last_failed: Timestamp::from_unix_secs(0),
+                });
+                failure.count += 1;
+                failure.last_error = e.to_string();
+                failure.last_failed = Timestamp::now();
+            }
+        }
+        self.write(&failures)
+    }
+
+    /// Forget the failures of an entry, e.g. after the user fixed it
+    pub fn clear(&self, id: &str) -> io::Result<()> {
+        let _guard = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut failures = self.read();
+        if failures.remove(id).is_some() {
+            self.write(&failures)?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> BTreeMap<String, LaunchFailure> {
+        let Ok(content) = std::fs::read_to_string(&self.path) else {
+            return BTreeMap::new();
+        };
+
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(4, '\t');
+                let id = fields.next()?;
+                let count = fields.next()?.parse().ok()?;
+                let last_failed = fields.next()?.parse().ok()?;
+                let last_error = fields.next().unwrap_or_default();
+                Some((
+                    id.to_string(),
+                    LaunchFailure {
+                        count,
+                        last_error: last_error.to_string(),
+                        last_failed,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    fn write(&self, failures: &BTreeMap<String, LaunchFailure>) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut content = String::new();
+        for (id, failure) in failures {
+            content.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                id,
+                failure.count,
+                failure.last_failed,
+                failure.last_error.replace(['\t', '\n'], " ")
+            ));
+        }
+        std::fs::write(&self.path, content)
+    }
+}
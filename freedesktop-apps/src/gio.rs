@@ -0,0 +1,60 @@
+//! Exchanging entries with GLib's `GDesktopAppInfo`
+//!
+//! Projects moving from gio can keep passing data to the parts that still use
+//! it: [`ApplicationEntry::to_gio_keyfile`] gives the keyfile
+//! `g_desktop_app_info_new_from_keyfile()` takes, and
+//! [`ApplicationEntry::from_gio_data`] reads what `g_key_file_to_data()` wrote.
+//! The `gio_*` accessors return what the `g_app_info_get_*` functions of the same
+//! name return, where that differs from this crate's accessors.
+
+use std::io;
+use std::path::Path;
+
+use freedesktop_core::keyfile::KeyFile;
+
+use crate::parser::DesktopEntry;
+use crate::{parse_command_line, ApplicationEntry, ParseError, ParseOptions};
+
+impl ApplicationEntry {
+    /// Get the ID as `g_app_info_get_id()` returns it: the desktop file ID with its
+    /// `.desktop` suffix
+    pub fn gio_id(&self) -> Option<String> {
+        self.id().map(|id| format!("{}.desktop", id))
+    }
+
+    /// Get the name as `g_app_info_get_display_name()` returns it
+    ///
+    /// GLib prefers the X-GNOME-FullName key, falling back to Name.
+    pub fn gio_display_name(&self) -> Option<String> {
+        self.get_string("X-GNOME-FullName").or_else(|| self.name())
+    }
+
+    /// Get the program as `g_app_info_get_executable()` returns it: the first word
+    /// of the Exec key, unquoted
+    pub fn gio_executable(&self) -> Option<String> {
+        let exec = self.exec()?;
+        parse_command_line(&exec, false).ok().map(|(program, _)| program)
+    }
+
+    /// Get the keyfile to pass to `g_desktop_app_info_new_from_keyfile()`
+    ///
+    /// The desktop file is read again so every group and value reaches GLib exactly
+    /// as written, with its escapes and translations.
+    pub fn to_gio_keyfile(&self) -> io::Result<KeyFile> {
+        KeyFile::from_path(self.path())
+    }
+
+    /// Parse keyfile data written by `g_key_file_to_data()` for a `GDesktopAppInfo`
+    ///
+    /// `filename` is what `g_desktop_app_info_get_filename()` returns; the desktop
+    /// file ID is computed from it, and it doesn't have to exist. The data must be
+    /// a valid desktop entry.
+    pub fn from_gio_data<P: AsRef<Path>>(data: &str, filename: P) -> Result<Self, ParseError> {
+        let entry = DesktopEntry::from_reader_with(filename.as_ref(), data.as_bytes(), &ParseOptions::default())?;
+        Ok(ApplicationEntry {
+            inner: entry,
+            application_dirs: None,
+            full: None,
+        })
+    }
+}
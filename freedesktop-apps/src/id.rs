@@ -0,0 +1,112 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::ApplicationEntry;
+
+type MatchTier<'a> = &'a dyn Fn(&str, &ApplicationEntry) -> bool;
+
+/// Why a user-supplied identifier couldn't be resolved to a single desktop file ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdResolveError {
+    /// No installed entry matches
+    NotFound(String),
+    /// Several entries match equally well; holds their IDs
+    Ambiguous(Vec<String>),
+}
+
+impl fmt::Display for IdResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdResolveError::NotFound(input) => write!(f, "No application matches '{}'", input),
+            IdResolveError::Ambiguous(ids) => write!(f, "Ambiguous application, candidates: {}", ids.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for IdResolveError {}
+
+/// Normalize an identifier to desktop file ID form without consulting installed entries
+///
+/// Strips surrounding whitespace, any directory components and the `.desktop` suffix:
+/// `/usr/share/applications/firefox.desktop` and `firefox.desktop` both become `firefox`.
+pub fn normalize_desktop_id(input: &str) -> String {
+    let input = input.trim();
+    let file_name = if input.contains('/') {
+        Path::new(input)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| input.to_string())
+    } else {
+        input.to_string()
+    };
+
+    file_name
+        .strip_suffix(".desktop")
+        .map(str::to_string)
+        .unwrap_or(file_name)
+}
+
+/// Compute the desktop file ID of `path` relative to the given `applications` directories
+///
+/// The deepest matching directory wins, so nested data dirs resolve to the ID the
+/// innermost one would assign.
+pub(crate) fn desktop_file_id(path: &Path, application_dirs: &[PathBuf]) -> Option<String> {
+    let relative = application_dirs
+        .iter()
+        .filter_map(|dir| path.strip_prefix(dir).ok())
+        .min_by_key(|relative| relative.components().count())?;
+
+    let relative = relative.to_str()?;
+    relative
+        .strip_suffix(".desktop")
+        .filter(|id| !id.is_empty())
+        .map(|id| id.replace('/', "-"))
+}
+
+/// Resolve a loose identifier ("Firefox", "firefox.desktop", "org.mozilla.firefox")
+/// to the desktop file ID of an installed entry
+pub fn resolve_desktop_id(input: &str) -> Result<String, IdResolveError> {
+    resolve_desktop_id_in(input, &ApplicationEntry::all())
+}
+
+/// Resolve a loose identifier against the given entries
+///
+/// Candidates are tried in decreasing order of confidence: exact ID, case-insensitive
+/// ID, last component of a reverse-DNS ID, and finally the application name. The first
+/// tier with matches decides; several matches in that tier are reported as ambiguous.
+pub fn resolve_desktop_id_in(input: &str, entries: &[ApplicationEntry]) -> Result<String, IdResolveError> {
+    let wanted = normalize_desktop_id(input);
+    let ids: Vec<(String, &ApplicationEntry)> = entries
+        .iter()
+        .filter_map(|entry| entry.id().map(|id| (id, entry)))
+        .collect();
+
+    let tiers: [MatchTier; 4] = [
+        &|id, _| id == wanted,
+        &|id, _| id.eq_ignore_ascii_case(&wanted),
+        &|id, _| {
+            id.rsplit('.')
+                .next()
+                .is_some_and(|last| id.contains('.') && last.eq_ignore_ascii_case(&wanted))
+        },
+        &|_, entry| entry.name().is_some_and(|name| name.eq_ignore_ascii_case(input.trim())),
+    ];
+
+    for matches_tier in tiers {
+        let mut found: Vec<String> = ids
+            .iter()
+            .filter(|(id, entry)| matches_tier(id, entry))
+            .map(|(id, _)| id.clone())
+            .collect();
+        found.sort();
+        found.dedup();
+
+        match found.len() {
+            0 => continue,
+            1 => return Ok(found.remove(0)),
+            _ => return Err(IdResolveError::Ambiguous(found)),
+        }
+    }
+
+    Err(IdResolveError::NotFound(input.to_string()))
+}
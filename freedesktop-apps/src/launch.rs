@@ -0,0 +1,552 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{ApplicationEntry, AutoLauncher, Elevation, ExecuteError, Launcher, StartupNotifier};
+
+/// Supplies XDG activation tokens for launches
+///
+/// Wayland compositors only let a new window take focus when the application
+/// presents a token obtained through `xdg_activation_v1` by the client the user
+/// interacted with. Tokens are single-use, so launchers that start several
+/// applications with the same options provide a fresh one per launch.
+pub trait ActivationTokenProvider: fmt::Debug + Send + Sync {
+    /// Get a token for launching `entry`, or None to launch without one
+    fn token(&self, entry: &ApplicationEntry) -> Option<String>;
+}
+
+const GIO_LAUNCHED_DESKTOP_FILE: &str = "GIO_LAUNCHED_DESKTOP_FILE";
+const LAUNCH_ID: &str = "XDG_LAUNCH_ID";
+
+/// Downloads remote URLs for applications that only open local files
+///
+/// The specification lets a launcher hand such an application a temporary copy
+/// when it is asked to open, say, an `https://` link. Nothing is downloaded unless
+/// a fetcher such as [`DownloadFetcher`](crate::DownloadFetcher) is set; without
+/// one those launches fail with [`ExecuteError::UnsupportedUrl`](crate::ExecuteError::UnsupportedUrl).
+/// Fetched files are reported by [`Launched::downloads`](crate::Launched::downloads)
+/// and removed again if the launch fails.
+pub trait UrlFetcher: fmt::Debug + Send + Sync {
+    /// Download `url` to a local file and return its path
+    fn fetch(&self, url: &str) -> io::Result<PathBuf>;
+}
+
+/// Callbacks around spawning a launched program
+///
+/// Lets integrators add sandboxing, logging or resource limits without replacing
+/// the [`Launcher`]. Backends call the hooks through [`LaunchRequest::spawn`], so
+/// they see the process the backend starts: `systemd-run` or `flatpak-spawn` for
+/// those launchers, the intermediate process for [`DetachMode::DoubleFork`].
+/// D-Bus activation starts no process and calls neither hook.
+///
+/// [`LaunchRequest::spawn`]: crate::LaunchRequest::spawn
+pub trait LaunchHooks: fmt::Debug + Send + Sync {
+    /// Adjust the command before it is spawned; an error cancels the launch
+    fn before_spawn(&self, _entry: &ApplicationEntry, _command: &mut Command) -> Result<(), ExecuteError> {
+        Ok(())
+    }
+
+    /// Called once the process is running
+    fn after_spawn(&self, _entry: &ApplicationEntry, _pid: u32) {}
+}
+
+/// How a launched program is separated from this process, see [`LaunchOptions::detach_mode`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DetachMode {
+    /// Stay in this process's process group and share its stdin, stdout and stderr
+    None,
+    /// Get a new process group and no stdio, but stay in this session
+    #[default]
+    ProcessGroup,
+    /// Get a new session with `setsid`, leaving this process's controlling terminal
+    Session,
+    /// Fork twice so the program is reparented to init (or the subreaper) right away
+    ///
+    /// The program gets a new session as well. Its PID isn't known, so
+    /// [`SpawnLauncher`](crate::SpawnLauncher) reports none and
+    /// [`ApplicationEntry::spawn_with`] returns the intermediate process, which has
+    /// already exited.
+    DoubleFork,
+}
+
+/// Options for launching a single entry with [`ApplicationEntry::launch_with`]
+///
+/// ```no_run
+/// use freedesktop_apps::{ApplicationEntry, LaunchOptions};
+///
+/// let entry = ApplicationEntry::find_by_id("org.gnome.TextEditor").unwrap();
+/// let options = LaunchOptions::new()
+///     .env("XDG_ACTIVATION_TOKEN", "token")
+///     .env("LANG", "de_DE.UTF-8")
+///     .env_remove("http_proxy");
+/// entry.launch_with(&options).unwrap();
+/// ```
+///
+/// [`ApplicationEntry::launch_with`]: crate::ApplicationEntry::launch_with
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    hold_terminal: bool,
+    env: Vec<(String, String)>,
+    env_remove: Vec<String>,
+    env_clear: bool,
+    working_dir: Option<PathBuf>,
+    detach: DetachMode,
+    launcher: Option<Arc<dyn Launcher>>,
+    startup_notifier: Option<Arc<dyn StartupNotifier>>,
+    startup_time: Option<u32>,
+    activation_token: Option<String>,
+    token_provider: Option<Arc<dyn ActivationTokenProvider>>,
+    url_fetcher: Option<Arc<dyn UrlFetcher>>,
+    gio_launched_env: bool,
+    gio_launched_pid: bool,
+    launch_id: Option<String>,
+    startup_grace: Option<Duration>,
+    elevation: Elevation,
+    hooks: Option<Arc<dyn LaunchHooks>>,
+    require_trusted: bool,
+    #[cfg(feature = "dbus")]
+    call_options: freedesktop_core::call::CallOptions,
+    #[cfg(feature = "dbus")]
+    session_bus: Option<zbus::blocking::Connection>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        LaunchOptions {
+            hold_terminal: false,
+            env: Vec::new(),
+            env_remove: Vec::new(),
+            env_clear: false,
+            working_dir: None,
+            detach: DetachMode::ProcessGroup,
+            launcher: None,
+            startup_notifier: None,
+            startup_time: None,
+            activation_token: None,
+            token_provider: None,
+            url_fetcher: None,
+            gio_launched_env: true,
+            gio_launched_pid: false,
+            launch_id: None,
+            startup_grace: None,
+            elevation: Elevation::Never,
+            hooks: None,
+            require_trusted: false,
+            #[cfg(feature = "dbus")]
+            call_options: freedesktop_core::call::CallOptions::default(),
+            #[cfg(feature = "dbus")]
+            session_bus: None,
+        }
+    }
+}
+
+impl PartialEq for LaunchOptions {
+    /// Backends, notifiers, providers and hooks compare equal when they are the same instance
+    fn eq(&self, other: &Self) -> bool {
+        fn same<T: ?Sized>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+
+        #[cfg(feature = "dbus")]
+        if self.call_options != other.call_options
+            || self.session_bus.as_ref().map(|bus| bus.unique_name())
+                != other.session_bus.as_ref().map(|bus| bus.unique_name())
+        {
+            return false;
+        }
+        self.hold_terminal == other.hold_terminal
+            && self.env == other.env
+            && self.env_remove == other.env_remove
+            && self.env_clear == other.env_clear
+            && self.working_dir == other.working_dir
+            && self.detach == other.detach
+            && same(&self.launcher, &other.launcher)
+            && same(&self.startup_notifier, &other.startup_notifier)
+            && self.startup_time == other.startup_time
+            && self.activation_token == other.activation_token
+            && same(&self.token_provider, &other.token_provider)
+            && same(&self.url_fetcher, &other.url_fetcher)
+            && self.gio_launched_env == other.gio_launched_env
+            && self.gio_launched_pid == other.gio_launched_pid
+            && self.launch_id == other.launch_id
+            && self.startup_grace == other.startup_grace
+            && self.elevation == other.elevation
+            && same(&self.hooks, &other.hooks)
+            && self.require_trusted == other.require_trusted
+    }
+}
+
+impl Eq for LaunchOptions {}
+
+impl LaunchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the terminal window of a Terminal=true entry open after the program exits
+    ///
+    /// Lets users read the output of short-lived command line programs started from a
+    /// menu. Emulators with a hold flag (`-hold`, `--hold`) get that flag; any other
+    /// terminal runs the program through a shell that waits for Enter.
+    pub fn hold_terminal(mut self, hold: bool) -> Self {
+        self.hold_terminal = hold;
+        self
+    }
+
+    /// Set an environment variable for the launched program
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        let key = key.into();
+        self.env_remove.retain(|k| *k != key);
+        self.env.retain(|(k, _)| *k != key);
+        self.env.push((key, value.into()));
+        self
+    }
+
+    /// Remove an inherited environment variable
+    pub fn env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        let key = key.into();
+        self.env.retain(|(k, _)| *k != key);
+        self.env_remove.push(key);
+        self
+    }
+
+    /// Start the program with an empty environment
+    ///
+    /// The display variables (WAYLAND_DISPLAY, DISPLAY, XDG_RUNTIME_DIR,
+    /// XDG_SESSION_TYPE and XDG_CURRENT_DESKTOP) are still passed on so the program
+    /// can open windows; remove them with [`LaunchOptions::env_remove`] if needed.
+    pub fn env_clear(mut self, clear: bool) -> Self {
+        self.env_clear = clear;
+        self
+    }
+
+    /// Run the program in this directory instead of the entry's Path
+    pub fn working_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.working_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Detach the program from this process (the default)
+    ///
+    /// A detached program gets its own process group and no stdio. Without
+    /// detaching it inherits this process's stdin, stdout and stderr and stays in
+    /// its process group, so it receives signals such as Ctrl+C along with it.
+    /// Shorthand for [`DetachMode::ProcessGroup`] and [`DetachMode::None`].
+    pub fn detach(self, detach: bool) -> Self {
+        self.detach_mode(if detach { DetachMode::ProcessGroup } else { DetachMode::None })
+    }
+
+    /// Choose how the program is detached from this process
+    ///
+    /// Compositors may want their clients in a session of their own, while a CLI
+    /// wrapper that waits for the program keeps it attached.
+    pub fn detach_mode(mut self, mode: DetachMode) -> Self {
+        self.detach = mode;
+        self
+    }
+
+    /// Watch the program for `grace` after starting it and report it if it fails
+    ///
+    /// The program's stderr is captured during that time. If it exits unsuccessfully
+    /// before the grace period is over, for example because a library is missing, the
+    /// launch fails with [`ExecuteError::ExitedEarly`](crate::ExecuteError::ExitedEarly)
+    /// carrying what it printed. Afterwards its stderr is read and discarded. Only
+    /// launches started by [`SpawnLauncher`](crate::SpawnLauncher) without
+    /// [`DetachMode::DoubleFork`] are watched, and the launch call blocks for up to `grace`.
+    pub fn diagnose_startup(mut self, grace: Duration) -> Self {
+        self.startup_grace = Some(grace);
+        self
+    }
+
+    /// Run the program as root through `pkexec`
+    ///
+    /// Use this instead of putting `sudo` in Exec lines: polkit asks for the password
+    /// through the desktop's authentication agent. The program only gets the display
+    /// variables and those set with [`LaunchOptions::env`], see the [`Elevation`] modes.
+    /// Terminal=true entries run their terminal emulator as root. Launchers that
+    /// build their own command line instead of using [`LaunchRequest::command`]
+    /// ignore this.
+    ///
+    /// [`LaunchRequest::command`]: crate::LaunchRequest::command
+    pub fn elevation(mut self, elevation: Elevation) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    /// Call these hooks around spawning the program
+    pub fn hooks(mut self, hooks: Arc<dyn LaunchHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Refuse to run desktop files that aren't trusted
+    ///
+    /// Launching an entry for which [`ApplicationEntry::is_trusted`] is false fails with
+    /// [`ExecuteError::Untrusted`], so file managers and launchers opening arbitrary
+    /// files can ask the user to mark it executable first.
+    pub fn require_trusted(mut self, require: bool) -> Self {
+        self.require_trusted = require;
+        self
+    }
+
+    /// Limit how long D-Bus calls made for the launch may take and allow cancelling them
+    ///
+    /// Covers D-Bus activation and the D-Bus backed launchers. A call that times out
+    /// fails with [`ExecuteError::Timeout`], or falls back to the Exec line where
+    /// activation would fall back on any failure; a cancelled call always fails with
+    /// [`ExecuteError::Cancelled`].
+    #[cfg(feature = "dbus")]
+    pub fn call_options(mut self, options: freedesktop_core::call::CallOptions) -> Self {
+        self.call_options = options;
+        self
+    }
+
+    /// Make the D-Bus calls for the launch on this session bus connection
+    ///
+    /// Used by D-Bus activation and by the D-Bus backed launchers that weren't
+    /// given a connection of their own. Without one each launch connects anew.
+    #[cfg(feature = "dbus")]
+    pub fn session_bus(mut self, connection: zbus::blocking::Connection) -> Self {
+        self.session_bus = Some(connection);
+        self
+    }
+
+    /// Start the program through the given backend instead of [`AutoLauncher`]
+    pub fn launcher(mut self, launcher: Arc<dyn Launcher>) -> Self {
+        self.launcher = Some(launcher);
+        self
+    }
+
+    /// Announce and end startup notification sequences through this notifier
+    ///
+    /// Entries with StartupNotify=true get a `DESKTOP_STARTUP_ID` either way; the
+    /// notifier is for window managers that also expect the launcher's
+    /// `_NET_STARTUP_INFO` messages.
+    pub fn startup_notifier(mut self, notifier: Arc<dyn StartupNotifier>) -> Self {
+        self.startup_notifier = Some(notifier);
+        self
+    }
+
+    /// X server timestamp of the event that triggered the launch
+    ///
+    /// Added to the startup ID so the window manager's focus-stealing prevention
+    /// lets the new window take focus.
+    pub fn startup_time(mut self, time: u32) -> Self {
+        self.startup_time = Some(time);
+        self
+    }
+
+    /// Pass an XDG activation token to the application
+    ///
+    /// The token reaches the program as `XDG_ACTIVATION_TOKEN`, and DBusActivatable
+    /// entries get it as the `activation-token` platform data.
+    pub fn activation_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.activation_token = Some(token.into());
+        self
+    }
+
+    /// Ask the provider for an activation token on every launch
+    ///
+    /// Used when no token was given with [`LaunchOptions::activation_token`].
+    pub fn activation_token_provider(mut self, provider: Arc<dyn ActivationTokenProvider>) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    /// Download remote URLs with this fetcher when the entry only opens local files
+    ///
+    /// `file://` URLs are always passed on as paths and don't need a fetcher.
+    pub fn url_fetcher(mut self, fetcher: Arc<dyn UrlFetcher>) -> Self {
+        self.url_fetcher = Some(fetcher);
+        self
+    }
+
+    /// Tell the program which desktop file started it, the way GIO does (on by default)
+    ///
+    /// Sets `GIO_LAUNCHED_DESKTOP_FILE` to the entry's path. See
+    /// [`LaunchOptions::gio_launched_pid`] for the matching PID.
+    pub fn gio_launched_env(mut self, enabled: bool) -> Self {
+        self.gio_launched_env = enabled;
+        self
+    }
+
+    /// Also set `GIO_LAUNCHED_DESKTOP_FILE_PID` to the PID of the started program (off by default)
+    ///
+    /// Session tools compare it against their own PID to tell whether
+    /// `GIO_LAUNCHED_DESKTOP_FILE` is meant for them or was inherited. The PID is
+    /// only known once the process runs, so the program is exec'd through `sh`:
+    /// a program that can't be executed then shows up as `sh` exiting with 127
+    /// rather than as a spawn error. The PID is only right for programs started
+    /// directly, so it isn't set for elevated launches, and backends that wrap the
+    /// command, such as [`SystemdScopeLauncher`](crate::SystemdScopeLauncher) or
+    /// [`FlatpakSpawnLauncher`](crate::FlatpakSpawnLauncher), shouldn't be combined with it.
+    pub fn gio_launched_pid(mut self, enabled: bool) -> Self {
+        self.gio_launched_pid = enabled;
+        self
+    }
+
+    /// Tag the launch with an opaque ID for tracing it across processes
+    ///
+    /// The program gets the ID as `XDG_LAUNCH_ID` and the [`AuditLog`](crate::AuditLog)
+    /// records it with the launch, so a desktop suite can tell which UI action started
+    /// which process. Without an ID, launches pass on the one this process was started
+    /// with, if any.
+    pub fn launch_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.launch_id = Some(id.into());
+        self
+    }
+
+    /// Get the launch ID the program sees, given with [`LaunchOptions::launch_id`],
+    /// set as a variable or inherited from this process
+    pub(crate) fn launch_id_value(&self) -> Option<String> {
+        if let Some(id) = self.env_value(LAUNCH_ID).or(self.launch_id.as_deref()) {
+            return Some(id.to_string());
+        }
+        if self.env_clear || self.env_remove.iter().any(|k| k == LAUNCH_ID) {
+            return None;
+        }
+        std::env::var(LAUNCH_ID).ok().filter(|id| !id.is_empty())
+    }
+
+    /// Get these options with the variables the program gets for a launch of `entry`:
+    /// its activation token, launch ID and the GIO launched desktop file, see
+    /// [`LaunchOptions::gio_launched_env`]
+    ///
+    /// [`Elevation::IfRequired`] is also resolved for the entry.
+    pub(crate) fn with_launch_env(&self, entry: &ApplicationEntry) -> Cow<'_, LaunchOptions> {
+        let mut options = self.with_activation_token(entry);
+        if self.elevation == Elevation::IfRequired {
+            let elevation = if entry.requires_root() { Elevation::Always } else { Elevation::Never };
+            options = Cow::Owned(options.into_owned().elevation(elevation));
+        }
+        if let Some(id) = self.launch_id.as_ref().filter(|_| self.env_value(LAUNCH_ID).is_none()) {
+            options = Cow::Owned(options.into_owned().env(LAUNCH_ID, id.clone()));
+        }
+        if !self.gio_launched_env || self.env_value(GIO_LAUNCHED_DESKTOP_FILE).is_some() {
+            return options;
+        }
+        let path = entry.path().to_string_lossy().to_string();
+        Cow::Owned(options.into_owned().env(GIO_LAUNCHED_DESKTOP_FILE, path))
+    }
+
+    /// Check if commands built from these options set `GIO_LAUNCHED_DESKTOP_FILE_PID`
+    pub(crate) fn sets_gio_launched_pid(&self) -> bool {
+        self.gio_launched_pid && !self.elevates() && self.env_value(GIO_LAUNCHED_DESKTOP_FILE).is_some()
+    }
+
+    /// Get these options with `XDG_ACTIVATION_TOKEN` set to the token for launching `entry`
+    ///
+    /// A token already set with [`LaunchOptions::env`] is kept as it is.
+    pub(crate) fn with_activation_token(&self, entry: &ApplicationEntry) -> Cow<'_, LaunchOptions> {
+        if self.env_value("XDG_ACTIVATION_TOKEN").is_some() {
+            return Cow::Borrowed(self);
+        }
+
+        let token = self
+            .activation_token
+            .clone()
+            .or_else(|| self.token_provider.as_ref()?.token(entry));
+        match token {
+            Some(token) => Cow::Owned(self.clone().env("XDG_ACTIVATION_TOKEN", token)),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    pub(crate) fn startup_notifier_ref(&self) -> Option<&dyn StartupNotifier> {
+        self.startup_notifier.as_deref()
+    }
+
+    pub(crate) fn startup_time_value(&self) -> Option<u32> {
+        self.startup_time
+    }
+
+    #[cfg(feature = "dbus")]
+    pub(crate) fn call_options_ref(&self) -> &freedesktop_core::call::CallOptions {
+        &self.call_options
+    }
+
+    /// Get the connection set with [`LaunchOptions::session_bus`], or connect to the session bus
+    #[cfg(feature = "dbus")]
+    pub(crate) fn session_bus_connection(&self) -> zbus::Result<zbus::blocking::Connection> {
+        match &self.session_bus {
+            Some(connection) => Ok(connection.clone()),
+            None => zbus::blocking::Connection::session(),
+        }
+    }
+
+    pub(crate) fn hooks_ref(&self) -> Option<&dyn LaunchHooks> {
+        self.hooks.as_deref()
+    }
+
+    pub(crate) fn url_fetcher_ref(&self) -> Option<&dyn UrlFetcher> {
+        self.url_fetcher.as_deref()
+    }
+
+    pub(crate) fn launcher_or_auto(&self) -> &dyn Launcher {
+        self.launcher.as_deref().unwrap_or(&AutoLauncher)
+    }
+
+    pub(crate) fn requires_trusted(&self) -> bool {
+        self.require_trusted
+    }
+
+    pub(crate) fn holds_terminal(&self) -> bool {
+        self.hold_terminal
+    }
+
+    /// Check if commands built from these options run through pkexec
+    pub(crate) fn elevates(&self) -> bool {
+        self.elevation == Elevation::Always
+    }
+
+    pub(crate) fn startup_grace(&self) -> Option<Duration> {
+        self.startup_grace
+    }
+
+    pub(crate) fn detaches(&self) -> bool {
+        self.detach != DetachMode::None
+    }
+
+    pub(crate) fn detach_mode_value(&self) -> DetachMode {
+        self.detach
+    }
+
+    pub(crate) fn working_dir_override(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    /// Apply the environment changes to a command whose display variables are already set
+    pub(crate) fn apply_env(&self, cmd: &mut Command) {
+        for key in &self.env_remove {
+            cmd.env_remove(key);
+        }
+        cmd.envs(self.env.iter().map(|(k, v)| (k, v)));
+    }
+
+    /// Get a variable set with [`LaunchOptions::env`]
+    pub(crate) fn env_value(&self, key: &str) -> Option<&str> {
+        self.env.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Get the variables removed with [`LaunchOptions::env_remove`]
+    pub(crate) fn env_removed(&self) -> impl Iterator<Item = &str> {
+        self.env_remove.iter().map(String::as_str)
+    }
+
+    /// Get the variables set with [`LaunchOptions::env`]
+    pub(crate) fn env_vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.env.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub(crate) fn clears_env(&self) -> bool {
+        self.env_clear
+    }
+}
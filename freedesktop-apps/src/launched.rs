@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::StartupSequence;
+
+/// A handle to an application process started by a launch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launched {
+    id: Option<String>,
+    pid: Option<u32>,
+    started: Instant,
+    startup: Option<StartupSequence>,
+    downloads: Vec<PathBuf>,
+}
+
+impl Launched {
+    pub(crate) fn new(id: Option<String>, pid: Option<u32>) -> Self {
+        Launched {
+            id,
+            pid,
+            started: Instant::now(),
+            startup: None,
+            downloads: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_startup(mut self, startup: Option<StartupSequence>) -> Self {
+        self.startup = startup;
+        self
+    }
+
+    pub(crate) fn with_downloads(mut self, downloads: Vec<PathBuf>) -> Self {
+        self.downloads = downloads;
+        self
+    }
+
+    /// Get the desktop file ID of the launched entry, if it has one
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Get the process ID of the spawned program
+    ///
+    /// For programs that hand off to an existing instance and exit this is
+    /// the short-lived process, not the instance that shows the window. None
+    /// when the [`Launcher`](crate::Launcher) didn't report one, as with D-Bus activation.
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Get the startup notification sequence of the launch
+    ///
+    /// Pass it to [`StartupNotifier::complete`](crate::StartupNotifier::complete) once
+    /// the application's window appears or the launch is given up on.
+    pub fn startup(&self) -> Option<&StartupSequence> {
+        self.startup.as_ref()
+    }
+
+    /// Get the temporary copies of remote URLs downloaded for the launch
+    ///
+    /// See [`LaunchOptions::url_fetcher`](crate::LaunchOptions::url_fetcher). They
+    /// belong to the caller, who removes them once the application is done with
+    /// them, e.g. after it exits.
+    pub fn downloads(&self) -> &[PathBuf] {
+        &self.downloads
+    }
+
+    /// Get the time elapsed since the launch
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Check whether the spawned process is still running
+    ///
+    /// Exited processes that haven't been reaped yet count as not running, and so
+    /// do launches without a known PID.
+    pub fn is_running(&self) -> bool {
+        let Some(pid) = self.pid else {
+            return false;
+        };
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            // The state follows the parenthesized command name
+            Ok(stat) => stat
+                .rsplit_once(')')
+                .and_then(|(_, rest)| rest.split_whitespace().next())
+                .is_some_and(|state| state != "Z" && state != "X"),
+            Err(_) => unsafe { libc::kill(pid as libc::pid_t, 0) == 0 },
+        }
+    }
+}
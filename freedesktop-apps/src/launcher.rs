@@ -0,0 +1,600 @@
+//! Pluggable backends that start a prepared command
+//!
+//! [`ApplicationEntry::launch_with`] validates the entry, expands its field codes
+//! and wraps terminal programs, then hands the result to a [`Launcher`] as a
+//! [`LaunchRequest`]. Embedders such as compositors or kiosks can implement the
+//! trait to start programs through their own supervisor and set it with
+//! [`LaunchOptions::launcher`]; otherwise [`AutoLauncher`] picks a backend.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::elevate::pkexec_prefix;
+use crate::{ApplicationEntry, DetachMode, ExecuteError, LaunchOptions};
+
+/// Shell script that sets GIO_LAUNCHED_DESKTOP_FILE_PID and runs its arguments
+const GIO_PID_SCRIPT: &str = r#"GIO_LAUNCHED_DESKTOP_FILE_PID=$$; export GIO_LAUNCHED_DESKTOP_FILE_PID; exec "$@""#;
+
+/// Variables a program needs to reach the display, kept even when the environment is cleared
+const DISPLAY_VARS: [&str; 5] =
+    ["WAYLAND_DISPLAY", "DISPLAY", "XDG_RUNTIME_DIR", "XDG_SESSION_TYPE", "XDG_CURRENT_DESKTOP"];
+
+/// A backend that starts applications
+pub trait Launcher: fmt::Debug + Send + Sync {
+    /// Start the request, returning the PID of the started process if there is one
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError>;
+}
+
+/// A fully prepared launch, passed to a [`Launcher`]
+#[derive(Debug)]
+pub struct LaunchRequest<'a> {
+    entry: &'a ApplicationEntry,
+    program: String,
+    args: Vec<String>,
+    working_dir: Option<PathBuf>,
+    files: &'a [&'a str],
+    urls: &'a [&'a str],
+    options: &'a LaunchOptions,
+}
+
+impl<'a> LaunchRequest<'a> {
+    pub(crate) fn new(
+        entry: &'a ApplicationEntry,
+        (program, args): (String, Vec<String>),
+        files: &'a [&'a str],
+        urls: &'a [&'a str],
+        options: &'a LaunchOptions,
+    ) -> Self {
+        LaunchRequest {
+            entry,
+            program,
+            args,
+            working_dir: working_dir(entry, options),
+            files,
+            urls,
+            options,
+        }
+    }
+
+    /// Get the entry being launched
+    pub fn entry(&self) -> &ApplicationEntry {
+        self.entry
+    }
+
+    /// Get the program to run, after field code expansion and terminal wrapping
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Get the program's arguments
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Get the directory to run the program in
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    /// Get the files passed to the launch
+    pub fn files(&self) -> &[&str] {
+        self.files
+    }
+
+    /// Get the URLs passed to the launch
+    pub fn urls(&self) -> &[&str] {
+        self.urls
+    }
+
+    /// Get the options of the launch
+    pub fn options(&self) -> &LaunchOptions {
+        self.options
+    }
+
+    /// Build a command for the program with the working directory, environment and
+    /// detaching of the options applied
+    pub fn command(&self) -> Command {
+        self.command_for(&self.program, &self.args)
+    }
+
+    /// Build a command like [`LaunchRequest::command`] that runs `program` instead,
+    /// for backends that wrap the application's command line
+    pub fn command_for(&self, program: &str, args: &[String]) -> Command {
+        build_command(program, args, self.working_dir(), self.options)
+    }
+
+    /// Spawn a command built for this request, running the [`LaunchHooks`](crate::LaunchHooks) of the options
+    ///
+    /// Backends that start a process should spawn it through this.
+    pub fn spawn(&self, mut command: Command) -> Result<Child, ExecuteError> {
+        let hooks = self.options.hooks_ref();
+        if let Some(hooks) = hooks {
+            hooks.before_spawn(self.entry, &mut command)?;
+        }
+        let child = command.spawn()?;
+        if let Some(hooks) = hooks {
+            hooks.after_spawn(self.entry, child.id());
+        }
+        Ok(child)
+    }
+}
+
+/// Get the directory to run an entry's program in; the caller's working directory
+/// overrides the entry's Path
+pub(crate) fn working_dir(entry: &ApplicationEntry, options: &LaunchOptions) -> Option<PathBuf> {
+    options
+        .working_dir_override()
+        .map(Path::to_path_buf)
+        .or_else(|| entry.path_dir().map(PathBuf::from))
+}
+
+/// Build a command with the working directory, environment and detaching of the options applied
+pub(crate) fn build_command(program: &str, args: &[String], working_dir: Option<&Path>, options: &LaunchOptions) -> Command {
+    let mut argv = Vec::new();
+    if options.elevates() {
+        argv.extend(pkexec_prefix(working_dir, options));
+    }
+    if options.sets_gio_launched_pid() {
+        // The shell execs the program in place, so $$ is the program's PID
+        argv.extend(["sh", "-c", GIO_PID_SCRIPT, "sh"].map(String::from));
+    }
+    argv.push(program.to_string());
+
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]).args(args);
+
+    if options.detaches() {
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+    }
+
+    if let Some(dir) = working_dir {
+        cmd.current_dir(dir);
+    }
+
+    if options.clears_env() {
+        cmd.env_clear();
+    }
+
+    // Explicitly preserve important environment variables
+    for key in DISPLAY_VARS {
+        if let Ok(value) = std::env::var(key) {
+            cmd.env(key, value);
+        }
+    }
+
+    options.apply_env(&mut cmd);
+    detach(&mut cmd, options.detach_mode_value());
+
+    cmd
+}
+
+/// Move the program out of this process's group or session, as `mode` says
+#[cfg(unix)]
+fn detach(cmd: &mut Command, mode: DetachMode) {
+    use std::os::unix::process::CommandExt;
+
+    match mode {
+        DetachMode::None => {}
+        DetachMode::ProcessGroup => unsafe {
+            cmd.pre_exec(|| {
+                // Start new process group but don't create new session
+                // This allows detachment while preserving session environment
+                libc::setpgid(0, 0);
+                Ok(())
+            });
+        },
+        DetachMode::Session => unsafe {
+            cmd.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        },
+        DetachMode::DoubleFork => unsafe {
+            cmd.pre_exec(|| {
+                // The intermediate process exits at once; the grandchild goes on to
+                // exec the program and reports exec failures through std's pipe
+                match libc::fork() {
+                    -1 => return Err(std::io::Error::last_os_error()),
+                    0 => {}
+                    _ => libc::_exit(0),
+                }
+                libc::setsid();
+                Ok(())
+            });
+        },
+    }
+}
+
+/// Without process groups and sessions, detaching only releases the standard streams
+#[cfg(not(unix))]
+fn detach(_cmd: &mut Command, _mode: DetachMode) {}
+
+/// Wait for a child on a background thread so it doesn't linger as a zombie once
+/// it exits, returning its PID
+fn reap(mut child: Child) -> u32 {
+    let pid = child.id();
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    pid
+}
+
+/// Runs the command as a child process
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpawnLauncher;
+
+impl Launcher for SpawnLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let mut command = request.command();
+        if request.options().detach_mode_value() == DetachMode::DoubleFork {
+            // Reap the intermediate process; the program's own PID isn't known
+            request.spawn(command)?.wait()?;
+            return Ok(None);
+        }
+        let Some(grace) = request.options().startup_grace() else {
+            return Ok(Some(reap(request.spawn(command)?)));
+        };
+
+        command.stderr(Stdio::piped());
+        let mut child = request.spawn(command)?;
+        let pid = child.id();
+        let chunks = child.stderr.take().map(drain);
+        let mut stderr = Vec::new();
+        let deadline = Instant::now() + grace;
+
+        loop {
+            if let Some(status) = child.try_wait()? {
+                if status.success() {
+                    return Ok(Some(pid));
+                }
+                // Collect the rest of the output, unless a forked child keeps the pipe open
+                if let Some(chunks) = &chunks {
+                    while let Ok(chunk) = chunks.recv_timeout(Duration::from_millis(100)) {
+                        if stderr.len() < STDERR_LIMIT {
+                            stderr.extend(chunk);
+                        }
+                    }
+                }
+                return Err(ExecuteError::ExitedEarly {
+                    code: status.code(),
+                    stderr: String::from_utf8_lossy(&stderr).into_owned(),
+                });
+            }
+
+            let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|left| !left.is_zero()) else {
+                return Ok(Some(reap(child)));
+            };
+            let poll = left.min(Duration::from_millis(10));
+            match chunks.as_ref().map(|chunks| chunks.recv_timeout(poll)) {
+                Some(Ok(chunk)) if stderr.len() < STDERR_LIMIT => stderr.extend(chunk),
+                Some(Ok(_)) | Some(Err(RecvTimeoutError::Timeout)) => {}
+                None | Some(Err(RecvTimeoutError::Disconnected)) => std::thread::sleep(poll),
+            }
+        }
+    }
+}
+
+/// Most stderr output kept for [`ExecuteError::ExitedEarly`]
+const STDERR_LIMIT: usize = 64 * 1024;
+
+/// Read a pipe on a thread, sending what arrives until nobody listens any more
+///
+/// Reading goes on after that so the program doesn't get SIGPIPE when writing to stderr.
+fn drain(mut pipe: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let _ = sender.send(buf[..n].to_vec());
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    });
+    receiver
+}
+
+/// Runs the command in its own transient systemd scope with `systemd-run --user --scope`
+///
+/// Each application gets an `app-<id>-<random>.scope` unit, following the systemd
+/// desktop environment conventions, so resource control and `systemctl --user`
+/// apply to it separately from the launcher.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemdScopeLauncher;
+
+impl Launcher for SystemdScopeLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let mut args = vec![
+            "--user".to_string(),
+            "--scope".to_string(),
+            "--collect".to_string(),
+            "--quiet".to_string(),
+            format!("--unit={}", scope_unit_name(request.entry())),
+            "--".to_string(),
+            request.program().to_string(),
+        ];
+        args.extend(request.args().iter().cloned());
+
+        // systemd-run execs the program in place, so the PID is the application's
+        let child = request.spawn(request.command_for("systemd-run", &args))?;
+        Ok(Some(reap(child)))
+    }
+}
+
+/// Get a unique `app-<id>-<random>.scope` unit name for a launch of `entry`
+fn scope_unit_name(entry: &ApplicationEntry) -> String {
+    let id = entry.id().unwrap_or_else(|| "unknown".to_string());
+    let random = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or_default();
+    format!("app-{}-{:x}{:x}.scope", escape_unit_name(&id), std::process::id(), random)
+}
+
+/// Escape a string for use in a unit name, the way `systemd-escape` does
+fn escape_unit_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for (i, byte) in name.bytes().enumerate() {
+        match byte {
+            b'.' if i == 0 => escaped.push_str("\\x2e"),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b':' | b'_' | b'.' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Runs the command on the host from inside a Flatpak sandbox with `flatpak-spawn --host`
+///
+/// Works without the `dbus` feature, but like `FlatpakHostLauncher` needs
+/// `--talk-name=org.freedesktop.Flatpak`. The working directory and the variables
+/// set or removed with [`LaunchOptions`] are passed on; clearing the environment is
+/// not supported. The reported PID is that of `flatpak-spawn`, which stays in the
+/// sandbox until the host process exits and forwards signals to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatpakSpawnLauncher {
+    executable: String,
+}
+
+impl Default for FlatpakSpawnLauncher {
+    fn default() -> Self {
+        FlatpakSpawnLauncher {
+            executable: "flatpak-spawn".to_string(),
+        }
+    }
+}
+
+impl FlatpakSpawnLauncher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run this program instead of `flatpak-spawn`, e.g. a wrapper with the same options
+    pub fn with_executable<S: Into<String>>(executable: S) -> Self {
+        FlatpakSpawnLauncher {
+            executable: executable.into(),
+        }
+    }
+}
+
+impl Launcher for FlatpakSpawnLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let mut args = vec!["--host".to_string()];
+        if let Some(dir) = request.working_dir() {
+            args.push(format!("--directory={}", dir.display()));
+        }
+        args.extend(request.options().env_removed().map(|key| format!("--unset-env={}", key)));
+        args.extend(request.options().env_vars().map(|(key, value)| format!("--env={}={}", key, value)));
+        args.push(request.program().to_string());
+        args.extend(request.args().iter().cloned());
+
+        let child = request.spawn(request.command_for(&self.executable, &args))?;
+        Ok(Some(reap(child)))
+    }
+}
+
+/// Starts the command and moves it into its own transient systemd scope over D-Bus
+///
+/// Does what [`SystemdScopeLauncher`] does without needing `systemd-run`, the way
+/// GNOME Shell and Plasma do: the process is spawned directly and then handed to
+/// the user manager with `StartTransientUnit`. If the user manager can't be reached
+/// the program keeps running in the launcher's own cgroup.
+#[cfg(feature = "dbus")]
+#[derive(Debug, Clone, Default)]
+pub struct TransientScopeLauncher {
+    connection: Option<zbus::blocking::Connection>,
+}
+
+#[cfg(feature = "dbus")]
+impl TransientScopeLauncher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use an existing session bus connection instead of the launch options' one
+    pub fn with_connection(connection: zbus::blocking::Connection) -> Self {
+        Self {
+            connection: Some(connection),
+        }
+    }
+
+    fn start_scope(&self, unit: &str, pid: u32, options: &LaunchOptions) -> Result<(), ExecuteError> {
+        use zbus::zvariant::Value;
+
+        let connection = match &self.connection {
+            Some(connection) => connection.clone(),
+            None => options.session_bus_connection()?,
+        };
+
+        let properties: Vec<(&str, Value<'_>)> = vec![
+            ("PIDs", Value::from(vec![pid])),
+            ("CollectMode", Value::from("inactive-or-failed")),
+        ];
+        let aux: Vec<(&str, Vec<(&str, Value<'_>)>)> = Vec::new();
+        options.call_options_ref().block_on(async {
+            let proxy = zbus::Proxy::new(
+                connection.inner(),
+                "org.freedesktop.systemd1",
+                "/org/freedesktop/systemd1",
+                "org.freedesktop.systemd1.Manager",
+            )
+            .await?;
+            let _job: zbus::zvariant::OwnedObjectPath =
+                proxy.call("StartTransientUnit", &(unit, "fail", properties, aux)).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl Launcher for TransientScopeLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let child = request.spawn(request.command())?;
+        // The program is already running, so a failure to place it isn't a failed launch
+        let _ = self.start_scope(&scope_unit_name(request.entry()), child.id(), request.options());
+        Ok(Some(reap(child)))
+    }
+}
+
+/// Activates DBusActivatable entries through `org.freedesktop.Application`
+///
+/// Files and URLs are passed to `Open`, anything else calls `Activate`. Fails for
+/// entries that aren't DBusActivatable. No process is reported.
+#[cfg(feature = "dbus")]
+#[derive(Debug, Clone, Default)]
+pub struct DBusLauncher {
+    connection: Option<zbus::blocking::Connection>,
+}
+
+#[cfg(feature = "dbus")]
+impl DBusLauncher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use an existing session bus connection instead of the launch options' one
+    pub fn with_connection(connection: zbus::blocking::Connection) -> Self {
+        Self {
+            connection: Some(connection),
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl Launcher for DBusLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let connection = match &self.connection {
+            Some(connection) => connection.clone(),
+            None => request.options().session_bus_connection()?,
+        };
+
+        let mut uris: Vec<String> = request.urls().iter().map(|url| url.to_string()).collect();
+        uris.extend(request.files().iter().map(|file| {
+            let path = std::fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file));
+            freedesktop_core::file_uri(&path)
+        }));
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+
+        request.entry().dbus_activate(&connection, &uris, request.options())?;
+        Ok(None)
+    }
+}
+
+/// Runs the command on the host from inside a Flatpak sandbox
+///
+/// Uses `HostCommand` of the Flatpak session helper (`org.freedesktop.Flatpak`),
+/// which the app needs `--talk-name=org.freedesktop.Flatpak` to reach. Variables set
+/// with [`LaunchOptions::env`] are passed on; removing or clearing variables is not
+/// supported by the helper.
+#[cfg(feature = "dbus")]
+#[derive(Debug, Clone, Default)]
+pub struct FlatpakHostLauncher {
+    connection: Option<zbus::blocking::Connection>,
+}
+
+#[cfg(feature = "dbus")]
+impl FlatpakHostLauncher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use an existing session bus connection instead of the launch options' one
+    pub fn with_connection(connection: zbus::blocking::Connection) -> Self {
+        Self {
+            connection: Some(connection),
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl Launcher for FlatpakHostLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        use std::collections::HashMap;
+        use std::os::unix::ffi::OsStrExt;
+
+        let connection = match &self.connection {
+            Some(connection) => connection.clone(),
+            None => request.options().session_bus_connection()?,
+        };
+
+        // Paths and arguments are NUL-terminated byte strings
+        let bytes = |s: &[u8]| s.iter().copied().chain(std::iter::once(0)).collect::<Vec<u8>>();
+        let cwd = bytes(request.working_dir().unwrap_or(Path::new("/")).as_os_str().as_bytes());
+        let argv: Vec<Vec<u8>> = std::iter::once(request.program())
+            .chain(request.args().iter().map(String::as_str))
+            .map(|arg| bytes(arg.as_bytes()))
+            .collect();
+        let fds: HashMap<u32, zbus::zvariant::OwnedFd> = HashMap::new();
+        let envs: HashMap<&str, &str> = request.options().env_vars().collect();
+
+        request.options().call_options_ref().block_on(async {
+            let proxy = zbus::Proxy::new(
+                connection.inner(),
+                "org.freedesktop.Flatpak",
+                "/org/freedesktop/Flatpak/Development",
+                "org.freedesktop.Flatpak.Development",
+            )
+            .await?;
+            let pid: u32 = proxy.call("HostCommand", &(cwd, argv, fds, envs, 0u32)).await?;
+            Ok(Some(pid))
+        })
+    }
+}
+
+/// Picks a backend for each launch
+///
+/// DBusActivatable entries are activated over D-Bus, falling back to their Exec
+/// line if that fails. Everything else is started by [`SpawnLauncher`], which
+/// inside a Flatpak sandbox starts it in the sandbox. Running commands on the
+/// host is never automatic: set `FlatpakHostLauncher` or
+/// [`FlatpakSpawnLauncher`] with [`LaunchOptions::launcher`] to ask for it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutoLauncher;
+
+impl Launcher for AutoLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        #[cfg(feature = "dbus")]
+        {
+            if request.entry().dbus_activatable() {
+                match DBusLauncher::new().launch(request) {
+                    Ok(pid) => return Ok(pid),
+                    Err(ExecuteError::Cancelled) => return Err(ExecuteError::Cancelled),
+                    Err(_) => {}
+                }
+            }
+        }
+
+        SpawnLauncher.launch(request)
+    }
+}
+
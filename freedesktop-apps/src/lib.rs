@@ -1,18 +1,182 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
+#[cfg(feature = "dbus")]
+mod activation;
+pub mod appstream;
+mod audit;
+mod autostart;
+mod category;
+mod deprecated;
+mod desktop;
+mod discovery;
+mod download;
+mod duplicates;
+mod elevate;
+mod entry_type;
+mod export;
+mod failures;
+mod gio;
+mod id;
+mod launch;
+mod launcher;
+mod launched;
+mod lookup;
+mod mailto;
+mod mimeapps;
+mod mimeinfo;
+mod open;
 mod parser;
+mod plan;
+mod policy;
+mod query;
+mod recover;
+mod registry;
+mod role;
+mod search;
+mod session;
+mod startup;
+mod terminal;
+mod trust;
+mod validate;
+mod verify;
+mod visibility;
+mod watch;
+mod window;
+mod wine;
+use freedesktop_core::keyfile::KeyFile;
+use freedesktop_core::time::Timestamp;
 use parser::{DesktopEntry, ValueType};
 
-// Re-export the ParseError from parser
-pub use parser::ParseError;
+pub use audit::AuditLog;
+pub use autostart::{autostart_dirs, autostart_report, AutostartHandling};
+pub use category::{Category, MenuSection};
+pub use deprecated::{Deprecation, SPEC_VERSION};
+pub use desktop::Desktop;
+pub use discovery::ApplicationEntries;
+pub use download::DownloadFetcher;
+pub use duplicates::{find_duplicates, DuplicateGroup, DuplicateStrategy};
+pub use elevate::Elevation;
+pub use entry_type::{DirectoryEntry, EntryType, LinkEntry};
+pub use failures::{LaunchFailure, LaunchFailures};
+pub use id::{normalize_desktop_id, resolve_desktop_id, resolve_desktop_id_in, IdResolveError};
+pub use launch::{ActivationTokenProvider, DetachMode, LaunchHooks, LaunchOptions, UrlFetcher};
+#[cfg(feature = "dbus")]
+pub use launcher::{DBusLauncher, FlatpakHostLauncher, TransientScopeLauncher};
+pub use launcher::{AutoLauncher, FlatpakSpawnLauncher, LaunchRequest, Launcher, SpawnLauncher, SystemdScopeLauncher};
+pub use launched::Launched;
+pub use mailto::{open_mailto, open_mailto_with, MailtoBuilder};
+pub use mimeinfo::MimeInfoCache;
+pub use open::{open, open_with, opener_for, OpenError};
+pub use mimeapps::{
+    applications_for_mime, default_application_for, default_handler_for_scheme, defaults_list_paths,
+    handlers_for_scheme, migrate_defaults_list, mimeapps_list_paths, mimeapps_list_paths_for, scheme_mime_type,
+    set_default_application, MimeAssociations,
+};
+
+// Re-export the parser's public types
+pub use parser::{escape_list, escape_value, Limit, ParseError, ParseLimits, ParseMode, ParseOptions, ValuePolicy};
+pub use plan::LaunchPlan;
+pub use policy::AppPolicy;
+pub use query::EntryQuery;
+pub use recover::Recovered;
+pub use registry::{LaunchAllOptions, LaunchResult, Registry, SharedRegistry};
+pub use role::Role;
+pub use session::Session;
+pub use startup::{StartupNotifier, StartupSequence};
+pub use terminal::{register_terminal, TerminalLauncher, TerminalProfile};
+pub use validate::{validate_dir, FileReport, ValidationIssue, ValidationReport};
+pub use verify::{verify_installation, InstallationIssue, InstallationReport, REQUIRED_ICON_SIZES};
+pub use visibility::Visibility;
+pub use watch::{EntryChange, EntryWatcher, RefreshPolicy, RegistryEvent, RegistryWatcher};
+pub use window::{FocusOutcome, WindowHint};
 
 #[derive(Debug, Clone)]
 pub enum ExecuteError {
     NotExecutable(String),
     TerminalNotFound,
     InvalidCommand(String),
-    IoError(String),
+    IoError(Arc<std::io::Error>),
     ValidationFailed(String),
+    /// No entry with the given ID is available
+    NotFound(String),
+    /// A D-Bus activation call failed
+    ActivationFailed(String),
+    /// The application only opens local files and the URL couldn't be turned into one
+    UnsupportedUrl(String),
+    /// The program failed right after starting, see [`LaunchOptions::diagnose_startup`]
+    ExitedEarly {
+        /// Exit code, or None if a signal killed it
+        code: Option<i32>,
+        /// What the program wrote to stderr
+        stderr: String,
+    },
+    /// The desktop file isn't trusted, see [`LaunchOptions::require_trusted`]
+    Untrusted(PathBuf),
+    /// A D-Bus call made for the launch didn't finish within its timeout
+    Timeout(Duration),
+    /// A D-Bus call made for the launch was cancelled through its cancellation token
+    Cancelled,
+    /// An entry that starts an instance per file or URL failed after some instances started
+    PartialLaunch {
+        /// The instances that are running, with their downloads
+        launched: Vec<Launched>,
+        /// Why the next instance didn't start
+        error: Box<ExecuteError>,
+    },
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecuteError::NotExecutable(msg) => write!(f, "Entry is not executable: {}", msg),
+            ExecuteError::TerminalNotFound => write!(f, "No terminal emulator found"),
+            ExecuteError::InvalidCommand(msg) => write!(f, "Invalid Exec command: {}", msg),
+            ExecuteError::IoError(e) => write!(f, "Failed to spawn process: {}", e),
+            ExecuteError::ValidationFailed(msg) => write!(f, "Validation failed: {}", msg),
+            ExecuteError::NotFound(id) => write!(f, "No application with ID '{}'", id),
+            ExecuteError::ActivationFailed(msg) => write!(f, "D-Bus activation failed: {}", msg),
+            ExecuteError::UnsupportedUrl(url) => write!(f, "Application only opens local files, not {}", url),
+            ExecuteError::Untrusted(path) => {
+                write!(f, "Desktop file {} is not trusted, mark it executable to run it", path.display())
+            }
+            ExecuteError::Timeout(timeout) => write!(f, "D-Bus call timed out after {:?}", timeout),
+            ExecuteError::Cancelled => write!(f, "Launch was cancelled"),
+            ExecuteError::PartialLaunch { launched, error } => {
+                write!(f, "Only {} instances were launched: {}", launched.len(), error)
+            }
+            ExecuteError::ExitedEarly { code, stderr } => {
+                match code {
+                    Some(code) => write!(f, "Application exited with code {} during startup", code)?,
+                    None => write!(f, "Application was killed during startup")?,
+                }
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {}", stderr),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecuteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExecuteError::IoError(e) => Some(e.as_ref()),
+            ExecuteError::PartialLaunch { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ExecuteError {
+    fn from(e: std::io::Error) -> Self {
+        ExecuteError::IoError(Arc::new(e))
+    }
 }
 
 pub fn application_entry_paths() -> Vec<PathBuf> {
@@ -23,12 +187,70 @@ pub fn application_entry_paths() -> Vec<PathBuf> {
         .collect()
 }
 
-#[derive(Debug)]
+/// XDG data directories ordered from highest to lowest precedence:
+/// XDG_DATA_HOME first, then XDG_DATA_DIRS in the order they are listed
+fn data_dirs_by_precedence() -> Vec<PathBuf> {
+    let mut dirs = freedesktop_core::base_directories();
+    let data_home = std::env::var("XDG_DATA_HOME").ok().map(PathBuf::from);
+
+    if let Some(pos) = data_home.and_then(|home| dirs.iter().rposition(|p| *p == home)) {
+        let home = dirs.remove(pos);
+        dirs.insert(0, home);
+    }
+
+    dirs
+}
+
+/// Application directories ordered from highest to lowest precedence
+fn application_entry_paths_by_precedence() -> Vec<PathBuf> {
+    data_dirs_by_precedence()
+        .iter()
+        .map(|path| path.join("applications"))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// Application directories exported by Flatpak and Snap that aren't already
+/// in the XDG data dirs, from highest to lowest precedence
+fn export_application_paths() -> Vec<PathBuf> {
+    let standard = application_entry_paths();
+    freedesktop_core::sandbox_export_directories()
+        .iter()
+        .map(|path| path.join("applications"))
+        .filter(|path| path.exists() && !standard.contains(path))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
 #[derive(Default)]
 pub struct ApplicationEntry {
     inner: DesktopEntry,
+    /// Applications directories the ID is computed against, when discovered
+    /// outside the standard directories
+    application_dirs: Option<Arc<[PathBuf]>>,
+    /// The full parse of an entry read with [`ApplicationEntry::metadata_from_path`],
+    /// done on first use and shared between clones
+    full: Option<Arc<OnceLock<Option<DesktopEntry>>>>,
 }
 
+/// The keys [`ApplicationEntry::metadata_from_path`] reads: what a launcher's list
+/// view shows and filters by, and what is needed to tell whether the entry is valid
+pub const METADATA_KEYS: [&str; 13] = [
+    "Type",
+    "Name",
+    "GenericName",
+    "Icon",
+    "NoDisplay",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "Categories",
+    "Keywords",
+    "Exec",
+    "DBusActivatable",
+    "URL",
+];
+
 
 impl ApplicationEntry {
     /// Get the application name
@@ -39,23 +261,19 @@ impl ApplicationEntry {
     /// Get the desktop file ID according to the freedesktop specification
     /// 
     /// The desktop file ID is computed by making the file path relative to the
-    /// XDG_DATA_DIRS component, removing "applications/" prefix, and converting
-    /// '/' to '-'. For example: /usr/share/applications/foo/bar.desktop → foo-bar.desktop
+    /// `applications` directory of an XDG data dir and converting '/' to '-'.
+    /// For example: /usr/share/applications/foo/bar.desktop → foo-bar
+    ///
+    /// Returns None for files outside the XDG data directories, which have no
+    /// desktop file ID. Entries found by [`ApplicationEntry::all_in`] use the
+    /// directories they were found in, and those found by
+    /// [`ApplicationEntry::all_with_exports`] the Flatpak and Snap export directories too.
     pub fn id(&self) -> Option<String> {
-        let file_path = &self.inner.path;
-        
-        // Check if this file is within any applications directory
-        if let Some(apps_pos) = file_path.to_string_lossy().find("/applications/") {
-            let after_apps = &file_path.to_string_lossy()[apps_pos + "/applications/".len()..];
-            if let Some(desktop_entry_path) = after_apps.strip_suffix(".desktop") {
-                // Convert path separators to dashes for subdirectories
-                return Some(desktop_entry_path.replace('/', "-"));
-            }
+        if let Some(dirs) = &self.application_dirs {
+            return id::desktop_file_id(&self.inner.path, dirs);
         }
-        
-        // Fallback: just use filename without extension
-        file_path.file_stem()
-            .map(|name| name.to_string_lossy().to_string())
+
+        id::desktop_file_id(&self.inner.path, &application_entry_paths())
     }
 
     /// Get the executable command
@@ -70,8 +288,7 @@ impl ApplicationEntry {
 
     /// Get a string value from the Desktop Entry group
     pub fn get_string(&self, key: &str) -> Option<String> {
-        self.inner
-            .get_desktop_entry_group()
+        self.group_for(key)
             .and_then(|group| group.get_field(key))
             .and_then(|value| match value {
                 ValueType::String(s) | ValueType::LocaleString(s) | ValueType::IconString(s) => {
@@ -83,8 +300,7 @@ impl ApplicationEntry {
 
     /// Get a localized string value from the Desktop Entry group
     pub fn get_localized_string(&self, key: &str, locale: Option<&str>) -> Option<String> {
-        self.inner
-            .get_desktop_entry_group()
+        self.group_for(key)
             .and_then(|group| group.get_localized_field(key, locale))
             .and_then(|value| match value {
                 ValueType::String(s) | ValueType::LocaleString(s) | ValueType::IconString(s) => {
@@ -96,8 +312,7 @@ impl ApplicationEntry {
 
     /// Get a boolean value from the Desktop Entry group
     pub fn get_bool(&self, key: &str) -> Option<bool> {
-        self.inner
-            .get_desktop_entry_group()
+        self.group_for(key)
             .and_then(|group| group.get_field(key))
             .and_then(|value| match value {
                 ValueType::Boolean(b) => Some(*b),
@@ -107,8 +322,7 @@ impl ApplicationEntry {
 
     /// Get a numeric value from the Desktop Entry group
     pub fn get_numeric(&self, key: &str) -> Option<f64> {
-        self.inner
-            .get_desktop_entry_group()
+        self.group_for(key)
             .and_then(|group| group.get_field(key))
             .and_then(|value| match value {
                 ValueType::Numeric(n) => Some(*n),
@@ -118,8 +332,7 @@ impl ApplicationEntry {
 
     /// Get a vector of strings from the Desktop Entry group
     pub fn get_vec(&self, key: &str) -> Option<Vec<String>> {
-        self.inner
-            .get_desktop_entry_group()
+        self.group_for(key)
             .and_then(|group| group.get_field(key))
             .and_then(|value| match value {
                 ValueType::StringList(list) | ValueType::LocaleStringList(list) => {
@@ -129,14 +342,53 @@ impl ApplicationEntry {
             })
     }
 
+    /// Get a localized string list from the Desktop Entry group
+    pub fn get_localized_vec(&self, key: &str, locale: Option<&str>) -> Option<Vec<String>> {
+        self.group_for(key)
+            .and_then(|group| group.get_localized_field(key, locale))
+            .and_then(|value| match value {
+                ValueType::StringList(list) | ValueType::LocaleStringList(list) => {
+                    Some(list.clone())
+                }
+                _ => None,
+            })
+    }
+
     /// Get the file path of this desktop entry
     pub fn path(&self) -> &Path {
         &self.inner.path
     }
 
+    /// Get the group names in the order they appear in the file
+    pub fn group_names(&self) -> Vec<&str> {
+        self.complete().group_order.iter().map(String::as_str).collect()
+    }
+
+    /// Get the keys and values of a group as written, in file order
+    ///
+    /// Keys keep their locale, as in `Name[de]`, and values their escapes, so the
+    /// group can be written back out unchanged apart from comments and blank lines.
+    pub fn group_entries(&self, group: &str) -> Option<&[(String, String)]> {
+        self.complete().groups.get(group).map(|group| group.ordered.as_slice())
+    }
+
+    /// Get every group and key as a keyfile, in file order
+    ///
+    /// Unlike [`ApplicationEntry::to_gio_keyfile`] the file isn't read again. Groups
+    /// without keys are left out.
+    pub fn to_keyfile(&self) -> KeyFile {
+        let mut keyfile = KeyFile::new();
+        for group in self.group_names() {
+            for (key, value) in self.group_entries(group).unwrap_or_default() {
+                keyfile.set(group, key, value);
+            }
+        }
+        keyfile
+    }
+
     /// Get the entry type (Application, Link, Directory)
-    pub fn entry_type(&self) -> Option<String> {
-        self.get_string("Type")
+    pub fn entry_type(&self) -> Option<EntryType> {
+        self.get_string("Type").map(|t| EntryType::from(t.as_str()))
     }
 
     /// Get generic name (e.g., "Web Browser")
@@ -153,6 +405,36 @@ impl ApplicationEntry {
         !self.is_hidden() && !self.no_display()
     }
 
+    /// Get the desktop environments the entry is restricted to
+    pub fn only_show_in(&self) -> Option<Vec<String>> {
+        self.get_vec("OnlyShowIn")
+    }
+
+    /// Get the desktop environments the entry must not be shown in
+    pub fn not_show_in(&self) -> Option<Vec<String>> {
+        self.get_vec("NotShowIn")
+    }
+
+    /// Check whether the entry is shown in the given desktop environment
+    ///
+    /// `desktop` may be a colon-separated list in the format of XDG_CURRENT_DESKTOP.
+    pub fn shows_in(&self, desktop: &str) -> bool {
+        let desktops: Vec<&str> = desktop.split(':').filter(|d| !d.is_empty()).collect();
+        let listed = |list: Option<Vec<String>>| {
+            list.map(|list| list.iter().any(|d| desktops.contains(&d.as_str())))
+        };
+
+        if listed(self.not_show_in()) == Some(true) {
+            return false;
+        }
+        listed(self.only_show_in()).unwrap_or(true)
+    }
+
+    /// Check if the application can be started over D-Bus
+    pub fn dbus_activatable(&self) -> bool {
+        self.get_bool("DBusActivatable").unwrap_or(false)
+    }
+
     /// Check if entry should be hidden
     pub fn is_hidden(&self) -> bool {
         self.get_bool("Hidden").unwrap_or(false)
@@ -173,6 +455,11 @@ impl ApplicationEntry {
         self.get_vec("Categories")
     }
 
+    /// Get the D-Bus interfaces the application implements
+    pub fn implements(&self) -> Option<Vec<String>> {
+        self.get_vec("Implements")
+    }
+
     /// Get keywords for searching
     pub fn keywords(&self) -> Option<Vec<String>> {
         self.get_vec("Keywords")
@@ -183,6 +470,22 @@ impl ApplicationEntry {
         self.get_bool("Terminal").unwrap_or(false)
     }
 
+    /// Get when the desktop file was installed or last changed
+    ///
+    /// Package managers usually preserve the modification time from the package, so
+    /// the later of the file's status change and modification times is used.
+    pub fn installed_at(&self) -> Option<Timestamp> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::metadata(&self.inner.path).ok()?;
+        Some(Timestamp::from_unix_secs(metadata.ctime().max(metadata.mtime())))
+    }
+
+    /// Check if the application only ever shows a single main window
+    pub fn single_main_window(&self) -> bool {
+        self.get_bool("SingleMainWindow").unwrap_or(false)
+    }
+
     /// Get working directory
     pub fn path_dir(&self) -> Option<String> {
         self.get_string("Path")
@@ -194,53 +497,281 @@ impl ApplicationEntry {
     }
 
     /// Execute this application with the given files
+    ///
+    /// If the Exec line only takes a single file (`%f`), one instance is started per file.
     pub fn execute_with_files(&self, files: &[&str]) -> Result<(), ExecuteError> {
         self.execute_internal(files, &[])
     }
 
     /// Execute this application with the given URLs
+    ///
+    /// If the Exec line only takes a single URL (`%u`), one instance is started per URL.
     pub fn execute_with_urls(&self, urls: &[&str]) -> Result<(), ExecuteError> {
         self.execute_internal(&[], urls)
     }
 
+    /// Launch this application with no files, returning a handle to the spawned process
+    pub fn launch(&self) -> Result<Launched, ExecuteError> {
+        self.launch_internal(&[], &[], &LaunchOptions::default())
+    }
+
+    /// Launch this application with no files using the given options
+    pub fn launch_with(&self, options: &LaunchOptions) -> Result<Launched, ExecuteError> {
+        self.launch_internal(&[], &[], options)
+    }
+
+    /// Launch this application with the given files using the given options
+    ///
+    /// Returns a handle per started instance, see [`ApplicationEntry::execute_with_files`].
+    pub fn launch_with_files(&self, files: &[&str], options: &LaunchOptions) -> Result<Vec<Launched>, ExecuteError> {
+        self.launch_instances(files, &[], options)
+    }
+
+    /// Launch this application with the given URLs using the given options
+    ///
+    /// Returns a handle per started instance, see [`ApplicationEntry::execute_with_urls`].
+    /// URLs downloaded for entries that only open files are listed in [`Launched::downloads`].
+    pub fn launch_with_urls(&self, urls: &[&str], options: &LaunchOptions) -> Result<Vec<Launched>, ExecuteError> {
+        self.launch_instances(&[], urls, options)
+    }
+
+    /// Start this application and return the child process so it can be waited on or killed
+    ///
+    /// Unlike [`ApplicationEntry::launch_with`] the process isn't forgotten. It is
+    /// still detached unless [`LaunchOptions::detach`] is turned off, which only
+    /// changes its process group and stdio. Terminal=true entries return the
+    /// terminal emulator's process.
+    pub fn spawn_with(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<Child, ExecuteError> {
+        self.spawn_internal(files, urls, options)
+    }
+
     /// Prepare the command for execution without actually executing it (for testing)
     pub fn prepare_command(&self, files: &[&str], urls: &[&str]) -> Result<(String, Vec<String>), ExecuteError> {
+        self.prepare_command_with(files, urls, &LaunchOptions::default())
+    }
+
+    /// Prepare the command [`ApplicationEntry::launch_with`] would run, without running it
+    pub fn prepare_command_with(
+        &self,
+        files: &[&str],
+        urls: &[&str],
+        options: &LaunchOptions,
+    ) -> Result<(String, Vec<String>), ExecuteError> {
+        self.prepare(files, urls, options).map(|prepared| prepared.command)
+    }
+
+    /// Validate and build the command, wrapping terminal applications
+    fn prepare(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<Prepared, ExecuteError> {
+        let mut prepared = self.expanded_command(files, urls, options)?;
+
+        // Handle terminal applications
+        if self.terminal() {
+            let (program, args) = &prepared.command;
+            prepared.command = self.wrap_with_terminal(program, args, options.holds_terminal())?;
+        }
+        Ok(prepared)
+    }
+
+    /// Validate the entry and expand its Exec line, without terminal wrapping
+    fn expanded_command(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<Prepared, ExecuteError> {
+        if options.requires_trusted() && !self.is_trusted() {
+            return Err(ExecuteError::Untrusted(self.path().to_path_buf()));
+        }
+
         // Validate the application can be executed
         self.validate_executable()?;
 
+        let (local_files, downloads) = match self.urls_as_files(urls, options)? {
+            Some((local, downloads)) => (Some(local), downloads),
+            None => (None, Vec::new()),
+        };
+        let (files, urls) = match &local_files {
+            Some(local) => (files.iter().copied().chain(local.iter().map(String::as_str)).collect(), &[][..]),
+            None => (files.to_vec(), urls),
+        };
+
         // Get the command and arguments
-        let (program, args) = self.parse_exec_command(files, urls)?;
+        match self.parse_exec_command(&files, urls) {
+            Ok(command) => Ok(Prepared { command, downloads }),
+            Err(e) => {
+                remove_downloads(&downloads);
+                Err(e)
+            }
+        }
+    }
 
-        // Handle terminal applications
-        let (final_program, final_args) = if self.terminal() {
-            self.wrap_with_terminal(&program, &args)?
-        } else {
-            (program, args)
-        };
+    /// Turn URLs into local files if the Exec line takes files but no URLs
+    ///
+    /// `file://` URLs become their path and other URLs are downloaded by the
+    /// options' [`UrlFetcher`]. Returns the paths with the downloaded files among
+    /// them, or None when the URLs can be passed as they are.
+    fn urls_as_files(&self, urls: &[&str], options: &LaunchOptions) -> Result<Option<LocalFiles>, ExecuteError> {
+        let codes = self.exec_field_codes();
+        let takes_files = codes.contains(&'f') || codes.contains(&'F');
+        let takes_urls = codes.contains(&'u') || codes.contains(&'U');
+        if urls.is_empty() || !takes_files || takes_urls {
+            return Ok(None);
+        }
 
-        Ok((final_program, final_args))
+        let mut paths = Vec::with_capacity(urls.len());
+        let mut downloads = Vec::new();
+        for url in urls {
+            if let Some(path) = freedesktop_core::file_uri_to_path(url) {
+                paths.push(path.to_string_lossy().to_string());
+                continue;
+            }
+            let fetched = match options.url_fetcher_ref() {
+                Some(fetcher) => fetcher.fetch(url).map_err(ExecuteError::from),
+                None => Err(ExecuteError::UnsupportedUrl(url.to_string())),
+            };
+            match fetched {
+                Ok(path) => {
+                    paths.push(path.to_string_lossy().to_string());
+                    downloads.push(path);
+                }
+                Err(e) => {
+                    remove_downloads(&downloads);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(Some((paths, downloads)))
     }
 
     fn execute_internal(&self, files: &[&str], urls: &[&str]) -> Result<(), ExecuteError> {
-        // Validate the application can be executed
-        self.validate_executable()?;
+        self.launch_instances(files, urls, &LaunchOptions::default()).map(|_| ())
+    }
 
-        // Get the command and arguments
-        let (program, args) = self.parse_exec_command(files, urls)?;
+    fn launch_instances(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<Vec<Launched>, ExecuteError> {
+        // %f and %u take a single argument, so each file or URL gets its own instance
+        let codes = self.exec_field_codes();
+        let takes_urls = codes.contains(&'u') || codes.contains(&'U');
+        if codes.contains(&'f') && !codes.contains(&'F') && !takes_urls && files.len() + urls.len() > 1 {
+            // URLs are opened as files here, so they get an instance each too
+            let files = files.iter().map(|file| (vec![*file], Vec::new()));
+            let urls = urls.iter().map(|url| (Vec::new(), vec![*url]));
+            return self.launch_each(files.chain(urls), options);
+        }
+        if files.len() > 1 && codes.contains(&'f') && !codes.contains(&'F') {
+            return self.launch_each(files.iter().map(|file| (vec![*file], urls.to_vec())), options);
+        }
+        if urls.len() > 1 && codes.contains(&'u') && !codes.contains(&'U') {
+            return self.launch_each(urls.iter().map(|url| (files.to_vec(), vec![*url])), options);
+        }
 
-        // Handle terminal applications
-        let (final_program, final_args) = if self.terminal() {
-            self.wrap_with_terminal(&program, &args)?
-        } else {
-            (program, args)
+        self.launch_internal(files, urls, options).map(|launched| vec![launched])
+    }
+
+    /// Launch an instance per set of files and URLs, stopping at the first failure
+    ///
+    /// A failure after some instances started is an [`ExecuteError::PartialLaunch`]
+    /// holding them, so their processes and downloads aren't lost.
+    fn launch_each<'a>(
+        &self,
+        instances: impl Iterator<Item = (Vec<&'a str>, Vec<&'a str>)>,
+        options: &LaunchOptions,
+    ) -> Result<Vec<Launched>, ExecuteError> {
+        let mut launched = Vec::new();
+        for (files, urls) in instances {
+            match self.launch_internal(&files, &urls, options) {
+                Ok(instance) => launched.push(instance),
+                Err(error) if launched.is_empty() => return Err(error),
+                Err(error) => {
+                    return Err(ExecuteError::PartialLaunch {
+                        launched,
+                        error: Box::new(error),
+                    })
+                }
+            }
+        }
+        Ok(launched)
+    }
+
+    /// Get the field codes used in the Exec key, without `%%`
+    fn exec_field_codes(&self) -> Vec<char> {
+        let exec = self.exec().unwrap_or_default();
+        let mut codes = Vec::new();
+        let mut chars = exec.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '%' {
+                match chars.next() {
+                    Some('%') | None => {}
+                    Some(code) => codes.push(code),
+                }
+            }
+        }
+        codes
+    }
+
+    fn launch_internal(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<Launched, ExecuteError> {
+        self.launch_using(files, urls, options, options.launcher_or_auto())
+    }
+
+    fn launch_using(
+        &self,
+        files: &[&str],
+        urls: &[&str],
+        options: &LaunchOptions,
+        launcher: &dyn Launcher,
+    ) -> Result<Launched, ExecuteError> {
+        self.start(files, urls, options, |request| launcher.launch(request))
+            .map(|(pid, started)| {
+                Launched::new(self.id(), pid)
+                    .with_startup(started.startup)
+                    .with_downloads(started.downloads)
+            })
+    }
+
+    fn spawn_internal(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<Child, ExecuteError> {
+        self.start(files, urls, options, |request| request.spawn(request.command()))
+            .map(|(child, _)| child)
+    }
+
+    /// Validate and build the command, wrapping terminal applications, start it and record the launch
+    fn start<T>(
+        &self,
+        files: &[&str],
+        urls: &[&str],
+        options: &LaunchOptions,
+        run: impl FnOnce(&LaunchRequest<'_>) -> Result<T, ExecuteError>,
+    ) -> Result<(T, Started), ExecuteError> {
+        let prepared = self.prepare(files, urls, options);
+
+        let argv: Vec<String> = prepared
+            .as_ref()
+            .map(|Prepared { command: (program, args), .. }| {
+                std::iter::once(program.clone()).chain(args.iter().cloned()).collect()
+            })
+            .unwrap_or_default();
+        let downloads = prepared.as_ref().map(|prepared| prepared.downloads.clone()).unwrap_or_default();
+
+        // The activation token and startup ID reach the program through its environment
+        let options = match &prepared {
+            Ok(_) => options.with_launch_env(self),
+            Err(_) => Cow::Borrowed(options),
+        };
+        let startup = prepared.as_ref().ok().and_then(|_| self.startup_sequence(&options));
+        let options = match &startup {
+            Some(startup) => &options.into_owned().env("DESKTOP_STARTUP_ID", startup.id()),
+            None => &*options,
         };
+        let notifier = options.startup_notifier_ref();
+        if let (Some(notifier), Some(startup)) = (notifier, &startup) {
+            notifier.begin(startup);
+        }
 
-        // Set working directory if specified
-        let working_dir = self.path_dir();
-        
-        // Spawn the process detached
-        spawn_detached_with_env(&final_program, &final_args, working_dir.as_deref())
-            .map_err(|e| ExecuteError::IoError(format!("Failed to spawn process: {}", e)))
+        let result = prepared.and_then(|prepared| run(&LaunchRequest::new(self, prepared.command, files, urls, options)));
+        let launch_id = options.launch_id_value();
+        audit::record(self, &argv, launch_id.as_deref(), &result.as_ref().map(|_| ()).map_err(Clone::clone));
+
+        if result.is_err() {
+            if let (Some(notifier), Some(startup)) = (notifier, &startup) {
+                notifier.complete(startup);
+            }
+            remove_downloads(&downloads);
+        }
+
+        result.map(|value| (value, Started { startup, downloads }))
     }
 
     fn validate_executable(&self) -> Result<(), ExecuteError> {
@@ -267,109 +798,93 @@ impl ApplicationEntry {
 
     fn parse_exec_command(&self, files: &[&str], urls: &[&str]) -> Result<(String, Vec<String>), ExecuteError> {
         let exec = self.exec().unwrap(); // Already validated in validate_executable
+
+        // Wine entries are shell command lines and their programs expect Windows paths
+        let wine = self.is_wine();
+        let windows_files: Vec<String> = if wine && !wine::takes_unix_paths(&exec) {
+            files.iter().map(|file| wine::to_windows_path(file)).collect()
+        } else {
+            files.iter().map(|file| file.to_string()).collect()
+        };
+        let files: Vec<&str> = windows_files.iter().map(String::as_str).collect();
         
-        // Expand field codes
-        let expanded = self.expand_field_codes(&exec, files, urls);
-        
-        // Parse the command line
-        parse_command_line(&expanded)
+        // Split the Exec line first, so expanded values never go through quoting again
+        let (program, args) = parse_command_line(&exec, wine)?;
+        let mut argv = Vec::with_capacity(args.len() + files.len() + urls.len() + 1);
+        for arg in std::iter::once(program).chain(args) {
+            argv.extend(self.expand_field_codes(&arg, &files, urls));
+        }
+
+        if argv.is_empty() {
+            return Err(ExecuteError::InvalidCommand("Empty command".to_string()));
+        }
+        let program = argv.remove(0);
+        Ok((program, argv))
     }
 
-    fn expand_field_codes(&self, exec: &str, files: &[&str], urls: &[&str]) -> String {
-        let mut result = String::new();
-        let mut chars = exec.chars().peekable();
+    /// Expand the field codes of one Exec argument into the arguments it stands for
+    ///
+    /// A standalone `%f`, `%F`, `%u`, `%U`, `%c`, `%k` or `%i` becomes zero or more whole
+    /// arguments (`%i` becomes `--icon <icon>`). Field codes inside a larger argument
+    /// are replaced in place, taking the first file or URL for `%F` and `%U`.
+    fn expand_field_codes(&self, arg: &str, files: &[&str], urls: &[&str]) -> Vec<String> {
+        let all = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        let first = |values: &[&str]| values.first().map(|value| value.to_string());
+        match arg {
+            "%f" => return first(files).into_iter().collect(),
+            "%F" => return all(files),
+            "%u" => return first(urls).into_iter().collect(),
+            "%U" => return all(urls),
+            "%c" => return self.name().into_iter().collect(),
+            "%k" => return vec![self.path().to_string_lossy().to_string()],
+            "%i" => {
+                return self
+                    .icon()
+                    .map(|icon| vec!["--icon".to_string(), icon])
+                    .unwrap_or_default()
+            }
+            // Deprecated field codes expand to nothing
+            "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => return Vec::new(),
+            _ => {}
+        }
 
+        let mut result = String::new();
+        let mut chars = arg.chars();
         while let Some(ch) = chars.next() {
-            if ch == '%' {
-                if let Some(&next_ch) = chars.peek() {
-                    chars.next(); // consume the next character
-                    match next_ch {
-                        '%' => result.push('%'),
-                        'f' => {
-                            if let Some(file) = files.first() {
-                                result.push_str(&shell_escape(file));
-                            }
-                        },
-                        'F' => {
-                            for (i, file) in files.iter().enumerate() {
-                                if i > 0 { result.push(' '); }
-                                result.push_str(&shell_escape(file));
-                            }
-                        },
-                        'u' => {
-                            if let Some(url) = urls.first() {
-                                result.push_str(&shell_escape(url));
-                            }
-                        },
-                        'U' => {
-                            for (i, url) in urls.iter().enumerate() {
-                                if i > 0 { result.push(' '); }
-                                result.push_str(&shell_escape(url));
-                            }
-                        },
-                        'i' => {
-                            if let Some(icon) = self.icon() {
-                                result.push_str("--icon ");
-                                result.push_str(&shell_escape(&icon));
-                            }
-                        },
-                        'c' => {
-                            if let Some(name) = self.name() {
-                                result.push_str(&shell_escape(&name));
-                            }
-                        },
-                        'k' => {
-                            let path = self.path().to_string_lossy();
-                            result.push_str(&shell_escape(&path));
-                        },
-                        // Deprecated field codes - ignore
-                        'd' | 'D' | 'n' | 'N' | 'v' | 'm' => {},
-                        // Unknown field code - this is an error per spec
-                        _ => {
-                            return format!("{}%{}{}", result, next_ch, chars.collect::<String>());
-                        }
-                    }
-                } else {
-                    result.push(ch);
-                }
-            } else {
+            if ch != '%' {
                 result.push(ch);
+                continue;
             }
-        }
 
-        result
+            match chars.next() {
+                Some('%') => result.push('%'),
+                Some('f' | 'F') => result.extend(files.first().copied()),
+                Some('u' | 'U') => result.extend(urls.first().copied()),
+                Some('c') => result.extend(self.name()),
+                Some('k') => result.push_str(&self.path().to_string_lossy()),
+                Some('i') => result.extend(self.icon()),
+                Some('d' | 'D' | 'n' | 'N' | 'v' | 'm') => {}
+                // Unknown field codes are passed through unchanged
+                Some(code) => {
+                    result.push('%');
+                    result.push(code);
+                }
+                None => result.push('%'),
+            }
+        }
+        vec![result]
     }
 
-    fn wrap_with_terminal(&self, program: &str, args: &[String]) -> Result<(String, Vec<String>), ExecuteError> {
-        let terminal = find_terminal().ok_or(ExecuteError::TerminalNotFound)?;
-        
+    fn wrap_with_terminal(&self, program: &str, args: &[String], hold: bool) -> Result<(String, Vec<String>), ExecuteError> {
+        let (terminal, launcher) = terminal::find_terminal().ok_or(ExecuteError::TerminalNotFound)?;
+
         // Build the command to run in terminal
-        let mut terminal_args = vec!["-e".to_string()];
-        terminal_args.push(program.to_string());
-        terminal_args.extend(args.iter().cloned());
-        
-        Ok((terminal, terminal_args))
+        let command: Vec<String> = std::iter::once(program.to_string()).chain(args.iter().cloned()).collect();
+        Ok((terminal, launcher.args(&command, hold)))
     }
 }
 
 impl ApplicationEntry {
-    /// Get all application entries from standard directories
-    pub fn all() -> Vec<ApplicationEntry> {
-        let mut entries: Vec<ApplicationEntry> = Vec::new();
-        for p in application_entry_paths() {
-            if let Ok(dir_entries) = std::fs::read_dir(p) {
-                for entry in dir_entries.filter_map(|e| e.ok()) {
-                    if entry.path().extension().is_some_and(|ext| ext == "desktop") {
-                        if let Ok(app_entry) = ApplicationEntry::try_from_path(entry.path()) {
-                            entries.push(app_entry);
-                        }
-                    }
-                }
-            }
-        }
-        entries
-    }
-
     /// Create an ApplicationEntry from a path, panicking on error (for compatibility)
     pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
         Self::try_from_path(path).unwrap_or_else(|_| {
@@ -383,132 +898,139 @@ impl ApplicationEntry {
         let desktop_entry = DesktopEntry::from_path(path)?;
         Ok(ApplicationEntry {
             inner: desktop_entry,
+            application_dirs: None,
+            full: None,
         })
     }
-}
 
-/// Spawn a process completely detached from the current process while preserving display environment
-fn spawn_detached_with_env(program: &str, args: &[String], working_dir: Option<&str>) -> Result<(), std::io::Error> {
-    use std::process::{Command, Stdio};
-    
-    #[cfg(unix)]
-    {
-        use std::os::unix::process::CommandExt;
-        
-        let mut cmd = Command::new(program);
-        cmd.args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null());
-
-        // Set working directory if provided
-        if let Some(dir) = working_dir {
-            cmd.current_dir(dir);
-        }
+    /// Read only the [`METADATA_KEYS`] of an entry, for listing many entries quickly
+    ///
+    /// Reading stops at the end of the `[Desktop Entry]` group. Any other key is
+    /// looked up by parsing the whole file the first time one is asked for, so
+    /// launching the entry works as usual.
+    pub fn metadata_from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let desktop_entry = DesktopEntry::metadata_from_path(path.as_ref(), &METADATA_KEYS, ParseLimits::default())?;
+        Ok(ApplicationEntry {
+            inner: desktop_entry,
+            application_dirs: None,
+            full: Some(Arc::default()),
+        })
+    }
 
-        // Explicitly preserve important environment variables
-        if let Ok(wayland_display) = std::env::var("WAYLAND_DISPLAY") {
-            cmd.env("WAYLAND_DISPLAY", wayland_display);
-        }
-        if let Ok(display) = std::env::var("DISPLAY") {
-            cmd.env("DISPLAY", display);
-        }
-        if let Ok(xdg_runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-            cmd.env("XDG_RUNTIME_DIR", xdg_runtime_dir);
-        }
-        if let Ok(xdg_session_type) = std::env::var("XDG_SESSION_TYPE") {
-            cmd.env("XDG_SESSION_TYPE", xdg_session_type);
-        }
-        if let Ok(xdg_current_desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
-            cmd.env("XDG_CURRENT_DESKTOP", xdg_current_desktop);
-        }
+    /// Check if the entry was read with [`ApplicationEntry::metadata_from_path`] and
+    /// hasn't been fully parsed yet
+    pub fn is_metadata_only(&self) -> bool {
+        self.full.as_ref().is_some_and(|full| full.get().is_none())
+    }
 
-        unsafe {
-            cmd.pre_exec(|| {
-                // Start new process group but don't create new session
-                // This allows detachment while preserving session environment
-                libc::setpgid(0, 0);
-                Ok(())
-            });
+    /// Get the `[Desktop Entry]` group to look `key` up in
+    fn group_for(&self, key: &str) -> Option<&parser::DesktopEntryGroup> {
+        if METADATA_KEYS.contains(&key) {
+            return self.inner.get_desktop_entry_group();
         }
+        self.complete().get_desktop_entry_group()
+    }
 
-        cmd.spawn()?;
-        Ok(())
+    /// Get the fully parsed entry, parsing the file now if only metadata was read
+    ///
+    /// Falls back to the metadata if the file can no longer be parsed.
+    pub(crate) fn complete(&self) -> &DesktopEntry {
+        let Some(full) = &self.full else {
+            return &self.inner;
+        };
+        full.get_or_init(|| DesktopEntry::from_path(&self.inner.path).ok())
+            .as_ref()
+            .unwrap_or(&self.inner)
     }
-    
-    #[cfg(not(unix))]
-    {
-        let mut cmd = Command::new(program);
-        cmd.args(args)
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null());
-        
-        // Set working directory if provided
-        if let Some(dir) = working_dir {
-            cmd.current_dir(dir);
-        }
-        
-        cmd.spawn()?;
-        Ok(())
+
+    /// Try to create an ApplicationEntry from a path using the given parse options
+    pub fn try_from_path_with<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Self, ParseError> {
+        let desktop_entry = DesktopEntry::from_path_with(path, options)?;
+        Ok(ApplicationEntry {
+            inner: desktop_entry,
+            application_dirs: None,
+            full: None,
+        })
     }
 }
 
-/// Check if an executable is available in PATH or as absolute path
-fn is_executable_available(executable: &str) -> bool {
-    use std::path::Path;
-    
-    if Path::new(executable).is_absolute() {
-        // Absolute path - check if file exists and is executable
-        Path::new(executable).exists()
-    } else {
-        // Relative path - check in PATH
-        which_command(executable).is_some()
+/// Paths to pass for a list of URLs, and which of them were downloaded
+type LocalFiles = (Vec<String>, Vec<PathBuf>);
+
+/// A validated command, with the files downloaded for it
+struct Prepared {
+    command: (String, Vec<String>),
+    downloads: Vec<PathBuf>,
+}
+
+/// What a successful start leaves for the caller besides the started process
+struct Started {
+    startup: Option<StartupSequence>,
+    downloads: Vec<PathBuf>,
+}
+
+/// Remove files downloaded for a launch that didn't happen
+fn remove_downloads(downloads: &[PathBuf]) {
+    for path in downloads {
+        download::remove(path);
     }
 }
 
-/// Find an executable in PATH (simple implementation)
-fn which_command(executable: &str) -> Option<String> {
-    if let Ok(path_var) = std::env::var("PATH") {
-        for path_dir in path_var.split(':') {
-            let full_path = format!("{}/{}", path_dir, executable);
-            if std::path::Path::new(&full_path).exists() {
-                return Some(full_path);
-            }
-        }
+/// Check if an executable is available in PATH or as a path
+fn is_executable_available(executable: &str) -> bool {
+    which(executable).is_some()
+}
+
+/// Search path used when PATH isn't set, the same as glibc's execvp
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
+/// Find the executable a program name refers to, the way a shell would
+///
+/// Names containing a slash are taken as paths. Other names are looked up in
+/// each PATH directory in turn; an empty component stands for the current
+/// directory. Only regular files this process may execute are returned.
+pub fn which<P: AsRef<Path>>(program: P) -> Option<PathBuf> {
+    let program = program.as_ref();
+    if program.as_os_str().is_empty() {
+        return None;
+    }
+    if program.to_string_lossy().contains('/') {
+        return is_executable_file(program).then(|| program.to_path_buf());
     }
-    None
+
+    let path = std::env::var_os("PATH").unwrap_or_else(|| DEFAULT_PATH.into());
+    path.to_string_lossy().split(':').find_map(|dir| {
+        let dir = if dir.is_empty() { "." } else { dir };
+        let candidate = Path::new(dir).join(program);
+        is_executable_file(&candidate).then_some(candidate)
+    })
 }
 
-/// Find an available terminal emulator
-fn find_terminal() -> Option<String> {
-    // First check TERMINAL environment variable
-    if let Ok(terminal) = std::env::var("TERMINAL") {
-        if is_executable_available(&terminal) {
-            return Some(terminal);
-        }
+pub(crate) fn is_executable_file(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
     }
-    
-    // Try common terminal emulators
-    let terminals = [
-        "x-terminal-emulator",  // Debian/Ubuntu alternative
-        "gnome-terminal",
-        "konsole",
-        "xfce4-terminal", 
-        "mate-terminal",
-        "lxterminal",
-        "rxvt-unicode",
-        "rxvt",
-        "xterm",
-    ];
-    
-    for terminal in &terminals {
-        if is_executable_available(terminal) {
-            return Some(terminal.to_string());
-        }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        // access() checks the permission bits against this process's user and groups
+        unsafe { libc::access(path.as_ptr(), libc::X_OK) == 0 }
     }
-    
-    None
+    #[cfg(not(unix))]
+    true
+}
+
+/// Get the file name of a program path, `/usr/bin/wine` being `wine`
+pub(crate) fn basename(program: &str) -> &str {
+    Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program)
 }
 
 /// Escape a string for safe shell usage
@@ -520,64 +1042,105 @@ fn shell_escape(s: &str) -> String {
     }
 }
 
-/// Parse a command line into program and arguments, handling quotes
-fn parse_command_line(command: &str) -> Result<(String, Vec<String>), ExecuteError> {
+/// Characters the specification reserves in Exec arguments; an argument that
+/// contains one of them has to be quoted
+const EXEC_RESERVED: &str = " \t\n\"'\\><~|&;$*?#()`";
+
+/// Quote an argument for an Exec line, following the specification's quoting rules
+///
+/// Arguments with reserved characters are put in double quotes, with `"`, `` ` ``,
+/// `$` and `\` escaped. Pass the whole Exec line through [`escape_value`] before
+/// writing it, which doubles the backslashes once more.
+pub fn quote_exec_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| EXEC_RESERVED.contains(c)) {
+        return arg.to_string();
+    }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for ch in arg.chars() {
+        if matches!(ch, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split an Exec line into program and arguments
+///
+/// Follows the specification's quoting rules: arguments are separated by spaces and
+/// quoted only with double quotes, inside which a backslash escapes `"`, `` ` ``, `$`
+/// and `\`. The string escapes were already decoded when the file was parsed, which
+/// is why a literal backslash takes four in the file. Reserved characters outside
+/// quotes are kept as they are, since many entries don't quote them.
+///
+/// With `shell_escapes` the line is split the way a shell would instead, which
+/// Wine-generated entries rely on: single quotes quote too, tabs separate arguments
+/// and a backslash outside quotes escapes the next character.
+fn parse_command_line(command: &str, shell_escapes: bool) -> Result<(String, Vec<String>), ExecuteError> {
     let mut parts = Vec::new();
     let mut current = String::new();
-    let mut in_quotes = false;
-    let mut quote_char = '"';
-    let mut chars = command.chars().peekable();
-    
+    // Tracks quoted empty arguments, which are still arguments
+    let mut started = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
     while let Some(ch) = chars.next() {
-        match ch {
-            '"' | '\'' if !in_quotes => {
-                in_quotes = true;
-                quote_char = ch;
-            },
-            ch if ch == quote_char && in_quotes => {
-                in_quotes = false;
-            },
-            '\\' if in_quotes => {
-                // Handle escape sequences in quotes
-                if let Some(&next_ch) = chars.peek() {
-                    chars.next();
-                    match next_ch {
-                        '"' | '\'' | '\\' | '$' | '`' => current.push(next_ch),
-                        _ => {
-                            current.push('\\');
-                            current.push(next_ch);
-                        }
-                    }
-                } else {
+        match (quote, ch) {
+            (Some('"'), '"') | (Some('\''), '\'') => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some(next) if matches!(next, '"' | '`' | '$' | '\\') => current.push(next),
+                // Other escapes are undefined, keep the backslash
+                Some(next) => {
                     current.push('\\');
+                    current.push(next);
                 }
+                None => current.push('\\'),
             },
-            ' ' | '\t' if !in_quotes => {
-                if !current.is_empty() {
-                    parts.push(current);
-                    current = String::new();
+            (Some(_), ch) => current.push(ch),
+            (None, '"') => {
+                quote = Some('"');
+                started = true;
+            }
+            (None, '\'') if shell_escapes => {
+                quote = Some('\'');
+                started = true;
+            }
+            (None, '\\') if shell_escapes => {
+                current.push(chars.next().unwrap_or('\\'));
+                started = true;
+            }
+            (None, ' ') => {
+                if started {
+                    parts.push(std::mem::take(&mut current));
+                    started = false;
                 }
-                // Skip multiple spaces
-                while chars.peek() == Some(&' ') || chars.peek() == Some(&'\t') {
-                    chars.next();
+            }
+            (None, '\t' | '\n') if shell_escapes => {
+                if started {
+                    parts.push(std::mem::take(&mut current));
+                    started = false;
                 }
-            },
-            _ => current.push(ch),
+            }
+            (None, ch) => {
+                current.push(ch);
+                started = true;
+            }
         }
     }
-    
-    if !current.is_empty() {
-        parts.push(current);
-    }
-    
-    if in_quotes {
+
+    if quote.is_some() {
         return Err(ExecuteError::InvalidCommand("Unterminated quote".to_string()));
     }
-    
+    if started {
+        parts.push(current);
+    }
+
     if parts.is_empty() {
         return Err(ExecuteError::InvalidCommand("Empty command".to_string()));
     }
-    
+
     let program = parts.remove(0);
     Ok((program, parts))
 }
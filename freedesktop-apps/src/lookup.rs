@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use crate::{application_entry_paths_by_precedence, normalize_desktop_id, ApplicationEntry, MimeInfoCache};
+
+impl ApplicationEntry {
+    /// Find the entry with the given desktop file ID without loading every entry
+    ///
+    /// Data dirs are checked in precedence order. Since a `-` in an ID may stand for
+    /// a subdirectory separator, `foo-bar` matches both `applications/foo-bar.desktop`
+    /// and `applications/foo/bar.desktop`. An entry with Hidden=true counts as deleted,
+    /// while a copy that fails to parse is skipped in favor of the next one.
+    pub fn find_by_id(id: &str) -> Option<ApplicationEntry> {
+        find_parsed(&application_entry_paths_by_precedence(), id)
+    }
+
+    /// Find the entry with the given desktop file ID in the given `applications` directories
+    ///
+    /// `dirs` are ordered from highest to lowest precedence, as for
+    /// [`ApplicationEntry::all_in`], and the entry's ID is relative to them.
+    pub fn find_by_id_in(dirs: &[PathBuf], id: &str) -> Option<ApplicationEntry> {
+        let mut entry = find_parsed(dirs, id)?;
+        entry.application_dirs = Some(dirs.into());
+        Some(entry)
+    }
+
+    /// Find entries whose name matches, ignoring case
+    pub fn find_by_name(name: &str) -> Vec<ApplicationEntry> {
+        let name = name.trim().to_lowercase();
+        ApplicationEntry::all()
+            .into_iter()
+            .filter(|entry| entry.name().is_some_and(|n| n.to_lowercase() == name))
+            .collect()
+    }
+
+    /// Find entries that declare support for the given MIME type
+    ///
+    /// Uses the `mimeinfo.cache` of each applications directory where it is up to
+    /// date, so only the matching desktop files are parsed. The caches are read on
+    /// every call; keep a [`MimeInfoCache`] to look up many types.
+    pub fn find_by_mime_type(mime_type: &str) -> Vec<ApplicationEntry> {
+        MimeInfoCache::load().applications_for(mime_type)
+    }
+}
+
+/// Parse the highest-precedence copy of `id` that can be parsed, unless it has Hidden=true
+fn find_parsed(dirs: &[PathBuf], id: &str) -> Option<ApplicationEntry> {
+    let id = normalize_desktop_id(id);
+    if id.is_empty() {
+        return None;
+    }
+
+    let entry = dirs
+        .iter()
+        .filter_map(|dir| find_id_in(dir, &id))
+        .find_map(|path| ApplicationEntry::try_from_path(path).ok())?;
+    (!entry.is_hidden()).then_some(entry)
+}
+
+/// Locate the file for `id` below `dir`, trying each `-` as a possible directory separator
+pub(crate) fn find_id_in(dir: &Path, id: &str) -> Option<PathBuf> {
+    let file = dir.join(format!("{}.desktop", id));
+    if file.is_file() {
+        return Some(file);
+    }
+
+    id.match_indices('-').find_map(|(i, _)| {
+        let subdir = dir.join(&id[..i]);
+        if subdir.is_dir() {
+            find_id_in(&subdir, &id[i + 1..])
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,149 @@
+//! Composing an email in the preferred mail client, like `xdg-email`
+//!
+//! The message is written as a `mailto:` URI as RFC 6068 describes and opened
+//! with the handler of `x-scheme-handler/mailto`. Attachments aren't part of
+//! the RFC; they are passed as `attach` fields holding `file://` URLs, which
+//! Thunderbird, Evolution and KMail read and other clients ignore.
+//!
+//! ```no_run
+//! use freedesktop_apps::{open_mailto, MailtoBuilder};
+//!
+//! let mail = MailtoBuilder::new()
+//!     .to("someone@example.com")
+//!     .subject("Report")
+//!     .body("Attached.")
+//!     .attach("report.pdf");
+//! open_mailto(&mail).unwrap();
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{open_with, LaunchOptions, Launched, OpenError};
+
+/// The fields of a message to compose, written as a `mailto:` URI
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MailtoBuilder {
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+    subject: Option<String>,
+    body: Option<String>,
+    attachments: Vec<PathBuf>,
+}
+
+impl MailtoBuilder {
+    /// Start an empty message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a recipient
+    pub fn to<S: Into<String>>(mut self, address: S) -> Self {
+        self.to.push(address.into());
+        self
+    }
+
+    /// Add a carbon copy recipient
+    pub fn cc<S: Into<String>>(mut self, address: S) -> Self {
+        self.cc.push(address.into());
+        self
+    }
+
+    /// Add a blind carbon copy recipient
+    pub fn bcc<S: Into<String>>(mut self, address: S) -> Self {
+        self.bcc.push(address.into());
+        self
+    }
+
+    /// Set the subject
+    pub fn subject<S: Into<String>>(mut self, subject: S) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    /// Set the body text
+    ///
+    /// Line breaks are sent as CRLF, as the RFC requires.
+    pub fn body<S: Into<String>>(mut self, body: S) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Attach a file, in clients that support attachments
+    ///
+    /// A relative path is taken from the current directory when the URI is built.
+    pub fn attach<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.attachments.push(path.into());
+        self
+    }
+
+    /// Build the `mailto:` URI
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!("mailto:{}", addresses(&self.to));
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        if !self.cc.is_empty() {
+            fields.push(("cc", addresses(&self.cc)));
+        }
+        if !self.bcc.is_empty() {
+            fields.push(("bcc", addresses(&self.bcc)));
+        }
+        if let Some(subject) = &self.subject {
+            fields.push(("subject", encode(subject)));
+        }
+        if let Some(body) = &self.body {
+            let body = body.replace("\r\n", "\n").replace('\n', "\r\n");
+            fields.push(("body", encode(&body)));
+        }
+        for path in &self.attachments {
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            fields.push(("attach", encode(&freedesktop_core::file_uri(&path))));
+        }
+
+        for (i, (name, value)) in fields.iter().enumerate() {
+            uri.push(if i == 0 { '?' } else { '&' });
+            uri.push_str(name);
+            uri.push('=');
+            uri.push_str(value);
+        }
+        uri
+    }
+}
+
+impl fmt::Display for MailtoBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_uri())
+    }
+}
+
+/// Open a compose window in the preferred mail client
+///
+/// See [`open_mailto_with`].
+pub fn open_mailto(mail: &MailtoBuilder) -> Result<Vec<Launched>, OpenError> {
+    open_mailto_with(mail, &LaunchOptions::default())
+}
+
+/// Open a compose window in the preferred mail client, launched with the given options
+///
+/// Fails with [`OpenError::NoApplication`] when nothing handles
+/// `x-scheme-handler/mailto`.
+pub fn open_mailto_with(mail: &MailtoBuilder, options: &LaunchOptions) -> Result<Vec<Launched>, OpenError> {
+    open_with(&mail.to_uri(), options)
+}
+
+/// Join addresses with the commas the RFC separates them with
+fn addresses(addresses: &[String]) -> String {
+    addresses.iter().map(|address| encode(address)).collect::<Vec<_>>().join(",")
+}
+
+/// Percent-encode everything but unreserved characters and `@`
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
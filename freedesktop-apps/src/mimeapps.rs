@@ -0,0 +1,589 @@
+//! Default applications and associations from `mimeapps.list`
+//!
+//! The MIME Applications Associations specification lets users and vendors
+//! choose the application that opens a type, and add or remove associations
+//! that desktop files declare. Copies of `mimeapps.list` are read most
+//! important first:
+//!
+//! 1. `$XDG_CONFIG_HOME/$desktop-mimeapps.list`, then `$XDG_CONFIG_HOME/mimeapps.list`
+//! 2. the same two names in each XDG_CONFIG_DIRS entry
+//! 3. `$desktop-mimeapps.list` and `mimeapps.list` in `$XDG_DATA_HOME/applications`
+//!    (deprecated) and in the `applications` directory of each XDG_DATA_DIRS entry
+//!
+//! `$desktop` stands for each name in XDG_CURRENT_DESKTOP in lower case, in the
+//! order they are listed.
+//!
+//! Older systems also carry `defaults.list` in the same `applications`
+//! directories. It only has default applications and is read after every
+//! `mimeapps.list`; [`migrate_defaults_list`] moves the user's copy over.
+//!
+//! URL schemes are handled through the same lists: the application for `https`
+//! URLs is the one for the `x-scheme-handler/https` type.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use freedesktop_core::keyfile::KeyFile;
+use freedesktop_mime::MimeDatabase;
+
+use crate::{data_dirs_by_precedence, normalize_desktop_id, ApplicationEntry, EntryType, MimeInfoCache};
+
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+
+/// Prefix of the types that stand for URL schemes
+const SCHEME_HANDLER: &str = "x-scheme-handler/";
+
+/// Get the `mimeapps.list` files for the current desktop, most important first
+///
+/// Only existing files are returned.
+pub fn mimeapps_list_paths() -> Vec<PathBuf> {
+    let desktops = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    mimeapps_list_paths_for(&desktops)
+}
+
+/// Get the `mimeapps.list` files for the given desktops, most important first
+///
+/// `desktop` is a colon-separated list in the format of XDG_CURRENT_DESKTOP.
+/// Only existing files are returned.
+pub fn mimeapps_list_paths_for(desktop: &str) -> Vec<PathBuf> {
+    let mut names: Vec<String> = desktop
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(|d| format!("{}-mimeapps.list", d.to_lowercase()))
+        .collect();
+    names.push("mimeapps.list".to_string());
+
+    let config = freedesktop_core::config_home().into_iter().chain(freedesktop_core::config_dirs());
+    let data = data_dirs_by_precedence().into_iter().map(|dir| dir.join("applications"));
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for dir in config.chain(data) {
+        for name in &names {
+            let path = dir.join(name);
+            if path.is_file() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+/// Get the legacy `defaults.list` files, most important first
+///
+/// Only existing files are returned.
+pub fn defaults_list_paths() -> Vec<PathBuf> {
+    data_dirs_by_precedence()
+        .into_iter()
+        .map(|dir| dir.join("applications").join("defaults.list"))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Copy the defaults of the user's `defaults.list` into their `mimeapps.list`
+///
+/// Reads `$XDG_DATA_HOME/applications/defaults.list` and adds each type that
+/// `$XDG_CONFIG_HOME/mimeapps.list` has no default for, so choices made in the
+/// new file always win. The `mimeapps.list` is replaced atomically; comments in
+/// it aren't kept. `defaults.list` is left in place for programs that still read
+/// it. Returns the migrated MIME types.
+pub fn migrate_defaults_list() -> io::Result<Vec<String>> {
+    let (Some(data_home), Some(config_home)) = (freedesktop_core::data_home(), freedesktop_core::config_home()) else {
+        return Ok(Vec::new());
+    };
+    let legacy = match KeyFile::from_path(data_home.join("applications").join("defaults.list")) {
+        Ok(legacy) => legacy,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let path = config_home.join("mimeapps.list");
+    let mut keyfile = read_optional(&path)?;
+    let mut migrated = Vec::new();
+    for (mime_type, value) in legacy.entries(DEFAULT_APPLICATIONS).unwrap_or_default() {
+        let known = keyfile.get(DEFAULT_APPLICATIONS, &existing_key(&keyfile, DEFAULT_APPLICATIONS, mime_type));
+        if known.is_none() && !value.is_empty() {
+            keyfile.set(DEFAULT_APPLICATIONS, mime_type, value);
+            migrated.push(mime_type.clone());
+        }
+    }
+
+    if !migrated.is_empty() {
+        write_atomically(&path, &keyfile.to_string())?;
+    }
+    Ok(migrated)
+}
+
+/// Make an application the default for a type, like `xdg-mime default`
+///
+/// Edits `$XDG_CONFIG_HOME/mimeapps.list`, replacing the file atomically. As gio
+/// does, the application also goes first in the type's added associations and
+/// leaves its removed associations. Other types and groups are kept; comments
+/// aren't. A desktop-specific `$desktop-mimeapps.list` of the user still takes
+/// precedence over the result.
+pub fn set_default_application(mime_type: &str, desktop_id: &str) -> io::Result<()> {
+    let mime_type = mime_type.trim();
+    let id = normalize_desktop_id(desktop_id);
+    if !mime_type.contains('/') || mime_type.contains(['=', '[', ']', '\n']) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid MIME type: {}", mime_type)));
+    }
+    if id.is_empty() || id.contains([';', '\n']) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid desktop file ID: {}", desktop_id)));
+    }
+    let file = format!("{}.desktop", id);
+    let Some(config_home) = freedesktop_core::config_home() else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no config directory"));
+    };
+
+    let path = config_home.join("mimeapps.list");
+    let mut keyfile = read_optional(&path)?;
+    keyfile.set(DEFAULT_APPLICATIONS, &existing_key(&keyfile, DEFAULT_APPLICATIONS, mime_type), &file);
+
+    let key = existing_key(&keyfile, ADDED_ASSOCIATIONS, mime_type);
+    let others = keyfile.get_list(ADDED_ASSOCIATIONS, &key).unwrap_or_default();
+    let added: Vec<String> = std::iter::once(file.clone())
+        .chain(others.into_iter().filter(|other| normalize_desktop_id(other) != id))
+        .collect();
+    keyfile.set(ADDED_ASSOCIATIONS, &key, &format!("{};", added.join(";")));
+
+    let key = existing_key(&keyfile, REMOVED_ASSOCIATIONS, mime_type);
+    if let Some(removed) = keyfile.get_list(REMOVED_ASSOCIATIONS, &key) {
+        let kept: Vec<String> = removed.into_iter().filter(|other| normalize_desktop_id(other) != id).collect();
+        if kept.is_empty() {
+            keyfile.remove(REMOVED_ASSOCIATIONS, &key);
+        } else {
+            keyfile.set(REMOVED_ASSOCIATIONS, &key, &format!("{};", kept.join(";")));
+        }
+    }
+
+    write_atomically(&path, &keyfile.to_string())
+}
+
+/// Get the applications that can open a type, best first, for an "Open With" list
+///
+/// Loads the associations and the MIME database on every call; see
+/// [`MimeAssociations::ranked_applications`].
+pub fn applications_for_mime(mime_type: &str) -> Vec<ApplicationEntry> {
+    MimeAssociations::load().ranked_applications(mime_type, &MimeDatabase::load())
+}
+
+/// Get the application that opens a type, like `xdg-mime query default`
+///
+/// Reads the `mimeapps.list` files on every call; keep a [`MimeAssociations`] to
+/// look up many types. See [`MimeAssociations::default_application`].
+pub fn default_application_for(mime_type: &str) -> Option<ApplicationEntry> {
+    MimeAssociations::load().default_application(mime_type)
+}
+
+/// Get the application that opens URLs of a scheme, such as the default browser
+/// for `https` or the mail client for `mailto`
+///
+/// Reads the `mimeapps.list` files on every call; see
+/// [`MimeAssociations::default_handler_for_scheme`].
+pub fn default_handler_for_scheme(scheme: &str) -> Option<ApplicationEntry> {
+    MimeAssociations::load().default_handler_for_scheme(scheme)
+}
+
+/// Get the applications that open URLs of a scheme, default first
+///
+/// Reads the `mimeapps.list` files on every call; see
+/// [`MimeAssociations::handlers_for_scheme`].
+pub fn handlers_for_scheme(scheme: &str) -> Vec<ApplicationEntry> {
+    MimeAssociations::load().handlers_for_scheme(scheme)
+}
+
+/// Get the `x-scheme-handler/*` type of a URL scheme
+///
+/// A trailing colon is ignored and the scheme is lower cased, so `HTTPS:` gives
+/// `x-scheme-handler/https`. Returns `None` for anything that isn't a valid
+/// scheme as RFC 3986 defines it: a letter followed by letters, digits, `+`,
+/// `-` or `.`.
+pub fn scheme_mime_type(scheme: &str) -> Option<String> {
+    let scheme = scheme.strip_suffix(':').unwrap_or(scheme);
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| format!("{}{}", SCHEME_HANDLER, scheme.to_ascii_lowercase()))
+}
+
+/// The merged `mimeapps.list` files, keyed by lower case MIME type
+///
+/// Default applications are kept in preference order across all files. An
+/// association removed in one file stays on the list when a more important file
+/// adds it back, as the specification describes.
+#[derive(Debug, Clone, Default)]
+pub struct MimeAssociations {
+    defaults: HashMap<String, Vec<String>>,
+    added: HashMap<String, Vec<String>>,
+    removed: HashMap<String, Vec<String>>,
+    /// Resolves aliases, loaded on first use unless set with [`MimeAssociations::with_database`]
+    database: OnceLock<Arc<MimeDatabase>>,
+    /// Lists the entries per type, loaded on first use unless set with
+    /// [`MimeAssociations::with_mime_info_cache`]
+    cache: OnceLock<Arc<MimeInfoCache>>,
+}
+
+impl PartialEq for MimeAssociations {
+    fn eq(&self, other: &Self) -> bool {
+        self.defaults == other.defaults && self.added == other.added && self.removed == other.removed
+    }
+}
+
+impl Eq for MimeAssociations {}
+
+impl MimeAssociations {
+    /// Read the `mimeapps.list` files of the current desktop, see [`mimeapps_list_paths`],
+    /// followed by the legacy [`defaults_list_paths`]
+    pub fn load() -> Self {
+        Self::from_paths(mimeapps_list_paths().into_iter().chain(defaults_list_paths()))
+    }
+
+    /// Read the given `mimeapps.list` files, most important first
+    ///
+    /// Missing or unreadable files are skipped.
+    pub fn from_paths<I, P>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let keyfiles: Vec<KeyFile> = paths.into_iter().filter_map(|path| KeyFile::from_path(path).ok()).collect();
+        Self::from_keyfiles(&keyfiles)
+    }
+
+    /// Merge already parsed `mimeapps.list` files, most important first
+    pub fn from_keyfiles(keyfiles: &[KeyFile]) -> Self {
+        let mut associations = MimeAssociations::default();
+        for keyfile in keyfiles {
+            for (mime_type, ids) in list_entries(keyfile, DEFAULT_APPLICATIONS) {
+                add_unique(associations.defaults.entry(mime_type).or_default(), ids);
+            }
+            // Added associations of a file win over its own removed ones
+            for (mime_type, ids) in list_entries(keyfile, ADDED_ASSOCIATIONS) {
+                let removed = associations.removed.get(&mime_type).cloned().unwrap_or_default();
+                let ids = ids.into_iter().filter(|id| !removed.contains(id)).collect();
+                add_unique(associations.added.entry(mime_type).or_default(), ids);
+            }
+            for (mime_type, ids) in list_entries(keyfile, REMOVED_ASSOCIATIONS) {
+                add_unique(associations.removed.entry(mime_type).or_default(), ids);
+            }
+        }
+        associations
+    }
+
+    /// Resolve aliases with an already loaded MIME database instead of loading one
+    pub fn with_database(mut self, database: Arc<MimeDatabase>) -> Self {
+        self.database = OnceLock::from(database);
+        self
+    }
+
+    /// Find the entries for each type with an already loaded cache instead of loading one
+    ///
+    /// Otherwise the caches are read on first use and kept, so make a new
+    /// `MimeAssociations` after applications are installed or removed.
+    pub fn with_mime_info_cache(mut self, cache: Arc<MimeInfoCache>) -> Self {
+        self.cache = OnceLock::from(cache);
+        self
+    }
+
+    fn database(&self) -> &MimeDatabase {
+        self.database.get_or_init(|| Arc::new(MimeDatabase::load()))
+    }
+
+    fn mime_info_cache(&self) -> &MimeInfoCache {
+        self.cache.get_or_init(|| Arc::new(MimeInfoCache::load()))
+    }
+
+    /// Get the desktop file IDs set as default for a type, in preference order
+    ///
+    /// Not every ID is necessarily installed; see [`MimeAssociations::default_application`].
+    pub fn default_applications(&self, mime_type: &str) -> &[String] {
+        lookup(&self.defaults, mime_type)
+    }
+
+    /// Get the desktop file IDs associated with a type on top of their MimeType keys
+    pub fn added_associations(&self, mime_type: &str) -> &[String] {
+        lookup(&self.added, mime_type)
+    }
+
+    /// Get the desktop file IDs whose association with a type was removed
+    pub fn removed_associations(&self, mime_type: &str) -> &[String] {
+        lookup(&self.removed, mime_type)
+    }
+
+    /// Check whether an entry handles a type, per its MimeType key and these associations
+    pub fn is_associated(&self, entry: &ApplicationEntry, mime_type: &str) -> bool {
+        let Some(id) = entry.id() else {
+            return false;
+        };
+        if self.added_associations(mime_type).contains(&id) {
+            return true;
+        }
+        let declared = entry
+            .mime_types()
+            .is_some_and(|types| types.iter().any(|t| t.eq_ignore_ascii_case(mime_type)));
+        declared && !self.removed_associations(mime_type).contains(&id)
+    }
+
+    /// Get the installed applications that handle a type, most preferred first
+    ///
+    /// Added associations come first, followed by the entries whose MimeType key
+    /// lists the type, minus the removed associations. Entries that can't be
+    /// launched are left out. An alias finds the applications of the type it
+    /// stands for and of its other aliases too.
+    pub fn applications_for(&self, mime_type: &str) -> Vec<ApplicationEntry> {
+        self.applications_with(mime_type, self.mime_info_cache())
+    }
+
+    /// [`MimeAssociations::applications_for`] with already loaded caches
+    pub fn applications_with(&self, mime_type: &str, cache: &MimeInfoCache) -> Vec<ApplicationEntry> {
+        self.applications_named(&type_names(mime_type, self.database()), cache)
+    }
+
+    /// Rank the applications for a type, for an "Open With" list
+    ///
+    /// The default application comes first, followed by the applications for the
+    /// type itself as [`MimeAssociations::applications_for`] lists them, then those
+    /// for each type it is a subclass of, closest first, so text editors follow the
+    /// handlers of a source file. An application whose association with the type
+    /// was removed stays off the list even when it handles a parent type.
+    ///
+    /// An alias such as `text/x-markdown` is looked up as the type it stands for,
+    /// and applications listing either name, or another alias of it, are found.
+    pub fn ranked_applications(&self, mime_type: &str, database: &MimeDatabase) -> Vec<ApplicationEntry> {
+        let cache = self.mime_info_cache();
+        let names = type_names(mime_type, database);
+
+        // Without a configured default, the first application found below is the default
+        let mut ranked: Vec<ApplicationEntry> =
+            names.iter().find_map(|name| self.configured_default(name)).into_iter().collect();
+        let mut ids: Vec<Option<String>> = ranked.iter().map(ApplicationEntry::id).collect();
+        let removed: Vec<&String> = names.iter().flat_map(|name| self.removed_associations(name)).collect();
+
+        let parents = database.ancestors(database.unalias(mime_type));
+        let candidates = std::iter::once(names.clone()).chain(parents.into_iter().map(|parent| vec![parent]));
+        for candidate_names in candidates {
+            for entry in self.applications_named(&candidate_names, cache) {
+                let id = entry.id();
+                if ids.contains(&id) || id.as_ref().is_some_and(|id| removed.contains(&id)) {
+                    continue;
+                }
+                ids.push(id);
+                ranked.push(entry);
+            }
+        }
+        ranked
+    }
+
+    /// Get the application that opens a type
+    ///
+    /// Defaults are tried in preference order, as gio does: one is skipped when its
+    /// desktop file is gone or Hidden, when it isn't an application with an Exec
+    /// key whose TryExec program is installed, or when it doesn't handle the type.
+    /// Without a usable default, the most preferred of
+    /// [`MimeAssociations::applications_for`] is used. Defaults set for an alias
+    /// of the type, or for the type an alias stands for, count too.
+    pub fn default_application(&self, mime_type: &str) -> Option<ApplicationEntry> {
+        self.default_application_with(mime_type, self.mime_info_cache())
+    }
+
+    /// [`MimeAssociations::default_application`] with already loaded caches
+    pub fn default_application_with(&self, mime_type: &str, cache: &MimeInfoCache) -> Option<ApplicationEntry> {
+        let names = type_names(mime_type, self.database());
+        names
+            .iter()
+            .find_map(|name| self.configured_default(name))
+            .or_else(|| self.applications_named(&names, cache).into_iter().next())
+    }
+
+    /// Get the application that opens URLs of a scheme, see [`scheme_mime_type`]
+    /// and [`MimeAssociations::default_application`]
+    pub fn default_handler_for_scheme(&self, scheme: &str) -> Option<ApplicationEntry> {
+        self.default_application(&scheme_mime_type(scheme)?)
+    }
+
+    /// Get the applications that open URLs of a scheme, default first
+    ///
+    /// The rest follow in the order of [`MimeAssociations::applications_for`].
+    /// An invalid scheme has no handlers.
+    pub fn handlers_for_scheme(&self, scheme: &str) -> Vec<ApplicationEntry> {
+        let Some(mime_type) = scheme_mime_type(scheme) else {
+            return Vec::new();
+        };
+        let cache = self.mime_info_cache();
+        let mut handlers: Vec<ApplicationEntry> = self.configured_default(&mime_type).into_iter().collect();
+        for entry in self.applications_with(&mime_type, cache) {
+            if !handlers.iter().any(|known| known.id() == entry.id()) {
+                handlers.push(entry);
+            }
+        }
+        handlers
+    }
+
+    /// Get the applications for a type that goes by any of `names`
+    fn applications_named(&self, names: &[&str], cache: &MimeInfoCache) -> Vec<ApplicationEntry> {
+        let mut applications: Vec<ApplicationEntry> = Vec::new();
+        let added = names.iter().flat_map(|name| self.added_associations(name));
+        for entry in added.filter_map(|id| ApplicationEntry::find_by_id(id)).filter(is_launchable) {
+            if !applications.iter().any(|known| known.id() == entry.id()) {
+                applications.push(entry);
+            }
+        }
+        for name in names {
+            for entry in cache.applications_for(name) {
+                let listed = applications.iter().any(|known| known.id() == entry.id());
+                let removed = names.iter().any(|name| {
+                    entry.id().is_some_and(|id| self.removed_associations(name).contains(&id))
+                });
+                if !listed && !removed && is_launchable(&entry) && self.is_associated(&entry, name) {
+                    applications.push(entry);
+                }
+            }
+        }
+        applications
+    }
+
+    /// Get the first usable entry of the Default Applications for a type
+    fn configured_default(&self, mime_type: &str) -> Option<ApplicationEntry> {
+        self.default_applications(mime_type)
+            .iter()
+            .filter_map(|id| ApplicationEntry::find_by_id(id))
+            .find(|entry| is_launchable(entry) && self.is_associated(entry, mime_type))
+    }
+}
+
+impl ApplicationEntry {
+    /// Check whether the MimeType key lists the type of a file
+    ///
+    /// Loads the MIME database on every call; see [`ApplicationEntry::can_open_with`].
+    pub fn can_open<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.can_open_with(path, &MimeDatabase::load())
+    }
+
+    /// Check whether the MimeType key lists the type of a file, read with `database`
+    ///
+    /// The file's type is read from its name and, when needed, its contents. A
+    /// file that can't be read can't be opened. See [`ApplicationEntry::can_open_type`].
+    pub fn can_open_with<P: AsRef<Path>>(&self, path: P, database: &MimeDatabase) -> bool {
+        database
+            .mime_type_for_file(path)
+            .is_ok_and(|mime_type| self.can_open_type(&mime_type, database))
+    }
+
+    /// Check whether the MimeType key lists a type, an alias of it or a type it
+    /// is a subclass of
+    ///
+    /// An editor listing `text/plain` opens `text/x-csrc` files. Every type is
+    /// a subclass of `application/octet-stream`, but that only counts where the
+    /// database declares it, so hex editors aren't offered for everything.
+    pub fn can_open_type(&self, mime_type: &str, database: &MimeDatabase) -> bool {
+        let Some(listed) = self.mime_types() else {
+            return false;
+        };
+        let canonical = database.unalias(mime_type);
+        let accepted: Vec<&str> = std::iter::once(canonical).chain(database.ancestors(canonical)).collect();
+        listed
+            .iter()
+            .map(|listed| listed.to_ascii_lowercase())
+            .any(|listed| accepted.iter().any(|t| t.eq_ignore_ascii_case(database.unalias(&listed))))
+    }
+}
+
+/// Get the key a group already uses for a MIME type, which may differ in case
+fn existing_key(keyfile: &KeyFile, group: &str, mime_type: &str) -> String {
+    keyfile
+        .entries(group)
+        .unwrap_or_default()
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(mime_type))
+        .map_or_else(|| mime_type.to_string(), |(key, _)| key.clone())
+}
+
+/// Read a keyfile that may not exist yet
+fn read_optional(path: &Path) -> io::Result<KeyFile> {
+    match KeyFile::from_path(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(KeyFile::new()),
+        result => result,
+    }
+}
+
+/// Get the names a type goes by: the name given, the type it is an alias of, and
+/// that type's other aliases
+fn type_names<'a>(mime_type: &'a str, database: &'a MimeDatabase) -> Vec<&'a str> {
+    let canonical = database.unalias(mime_type);
+    let mut names = vec![mime_type];
+    for name in std::iter::once(canonical).chain(database.aliases(canonical)) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Replace a file through a temporary file next to it, so readers never see half of it
+///
+/// The temporary name is unique to the call, so concurrent writers in one process
+/// don't share it, and its contents are on disk before it replaces the file.
+fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temp = dir.join(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        });
+    written.and_then(|()| fs::rename(&temp, path)).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+/// Check that an entry is an application that can be started
+fn is_launchable(entry: &ApplicationEntry) -> bool {
+    entry.entry_type() == Some(EntryType::Application) && entry.validate_executable().is_ok()
+}
+
+/// Get the MIME types of a group with their lists of desktop file IDs
+fn list_entries(keyfile: &KeyFile, group: &str) -> Vec<(String, Vec<String>)> {
+    let Some(entries) = keyfile.entries(group) else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .map(|(mime_type, value)| {
+            let ids = value
+                .split(';')
+                .filter(|id| !id.trim().is_empty())
+                .map(normalize_desktop_id)
+                .collect();
+            (mime_type.to_ascii_lowercase(), ids)
+        })
+        .collect()
+}
+
+fn add_unique(list: &mut Vec<String>, ids: Vec<String>) {
+    for id in ids {
+        if !list.contains(&id) {
+            list.push(id);
+        }
+    }
+}
+
+fn lookup<'a>(map: &'a HashMap<String, Vec<String>>, mime_type: &str) -> &'a [String] {
+    map.get(&mime_type.to_ascii_lowercase()).map(Vec::as_slice).unwrap_or_default()
+}
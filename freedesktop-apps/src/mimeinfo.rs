@@ -0,0 +1,189 @@
+//! Finding the applications for a MIME type through `mimeinfo.cache`
+//!
+//! `update-desktop-database` writes a `mimeinfo.cache` into each applications
+//! directory, listing the desktop file IDs whose MimeType key names each type.
+//! Reading it avoids parsing every desktop file to answer which applications
+//! open a type. A directory without a cache, or one changed after the cache was
+//! written, is scanned instead.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use freedesktop_core::keyfile::KeyFile;
+
+use crate::discovery::ApplicationEntries;
+use crate::lookup::find_id_in;
+use crate::{application_entry_paths_by_precedence, normalize_desktop_id, ApplicationEntry};
+
+const MIME_CACHE: &str = "MIME Cache";
+
+/// The desktop file IDs for each MIME type, per applications directory
+#[derive(Debug, Clone, Default)]
+pub struct MimeInfoCache {
+    dirs: Vec<DirIndex>,
+    /// The directories given to [`MimeInfoCache::load_from`], for the entries' IDs
+    custom_dirs: Option<Vec<PathBuf>>,
+}
+
+#[derive(Debug, Clone)]
+struct DirIndex {
+    dir: PathBuf,
+    /// Desktop file IDs by lower case MIME type
+    types: HashMap<String, Vec<String>>,
+    /// Whether `types` was read from the cache rather than by scanning
+    cached: bool,
+}
+
+impl MimeInfoCache {
+    /// Read the caches of the standard applications directories
+    pub fn load() -> Self {
+        MimeInfoCache {
+            dirs: application_entry_paths_by_precedence().iter().map(|dir| DirIndex::load(dir)).collect(),
+            custom_dirs: None,
+        }
+    }
+
+    /// Read the caches of the given applications directories, most important first
+    pub fn load_from(dirs: &[PathBuf]) -> Self {
+        MimeInfoCache {
+            dirs: dirs.iter().map(|dir| DirIndex::load(dir)).collect(),
+            custom_dirs: Some(dirs.to_vec()),
+        }
+    }
+
+    /// Get the directories that were scanned because their cache was missing or
+    /// out of date
+    pub fn stale_dirs(&self) -> Vec<&Path> {
+        self.dirs.iter().filter(|index| !index.cached).map(|index| index.dir.as_path()).collect()
+    }
+
+    /// Get the desktop file IDs listed for a type, in directory precedence order
+    ///
+    /// An ID may belong to a file that was deleted since the cache was written, or
+    /// be shadowed by a copy that no longer lists the type;
+    /// [`MimeInfoCache::applications_for`] checks each one.
+    pub fn ids_for(&self, mime_type: &str) -> Vec<&str> {
+        let mime_type = mime_type.to_ascii_lowercase();
+        let mut ids: Vec<&str> = Vec::new();
+        for index in &self.dirs {
+            for id in index.types.get(&mime_type).into_iter().flatten() {
+                if !ids.contains(&id.as_str()) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids
+    }
+
+    /// Get the installed entries whose MimeType key lists a type
+    ///
+    /// Only the listed desktop files are parsed. Each ID resolves to its
+    /// highest-precedence copy, which is left out when it is Hidden or doesn't
+    /// list the type itself.
+    pub fn applications_for(&self, mime_type: &str) -> Vec<ApplicationEntry> {
+        self.ids_for(mime_type)
+            .into_iter()
+            .filter_map(|id| self.resolve(id))
+            .filter(|entry| {
+                !entry.is_hidden()
+                    && entry
+                        .mime_types()
+                        .is_some_and(|types| types.iter().any(|t| t.eq_ignore_ascii_case(mime_type)))
+            })
+            .collect()
+    }
+
+    fn resolve(&self, id: &str) -> Option<ApplicationEntry> {
+        let path = self.dirs.iter().find_map(|index| find_id_in(&index.dir, id))?;
+        let mut entry = ApplicationEntry::try_from_path(path).ok()?;
+        entry.application_dirs = self.custom_dirs.as_deref().map(Into::into);
+        Some(entry)
+    }
+}
+
+impl DirIndex {
+    /// Read a directory's cache, or scan it when the cache can't be trusted
+    fn load(dir: &Path) -> Self {
+        let cache = dir.join("mimeinfo.cache");
+        let written = std::fs::metadata(&cache).and_then(|metadata| metadata.modified()).ok();
+        let keyfile = written
+            .filter(|written| !changed_since(dir, *written))
+            .and_then(|_| KeyFile::from_path(&cache).ok());
+
+        match keyfile {
+            Some(keyfile) => DirIndex {
+                dir: dir.to_path_buf(),
+                types: read_cache(&keyfile),
+                cached: true,
+            },
+            None => DirIndex {
+                dir: dir.to_path_buf(),
+                types: scan(dir),
+                cached: false,
+            },
+        }
+    }
+}
+
+fn read_cache(keyfile: &KeyFile) -> HashMap<String, Vec<String>> {
+    let mut types: HashMap<String, Vec<String>> = HashMap::new();
+    for (mime_type, value) in keyfile.entries(MIME_CACHE).unwrap_or_default() {
+        let ids = types.entry(mime_type.to_ascii_lowercase()).or_default();
+        for id in value.split(';').filter(|id| !id.trim().is_empty()) {
+            let id = normalize_desktop_id(id);
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    types
+}
+
+/// Build what the cache would hold by parsing every desktop file in `dir`
+fn scan(dir: &Path) -> HashMap<String, Vec<String>> {
+    let dirs = [dir.to_path_buf()];
+    let mut types: HashMap<String, Vec<String>> = HashMap::new();
+    // IDs are relative to `dir`, as update-desktop-database writes them
+    for entry in ApplicationEntries::in_dirs(&dirs, true) {
+        let Some(id) = entry.id() else {
+            continue;
+        };
+        for mime_type in entry.mime_types().unwrap_or_default() {
+            let ids = types.entry(mime_type.to_ascii_lowercase()).or_default();
+            if !ids.contains(&id) {
+                ids.push(id.clone());
+            }
+        }
+    }
+    types
+}
+
+/// Check whether a desktop file below `dir` was modified after `time`, or files
+/// were added or removed since
+///
+/// Adding or removing a file, even one installed with an older time, updates its
+/// directory's time. Directory times are compared in whole seconds, since
+/// replacing the cache updates the directory a moment after the cache was written.
+fn changed_since(dir: &Path, time: SystemTime) -> bool {
+    let seconds = |time: SystemTime| time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let dir_changed = std::fs::metadata(dir)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| seconds(modified) > seconds(time));
+    if dir_changed {
+        return true;
+    }
+    let Ok(dir_entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    dir_entries.filter_map(|e| e.ok()).map(|e| e.path()).any(|path| {
+        if path.is_dir() {
+            changed_since(&path, time)
+        } else {
+            path.extension().is_some_and(|ext| ext == "desktop")
+                && std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| modified > time)
+        }
+    })
+}
@@ -0,0 +1,123 @@
+//! Opening a file or URL with the preferred application, like `xdg-open`
+//!
+//! An argument with a URL scheme goes to the handler of its
+//! `x-scheme-handler/*` type; anything else is a path, whose MIME type is read
+//! from its name and contents. The application is started with the argument as
+//! a URL, which entries that only take files receive as a path.
+//!
+//! ```no_run
+//! freedesktop_apps::open("report.pdf").unwrap();
+//! freedesktop_apps::open("https://freedesktop.org").unwrap();
+//! ```
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use freedesktop_mime::MimeDatabase;
+
+use crate::{scheme_mime_type, ApplicationEntry, ExecuteError, LaunchOptions, Launched, MimeAssociations};
+
+/// Why a file or URL couldn't be opened
+#[derive(Debug, Clone)]
+pub enum OpenError {
+    /// The file couldn't be read to find its type
+    Io(Arc<io::Error>),
+    /// No application opens the type; holds the MIME type
+    NoApplication(String),
+    /// The application was found but didn't start
+    Launch(ExecuteError),
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Io(e) => write!(f, "Failed to read file: {}", e),
+            OpenError::NoApplication(mime_type) => write!(f, "No application opens {}", mime_type),
+            OpenError::Launch(e) => write!(f, "Failed to launch application: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for OpenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OpenError::Io(e) => Some(e.as_ref()),
+            OpenError::Launch(e) => Some(e),
+            OpenError::NoApplication(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for OpenError {
+    fn from(e: io::Error) -> Self {
+        OpenError::Io(Arc::new(e))
+    }
+}
+
+impl From<ExecuteError> for OpenError {
+    fn from(e: ExecuteError) -> Self {
+        OpenError::Launch(e)
+    }
+}
+
+/// Open a file or URL with the preferred application
+///
+/// Loads the MIME database and associations on every call; see [`open_with`].
+pub fn open(target: &str) -> Result<Vec<Launched>, OpenError> {
+    open_with(target, &LaunchOptions::default())
+}
+
+/// Open a file or URL with the preferred application, launched with the given options
+///
+/// Returns a handle per started instance, see [`ApplicationEntry::launch_with_urls`].
+/// Inside a Flatpak sandbox the application is started in the sandbox; to open
+/// it on the host, set a host backend such as
+/// [`FlatpakSpawnLauncher`](crate::FlatpakSpawnLauncher) with [`LaunchOptions::launcher`].
+pub fn open_with(target: &str, options: &LaunchOptions) -> Result<Vec<Launched>, OpenError> {
+    let (entry, url) = opener_for(target)?;
+    Ok(entry.launch_with_urls(&[&url], options)?)
+}
+
+/// Find the application that would open a file or URL, without starting it
+///
+/// Returns the entry and the URL it would be given: a `file://` URL for paths,
+/// which are made absolute first, or the argument itself.
+pub fn opener_for(target: &str) -> Result<(ApplicationEntry, String), OpenError> {
+    let associations = MimeAssociations::load();
+    match url_scheme(target) {
+        Some(scheme) if !scheme.eq_ignore_ascii_case("file") => {
+            let entry = associations
+                .default_handler_for_scheme(scheme)
+                .ok_or_else(|| OpenError::NoApplication(scheme_mime_type(scheme).unwrap_or_default()))?;
+            Ok((entry, target.to_string()))
+        }
+        scheme => {
+            let path = match scheme {
+                Some(_) => freedesktop_core::file_uri_to_path(target).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a local file", target))
+                })?,
+                None => std::path::absolute(target)?,
+            };
+            let database = MimeDatabase::load();
+            let mime_type = database.mime_type_for_file(&path)?;
+            let entry = associations
+                .ranked_applications(&mime_type, &database)
+                .into_iter()
+                .next()
+                .ok_or(OpenError::NoApplication(mime_type))?;
+            Ok((entry, freedesktop_core::file_uri(&path)))
+        }
+    }
+}
+
+/// Get the scheme of an argument that is a URL rather than a path
+///
+/// An existing file whose name merely looks like a URL, such as `notes:draft`,
+/// is a path.
+fn url_scheme(target: &str) -> Option<&str> {
+    let (scheme, _) = target.split_once(':')?;
+    scheme_mime_type(scheme)?;
+    (!Path::new(target).exists()).then_some(scheme)
+}
@@ -1,16 +1,271 @@
 use regex::Regex;
 use std::{
     collections::HashMap,
+    fmt,
     path::{Path, PathBuf},
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read},
+    sync::Arc,
 };
 
+use crate::EntryType;
+
+/// Pattern matching a group header line, capturing the group name
+const GROUP_HEADER: &str = r"^\[([^\[\]]+)\]$";
+
 #[derive(Debug, Clone)]
 pub enum ParseError {
-    IoError(String),
-    InvalidFormat(String),
-    MissingRequiredKey(String),
+    /// The file could not be opened or read
+    IoError {
+        path: PathBuf,
+        line: Option<usize>,
+        source: Arc<io::Error>,
+    },
+    /// The file does not follow the desktop entry format
+    InvalidFormat {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A key (or the `[Desktop Entry]` group) required by the specification is missing
+    MissingRequiredKey { path: PathBuf, key: String },
+    /// The file exceeds one of the configured [`ParseLimits`]
+    LimitExceeded {
+        path: PathBuf,
+        line: Option<usize>,
+        limit: Limit,
+    },
+}
+
+/// A limit from [`ParseLimits`], with its configured value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// Maximum file size in bytes
+    FileSize(u64),
+    /// Maximum number of keys across all groups
+    Keys(usize),
+    /// Maximum length of a line in bytes
+    LineLength(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::FileSize(max) => write!(f, "file is larger than {} bytes", max),
+            Limit::Keys(max) => write!(f, "file has more than {} keys", max),
+            Limit::LineLength(max) => write!(f, "line is longer than {} bytes", max),
+        }
+    }
+}
+
+impl ParseError {
+    pub(crate) fn io(path: &Path, line: Option<usize>, source: io::Error) -> Self {
+        ParseError::IoError {
+            path: path.to_path_buf(),
+            line,
+            source: Arc::new(source),
+        }
+    }
+
+    pub(crate) fn invalid<M: Into<String>>(path: &Path, line: Option<usize>, message: M) -> Self {
+        ParseError::InvalidFormat {
+            path: path.to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn missing<K: Into<String>>(path: &Path, key: K) -> Self {
+        ParseError::MissingRequiredKey {
+            path: path.to_path_buf(),
+            key: key.into(),
+        }
+    }
+
+    pub(crate) fn limit(path: &Path, line: Option<usize>, limit: Limit) -> Self {
+        ParseError::LimitExceeded {
+            path: path.to_path_buf(),
+            line,
+            limit,
+        }
+    }
+
+    /// The file the error occurred in
+    pub fn path(&self) -> &Path {
+        match self {
+            ParseError::IoError { path, .. }
+            | ParseError::InvalidFormat { path, .. }
+            | ParseError::MissingRequiredKey { path, .. }
+            | ParseError::LimitExceeded { path, .. } => path,
+        }
+    }
+
+    /// The 1-based line the error occurred on, when known
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParseError::IoError { line, .. }
+            | ParseError::InvalidFormat { line, .. }
+            | ParseError::LimitExceeded { line, .. } => *line,
+            ParseError::MissingRequiredKey { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path().display())?;
+        if let Some(line) = self.line() {
+            write!(f, ":{}", line)?;
+        }
+        match self {
+            ParseError::IoError { source, .. } => write!(f, ": I/O error: {}", source),
+            ParseError::InvalidFormat { message, .. } => write!(f, ": {}", message),
+            ParseError::MissingRequiredKey { key, .. } => write!(f, ": missing required key {}", key),
+            ParseError::LimitExceeded { limit, .. } => write!(f, ": {}", limit),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::IoError { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// How closely the parser follows the Desktop Entry specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Reject anything the specification does not allow: duplicate groups or keys,
+    /// lines that are neither comments, group headers nor key-value pairs, and
+    /// unknown escape sequences
+    Strict,
+    /// Accept the kind of files found in the wild, skipping or merging what can't be
+    /// interpreted
+    #[default]
+    Permissive,
+}
+
+/// How values containing control characters or invalid UTF-8 are handled
+///
+/// The specification only allows UTF-8 text without control characters in values;
+/// tabs and carriage returns have to be written as the `\t` and `\r` escapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValuePolicy {
+    /// Keep control characters as they are; invalid UTF-8 fails with an I/O error
+    #[default]
+    Lenient,
+    /// Fail with [`ParseError::InvalidFormat`] on control characters or invalid UTF-8
+    Reject,
+    /// Replace invalid UTF-8 with U+FFFD, escape tabs and carriage returns and drop
+    /// other control characters
+    Repair,
+}
+
+/// Bounds on the size of a desktop file, protecting scans of untrusted directories
+///
+/// The defaults are far above anything found in real entries: 4 MiB per file,
+/// 16384 keys and 64 KiB per line. Reading stops as soon as a limit is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    max_file_size: u64,
+    max_keys: usize,
+    max_line_length: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_file_size: 4 * 1024 * 1024,
+            max_keys: 16384,
+            max_line_length: 64 * 1024,
+        }
+    }
+}
+
+impl ParseLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No limits, for files from trusted sources
+    pub fn unlimited() -> Self {
+        ParseLimits {
+            max_file_size: u64::MAX,
+            max_keys: usize::MAX,
+            max_line_length: usize::MAX,
+        }
+    }
+
+    /// Maximum size of the file in bytes
+    pub fn max_file_size(mut self, max: u64) -> Self {
+        self.max_file_size = max;
+        self
+    }
+
+    /// Maximum number of keys, counted across all groups
+    pub fn max_keys(mut self, max: usize) -> Self {
+        self.max_keys = max;
+        self
+    }
+
+    /// Maximum length of a line in bytes, not counting the newline
+    pub fn max_line_length(mut self, max: usize) -> Self {
+        self.max_line_length = max;
+        self
+    }
+}
+
+/// Options controlling how desktop files are parsed
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+    pub values: ValuePolicy,
+    pub limits: ParseLimits,
+}
+
+impl ParseOptions {
+    /// Options for strict specification enforcement, suitable for validators
+    pub fn strict() -> Self {
+        Self {
+            mode: ParseMode::Strict,
+            values: ValuePolicy::Reject,
+            limits: ParseLimits::default(),
+        }
+    }
+
+    /// Options for permissive real-world parsing, suitable for launchers
+    pub fn permissive() -> Self {
+        Self {
+            mode: ParseMode::Permissive,
+            values: ValuePolicy::Lenient,
+            limits: ParseLimits::default(),
+        }
+    }
+
+    /// Set the parse mode
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set how control characters and invalid UTF-8 in values are handled
+    pub fn values(mut self, values: ValuePolicy) -> Self {
+        self.values = values;
+        self
+    }
+
+    /// Set the limits on file size, key count and line length
+    pub fn limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn is_strict(&self) -> bool {
+        self.mode == ParseMode::Strict
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -54,12 +309,14 @@ impl LocalizedKey {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct DesktopEntryGroup {
     #[allow(dead_code)] // Reserved for future group name tracking
     pub name: String,
     pub fields: HashMap<String, ValueType>,
     pub localized_fields: HashMap<String, HashMap<String, ValueType>>,
+    /// Keys as written, with their locale, and their values before unescaping, in file order
+    pub ordered: Vec<(String, String)>,
 }
 
 impl DesktopEntryGroup {
@@ -68,6 +325,15 @@ impl DesktopEntryGroup {
             name: name.into(),
             fields: HashMap::new(),
             localized_fields: HashMap::new(),
+            ordered: Vec::new(),
+        }
+    }
+
+    /// Record a key and its value as written; a repeated key keeps its first position
+    pub fn insert_raw(&mut self, key: &str, value: &str) {
+        match self.ordered.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => self.ordered.push((key.to_string(), value.to_string())),
         }
     }
 
@@ -88,6 +354,18 @@ impl DesktopEntryGroup {
         self.fields.get(key)
     }
 
+    /// Check whether a (possibly localized) key is already present
+    pub fn contains_key(&self, key: &str) -> bool {
+        let localized_key = LocalizedKey::parse(key);
+        match localized_key.locale {
+            Some(locale) => self
+                .localized_fields
+                .get(&localized_key.key)
+                .is_some_and(|map| map.contains_key(&locale)),
+            None => self.fields.contains_key(&localized_key.key),
+        }
+    }
+
     pub fn get_localized_field(&self, key: &str, locale: Option<&str>) -> Option<&ValueType> {
         if let Some(locale) = locale {
             if let Some(localized_map) = self.localized_fields.get(key) {
@@ -177,29 +455,67 @@ impl DesktopEntryGroup {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct DesktopEntry {
     pub path: PathBuf,
     pub groups: HashMap<String, DesktopEntryGroup>,
+    /// Group names in the order they first appear in the file
+    pub group_order: Vec<String>,
 }
 
 impl DesktopEntry {
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
-        let file = File::open(path.as_ref())
-            .map_err(|e| ParseError::IoError(format!("Failed to open file: {}", e)))?;
-        let reader = BufReader::new(file);
+        Self::from_path_with(path, &ParseOptions::default())
+    }
+
+    pub fn from_path_with<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Self, ParseError> {
+        let path = path.as_ref();
+        let limits = options.limits;
+        let file = File::open(path).map_err(|e| ParseError::io(path, None, e))?;
+        let metadata = file.metadata().map_err(|e| ParseError::io(path, None, e))?;
+        if metadata.len() > limits.max_file_size {
+            return Err(ParseError::limit(path, None, Limit::FileSize(limits.max_file_size)));
+        }
+        Self::from_reader_with(path, file, options)
+    }
+
+    /// Parse desktop file data from a reader, reporting errors against `path`
+    pub(crate) fn from_reader_with<R: Read>(path: &Path, reader: R, options: &ParseOptions) -> Result<Self, ParseError> {
+        let limits = options.limits;
+        // Files that don't report their size, like pipes or devices, are cut off while reading
+        let mut reader = BufReader::new(reader.take(limits.max_file_size.saturating_add(1)));
         
-        let group_header_regex = Regex::new(r"^\[([^\[\]]+)\]$")
-            .map_err(|e| ParseError::InvalidFormat(format!("Regex error: {}", e)))?;
+        let group_header_regex = Regex::new(GROUP_HEADER)
+            .map_err(|e| ParseError::invalid(path, None, format!("Regex error: {}", e)))?;
 
         let mut current_group: Option<String> = None;
         let mut entry = DesktopEntry { 
-            path: path.as_ref().to_path_buf(), 
+            path: path.to_path_buf(), 
             ..Default::default() 
         };
         
-        for (line_num, line) in reader.lines().enumerate() {
-            let line = line.map_err(|e| ParseError::IoError(format!("Failed to read line {}: {}", line_num + 1, e)))?;
+        let mut bytes_read: u64 = 0;
+        let mut key_count: usize = 0;
+        let mut buffer = Vec::new();
+        for line_num in 0.. {
+            match read_line(&mut reader, &mut buffer, limits.max_line_length) {
+                Ok(LineRead::Line) => {}
+                Ok(LineRead::End) => break,
+                Ok(LineRead::TooLong) => {
+                    return Err(ParseError::limit(path, Some(line_num + 1), Limit::LineLength(limits.max_line_length)));
+                }
+                Err(e) => return Err(ParseError::io(path, Some(line_num + 1), e)),
+            }
+            bytes_read += buffer.len() as u64 + 1;
+            if bytes_read > limits.max_file_size.saturating_add(1) {
+                return Err(ParseError::limit(path, Some(line_num + 1), Limit::FileSize(limits.max_file_size)));
+            }
+
+            let line = decode_line(std::mem::take(&mut buffer), options.values)
+                .map_err(|e| match e {
+                    LineError::Io(e) => ParseError::io(path, Some(line_num + 1), e),
+                    LineError::Invalid(message) => ParseError::invalid(path, Some(line_num + 1), message),
+                })?;
             let line = line.trim();
 
             // Skip empty lines and comments
@@ -210,9 +526,15 @@ impl DesktopEntry {
             // Check for group header
             if let Some(captures) = group_header_regex.captures(line) {
                 let group_name = captures[1].to_string();
-                current_group = Some(group_name.clone());
-                entry.groups.entry(group_name.clone())
-                    .or_insert_with(|| DesktopEntryGroup::new(group_name));
+                if options.is_strict() && entry.groups.contains_key(&group_name) {
+                    return Err(ParseError::invalid(
+                        path,
+                        Some(line_num + 1),
+                        format!("Duplicate group [{}]", group_name),
+                    ));
+                }
+                entry.add_group(&group_name);
+                current_group = Some(group_name);
                 continue;
             }
 
@@ -220,23 +542,66 @@ impl DesktopEntry {
             if let Some(eq_pos) = line.find('=') {
                 let key = line[..eq_pos].trim();
                 let value = line[eq_pos + 1..].trim();
+                let repaired;
+                let value = match options.values {
+                    ValuePolicy::Lenient => value,
+                    ValuePolicy::Reject => {
+                        if let Some(c) = value.chars().find(|c| c.is_control()) {
+                            return Err(ParseError::invalid(
+                                path,
+                                Some(line_num + 1),
+                                format!("Control character U+{:04X} in value of {}", c as u32, key),
+                            ));
+                        }
+                        value
+                    }
+                    ValuePolicy::Repair => {
+                        repaired = repair_control_chars(value);
+                        &repaired
+                    }
+                };
 
                 if key.is_empty() {
                     continue; // Skip invalid entries
                 }
 
                 if !is_valid_key_name(key) {
-                    return Err(ParseError::InvalidFormat(format!("Invalid key name: {}", key)));
+                    return Err(ParseError::invalid(path, Some(line_num + 1), format!("Invalid key name: {}", key)));
                 }
 
                 if let Some(ref group_name) = current_group {
+                    key_count += 1;
+                    if key_count > limits.max_keys {
+                        return Err(ParseError::limit(path, Some(line_num + 1), Limit::Keys(limits.max_keys)));
+                    }
+                    if options.is_strict() {
+                        check_escapes(value).map_err(|e| ParseError::invalid(path, Some(line_num + 1), e))?;
+                    }
                     let parsed_value = parse_value(value)?;
                     if let Some(group) = entry.groups.get_mut(group_name) {
+                        if options.is_strict() && group.contains_key(key) {
+                            return Err(ParseError::invalid(
+                                path,
+                                Some(line_num + 1),
+                                format!("Duplicate key {}", key),
+                            ));
+                        }
                         group.insert_field(key, parsed_value);
+                        group.insert_raw(key, value);
                     }
                 } else {
-                    return Err(ParseError::InvalidFormat("Key-value pair found before any group header".to_string()));
+                    return Err(ParseError::invalid(
+                        path,
+                        Some(line_num + 1),
+                        "Key-value pair found before any group header",
+                    ));
                 }
+            } else if options.is_strict() {
+                return Err(ParseError::invalid(
+                    path,
+                    Some(line_num + 1),
+                    "Line is not a comment, group header or key-value pair",
+                ));
             }
         }
 
@@ -246,42 +611,205 @@ impl DesktopEntry {
         Ok(entry)
     }
 
-    fn validate(&self) -> Result<(), ParseError> {
+    /// Read only the given keys of the `[Desktop Entry]` group, with their translations
+    ///
+    /// Reading stops at the end of the group, so actions and other groups aren't
+    /// looked at. Lines are handled permissively and the limits apply as usual.
+    pub(crate) fn metadata_from_path(path: &Path, keys: &[&str], limits: ParseLimits) -> Result<Self, ParseError> {
+        let file = File::open(path).map_err(|e| ParseError::io(path, None, e))?;
+        let mut reader = BufReader::new(file.take(limits.max_file_size.saturating_add(1)));
+        let group_header_regex = Regex::new(GROUP_HEADER)
+            .map_err(|e| ParseError::invalid(path, None, format!("Regex error: {}", e)))?;
+        let mut in_group = false;
+        let mut group: Option<DesktopEntryGroup> = None;
+        let mut bytes_read: u64 = 0;
+        let mut key_count: usize = 0;
+        let mut buffer = Vec::new();
+
+        for line_num in 0.. {
+            match read_line(&mut reader, &mut buffer, limits.max_line_length) {
+                Ok(LineRead::Line) => {}
+                Ok(LineRead::End) => break,
+                Ok(LineRead::TooLong) => {
+                    return Err(ParseError::limit(path, Some(line_num + 1), Limit::LineLength(limits.max_line_length)));
+                }
+                Err(e) => return Err(ParseError::io(path, Some(line_num + 1), e)),
+            }
+            bytes_read += buffer.len() as u64 + 1;
+            if bytes_read > limits.max_file_size.saturating_add(1) {
+                return Err(ParseError::limit(path, Some(line_num + 1), Limit::FileSize(limits.max_file_size)));
+            }
+            let line = decode_line(std::mem::take(&mut buffer), ValuePolicy::Lenient).map_err(|e| match e {
+                LineError::Io(e) => ParseError::io(path, Some(line_num + 1), e),
+                LineError::Invalid(message) => ParseError::invalid(path, Some(line_num + 1), message),
+            })?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(captures) = group_header_regex.captures(line) {
+                if group.is_some() {
+                    break;
+                }
+                if &captures[1] == "Desktop Entry" {
+                    group = Some(DesktopEntryGroup::new("Desktop Entry"));
+                }
+                in_group = true;
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=').filter(|_| in_group) else {
+                continue;
+            };
+            key_count += 1;
+            if key_count > limits.max_keys {
+                return Err(ParseError::limit(path, Some(line_num + 1), Limit::Keys(limits.max_keys)));
+            }
+            let Some(group) = group.as_mut() else {
+                continue;
+            };
+            let key = key.trim();
+            if keys.contains(&LocalizedKey::parse(key).key.as_str()) {
+                group.insert_field(key, parse_value(value.trim())?);
+                group.insert_raw(key, value.trim());
+            }
+        }
+
+        let mut entry = DesktopEntry {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        if let Some(group) = group {
+            entry.group_order.push(group.name.clone());
+            entry.groups.insert(group.name.clone(), group);
+        }
+        entry.validate()?;
+        Ok(entry)
+    }
+
+    /// Salvage what can be read from a damaged file
+    ///
+    /// Lines that aren't valid UTF-8, contain control characters, are too long or
+    /// don't form a group header or key-value pair are skipped instead of failing the
+    /// parse, and the file is cut off at the size limit. Required keys aren't checked.
+    /// Returns the entry with the number of content lines (neither blank nor comments)
+    /// and how many of them were kept.
+    pub(crate) fn recover_from_path(path: &Path, limits: ParseLimits) -> Result<(Self, usize, usize), ParseError> {
+        let file = File::open(path).map_err(|e| ParseError::io(path, None, e))?;
+        let mut reader = BufReader::new(file.take(limits.max_file_size));
+        let group_header_regex = Regex::new(GROUP_HEADER)
+            .map_err(|e| ParseError::invalid(path, None, format!("Regex error: {}", e)))?;
+
+        let mut entry = DesktopEntry {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        let mut current_group: Option<String> = None;
+        let (mut total, mut kept, mut keys) = (0, 0, 0);
+        let mut buffer = Vec::new();
+
+        for line_num in 0.. {
+            let line = match read_line(&mut reader, &mut buffer, limits.max_line_length) {
+                Ok(LineRead::Line) => String::from_utf8(std::mem::take(&mut buffer)).ok(),
+                Ok(LineRead::End) => break,
+                Ok(LineRead::TooLong) => {
+                    skip_line(&mut reader).map_err(|e| ParseError::io(path, Some(line_num + 1), e))?;
+                    None
+                }
+                Err(e) => return Err(ParseError::io(path, Some(line_num + 1), e)),
+            };
+
+            let trimmed = line.as_deref().map(str::trim);
+            if trimmed.is_some_and(|line| line.is_empty() || line.starts_with('#')) {
+                continue;
+            }
+            total += 1;
+
+            // Binary garbage shows up as invalid UTF-8 or control characters
+            let Some(line) = trimmed.filter(|line| !line.chars().any(char::is_control)) else {
+                continue;
+            };
+
+            if let Some(captures) = group_header_regex.captures(line) {
+                let group_name = captures[1].to_string();
+                entry.add_group(&group_name);
+                current_group = Some(group_name);
+                kept += 1;
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            if key.is_empty() || !is_valid_key_name(key) || keys >= limits.max_keys {
+                continue;
+            }
+            let Some(group) = current_group.as_ref().and_then(|name| entry.groups.get_mut(name)) else {
+                continue;
+            };
+            if let Ok(parsed) = parse_value(value.trim()) {
+                group.insert_field(key, parsed);
+                group.insert_raw(key, value.trim());
+                keys += 1;
+                kept += 1;
+            }
+        }
+
+        Ok((entry, total, kept))
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ParseError> {
         let desktop_entry = self.groups.get("Desktop Entry")
-            .ok_or_else(|| ParseError::MissingRequiredKey("Desktop Entry group is required".to_string()))?;
+            .ok_or_else(|| ParseError::missing(&self.path, "[Desktop Entry]"))?;
 
         // Type is required
         let entry_type = desktop_entry.get_field("Type")
-            .ok_or_else(|| ParseError::MissingRequiredKey("Type key is required".to_string()))?;
+            .ok_or_else(|| ParseError::missing(&self.path, "Type"))?;
 
         // Name is required
         desktop_entry.get_field("Name")
-            .ok_or_else(|| ParseError::MissingRequiredKey("Name key is required".to_string()))?;
+            .ok_or_else(|| ParseError::missing(&self.path, "Name"))?;
 
         // For Application type, Exec is required unless DBusActivatable=true
         if let ValueType::String(type_val) = entry_type {
-            if type_val == "Application" {
-                let dbus_activatable = desktop_entry.get_field("DBusActivatable")
-                    .and_then(|v| match v {
-                        ValueType::Boolean(b) => Some(*b),
-                        _ => None,
-                    })
-                    .unwrap_or(false);
-
-                if !dbus_activatable {
-                    desktop_entry.get_field("Exec")
-                        .ok_or_else(|| ParseError::MissingRequiredKey("Exec key is required for Application type".to_string()))?;
+            match EntryType::from(type_val.as_str()) {
+                EntryType::Application => {
+                    let dbus_activatable = desktop_entry.get_field("DBusActivatable")
+                        .and_then(|v| match v {
+                            ValueType::Boolean(b) => Some(*b),
+                            _ => None,
+                        })
+                        .unwrap_or(false);
+
+                    if !dbus_activatable {
+                        desktop_entry.get_field("Exec")
+                            .ok_or_else(|| ParseError::missing(&self.path, "Exec"))?;
+                    }
+                }
+                EntryType::Link => {
+                    // URL is required for Link type
+                    desktop_entry.get_field("URL")
+                        .ok_or_else(|| ParseError::missing(&self.path, "URL"))?;
                 }
-            } else if type_val == "Link" {
-                // URL is required for Link type
-                desktop_entry.get_field("URL")
-                    .ok_or_else(|| ParseError::MissingRequiredKey("URL key is required for Link type".to_string()))?;
+                EntryType::Directory | EntryType::Other(_) => {}
             }
         }
 
         Ok(())
     }
 
+    /// Get a group, adding it after the existing ones if it is new
+    fn add_group(&mut self, name: &str) -> &mut DesktopEntryGroup {
+        if !self.groups.contains_key(name) {
+            self.group_order.push(name.to_string());
+        }
+        self.groups
+            .entry(name.to_string())
+            .or_insert_with(|| DesktopEntryGroup::new(name))
+    }
+
     pub fn get_desktop_entry_group(&self) -> Option<&DesktopEntryGroup> {
         self.groups.get("Desktop Entry")
     }
@@ -325,6 +853,142 @@ fn parse_value(value: &str) -> Result<ValueType, ParseError> {
     Ok(ValueType::String(unescaped))
 }
 
+enum LineError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+/// Outcome of reading a single line with `read_line`
+enum LineRead {
+    Line,
+    End,
+    TooLong,
+}
+
+/// Read the next line without its newline into `buffer`, giving up once it is
+/// longer than `max` bytes so a single huge line isn't held in memory
+fn read_line<R: BufRead>(reader: &mut R, buffer: &mut Vec<u8>, max: usize) -> io::Result<LineRead> {
+    buffer.clear();
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(if buffer.is_empty() { LineRead::End } else { LineRead::Line });
+        }
+
+        let newline = available.iter().position(|&b| b == b'\n');
+        let chunk = &available[..newline.unwrap_or(available.len())];
+        if buffer.len() + chunk.len() > max {
+            return Ok(LineRead::TooLong);
+        }
+        buffer.extend_from_slice(chunk);
+
+        let consumed = chunk.len() + usize::from(newline.is_some());
+        reader.consume(consumed);
+        if newline.is_some() {
+            return Ok(LineRead::Line);
+        }
+    }
+}
+
+/// Skip the rest of the current line, including its newline
+fn skip_line<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(());
+        }
+        match available.iter().position(|&b| b == b'\n') {
+            Some(newline) => {
+                reader.consume(newline + 1);
+                return Ok(());
+            }
+            None => {
+                let len = available.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Decode a raw line according to the value policy
+fn decode_line(bytes: Vec<u8>, policy: ValuePolicy) -> Result<String, LineError> {
+    match String::from_utf8(bytes) {
+        Ok(line) => Ok(line),
+        Err(e) => match policy {
+            ValuePolicy::Lenient => Err(LineError::Io(io::Error::new(io::ErrorKind::InvalidData, e))),
+            ValuePolicy::Reject => Err(LineError::Invalid(format!("Invalid UTF-8: {}", e.utf8_error()))),
+            ValuePolicy::Repair => Ok(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        },
+    }
+}
+
+/// Escape tabs and carriage returns and drop other control characters
+fn repair_control_chars(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            c if c.is_control() => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Escape a value for writing to a desktop file
+///
+/// Produces only the escape sequences defined by the specification, so the output
+/// is accepted by strict parsers. Semicolons are left alone since they only
+/// separate items of list values; write those with [`escape_list`].
+pub fn escape_value(value: &str) -> String {
+    escape(value, false)
+}
+
+/// Escape the items of a list value and join them, ending with a `;`
+///
+/// Semicolons within an item are escaped so they aren't read as separators.
+pub fn escape_list(items: &[&str]) -> String {
+    items.iter().map(|item| escape(item, true) + ";").collect()
+}
+
+fn escape(value: &str, list_item: bool) -> String {
+    // Spaces at either end would be lost to trimming when the value is read back
+    let start = value.len() - value.trim_start_matches(' ').len();
+    let end = value.trim_end_matches(' ').len();
+    let mut result = String::with_capacity(value.len());
+    for (i, ch) in value.char_indices() {
+        match ch {
+            ' ' if i < start || i >= end => result.push_str("\\s"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            '\\' => result.push_str("\\\\"),
+            ';' if list_item => result.push_str("\\;"),
+            c if c.is_control() => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Ensure a raw value only uses the escape sequences defined by the specification
+fn check_escapes(value: &str) -> Result<(), String> {
+    let mut chars = value.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('s' | 'n' | 't' | 'r' | '\\' | ';') => {}
+                Some(other) => return Err(format!("Unknown escape sequence \\{}", other)),
+                None => return Err("Trailing backslash".to_string()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn unescape_value(value: &str) -> String {
     let mut result = String::new();
     let mut chars = value.chars();
@@ -434,6 +1098,70 @@ mod tests {
         assert_eq!(unescape_value("backslash\\\\"), "backslash\\");
     }
 
+    #[test]
+    fn test_strict_escape_check() {
+        assert!(check_escapes("hello\\sworld\\;").is_ok());
+        assert!(check_escapes("bad\\qescape").is_err());
+        assert!(check_escapes("trailing\\").is_err());
+    }
+
+    #[test]
+    fn test_value_repair_and_escape() {
+        assert_eq!(repair_control_chars("a\tb\u{7}c\r"), "a\\tbc\\r");
+        assert_eq!(escape_value(" lead;x\\y\nz\u{1}"), "\\slead;x\\\\y\\nz");
+        assert!(check_escapes(&escape_value(" \t\\;\n")).is_ok());
+        assert_eq!(escape_list(&["a;b", "c"]), "a\\;b;c;");
+        assert_eq!(split_semicolon_list(&escape_list(&["a;b", " c\\"])), ["a;b", " c\\"]);
+        assert_eq!(unescape_value(&escape_value("tab\there\\")), "tab\there\\");
+    }
+
+    #[test]
+    fn test_escaped_spaces_round_trip() {
+        assert_eq!(escape_value(" a b  "), "\\sa b\\s\\s");
+        assert_eq!(escape_list(&[" x ", "y"]), "\\sx\\s;y;");
+
+        let content = format!(
+            "[Desktop Entry]\nType=Application\nExec=app\nName={}\nKeywords={}\n",
+            escape_value("  two words  "),
+            escape_list(&[" one ", "two "]),
+        );
+        let entry = DesktopEntry::from_reader_with(Path::new("test.desktop"), content.as_bytes(), &ParseOptions::default()).unwrap();
+        let group = entry.get_desktop_entry_group().unwrap();
+        assert_eq!(group.get_field("Name"), Some(&ValueType::String("  two words  ".to_string())));
+        assert_eq!(
+            group.get_field("Keywords"),
+            Some(&ValueType::StringList(vec![" one ".to_string(), "two ".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_metadata_applies_limits() {
+        let path = Path::new("/tmp/parser_metadata_limits.desktop");
+        let keys = ["Name", "Exec", "Type"];
+        std::fs::write(
+            path,
+            "[Desktop Entry]\nType=Application\n[Not]A Header]\nName=App\nExec=app\nComment=A long comment\n[Other]\nX=1\n",
+        )
+        .unwrap();
+
+        // Lines that only look like a header don't end the group
+        let entry = DesktopEntry::metadata_from_path(path, &keys, ParseLimits::default()).unwrap();
+        let group = entry.get_desktop_entry_group().unwrap();
+        assert_eq!(group.get_field("Name"), Some(&ValueType::String("App".to_string())));
+
+        assert!(matches!(
+            DesktopEntry::metadata_from_path(path, &keys, ParseLimits::default().max_file_size(64)),
+            Err(ParseError::LimitExceeded { limit: Limit::FileSize(64), .. })
+        ));
+        // Keys that aren't read still count
+        assert!(matches!(
+            DesktopEntry::metadata_from_path(path, &keys, ParseLimits::default().max_keys(3)),
+            Err(ParseError::LimitExceeded { limit: Limit::Keys(3), .. })
+        ));
+
+        std::fs::remove_file(path).ok();
+    }
+
     #[test]
     fn test_key_validation() {
         assert!(is_valid_key_name("Name"));
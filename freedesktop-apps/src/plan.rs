@@ -0,0 +1,136 @@
+//! Everything needed to start an entry, for callers that start processes themselves
+//!
+//! Compositors and session managers often run applications under their own process
+//! supervisor. [`ApplicationEntry::plan`] does the same preparation as
+//! [`ApplicationEntry::launch_with`] and returns the result instead of starting it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::launcher::{build_command, working_dir};
+use crate::{ApplicationEntry, DetachMode, ExecuteError, LaunchOptions, StartupSequence};
+
+/// A prepared launch of an entry, see [`ApplicationEntry::plan`]
+#[derive(Debug, Clone)]
+pub struct LaunchPlan {
+    program: String,
+    args: Vec<String>,
+    terminal_command: Option<Vec<String>>,
+    working_dir: Option<PathBuf>,
+    startup: Option<StartupSequence>,
+    downloads: Vec<PathBuf>,
+    options: LaunchOptions,
+}
+
+impl LaunchPlan {
+    /// Get the program to run, which is the terminal emulator for Terminal=true entries
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Get the program's arguments
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Get the application's own command line if it was wrapped in a terminal emulator
+    ///
+    /// Callers with their own terminal handling run this instead of
+    /// [`LaunchPlan::program`] and [`LaunchPlan::args`].
+    pub fn terminal_command(&self) -> Option<&[String]> {
+        self.terminal_command.as_deref()
+    }
+
+    /// Get the directory to run the program in
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    /// Get the variables to set, including `XDG_ACTIVATION_TOKEN` and `DESKTOP_STARTUP_ID`
+    pub fn env(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.options.env_vars()
+    }
+
+    /// Get the inherited variables to remove
+    pub fn env_remove(&self) -> impl Iterator<Item = &str> {
+        self.options.env_removed()
+    }
+
+    /// Check if the program should start with an empty environment
+    ///
+    /// The display variables are kept in that case, see [`LaunchOptions::env_clear`].
+    pub fn clears_env(&self) -> bool {
+        self.options.clears_env()
+    }
+
+    /// Check if the program runs as root through `pkexec`, see [`LaunchOptions::elevation`]
+    ///
+    /// [`LaunchPlan::command`] includes pkexec; [`LaunchPlan::program`] doesn't.
+    pub fn elevates(&self) -> bool {
+        self.options.elevates()
+    }
+
+    /// Check if the program should get its own process group and no stdio
+    pub fn detaches(&self) -> bool {
+        self.options.detaches()
+    }
+
+    /// Get how the program should be detached, see [`LaunchOptions::detach_mode`]
+    pub fn detach_mode(&self) -> DetachMode {
+        self.options.detach_mode_value()
+    }
+
+    /// Get the startup notification sequence of the launch
+    ///
+    /// Its ID is already in [`LaunchPlan::env`]; announcing and ending it is up to the caller.
+    pub fn startup(&self) -> Option<&StartupSequence> {
+        self.startup.as_ref()
+    }
+
+    /// Get the temporary copies of remote URLs downloaded for the launch
+    ///
+    /// They belong to the caller, who removes them once the application is done
+    /// with them or the plan is dropped without being run.
+    pub fn downloads(&self) -> &[PathBuf] {
+        &self.downloads
+    }
+
+    /// Build a command that carries out the plan the way [`ApplicationEntry::launch_with`] would
+    pub fn command(&self) -> Command {
+        build_command(&self.program, &self.args, self.working_dir(), &self.options)
+    }
+}
+
+impl ApplicationEntry {
+    /// Prepare a launch without starting it
+    ///
+    /// Validates the entry, expands field codes, wraps Terminal=true entries and
+    /// assigns the activation token and startup ID, like [`ApplicationEntry::launch_with`].
+    /// Nothing is recorded in the audit log and the startup notifier isn't called.
+    pub fn plan(&self, files: &[&str], urls: &[&str], options: &LaunchOptions) -> Result<LaunchPlan, ExecuteError> {
+        let expanded = self.expanded_command(files, urls, options)?;
+        let (program, args) = expanded.command;
+        let ((program, args), terminal_command) = if self.terminal() {
+            let wrapped = self.wrap_with_terminal(&program, &args, options.holds_terminal())?;
+            (wrapped, Some(std::iter::once(program).chain(args).collect()))
+        } else {
+            ((program, args), None)
+        };
+
+        let mut options = options.with_launch_env(self).into_owned();
+        let startup = self.startup_sequence(&options);
+        if let Some(startup) = &startup {
+            options = options.env("DESKTOP_STARTUP_ID", startup.id());
+        }
+
+        Ok(LaunchPlan {
+            program,
+            args,
+            terminal_command,
+            working_dir: working_dir(self, &options),
+            startup,
+            downloads: expanded.downloads,
+            options,
+        })
+    }
+}
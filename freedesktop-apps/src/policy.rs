@@ -0,0 +1,122 @@
+//! Central restrictions on which applications a registry offers
+//!
+//! Kiosk and enterprise deployments limit the launchable applications with a
+//! config file instead of deleting desktop files, which package updates would
+//! bring back. The file is a keyfile with a `[Policy]` group:
+//!
+//! ```ini
+//! [Policy]
+//! AllowCategories=Office;Network;
+//! AllowIds=org.gnome.Calculator;
+//! DenyIds=org.gnome.Terminal;
+//! DenyCategories=Game;
+//! ```
+//!
+//! Denying wins over allowing. Once either allow list is set, only entries on it
+//! are permitted.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use freedesktop_core::keyfile::KeyFile;
+
+use crate::{normalize_desktop_id, ApplicationEntry};
+
+const POLICY_GROUP: &str = "Policy";
+
+/// Allow and deny lists of desktop file IDs and categories, see [`Registry::with_policy`]
+///
+/// [`Registry::with_policy`]: crate::Registry::with_policy
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppPolicy {
+    allow_ids: Vec<String>,
+    allow_categories: Vec<String>,
+    deny_ids: Vec<String>,
+    deny_categories: Vec<String>,
+}
+
+impl AppPolicy {
+    /// A policy that permits every entry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the policy from a keyfile's `[Policy]` group
+    pub fn from_keyfile(keyfile: &KeyFile) -> Self {
+        let list = |key: &str| keyfile.get_list(POLICY_GROUP, key).unwrap_or_default();
+        AppPolicy {
+            allow_ids: list("AllowIds").iter().map(|id| normalize_desktop_id(id)).collect(),
+            allow_categories: list("AllowCategories"),
+            deny_ids: list("DenyIds").iter().map(|id| normalize_desktop_id(id)).collect(),
+            deny_categories: list("DenyCategories"),
+        }
+    }
+
+    /// Read the policy from a file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        KeyFile::from_path(path).map(|keyfile| Self::from_keyfile(&keyfile))
+    }
+
+    /// Default location of the policy, `freedesktop/app-policy.conf` in the first
+    /// XDG_CONFIG_DIRS entry that has one
+    ///
+    /// XDG_CONFIG_HOME isn't searched, so users can't lift a system-wide policy.
+    pub fn default_path() -> Option<PathBuf> {
+        freedesktop_core::config_dirs()
+            .into_iter()
+            .map(|dir| dir.join("freedesktop").join("app-policy.conf"))
+            .find(|path| path.is_file())
+    }
+
+    /// Load the policy from [`AppPolicy::default_path`], if one is installed
+    pub fn load() -> Option<Self> {
+        Self::from_path(Self::default_path()?).ok()
+    }
+
+    /// Permit the entry with this desktop file ID
+    pub fn allow_id(mut self, id: &str) -> Self {
+        self.allow_ids.push(normalize_desktop_id(id));
+        self
+    }
+
+    /// Permit entries in this category
+    pub fn allow_category<S: Into<String>>(mut self, category: S) -> Self {
+        self.allow_categories.push(category.into());
+        self
+    }
+
+    /// Refuse the entry with this desktop file ID
+    pub fn deny_id(mut self, id: &str) -> Self {
+        self.deny_ids.push(normalize_desktop_id(id));
+        self
+    }
+
+    /// Refuse entries in this category
+    pub fn deny_category<S: Into<String>>(mut self, category: S) -> Self {
+        self.deny_categories.push(category.into());
+        self
+    }
+
+    /// Check if the policy restricts anything at all
+    pub fn is_empty(&self) -> bool {
+        self.allow_ids.is_empty()
+            && self.allow_categories.is_empty()
+            && self.deny_ids.is_empty()
+            && self.deny_categories.is_empty()
+    }
+
+    /// Check whether the policy lets users see and launch the entry
+    pub fn permits(&self, entry: &ApplicationEntry) -> bool {
+        let id = entry.id();
+        let categories = entry.categories().unwrap_or_default();
+        let listed = |ids: &[String], list: &[String]| {
+            id.as_ref().is_some_and(|id| ids.contains(id)) || categories.iter().any(|c| list.contains(c))
+        };
+
+        if listed(&self.deny_ids, &self.deny_categories) {
+            return false;
+        }
+        let restricted = !self.allow_ids.is_empty() || !self.allow_categories.is_empty();
+        !restricted || listed(&self.allow_ids, &self.allow_categories)
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use crate::{ApplicationEntries, ApplicationEntry, EntryType, Registry, Role};
+
+/// A set of filters for finding application entries
+///
+/// ```no_run
+/// use freedesktop_apps::EntryQuery;
+///
+/// let editors = EntryQuery::new()
+///     .category("Development")
+///     .shows_in("GNOME")
+///     .terminal(false)
+///     .mime_type("text/plain")
+///     .run();
+/// ```
+///
+/// All filters must match. Entries that shouldn't be shown (Hidden or NoDisplay) are
+/// skipped unless [`EntryQuery::include_hidden`] is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryQuery {
+    entry_type: Option<EntryType>,
+    categories: Vec<String>,
+    shows_in: Option<String>,
+    terminal: Option<bool>,
+    mime_types: Vec<String>,
+    roles: Vec<Role>,
+    include_hidden: bool,
+    include_exports: bool,
+    dirs: Option<Vec<PathBuf>>,
+}
+
+impl EntryQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require the entry to have the type, see [`ApplicationEntry::entry_type`]
+    pub fn entry_type(mut self, entry_type: EntryType) -> Self {
+        self.entry_type = Some(entry_type);
+        self
+    }
+
+    /// Require the entry to list the category
+    pub fn category(mut self, category: &str) -> Self {
+        self.categories.push(category.to_string());
+        self
+    }
+
+    /// Require the entry to be shown in the desktop environment, see [`ApplicationEntry::shows_in`]
+    pub fn shows_in(mut self, desktop: &str) -> Self {
+        self.shows_in = Some(desktop.to_string());
+        self
+    }
+
+    /// Require the entry to run (or not run) in a terminal
+    pub fn terminal(mut self, terminal: bool) -> Self {
+        self.terminal = Some(terminal);
+        self
+    }
+
+    /// Require the entry to support the MIME type
+    pub fn mime_type(mut self, mime_type: &str) -> Self {
+        self.mime_types.push(mime_type.to_string());
+        self
+    }
+
+    /// Require the entry to fill the role, see [`Role::matches`]
+    pub fn role(mut self, role: Role) -> Self {
+        self.roles.push(role);
+        self
+    }
+
+    /// Also match entries marked Hidden or NoDisplay
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    /// Also scan the Flatpak and Snap export directories, see [`ApplicationEntry::all_with_exports`]
+    pub fn include_exports(mut self, include_exports: bool) -> Self {
+        self.include_exports = include_exports;
+        self
+    }
+
+    /// Scan the given `applications` directories instead of the standard ones,
+    /// see [`ApplicationEntry::all_in`]
+    ///
+    /// Export directories are not added to custom directories.
+    pub fn dirs(mut self, dirs: &[PathBuf]) -> Self {
+        self.dirs = Some(dirs.to_vec());
+        self
+    }
+
+    /// Check whether an entry passes every filter
+    ///
+    /// Cheap checks run first so most entries are rejected without building lists.
+    pub fn matches(&self, entry: &ApplicationEntry) -> bool {
+        if !self.include_hidden && !entry.should_show() {
+            return false;
+        }
+        if self.entry_type.as_ref().is_some_and(|t| entry.entry_type().as_ref() != Some(t)) {
+            return false;
+        }
+        if self.terminal.is_some_and(|terminal| entry.terminal() != terminal) {
+            return false;
+        }
+        if self.shows_in.as_deref().is_some_and(|desktop| !entry.shows_in(desktop)) {
+            return false;
+        }
+
+        if !self.categories.is_empty() {
+            let categories = entry.categories().unwrap_or_default();
+            if !self.categories.iter().all(|c| categories.contains(c)) {
+                return false;
+            }
+        }
+        if !self.mime_types.is_empty() {
+            let mime_types = entry.mime_types().unwrap_or_default();
+            if !self
+                .mime_types
+                .iter()
+                .all(|m| mime_types.iter().any(|t| t.eq_ignore_ascii_case(m)))
+            {
+                return false;
+            }
+        }
+        if !self.roles.iter().all(|role| role.matches(entry)) {
+            return false;
+        }
+
+        true
+    }
+
+    /// Lazily scan the installed entries for matches
+    pub fn iter(&self) -> impl Iterator<Item = ApplicationEntry> + '_ {
+        let entries = match &self.dirs {
+            Some(dirs) => ApplicationEntries::in_dirs(dirs, false),
+            None => ApplicationEntries::new(false, self.include_exports),
+        };
+        entries.filter(|entry| self.matches(entry))
+    }
+
+    /// Get every installed entry that matches
+    pub fn run(&self) -> Vec<ApplicationEntry> {
+        self.iter().collect()
+    }
+
+    /// Get the first installed entry that matches, stopping the scan there
+    pub fn first(&self) -> Option<ApplicationEntry> {
+        self.iter().next()
+    }
+}
+
+impl Registry {
+    /// Get the entries in the registry matching the query
+    pub fn query(&self, query: &EntryQuery) -> Vec<&ApplicationEntry> {
+        self.entries().iter().filter(|entry| query.matches(entry)).collect()
+    }
+}
@@ -0,0 +1,80 @@
+//! Best-effort reading of damaged desktop files
+//!
+//! Indexers that come across a truncated file, or one with binary data written into
+//! it, can still show whatever name and icon survived instead of dropping the entry.
+
+use std::path::Path;
+
+use crate::parser::DesktopEntry;
+use crate::{ApplicationEntry, ParseError, ParseLimits};
+
+/// The part of a desktop file that could be read, see [`ApplicationEntry::recover`]
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    entry: ApplicationEntry,
+    total_lines: usize,
+    kept_lines: usize,
+}
+
+impl Recovered {
+    /// Get the recovered entry, which may lack required keys
+    pub fn entry(&self) -> &ApplicationEntry {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> ApplicationEntry {
+        self.entry
+    }
+
+    /// Get the number of lines with content, not counting blank lines and comments
+    pub fn total_lines(&self) -> usize {
+        self.total_lines
+    }
+
+    /// Get the number of lines with content that were kept
+    pub fn kept_lines(&self) -> usize {
+        self.kept_lines
+    }
+
+    /// Get the share of content lines that were kept, from 0.0 to 1.0
+    ///
+    /// A file without any content lines counts as fully salvaged.
+    pub fn salvage_ratio(&self) -> f64 {
+        if self.total_lines == 0 {
+            return 1.0;
+        }
+        self.kept_lines as f64 / self.total_lines as f64
+    }
+
+    /// Check if the recovered entry has every key the specification requires
+    pub fn is_complete(&self) -> bool {
+        self.entry.inner.validate().is_ok()
+    }
+}
+
+impl ApplicationEntry {
+    /// Read whatever valid groups and keys a damaged file still has
+    ///
+    /// Unlike [`ApplicationEntry::try_from_path`] this only fails if the file can't be
+    /// opened or read. Unreadable lines are skipped, see [`Recovered::salvage_ratio`].
+    pub fn recover<P: AsRef<Path>>(path: P) -> Result<Recovered, ParseError> {
+        Self::recover_with(path, ParseLimits::default())
+    }
+
+    /// Like [`ApplicationEntry::recover`], with the given limits
+    ///
+    /// The file is cut off at the size limit, overlong lines are skipped and keys
+    /// beyond the key limit are dropped.
+    pub fn recover_with<P: AsRef<Path>>(path: P, limits: ParseLimits) -> Result<Recovered, ParseError> {
+        let (inner, total_lines, kept_lines) = DesktopEntry::recover_from_path(path.as_ref(), limits)?;
+        Ok(Recovered {
+            entry: ApplicationEntry {
+                inner,
+                application_dirs: None,
+                full: None,
+            },
+            total_lines,
+            kept_lines,
+        })
+    }
+}
@@ -0,0 +1,573 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
+
+use freedesktop_core::time::Timestamp;
+
+use crate::{
+    find_duplicates, normalize_desktop_id, resolve_desktop_id_in, AppPolicy, ApplicationEntry, DuplicateGroup,
+    DuplicateStrategy, ExecuteError, FocusOutcome, IdResolveError,
+    LaunchFailure, LaunchFailures, LaunchOptions, Launched, RegistryEvent, WindowHint,
+};
+
+/// How long launches are watched for an early exit when failures are tracked
+const DEFAULT_FAILURE_GRACE: Duration = Duration::from_millis(500);
+
+/// A loaded set of application entries that can be looked up by desktop file ID
+///
+/// Cloning is cheap: the entries are shared until a clone changes them, and the
+/// launch history used for debouncing is shared between all clones. To read a
+/// registry on several threads while another one refreshes it, use [`SharedRegistry`].
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    entries: Arc<Vec<ApplicationEntry>>,
+    /// The `applications` directories entries are loaded from, when not the standard ones
+    pub(crate) dirs: Option<Arc<[PathBuf]>>,
+    debounce: Option<Duration>,
+    recent: Arc<LaunchHistory>,
+    failures: Option<LaunchFailures>,
+    failure_grace: Option<Duration>,
+    policy: Option<AppPolicy>,
+    duplicate_strategy: Option<DuplicateStrategy>,
+    duplicates: Arc<Vec<DuplicateGroup>>,
+}
+
+impl Registry {
+    /// Load every installed application entry
+    pub fn load() -> Self {
+        Self::from_entries(ApplicationEntry::all())
+    }
+
+    /// Load the application entries in the given `applications` directories, see [`ApplicationEntry::all_in`]
+    ///
+    /// Changes applied later are looked up in these directories as well.
+    pub fn load_from(dirs: &[PathBuf]) -> Self {
+        Registry {
+            dirs: Some(dirs.into()),
+            ..Self::from_entries(ApplicationEntry::all_in(dirs))
+        }
+    }
+
+    /// Build a registry from already parsed entries
+    ///
+    /// Entries found by [`ApplicationEntry::all_in`] keep their directories for
+    /// the changes applied later.
+    pub fn from_entries(entries: Vec<ApplicationEntry>) -> Self {
+        Registry {
+            dirs: entries.iter().find_map(|entry| entry.application_dirs.clone()),
+            entries: Arc::new(entries),
+            ..Default::default()
+        }
+    }
+
+    /// Read every entry from the registry's directories, leaving out those the policy refuses
+    fn load_entries(&self) -> Vec<ApplicationEntry> {
+        let mut entries = match &self.dirs {
+            Some(dirs) => ApplicationEntry::all_in(dirs),
+            None => ApplicationEntry::all(),
+        };
+        entries.retain(|entry| self.permits(entry));
+        entries
+    }
+
+    /// Guard against accidental repeated launches of the same entry
+    ///
+    /// Launching an ID again within `window` of the previous launch returns the
+    /// previous [`Launched`] handle instead of spawning. Entries with
+    /// SingleMainWindow=true are additionally not relaunched while their
+    /// previous process is still running.
+    pub fn with_debounce(mut self, window: Duration) -> Self {
+        self.debounce = Some(window);
+        self
+    }
+
+    /// Record launch failures in the given store so broken entries can be detected
+    ///
+    /// Besides programs that fail to start, a program that exits unsuccessfully
+    /// within half a second of starting counts as failed, see
+    /// [`Registry::with_failure_grace`]. Launches block for that long.
+    pub fn with_failure_tracking(mut self, failures: LaunchFailures) -> Self {
+        self.failures = Some(failures);
+        self
+    }
+
+    /// Set how long a tracked launch is watched for an early exit
+    ///
+    /// See [`LaunchOptions::diagnose_startup`]. A zero duration only records
+    /// programs that fail to start.
+    pub fn with_failure_grace(mut self, grace: Duration) -> Self {
+        self.failure_grace = Some(grace);
+        self
+    }
+
+    /// Only offer and launch the entries the policy permits
+    ///
+    /// Other entries are dropped, so looking them up or launching them fails with
+    /// [`ExecuteError::NotFound`]. The policy stays in effect for entries added by
+    /// [`Registry::apply_event`] and [`SharedRegistry::reload`].
+    pub fn with_policy(mut self, policy: AppPolicy) -> Self {
+        if !self.entries.iter().all(|entry| policy.permits(entry)) {
+            let entries = Arc::make_mut(&mut self.entries);
+            entries.retain(|entry| policy.permits(entry));
+        }
+        self.policy = Some(policy);
+        self.regroup();
+        self
+    }
+
+    /// Get the policy restricting the entries, if any
+    pub fn policy(&self) -> Option<&AppPolicy> {
+        self.policy.as_ref()
+    }
+
+    fn permits(&self, entry: &ApplicationEntry) -> bool {
+        self.policy.as_ref().is_none_or(|policy| policy.permits(entry))
+    }
+
+    /// Detect entries that are the same application under different IDs, see [`find_duplicates`]
+    ///
+    /// With [`DuplicateStrategy::Merge`], only the kept entry of each group is in
+    /// [`Registry::entries`], and looking up or launching a duplicate's ID uses
+    /// the kept entry. The groups are kept up to date by [`Registry::apply_event`]
+    /// and [`SharedRegistry::reload`].
+    pub fn with_duplicate_strategy(mut self, strategy: DuplicateStrategy) -> Self {
+        self.duplicate_strategy = Some(strategy);
+        self.regroup();
+        self
+    }
+
+    /// Get the groups of duplicate entries, if duplicate detection is enabled
+    pub fn duplicates(&self) -> &[DuplicateGroup] {
+        &self.duplicates
+    }
+
+    /// Put merged duplicates back into the entries
+    fn unmerge(&mut self) {
+        if self.duplicates.is_empty() || self.duplicate_strategy != Some(DuplicateStrategy::Merge) {
+            return;
+        }
+        let merged: Vec<ApplicationEntry> = self
+            .duplicates
+            .iter()
+            .flat_map(|group| group.duplicates())
+            .filter(|entry| self.permits(entry))
+            .cloned()
+            .collect();
+        Arc::make_mut(&mut self.entries).extend(merged);
+        self.duplicates = Arc::default();
+    }
+
+    /// Find the duplicate groups again after the entries changed
+    fn regroup(&mut self) {
+        let Some(strategy) = self.duplicate_strategy else {
+            return;
+        };
+        self.unmerge();
+        let groups = find_duplicates(&self.entries);
+        if strategy == DuplicateStrategy::Merge && !groups.is_empty() {
+            let merged: HashSet<String> = groups.iter().flat_map(|group| group.duplicate_ids()).collect();
+            let entries = Arc::make_mut(&mut self.entries);
+            entries.retain(|entry| entry.id().is_none_or(|id| !merged.contains(&id)));
+        }
+        self.duplicates = Arc::new(groups);
+    }
+
+    /// Check whether launching the entry has failed repeatedly, see [`LaunchFailures::is_failing`]
+    pub fn is_failing(&self, id: &str) -> bool {
+        self.failures
+            .as_ref()
+            .is_some_and(|failures| failures.is_failing(&normalize_desktop_id(id)))
+    }
+
+    /// Get the recorded launch failures of the entry, if failure tracking is enabled
+    pub fn last_failure(&self, id: &str) -> Option<LaunchFailure> {
+        self.failures
+            .as_ref()
+            .and_then(|failures| failures.failure(&normalize_desktop_id(id)))
+    }
+
+    /// Update the registry for a change reported by [`RegistryWatcher`]
+    ///
+    /// The affected ID is looked up again in the registry's directories, so
+    /// removing a file reveals a lower-precedence copy, and Hidden entries and
+    /// those the policy refuses are dropped. [`RegistryEvent::Rescan`] loads every
+    /// entry again.
+    pub fn apply_event(&mut self, event: &RegistryEvent) {
+        self.unmerge();
+        let Some(id) = event.id() else {
+            self.entries = Arc::new(self.load_entries());
+            self.regroup();
+            return;
+        };
+        let position = self
+            .entries
+            .iter()
+            .position(|entry| entry.id().as_deref() == Some(id));
+
+        let found = match &self.dirs {
+            Some(dirs) => ApplicationEntry::find_by_id_in(dirs, id),
+            None => ApplicationEntry::find_by_id(id),
+        };
+        let found = found.filter(|entry| self.permits(entry));
+
+        // Copies the entries first if another clone still uses them
+        let entries = Arc::make_mut(&mut self.entries);
+        match (found, position) {
+            (Some(entry), Some(index)) => entries[index] = entry,
+            (Some(entry), None) => entries.push(entry),
+            (None, Some(index)) => {
+                entries.remove(index);
+            }
+            (None, None) => {}
+        }
+        self.regroup();
+    }
+
+    /// Apply a batch of events, such as one from [`RegistryWatcher::next_batch`]
+    ///
+    /// `progress` is called after each event with the number of events applied so
+    /// far and the batch size, so large refreshes can be reported to the user.
+    pub fn apply_events<F: FnMut(usize, usize)>(&mut self, events: &[RegistryEvent], mut progress: F) {
+        for (i, event) in events.iter().enumerate() {
+            self.apply_event(event);
+            progress(i + 1, events.len());
+        }
+    }
+
+    /// Get all entries in the registry
+    pub fn entries(&self) -> &[ApplicationEntry] {
+        &self.entries
+    }
+
+    /// Get the entry with the given desktop file ID, with or without the `.desktop` suffix
+    ///
+    /// The ID of a merged duplicate gets the entry kept in its place.
+    pub fn get(&self, id: &str) -> Option<&ApplicationEntry> {
+        let id = normalize_desktop_id(id);
+        self.entries
+            .iter()
+            .find(|entry| entry.id().as_deref() == Some(id.as_str()))
+            .or_else(|| {
+                let group = self.duplicates.iter().find(|group| group.contains_duplicate(&id))?;
+                let kept = group.kept().id()?;
+                self.entries.iter().find(|entry| entry.id().as_deref() == Some(kept.as_str()))
+            })
+    }
+
+    /// Get the entries installed at or after `since`, newest first
+    ///
+    /// Intended for "New" badges after package installs; see [`ApplicationEntry::installed_at`].
+    pub fn recently_added(&self, since: Timestamp) -> Vec<&ApplicationEntry> {
+        let mut added: Vec<(Timestamp, &ApplicationEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| entry.installed_at().map(|time| (time, entry)))
+            .filter(|(time, _)| *time >= since)
+            .collect();
+        added.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+        added.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Find the entry a loose identifier refers to, see [`resolve_desktop_id_in`]
+    pub fn resolve(&self, input: &str) -> Result<&ApplicationEntry, IdResolveError> {
+        let id = resolve_desktop_id_in(input, &self.entries)?;
+        self.get(&id).ok_or(IdResolveError::NotFound(id))
+    }
+
+    /// Launch the entry with the given desktop file ID
+    pub fn launch(&self, id: &str) -> Result<Launched, ExecuteError> {
+        let entry = self
+            .get(id)
+            .ok_or_else(|| ExecuteError::NotFound(id.to_string()))?;
+        let key = entry.id().unwrap_or_else(|| normalize_desktop_id(id));
+        let Some(window) = self.debounce else {
+            return self.launch_entry(entry, &key);
+        };
+
+        let (launched, _) = self.recent.launch(
+            &key,
+            |previous| previous.elapsed() < window || (entry.single_main_window() && previous.is_running()),
+            || self.launch_entry(entry, &key),
+        )?;
+        Ok(launched)
+    }
+
+    /// Activate an existing window of the entry, or launch it if there is none
+    ///
+    /// `windows` are the windows currently open, as known to the caller. The first one
+    /// matching the entry (see [`ApplicationEntry::matches_window`]) is activated. When
+    /// no window could be activated, an entry with SingleMainWindow=true whose previous
+    /// launch through this registry is still running isn't spawned again.
+    pub fn focus_or_launch<W: WindowHint>(&self, id: &str, windows: &[W]) -> Result<FocusOutcome, ExecuteError> {
+        let entry = self
+            .get(id)
+            .ok_or_else(|| ExecuteError::NotFound(id.to_string()))?;
+
+        let focused = windows
+            .iter()
+            .filter(|window| entry.matches_window(window.wm_class(), window.app_id()))
+            .any(|window| window.activate());
+        if focused {
+            return Ok(FocusOutcome::Focused);
+        }
+
+        let key = entry.id().unwrap_or_else(|| normalize_desktop_id(id));
+        let (launched, reused) = self.recent.launch(
+            &key,
+            |previous| entry.single_main_window() && previous.is_running(),
+            || self.launch_entry(entry, &key),
+        )?;
+        if reused {
+            return Ok(FocusOutcome::AlreadyRunning(launched));
+        }
+        Ok(FocusOutcome::Launched(launched))
+    }
+
+    fn launch_entry(&self, entry: &ApplicationEntry, key: &str) -> Result<Launched, ExecuteError> {
+        let Some(failures) = &self.failures else {
+            return entry.launch();
+        };
+        let grace = self.failure_grace.unwrap_or(DEFAULT_FAILURE_GRACE);
+        let mut options = LaunchOptions::default();
+        if !grace.is_zero() {
+            options = options.diagnose_startup(grace);
+        }
+        let result = entry.launch_with(&options);
+        // The state file is advisory; failing to update it mustn't fail the launch
+        let _ = failures.record(key, &result);
+        result
+    }
+
+    /// Launch several entries, returning one result per requested ID in the same order
+    ///
+    /// At most [`LaunchAllOptions::concurrency`] launches are in flight at once. Each
+    /// entry waits for its delay before being started, so delays on entries handled
+    /// by the same worker add up.
+    pub fn launch_all(&self, ids: &[&str], opts: &LaunchAllOptions) -> Vec<LaunchResult> {
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Result<(), ExecuteError>>>> = Mutex::new(vec![None; ids.len()]);
+        let workers = opts.concurrency.max(1).min(ids.len());
+
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(id) = ids.get(index) else {
+                        break;
+                    };
+
+                    let delay = opts.delay_for(id);
+                    if !delay.is_zero() {
+                        std::thread::sleep(delay);
+                    }
+
+                    let result = self.launch(id).map(|_| ());
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+
+        let results = results.into_inner().unwrap();
+        ids.iter()
+            .zip(results)
+            .map(|(id, result)| LaunchResult {
+                id: id.to_string(),
+                result: result.unwrap_or(Err(ExecuteError::NotFound(id.to_string()))),
+            })
+            .collect()
+    }
+}
+
+/// The launches made through a registry, shared between its clones
+///
+/// The lock is only held to look up and record launches, not while spawning, so
+/// different entries launch in parallel. A launch of an entry that is already
+/// being launched waits for that launch to finish instead.
+#[derive(Debug, Default)]
+struct LaunchHistory {
+    state: Mutex<HistoryState>,
+    finished: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    launched: HashMap<String, Launched>,
+    in_flight: HashSet<String>,
+}
+
+impl LaunchHistory {
+    fn lock(&self) -> MutexGuard<'_, HistoryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Launch `key` unless `reuse` accepts its previous launch, returning whether it was reused
+    fn launch(
+        &self,
+        key: &str,
+        reuse: impl Fn(&Launched) -> bool,
+        launch: impl FnOnce() -> Result<Launched, ExecuteError>,
+    ) -> Result<(Launched, bool), ExecuteError> {
+        let mut state = self.lock();
+        while state.in_flight.contains(key) {
+            state = self.finished.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if let Some(previous) = state.launched.get(key).filter(|previous| reuse(previous)) {
+            return Ok((previous.clone(), true));
+        }
+        state.in_flight.insert(key.to_string());
+        drop(state);
+
+        let claim = InFlight { history: self, key };
+        let launched = launch()?;
+        claim.history.lock().launched.insert(key.to_string(), launched.clone());
+        Ok((launched, false))
+    }
+}
+
+/// Clears the in-flight mark when dropped, so a failed launch doesn't block others
+struct InFlight<'a> {
+    history: &'a LaunchHistory,
+    key: &'a str,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.history.lock().in_flight.remove(self.key);
+        self.history.finished.notify_all();
+    }
+}
+
+/// A [`Registry`] that threads can read while it is being refreshed
+///
+/// Readers take a [`SharedRegistry::snapshot`], which stays unchanged for as long
+/// as they hold it. Refreshing builds the updated registry on the side and swaps it
+/// in, so readers never wait for a refresh and never see a half-applied one.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use freedesktop_apps::{RefreshPolicy, SharedRegistry};
+///
+/// let shared = Arc::new(SharedRegistry::load());
+/// let refresher = Arc::clone(&shared);
+/// std::thread::spawn(move || {
+///     let mut watcher = refresher.snapshot().watch().unwrap();
+///     while let Ok(batch) = watcher.next_batch(&RefreshPolicy::default()) {
+///         refresher.apply_events(&batch, |_, _| {});
+///     }
+/// });
+///
+/// let registry = shared.snapshot();
+/// println!("{} applications", registry.entries().len());
+/// ```
+#[derive(Debug, Default)]
+pub struct SharedRegistry {
+    current: RwLock<Arc<Registry>>,
+    // Serializes refreshes so concurrent ones don't drop each other's changes
+    refresh: Mutex<()>,
+}
+
+impl SharedRegistry {
+    pub fn new(registry: Registry) -> Self {
+        SharedRegistry {
+            current: RwLock::new(Arc::new(registry)),
+            refresh: Mutex::new(()),
+        }
+    }
+
+    /// Load every installed application entry
+    pub fn load() -> Self {
+        Self::new(Registry::load())
+    }
+
+    /// Get the current registry
+    pub fn snapshot(&self) -> Arc<Registry> {
+        Arc::clone(&self.current.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Replace the registry; existing snapshots keep the previous one
+    pub fn replace(&self, registry: Registry) {
+        let _refresh = self.refresh.lock().unwrap_or_else(|e| e.into_inner());
+        self.swap(registry);
+    }
+
+    /// Apply a batch of events, see [`Registry::apply_events`]
+    pub fn apply_events<F: FnMut(usize, usize)>(&self, events: &[RegistryEvent], progress: F) {
+        let _refresh = self.refresh.lock().unwrap_or_else(|e| e.into_inner());
+        let mut next = Registry::clone(&self.snapshot());
+        next.apply_events(events, progress);
+        self.swap(next);
+    }
+
+    /// Load all entries again, keeping the registry's settings and launch history
+    pub fn reload(&self) {
+        let _refresh = self.refresh.lock().unwrap_or_else(|e| e.into_inner());
+        let mut next = Registry::clone(&self.snapshot());
+        next.entries = Arc::new(next.load_entries());
+        next.duplicates = Arc::default();
+        next.regroup();
+        self.swap(next);
+    }
+
+    fn swap(&self, registry: Registry) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(registry);
+    }
+}
+
+/// Options for [`Registry::launch_all`]
+#[derive(Debug, Clone)]
+pub struct LaunchAllOptions {
+    concurrency: usize,
+    delay: Duration,
+    delays: HashMap<String, Duration>,
+}
+
+impl Default for LaunchAllOptions {
+    fn default() -> Self {
+        LaunchAllOptions {
+            concurrency: 4,
+            delay: Duration::ZERO,
+            delays: HashMap::new(),
+        }
+    }
+}
+
+impl LaunchAllOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of launches running at the same time (at least 1)
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Delay applied before launching each entry without a specific delay
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Delay applied before launching the entry with the given ID
+    pub fn entry_delay(mut self, id: &str, delay: Duration) -> Self {
+        self.delays.insert(normalize_desktop_id(id), delay);
+        self
+    }
+
+    fn delay_for(&self, id: &str) -> Duration {
+        self.delays
+            .get(&normalize_desktop_id(id))
+            .copied()
+            .unwrap_or(self.delay)
+    }
+}
+
+/// The outcome of launching a single entry with [`Registry::launch_all`]
+#[derive(Debug, Clone)]
+pub struct LaunchResult {
+    /// The ID as it was requested
+    pub id: String,
+    pub result: Result<(), ExecuteError>,
+}
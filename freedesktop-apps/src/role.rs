@@ -0,0 +1,89 @@
+//! Common application roles for "default applications" settings
+//!
+//! No single key says an application is a web browser. Categories are the most
+//! direct signal but often missing or too broad, so they are combined with the MIME
+//! types and URL schemes an entry handles and the D-Bus interfaces it implements.
+
+use std::fmt;
+
+use crate::{ApplicationEntry, Category, Registry};
+
+/// A role an application can fill, such as the default web browser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    WebBrowser,
+    TextEditor,
+    TerminalEmulator,
+    ImageViewer,
+    AudioPlayer,
+}
+
+impl Role {
+    /// Every role, in declaration order
+    pub const ALL: [Role; 5] = [
+        Role::WebBrowser,
+        Role::TextEditor,
+        Role::TerminalEmulator,
+        Role::ImageViewer,
+        Role::AudioPlayer,
+    ];
+
+    /// Get a stable name for the role, e.g. for settings keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::WebBrowser => "web-browser",
+            Role::TextEditor => "text-editor",
+            Role::TerminalEmulator => "terminal-emulator",
+            Role::ImageViewer => "image-viewer",
+            Role::AudioPlayer => "audio-player",
+        }
+    }
+
+    /// Check whether an entry fills the role
+    pub fn matches(&self, entry: &ApplicationEntry) -> bool {
+        let categories = entry.categories_typed();
+        let has = |wanted: &[Category]| categories.iter().any(|c| wanted.contains(c));
+        let mime_types = entry.mime_types().unwrap_or_default();
+        let handles = |prefix: &str| mime_types.iter().any(|m| m.to_ascii_lowercase().starts_with(prefix));
+
+        match self {
+            // Only browsers register for http and https links
+            Role::WebBrowser => has(&[Category::WebBrowser]) || handles("x-scheme-handler/http"),
+            Role::TextEditor => {
+                has(&[Category::TextEditor])
+                    || handles("text/plain") && has(&[Category::IDE, Category::TextTools])
+            }
+            Role::TerminalEmulator => has(&[Category::TerminalEmulator]),
+            // Image editors handle images too, so a viewing category is needed
+            Role::ImageViewer => handles("image/") && has(&[Category::Viewer, Category::Photography]),
+            Role::AudioPlayer => {
+                let player = has(&[Category::Player])
+                    || entry.implements().unwrap_or_default().iter().any(|i| i == "org.mpris.MediaPlayer2");
+                handles("audio/") && player
+            }
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ApplicationEntry {
+    /// Get the roles the application fills
+    pub fn roles(&self) -> Vec<Role> {
+        Role::ALL.into_iter().filter(|role| role.matches(self)).collect()
+    }
+}
+
+impl Registry {
+    /// Get the shown entries that fill a role, for a default application chooser
+    pub fn with_role(&self, role: Role) -> Vec<&ApplicationEntry> {
+        self.entries()
+            .iter()
+            .filter(|entry| entry.should_show() && role.matches(entry))
+            .collect()
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{ApplicationEntry, Registry};
+
+// Relevance of a match, by field and kind; higher is better
+const NAME_EXACT: u32 = 100;
+const NAME_PREFIX: u32 = 80;
+const NAME_WORD_PREFIX: u32 = 60;
+const NAME_SUBSTRING: u32 = 40;
+const GENERIC_NAME_PREFIX: u32 = 35;
+const GENERIC_NAME_SUBSTRING: u32 = 25;
+const KEYWORD_PREFIX: u32 = 20;
+const EXEC_PREFIX: u32 = 15;
+const KEYWORD_SUBSTRING: u32 = 10;
+const CATEGORY: u32 = 5;
+
+impl Registry {
+    /// Search entries by localized Name, GenericName, Keywords, Categories and Exec
+    ///
+    /// Every whitespace-separated word of the query has to match one of the fields,
+    /// ignoring case. Results are ordered by relevance: prefix matches on the name
+    /// rank above substring matches, which rank above keyword and category matches.
+    /// Hidden and NoDisplay entries are not returned.
+    pub fn search(&self, query: &str, locale: Option<&str>) -> Vec<&ApplicationEntry> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut results: Vec<(u32, String, &ApplicationEntry)> = self
+            .entries()
+            .iter()
+            .filter(|entry| entry.should_show())
+            .filter_map(|entry| {
+                let fields = SearchFields::new(entry, locale);
+                let score = terms
+                    .iter()
+                    .map(|term| fields.score(term))
+                    .try_fold(0, |total, score| score.map(|score| total + score))?;
+                Some((score, fields.name, entry))
+            })
+            .collect();
+
+        results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        results.into_iter().map(|(_, _, entry)| entry).collect()
+    }
+}
+
+/// The lowercased searchable fields of an entry
+struct SearchFields {
+    name: String,
+    generic_name: String,
+    keywords: Vec<String>,
+    categories: Vec<String>,
+    exec: String,
+}
+
+impl SearchFields {
+    fn new(entry: &ApplicationEntry, locale: Option<&str>) -> Self {
+        let lower = |value: Option<String>| value.unwrap_or_default().to_lowercase();
+        let lower_all = |values: Option<Vec<String>>| {
+            values
+                .unwrap_or_default()
+                .iter()
+                .map(|value| value.to_lowercase())
+                .collect()
+        };
+
+        SearchFields {
+            name: lower(entry.get_localized_string("Name", locale)),
+            generic_name: lower(entry.get_localized_string("GenericName", locale)),
+            keywords: lower_all(entry.get_localized_vec("Keywords", locale)),
+            categories: lower_all(entry.categories()),
+            exec: lower(entry.exec_binary_name()),
+        }
+    }
+
+    /// Get the best score of a single query term, or None if nothing matches
+    fn score(&self, term: &str) -> Option<u32> {
+        let name_words = || self.name.split(|c: char| !c.is_alphanumeric());
+        let candidates = [
+            (self.name == term, NAME_EXACT),
+            (self.name.starts_with(term), NAME_PREFIX),
+            (name_words().any(|word| word.starts_with(term)), NAME_WORD_PREFIX),
+            (self.name.contains(term), NAME_SUBSTRING),
+            (self.generic_name.starts_with(term), GENERIC_NAME_PREFIX),
+            (self.generic_name.contains(term), GENERIC_NAME_SUBSTRING),
+            (self.keywords.iter().any(|k| k.starts_with(term)), KEYWORD_PREFIX),
+            (!self.exec.is_empty() && self.exec.starts_with(term), EXEC_PREFIX),
+            (self.keywords.iter().any(|k| k.contains(term)), KEYWORD_SUBSTRING),
+            (self.categories.iter().any(|c| c == term), CATEGORY),
+        ];
+
+        candidates
+            .iter()
+            .filter(|(matched, _)| *matched)
+            .map(|(_, score)| *score)
+            .max()
+    }
+}
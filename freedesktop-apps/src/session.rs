@@ -0,0 +1,153 @@
+//! Basic session save and restore
+//!
+//! A [`Session`] is the list of desktop file IDs of the applications that were running
+//! when it was captured. It is stored as plain text, one ID per line, under
+//! `$XDG_STATE_HOME/freedesktop/session` and relaunched through [`Registry::launch_all`].
+
+use std::collections::HashSet;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::{ApplicationEntry, LaunchAllOptions, LaunchResult, Registry};
+
+/// A snapshot of running applications
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    ids: Vec<String>,
+}
+
+impl Session {
+    /// Create a session from desktop file IDs
+    pub fn new(ids: Vec<String>) -> Self {
+        let mut seen = HashSet::new();
+        Session {
+            ids: ids.into_iter().filter(|id| seen.insert(id.clone())).collect(),
+        }
+    }
+
+    /// Get the desktop file IDs in the session
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// Capture the applications run by the current user, found by matching
+    /// process names against the entries in the registry
+    pub fn capture(registry: &Registry) -> Self {
+        let names = running_process_names();
+        Self::from_matches(registry, |entry| {
+            names.iter().any(|name| entry.matches_window(Some(name), None))
+        })
+    }
+
+    /// Capture the applications owning the given windows
+    ///
+    /// Each window is a `(wm_class, app_id)` pair as accepted by
+    /// [`ApplicationEntry::matches_window`], for compositors where the caller can list windows.
+    pub fn from_windows(registry: &Registry, windows: &[(Option<&str>, Option<&str>)]) -> Self {
+        Self::from_matches(registry, |entry| {
+            windows
+                .iter()
+                .any(|(wm_class, app_id)| entry.matches_window(*wm_class, *app_id))
+        })
+    }
+
+    fn from_matches(registry: &Registry, matches: impl Fn(&ApplicationEntry) -> bool) -> Self {
+        Self::new(
+            registry
+                .entries()
+                .iter()
+                .filter(|entry| entry.should_show() && matches(entry))
+                .filter_map(|entry| entry.id())
+                .collect(),
+        )
+    }
+
+    /// Default location of the saved session, `$XDG_STATE_HOME/freedesktop/session`
+    pub fn default_path() -> Option<PathBuf> {
+        freedesktop_core::state_home().map(|state| state.join("freedesktop").join("session"))
+    }
+
+    /// Save the session to the default location, returning the path written
+    pub fn save(&self) -> io::Result<PathBuf> {
+        let path = Self::default_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No state directory available"))?;
+        self.save_to(&path)?;
+        Ok(path)
+    }
+
+    /// Save the session to the given file, creating parent directories as needed
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut content = String::new();
+        for id in &self.ids {
+            content.push_str(id);
+            content.push('\n');
+        }
+        std::fs::write(path, content)
+    }
+
+    /// Load the session saved at the default location
+    pub fn load() -> io::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No state directory available"))?;
+        Self::load_from(path)
+    }
+
+    /// Load a session from the given file, ignoring blank lines and `#` comments
+    pub fn load_from<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+
+    /// Relaunch every application in the session
+    pub fn restore(&self, registry: &Registry, opts: &LaunchAllOptions) -> Vec<LaunchResult> {
+        let ids: Vec<&str> = self.ids.iter().map(String::as_str).collect();
+        registry.launch_all(&ids, opts)
+    }
+}
+
+/// Get the executable and command names of the current user's processes
+fn running_process_names() -> HashSet<String> {
+    let mut names = HashSet::new();
+    let uid = unsafe { libc::getuid() };
+    let own_pid = std::process::id().to_string();
+
+    let Ok(proc_entries) = std::fs::read_dir("/proc") else {
+        return names;
+    };
+
+    for entry in proc_entries.filter_map(|e| e.ok()) {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.bytes().all(|b| b.is_ascii_digit()) || file_name == own_pid {
+            continue;
+        }
+        if entry.metadata().map(|m| m.uid() != uid).unwrap_or(true) {
+            continue;
+        }
+
+        let proc_dir = entry.path();
+        if let Some(exe) = std::fs::read_link(proc_dir.join("exe"))
+            .ok()
+            .and_then(|exe| exe.file_name().map(|name| name.to_string_lossy().to_string()))
+        {
+            names.insert(exe);
+        }
+        if let Ok(comm) = std::fs::read_to_string(proc_dir.join("comm")) {
+            names.insert(comm.trim().to_string());
+        }
+    }
+
+    names
+}
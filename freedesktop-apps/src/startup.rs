@@ -0,0 +1,155 @@
+//! Startup notification for entries with StartupNotify=true
+//!
+//! Each launch of such an entry gets a startup ID that is passed to the program as
+//! `DESKTOP_STARTUP_ID`. The program hands it back to the window manager with its
+//! first window, which ends the busy cursor and lets focus-stealing prevention
+//! compare the window against the launch's timestamp. On X11 the launcher also
+//! announces and ends the sequence with `_NET_STARTUP_INFO` messages; this crate
+//! formats them and leaves sending to a [`StartupNotifier`] supplied through
+//! [`LaunchOptions::startup_notifier`](crate::LaunchOptions::startup_notifier).
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{basename, parse_command_line, ApplicationEntry, LaunchOptions};
+
+/// Sends startup notification messages to the window manager
+pub trait StartupNotifier: fmt::Debug + Send + Sync {
+    /// Announce a sequence before its program is started, e.g. by sending
+    /// [`StartupSequence::new_message`]
+    fn begin(&self, sequence: &StartupSequence);
+
+    /// End a sequence, e.g. by sending [`StartupSequence::remove_message`]
+    ///
+    /// Called by the crate when the launch fails. Callers call it themselves when the
+    /// application's window appears or the sequence times out.
+    fn complete(&self, sequence: &StartupSequence);
+}
+
+/// A startup notification sequence for one launch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupSequence {
+    id: String,
+    name: String,
+    screen: u32,
+    bin: Option<String>,
+    icon: Option<String>,
+    wm_class: Option<String>,
+    application_id: String,
+}
+
+impl StartupSequence {
+    /// Get the startup ID passed as `DESKTOP_STARTUP_ID`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the `new:` message that begins the sequence
+    pub fn new_message(&self) -> String {
+        let mut message = format!(
+            "new: ID={} NAME={} SCREEN={}",
+            quote(&self.id),
+            quote(&self.name),
+            self.screen
+        );
+        for (key, value) in [("BIN", &self.bin), ("ICON", &self.icon), ("WMCLASS", &self.wm_class)] {
+            if let Some(value) = value {
+                message.push_str(&format!(" {}={}", key, quote(value)));
+            }
+        }
+        message.push_str(&format!(" APPLICATION_ID={}", quote(&self.application_id)));
+        message
+    }
+
+    /// Get the `remove:` message that ends the sequence
+    pub fn remove_message(&self) -> String {
+        format!("remove: ID={}", quote(&self.id))
+    }
+}
+
+impl ApplicationEntry {
+    /// Check if the application sends a startup notification when its window appears
+    pub fn startup_notify(&self) -> bool {
+        self.get_bool("StartupNotify").unwrap_or(false)
+    }
+
+    /// Start a sequence for a launch, unless the entry doesn't have StartupNotify=true
+    /// or the caller already set `DESKTOP_STARTUP_ID`
+    ///
+    /// StartupWMClass alone doesn't start one; it only helps the window manager
+    /// match the window of a sequence that was started.
+    pub(crate) fn startup_sequence(&self, options: &LaunchOptions) -> Option<StartupSequence> {
+        if !self.startup_notify() {
+            return None;
+        }
+        if options.env_value("DESKTOP_STARTUP_ID").is_some() {
+            return None;
+        }
+
+        let bin = self
+            .exec()
+            .and_then(|exec| parse_command_line(&exec, false).ok())
+            .map(|(program, _)| basename(&program).to_string());
+
+        Some(StartupSequence {
+            id: startup_id(bin.as_deref().unwrap_or("unknown"), options.startup_time_value()),
+            name: self.name().unwrap_or_else(|| bin.clone().unwrap_or_default()),
+            screen: screen(),
+            bin,
+            icon: self.icon(),
+            wm_class: self.startup_wm_class(),
+            application_id: self.path().to_string_lossy().to_string(),
+        })
+    }
+}
+
+/// Build a startup ID unique to this launch, `<launcher>-<pid>-<host>-<bin>-<n>_TIME<time>`
+fn startup_id(bin: &str, time: Option<u32>) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // The ID is a single token, so keep only characters that can't split it
+    let clean = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' { c } else { '_' })
+            .collect()
+    };
+    let id = format!(
+        "freedesktop-{}-{}-{}-{}",
+        std::process::id(),
+        clean(&hostname()),
+        clean(bin),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+
+    // The timestamp of the triggering event is what focus-stealing prevention uses
+    match time {
+        Some(time) => format!("{}_TIME{}", id, time),
+        None => id,
+    }
+}
+
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return "localhost".to_string();
+    }
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).to_string()
+}
+
+/// Get the X screen number from DISPLAY, `:0.1` being screen 1
+fn screen() -> u32 {
+    std::env::var("DISPLAY")
+        .ok()
+        .and_then(|display| {
+            let (_, screen) = display.rsplit_once(':')?;
+            screen.split_once('.')?.1.parse().ok()
+        })
+        .unwrap_or(0)
+}
+
+/// Quote a value for a startup notification message
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,175 @@
+//! Running Terminal=true entries inside a terminal emulator
+//!
+//! Terminals disagree on how they take the command to run: xterm and konsole use
+//! `-e`, gnome-terminal wants `--`, xfce4-terminal's `-e` takes a single string so
+//! `-x` is needed, and kitty and foot take the command directly. Each terminal gets
+//! a [`TerminalLauncher`] that builds its arguments; the built-in ones are
+//! [`TerminalProfile`]s and applications can add their own with
+//! [`register_terminal`].
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use freedesktop_apps::{register_terminal, TerminalProfile};
+//!
+//! register_terminal(Arc::new(
+//!     TerminalProfile::new("my-terminal").exec_args(["--command"]).hold_args(["--keep-open"]),
+//! ));
+//! ```
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::is_executable_available;
+
+static REGISTERED: Mutex<Vec<Arc<dyn TerminalLauncher>>> = Mutex::new(Vec::new());
+
+/// Shell script that runs its arguments and waits for Enter before exiting
+const HOLD_SCRIPT: &str =
+    r#""$@"; status=$?; printf '\n[Process exited with status %s, press Enter to close]' "$status"; read -r _"#;
+
+/// Terminals tried in order when $TERMINAL isn't set, after registered ones
+const PREFERRED: &[&str] = &[
+    "x-terminal-emulator", // Debian/Ubuntu alternative
+    "gnome-terminal",
+    "kgx",
+    "konsole",
+    "xfce4-terminal",
+    "mate-terminal",
+    "lxterminal",
+    "terminator",
+    "alacritty",
+    "kitty",
+    "foot",
+    "wezterm",
+    "ghostty",
+    "urxvt",
+    "rxvt-unicode",
+    "rxvt",
+    "xterm",
+];
+
+/// Builds the command line that runs a program in a terminal emulator
+pub trait TerminalLauncher: fmt::Debug + Send + Sync {
+    /// Get the terminal's executable name, as looked up in PATH and matched against $TERMINAL
+    fn executable(&self) -> &str;
+
+    /// Get the terminal's arguments for running `command`, the program followed by its arguments
+    ///
+    /// With `hold` the window should stay open after the command exits.
+    fn args(&self, command: &[String], hold: bool) -> Vec<String>;
+}
+
+/// A terminal that takes its command after fixed arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalProfile {
+    executable: String,
+    exec_args: Vec<String>,
+    hold_args: Option<Vec<String>>,
+}
+
+impl TerminalProfile {
+    /// Profile for a terminal that runs the command given after `-e`
+    pub fn new<S: Into<String>>(executable: S) -> Self {
+        TerminalProfile {
+            executable: executable.into(),
+            exec_args: vec!["-e".to_string()],
+            hold_args: None,
+        }
+    }
+
+    /// Arguments placed right before the command, `-e` by default
+    pub fn exec_args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.exec_args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Arguments that keep the window open after the command exits
+    ///
+    /// Without them a held command runs through a shell that waits for Enter.
+    pub fn hold_args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.hold_args = Some(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Get the built-in profile of a known terminal by executable name or path
+    pub fn builtin(terminal: &str) -> Option<Self> {
+        let name = Path::new(terminal).file_name()?.to_str()?;
+        let profile = TerminalProfile::new(name);
+        let profile = match name {
+            "x-terminal-emulator" | "lxterminal" | "ghostty" => profile,
+            "gnome-terminal" | "kgx" => profile.exec_args(["--"]),
+            "xfce4-terminal" => profile.exec_args(["-x"]).hold_args(["--hold"]),
+            "mate-terminal" | "terminator" => profile.exec_args(["-x"]),
+            "konsole" | "alacritty" => profile.hold_args(["--hold"]),
+            "kitty" | "foot" => profile.exec_args(Vec::<String>::new()).hold_args(["--hold"]),
+            "wezterm" => profile.exec_args(["start", "--"]),
+            "xterm" | "rxvt" | "rxvt-unicode" | "urxvt" => profile.hold_args(["-hold"]),
+            _ => return None,
+        };
+        Some(profile)
+    }
+}
+
+impl TerminalLauncher for TerminalProfile {
+    fn executable(&self) -> &str {
+        &self.executable
+    }
+
+    fn args(&self, command: &[String], hold: bool) -> Vec<String> {
+        let mut args = Vec::new();
+        let hold_args = self.hold_args.as_ref().filter(|_| hold);
+        args.extend(hold_args.into_iter().flatten().cloned());
+        args.extend(self.exec_args.iter().cloned());
+
+        // Terminals without a hold flag wait for Enter in a shell instead
+        if hold && self.hold_args.is_none() {
+            args.extend(["sh", "-c", HOLD_SCRIPT, "sh"].map(str::to_string));
+        }
+        args.extend(command.iter().cloned());
+        args
+    }
+}
+
+/// Make a terminal available for Terminal=true entries
+///
+/// Registered terminals are preferred over the built-in ones and replace the
+/// built-in profile of a terminal with the same executable name, including when
+/// it is picked through $TERMINAL.
+pub fn register_terminal(terminal: Arc<dyn TerminalLauncher>) {
+    let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    registered.retain(|existing| existing.executable() != terminal.executable());
+    registered.push(terminal);
+}
+
+/// Find the terminal to run a command in, returning the program to start and its launcher
+///
+/// $TERMINAL wins, then registered terminals (most recent first), then the
+/// built-in ones in order of preference.
+pub(crate) fn find_terminal() -> Option<(String, Arc<dyn TerminalLauncher>)> {
+    let registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+    if let Ok(terminal) = std::env::var("TERMINAL") {
+        if is_executable_available(&terminal) {
+            let name = Path::new(&terminal).file_name().and_then(|name| name.to_str()).unwrap_or(&terminal);
+            let launcher = registered
+                .iter()
+                .find(|launcher| launcher.executable() == name || launcher.executable() == terminal)
+                .cloned()
+                .unwrap_or_else(|| Arc::new(TerminalProfile::builtin(name).unwrap_or_else(|| TerminalProfile::new(name))));
+            return Some((terminal, launcher));
+        }
+    }
+
+    let builtin = PREFERRED
+        .iter()
+        .filter_map(|name| TerminalProfile::builtin(name))
+        .map(|profile| Arc::new(profile) as Arc<dyn TerminalLauncher>);
+
+    registered
+        .into_iter()
+        .rev()
+        .chain(builtin)
+        .find(|launcher| is_executable_available(launcher.executable()))
+        .map(|launcher| (launcher.executable().to_string(), launcher))
+}
@@ -0,0 +1,57 @@
+//! Whether a desktop file may be run without asking the user
+//!
+//! Desktop files from the XDG data directories were installed by packages or
+//! the user and are trusted. A file anywhere else, such as one in a download
+//! directory, can run any command with a harmless-looking name and icon, so
+//! file managers only launch it once the user marked it executable. Browsers
+//! tag downloads with the `user.xdg.origin.url` extended attribute; such a file
+//! isn't trusted even if it is executable.
+
+use std::path::Path;
+
+use crate::{application_entry_paths_by_precedence, is_executable_file, ApplicationEntry};
+
+/// Extended attribute browsers set on downloaded files
+const ORIGIN_ATTRIBUTE: &str = "user.xdg.origin.url";
+
+impl ApplicationEntry {
+    /// Check if the entry may be launched without confirmation
+    ///
+    /// Entries in the `applications` directories of the XDG data dirs are
+    /// trusted, but not those found in other directories with
+    /// [`ApplicationEntry::all_in`]. Other files have to be executable by the
+    /// current user and must not carry the download origin attribute. See [`LaunchOptions::require_trusted`](crate::LaunchOptions::require_trusted).
+    pub fn is_trusted(&self) -> bool {
+        // Resolve `..` and symlinks so a path can't merely look like it is in a data dir
+        let Ok(path) = self.path().canonicalize() else {
+            return false;
+        };
+        if application_entry_paths_by_precedence()
+            .iter()
+            .filter_map(|dir| dir.canonicalize().ok())
+            .any(|dir| path.starts_with(dir))
+        {
+            return true;
+        }
+        is_executable_file(self.path()) && !has_origin_attribute(self.path())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn has_origin_attribute(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let (Ok(path), Ok(name)) = (
+        std::ffi::CString::new(path.as_os_str().as_bytes()),
+        std::ffi::CString::new(ORIGIN_ATTRIBUTE),
+    ) else {
+        return false;
+    };
+    // With an empty buffer getxattr only reports the value's size, failing if it isn't set
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) >= 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn has_origin_attribute(_path: &Path) -> bool {
+    false
+}
@@ -0,0 +1,204 @@
+//! Validation of every desktop file in an applications directory
+//!
+//! Meant for distribution QA jobs and diagnostic tools: [`validate_dir`] parses
+//! each file strictly and permissively, and checks the directory as a whole for
+//! files that collide on their desktop file ID or are hidden by a copy in a
+//! higher-precedence XDG directory.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::id::desktop_file_id;
+use crate::{application_entry_paths_by_precedence, ApplicationEntries, ApplicationEntry, Deprecation, ParseError, ParseOptions};
+
+/// A problem found in a desktop file
+#[derive(Debug, Clone)]
+pub enum ValidationIssue {
+    /// The file can't be parsed at all, so no launcher will show it
+    Unparsable(ParseError),
+    /// The file parses but breaks the specification
+    Strict(ParseError),
+    /// The file uses a deprecated construct
+    Deprecated(Deprecation),
+    /// Another file in the directory has the same desktop file ID
+    DuplicateId { id: String, other: PathBuf },
+    /// A file with the same desktop file ID in a higher-precedence directory hides this one
+    Shadowed { id: String, by: PathBuf },
+}
+
+impl ValidationIssue {
+    /// Check whether the issue keeps the entry from working as intended,
+    /// rather than being a warning
+    pub fn is_error(&self) -> bool {
+        matches!(self, ValidationIssue::Unparsable(_) | ValidationIssue::DuplicateId { .. })
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::Unparsable(e) => write!(f, "error: {}", e),
+            ValidationIssue::Strict(e) => write!(f, "warning: {}", e),
+            ValidationIssue::Deprecated(d) => write!(f, "warning: {}", d),
+            ValidationIssue::DuplicateId { id, other } => {
+                write!(f, "error: desktop file ID {} is also used by {}", id, other.display())
+            }
+            ValidationIssue::Shadowed { id, by } => {
+                write!(f, "warning: desktop file ID {} is shadowed by {}", id, by.display())
+            }
+        }
+    }
+}
+
+/// The issues found in one desktop file
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    path: PathBuf,
+    id: Option<String>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl FileReport {
+    /// Get the path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the desktop file ID the file has in the validated directory
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Get the issues found in the file
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// Check if no issues were found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// The result of validating an applications directory
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    files: Vec<FileReport>,
+}
+
+impl ValidationReport {
+    /// Get the report of every desktop file, sorted by path
+    pub fn files(&self) -> &[FileReport] {
+        &self.files
+    }
+
+    /// Get the reports of files with at least one issue
+    pub fn problems(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|file| !file.is_valid())
+    }
+
+    /// Count the issues that are errors
+    pub fn error_count(&self) -> usize {
+        self.issues().filter(|issue| issue.is_error()).count()
+    }
+
+    /// Count the issues that are warnings
+    pub fn warning_count(&self) -> usize {
+        self.issues().filter(|issue| !issue.is_error()).count()
+    }
+
+    /// Check if no errors were found; warnings are allowed
+    pub fn passed(&self) -> bool {
+        self.error_count() == 0
+    }
+
+    fn issues(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.files.iter().flat_map(|file| file.issues.iter())
+    }
+}
+
+/// Validate every desktop file in an applications directory and its subdirectories
+///
+/// Shadowing is only checked when `dir` is one of the XDG applications
+/// directories, against the directories that take precedence over it.
+pub fn validate_dir<P: AsRef<Path>>(dir: P) -> ValidationReport {
+    let dir = dir.as_ref().to_path_buf();
+    let dirs = [dir.clone()];
+    let mut walker = ApplicationEntries::in_dirs(&dirs, true);
+
+    let mut files = Vec::new();
+    while let Some(path) = walker.next_path() {
+        let id = desktop_file_id(&path, &dirs);
+        files.push(FileReport {
+            issues: file_issues(&path),
+            path,
+            id,
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    // foo/bar.desktop and foo-bar.desktop both get the ID foo-bar
+    let mut by_id: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for file in &files {
+        if let Some(id) = &file.id {
+            by_id.entry(id.clone()).or_default().push(file.path.clone());
+        }
+    }
+
+    let higher = higher_precedence_ids(&dir);
+    for file in &mut files {
+        let Some(id) = file.id.clone() else {
+            continue;
+        };
+        for other in by_id[&id].iter().filter(|other| **other != file.path) {
+            file.issues.push(ValidationIssue::DuplicateId {
+                id: id.clone(),
+                other: other.clone(),
+            });
+        }
+        if let Some(by) = higher.get(&id) {
+            file.issues.push(ValidationIssue::Shadowed { id, by: by.clone() });
+        }
+    }
+
+    ValidationReport { files }
+}
+
+pub(crate) fn file_issues(path: &Path) -> Vec<ValidationIssue> {
+    let entry = match ApplicationEntry::try_from_path(path) {
+        Ok(entry) => entry,
+        Err(e) => return vec![ValidationIssue::Unparsable(e)],
+    };
+
+    let mut issues = Vec::new();
+    if let Err(e) = ApplicationEntry::try_from_path_with(path, &ParseOptions::strict()) {
+        issues.push(ValidationIssue::Strict(e));
+    }
+    issues.extend(entry.deprecations().into_iter().map(ValidationIssue::Deprecated));
+    issues
+}
+
+/// Map the desktop file IDs in the XDG directories that take precedence over `dir`
+/// to the file that provides them
+fn higher_precedence_ids(dir: &Path) -> HashMap<String, PathBuf> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let target = canonical(dir);
+
+    let dirs = application_entry_paths_by_precedence();
+    let Some(position) = dirs.iter().position(|d| canonical(d) == target) else {
+        return HashMap::new();
+    };
+
+    let mut ids = HashMap::new();
+    for higher in &dirs[..position] {
+        let higher_dirs = [higher.clone()];
+        let mut walker = ApplicationEntries::in_dirs(&higher_dirs, true);
+        while let Some(path) = walker.next_path() {
+            if let Some(id) = desktop_file_id(&path, &higher_dirs) {
+                ids.entry(id).or_insert(path);
+            }
+        }
+    }
+    ids
+}
@@ -0,0 +1,203 @@
+//! End-to-end checks of an installed application's desktop integration
+//!
+//! [`verify_installation`] is meant for an application's own post-install tests:
+//! after `make install` or a package build, it checks that the desktop file is
+//! found and valid, and that the files it refers to were installed alongside it.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use freedesktop_core::keyfile::KeyFile;
+
+use crate::lookup::find_id_in;
+use crate::validate::file_issues;
+use crate::{application_entry_paths_by_precedence, data_dirs_by_precedence, normalize_desktop_id, ApplicationEntry, ValidationIssue};
+
+/// Icon sizes every application has to install in the hicolor theme, unless it
+/// installs a scalable icon
+///
+/// The icon theme specification makes 48x48 the size themes fall back to.
+pub const REQUIRED_ICON_SIZES: [u32; 1] = [48];
+
+/// A problem with an installed application's integration
+#[derive(Debug, Clone)]
+pub enum InstallationIssue {
+    /// No desktop file with the ID is installed
+    NotInstalled,
+    /// The desktop file has a validation issue, see [`validate_dir`](crate::validate_dir)
+    Invalid(ValidationIssue),
+    /// The entry is DBusActivatable but its ID isn't a valid D-Bus well-known name
+    BusNameMismatch,
+    /// The entry has no Icon key
+    NoIcon,
+    /// The icon isn't installed in the hicolor theme at this size
+    MissingIcon { icon: String, size: u32 },
+    /// The icon is an absolute path that doesn't exist
+    MissingIconFile(PathBuf),
+    /// No mimeinfo.cache lists the entry for a type in its MimeType key
+    MimeTypeNotCached(String),
+}
+
+impl InstallationIssue {
+    /// Check whether the issue breaks the integration, rather than being a warning
+    pub fn is_error(&self) -> bool {
+        match self {
+            InstallationIssue::Invalid(issue) => issue.is_error(),
+            InstallationIssue::NoIcon => false,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for InstallationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallationIssue::NotInstalled => write!(f, "error: no desktop file is installed"),
+            InstallationIssue::Invalid(issue) => write!(f, "{}", issue),
+            InstallationIssue::BusNameMismatch => {
+                write!(f, "error: DBusActivatable=true but the ID is not a D-Bus name")
+            }
+            InstallationIssue::NoIcon => write!(f, "warning: no Icon key"),
+            InstallationIssue::MissingIcon { icon, size } => {
+                write!(f, "error: icon {} is not installed in hicolor at {}x{}", icon, size, size)
+            }
+            InstallationIssue::MissingIconFile(path) => write!(f, "error: icon file {} does not exist", path.display()),
+            InstallationIssue::MimeTypeNotCached(mime) => {
+                write!(f, "error: {} is not in mimeinfo.cache, run update-desktop-database", mime)
+            }
+        }
+    }
+}
+
+/// The result of [`verify_installation`]
+#[derive(Debug, Clone)]
+pub struct InstallationReport {
+    id: String,
+    path: Option<PathBuf>,
+    issues: Vec<InstallationIssue>,
+}
+
+impl InstallationReport {
+    /// Get the verified desktop file ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the desktop file that was checked, if one is installed
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Get the issues found
+    pub fn issues(&self) -> &[InstallationIssue] {
+        &self.issues
+    }
+
+    /// Check if no errors were found; warnings are allowed
+    pub fn passed(&self) -> bool {
+        !self.issues.iter().any(InstallationIssue::is_error)
+    }
+}
+
+/// Check the desktop integration of an installed application
+///
+/// Finds the desktop file the way launchers do and checks that it validates,
+/// that DBusActivatable entries are named after their D-Bus name, that the icon
+/// is installed in hicolor at [`REQUIRED_ICON_SIZES`] (or as a scalable icon),
+/// and that every MimeType is in a mimeinfo.cache.
+pub fn verify_installation(app_id: &str) -> InstallationReport {
+    let id = normalize_desktop_id(app_id);
+    let path = application_entry_paths_by_precedence()
+        .iter()
+        .find_map(|dir| find_id_in(dir, &id));
+    let mut report = InstallationReport {
+        id,
+        path: path.clone(),
+        issues: Vec::new(),
+    };
+    let Some(path) = path else {
+        report.issues.push(InstallationIssue::NotInstalled);
+        return report;
+    };
+
+    report.issues.extend(file_issues(&path).into_iter().map(InstallationIssue::Invalid));
+    let Ok(entry) = ApplicationEntry::try_from_path(&path) else {
+        return report;
+    };
+
+    if entry.dbus_activatable() && !is_bus_name(&report.id) {
+        report.issues.push(InstallationIssue::BusNameMismatch);
+    }
+    report.issues.extend(icon_issues(&entry));
+
+    let cached = cached_mime_types(&report.id);
+    for mime in entry.mime_types().unwrap_or_default() {
+        if !cached.contains(&mime) {
+            report.issues.push(InstallationIssue::MimeTypeNotCached(mime));
+        }
+    }
+    report
+}
+
+fn icon_issues(entry: &ApplicationEntry) -> Vec<InstallationIssue> {
+    let Some(icon) = entry.icon().filter(|icon| !icon.is_empty()) else {
+        return vec![InstallationIssue::NoIcon];
+    };
+    if Path::new(&icon).is_absolute() {
+        return match Path::new(&icon).is_file() {
+            true => Vec::new(),
+            false => vec![InstallationIssue::MissingIconFile(PathBuf::from(icon))],
+        };
+    }
+
+    let themes: Vec<PathBuf> = data_dirs_by_precedence()
+        .iter()
+        .map(|dir| dir.join("icons").join("hicolor"))
+        .collect();
+    let installed = |size: &str, extensions: &[&str]| {
+        themes.iter().any(|theme| {
+            extensions
+                .iter()
+                .any(|ext| theme.join(size).join("apps").join(format!("{}.{}", icon, ext)).is_file())
+        })
+    };
+    if installed("scalable", &["svg"]) {
+        return Vec::new();
+    }
+    REQUIRED_ICON_SIZES
+        .iter()
+        .filter(|size| !installed(&format!("{0}x{0}", size), &["png", "svg", "xpm"]))
+        .map(|&size| InstallationIssue::MissingIcon { icon: icon.clone(), size })
+        .collect()
+}
+
+/// Get the MIME types the mimeinfo.cache files list the desktop file ID for
+fn cached_mime_types(id: &str) -> Vec<String> {
+    let file = format!("{}.desktop", id);
+    let mut types = Vec::new();
+    for dir in application_entry_paths_by_precedence() {
+        let Ok(cache) = KeyFile::from_path(dir.join("mimeinfo.cache")) else {
+            continue;
+        };
+        for (mime, _) in cache.entries("MIME Cache").unwrap_or_default() {
+            let listed = cache.get_list("MIME Cache", mime).unwrap_or_default();
+            if listed.contains(&file) && !types.contains(mime) {
+                types.push(mime.clone());
+            }
+        }
+    }
+    types
+}
+
+/// Check a desktop file ID against the D-Bus well-known name rules that
+/// DBusActivatable requires
+pub(crate) fn is_bus_name(id: &str) -> bool {
+    let elements: Vec<&str> = id.split('.').collect();
+    id.len() <= 255
+        && elements.len() >= 2
+        && elements.iter().all(|element| {
+            !element.is_empty()
+                && !element.starts_with(|c: char| c.is_ascii_digit())
+                && element.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        })
+}
@@ -0,0 +1,86 @@
+//! Checking which entries a desktop environment shows
+//!
+//! Menu editors and theming tools that target several desktops need to know what
+//! users of each one see, independent of the desktop they run under themselves.
+
+use std::fmt;
+
+use crate::{ApplicationEntry, Registry};
+
+/// Whether a desktop shows an entry in its menus, and why not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    Shown,
+    /// Hidden=true, the entry counts as deleted
+    Hidden,
+    /// NoDisplay=true, the entry can be launched but isn't listed
+    NoDisplay,
+    /// The desktop is in the entry's NotShowIn list
+    NotShownIn,
+    /// The entry has an OnlyShowIn list without the desktop
+    OnlyShownElsewhere,
+}
+
+impl Visibility {
+    pub fn is_shown(&self) -> bool {
+        *self == Visibility::Shown
+    }
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Visibility::Shown => "shown",
+            Visibility::Hidden => "hidden",
+            Visibility::NoDisplay => "not displayed",
+            Visibility::NotShownIn => "excluded by NotShowIn",
+            Visibility::OnlyShownElsewhere => "excluded by OnlyShowIn",
+        })
+    }
+}
+
+impl ApplicationEntry {
+    /// Check whether a desktop environment shows the entry in its menus
+    ///
+    /// `desktop` may be a colon-separated list in the format of XDG_CURRENT_DESKTOP,
+    /// see [`ApplicationEntry::shows_in`].
+    pub fn visibility_in(&self, desktop: &str) -> Visibility {
+        if self.is_hidden() {
+            Visibility::Hidden
+        } else if self.no_display() {
+            Visibility::NoDisplay
+        } else if self.shows_in(desktop) {
+            Visibility::Shown
+        } else if listed(self.not_show_in(), desktop) {
+            Visibility::NotShownIn
+        } else {
+            Visibility::OnlyShownElsewhere
+        }
+    }
+}
+
+/// Check if any of the colon-separated desktops is in an OnlyShowIn or NotShowIn list
+fn listed(list: Option<Vec<String>>, desktop: &str) -> bool {
+    let list = list.unwrap_or_default();
+    desktop.split(':').any(|d| list.iter().any(|listed| listed == d))
+}
+
+impl Registry {
+    /// Get the visibility of every entry as if running under `desktop`
+    ///
+    /// Lets tools preview, say, the GNOME menu while running under sway.
+    pub fn preview(&self, desktop: &str) -> Vec<(&ApplicationEntry, Visibility)> {
+        self.entries()
+            .iter()
+            .map(|entry| (entry, entry.visibility_in(desktop)))
+            .collect()
+    }
+
+    /// Get the entries a desktop environment shows in its menus
+    pub fn visible_in(&self, desktop: &str) -> Vec<&ApplicationEntry> {
+        self.entries()
+            .iter()
+            .filter(|entry| entry.visibility_in(desktop).is_shown())
+            .collect()
+    }
+}
@@ -0,0 +1,551 @@
+//! Change notifications for the applications directories, backed by inotify
+//!
+//! [`RegistryWatcher`] reports every desktop file that changes, while
+//! [`EntryWatcher`] follows a single entry through its override chain.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::lookup::find_id_in;
+use crate::parser::{DesktopEntry, ParseOptions};
+use crate::{
+    application_entry_paths, application_entry_paths_by_precedence, id, ApplicationEntry, Registry,
+};
+
+const WATCH_MASK: u32 = libc::IN_CREATE
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DELETE_SELF
+    | libc::IN_ONLYDIR;
+
+/// A change to a desktop file, carrying its desktop file ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RegistryEvent {
+    Added(String),
+    Modified(String),
+    Removed(String),
+    /// Changes were lost because the kernel's event queue overflowed, so every
+    /// entry has to be read again
+    Rescan,
+}
+
+impl RegistryEvent {
+    /// Get the desktop file ID the event is about, or `None` for [`RegistryEvent::Rescan`]
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            RegistryEvent::Added(id) | RegistryEvent::Modified(id) | RegistryEvent::Removed(id) => Some(id),
+            RegistryEvent::Rescan => None,
+        }
+    }
+}
+
+/// How [`RegistryWatcher::next_batch`] groups bursts of changes
+///
+/// A batch ends once no change has arrived for the quiet period, or when the
+/// maximum delay since its first change has passed, so a steady stream of
+/// changes still produces regular refreshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshPolicy {
+    quiet_period: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        RefreshPolicy {
+            quiet_period: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RefreshPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time without changes after which a batch is complete
+    pub fn quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.quiet_period = quiet_period;
+        self
+    }
+
+    /// Longest time a batch keeps collecting after its first change
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+}
+
+/// Watches the applications directories for changes to desktop files
+///
+/// Iterating blocks until the next change and yields an error when reading the
+/// changes fails. Events describe individual files, so a
+/// change to a shadowed copy is reported too; [`Registry::apply_event`] takes
+/// precedence into account. The watcher also implements [`AsFd`] for use with
+/// `poll`-based event loops.
+#[derive(Debug)]
+pub struct RegistryWatcher {
+    fd: OwnedFd,
+    roots: Vec<PathBuf>,
+    recursive: bool,
+    watches: HashMap<i32, PathBuf>,
+    known: HashSet<PathBuf>,
+    pending: VecDeque<RegistryEvent>,
+}
+
+impl Registry {
+    /// Start watching the directories the registry was loaded from
+    ///
+    /// These are the applications directories of all XDG data dirs, unless the
+    /// registry was built with [`Registry::load_from`]. Only directories that exist
+    /// when the watch starts are monitored.
+    pub fn watch(&self) -> io::Result<RegistryWatcher> {
+        let roots = match &self.dirs {
+            Some(dirs) => dirs.iter().filter(|dir| dir.exists()).cloned().collect(),
+            None => application_entry_paths(),
+        };
+        RegistryWatcher::new(roots, true)
+    }
+}
+
+impl RegistryWatcher {
+    fn new(roots: Vec<PathBuf>, recursive: bool) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut watcher = RegistryWatcher {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            roots: roots.clone(),
+            recursive,
+            watches: HashMap::new(),
+            known: HashSet::new(),
+            pending: VecDeque::new(),
+        };
+        for root in roots {
+            watcher.watch_tree(&root, false)?;
+        }
+        Ok(watcher)
+    }
+
+    /// Block until the next change and return it
+    pub fn next_event(&mut self) -> io::Result<RegistryEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            self.read_events()?;
+        }
+    }
+
+    /// Block until changes arrive and return them once they settle
+    ///
+    /// Package upgrades touch many files at once; collecting them per
+    /// [`RefreshPolicy`] lets consumers refresh once per burst. The batch holds one
+    /// event per ID, in the order the IDs first changed.
+    pub fn next_batch(&mut self, policy: &RefreshPolicy) -> io::Result<Vec<RegistryEvent>> {
+        let mut batch = Vec::new();
+        coalesce(&mut batch, self.next_event()?);
+        let deadline = Instant::now() + policy.max_delay;
+
+        loop {
+            while let Some(event) = self.pending.pop_front() {
+                coalesce(&mut batch, event);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !self.wait_readable(policy.quiet_period.min(remaining))? {
+                return Ok(batch);
+            }
+            self.read_events()?;
+        }
+    }
+
+    /// Wait up to `timeout` for inotify events, returning whether any are ready
+    pub(crate) fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+
+        loop {
+            let ready = unsafe { libc::poll(&mut pollfd, 1, timeout_ms) };
+            if ready >= 0 {
+                return Ok(ready > 0);
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Read the inotify events that are ready, returning whether a desktop file changed
+    ///
+    /// Only call this once [`RegistryWatcher::wait_readable`] said events are ready,
+    /// or it blocks.
+    pub(crate) fn read_ready(&mut self) -> io::Result<bool> {
+        self.read_events()?;
+        Ok(!self.pending.is_empty())
+    }
+
+    /// Watch `dir` and, when recursive, its subdirectories, recording the desktop files in them
+    fn watch_tree(&mut self, dir: &Path, report: bool) -> io::Result<()> {
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.watches.insert(wd, dir.to_path_buf());
+
+        let Ok(dir_entries) = std::fs::read_dir(dir) else {
+            return Ok(());
+        };
+        for path in dir_entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                if self.recursive {
+                    self.watch_tree(&path, report)?;
+                }
+            } else if is_desktop_file(&path) && self.known.insert(path.clone()) && report {
+                self.push(RegistryEvent::Added, &path);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_events(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 4096];
+        let len = loop {
+            let len = unsafe {
+                libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len())
+            };
+            if len >= 0 {
+                break len as usize;
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        };
+
+        let header = std::mem::size_of::<libc::inotify_event>();
+        let mut offset = 0;
+        while offset + header <= len {
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr().cast()) };
+            let name_bytes = &buffer[offset + header..offset + header + event.len as usize];
+            let name_len = name_bytes.iter().position(|b| *b == 0).unwrap_or(name_bytes.len());
+            let name = OsStr::from_bytes(&name_bytes[..name_len]);
+            offset += header + event.len as usize;
+
+            self.handle(event.wd, event.mask, name);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, wd: i32, mask: u32, name: &OsStr) {
+        if mask & libc::IN_Q_OVERFLOW != 0 {
+            self.rescan();
+            return;
+        }
+        let Some(dir) = self.watches.get(&wd).cloned() else {
+            return;
+        };
+        if mask & (libc::IN_DELETE_SELF | libc::IN_IGNORED) != 0 {
+            self.watches.remove(&wd);
+            return;
+        }
+        let path = dir.join(name);
+
+        if mask & libc::IN_ISDIR != 0 {
+            if !self.recursive {
+                return;
+            }
+            if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                let _ = self.watch_tree(&path, true);
+            } else if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                let removed: Vec<PathBuf> = self.known.iter().filter(|p| p.starts_with(&path)).cloned().collect();
+                for file in removed {
+                    self.known.remove(&file);
+                    self.push(RegistryEvent::Removed, &file);
+                }
+            }
+            return;
+        }
+
+        if !is_desktop_file(&path) {
+            return;
+        }
+
+        // A created file is reported once it has been written and closed, but
+        // links arrive complete and are never written
+        if mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) != 0 || (mask & libc::IN_CREATE != 0 && is_link(&path)) {
+            if self.known.insert(path.clone()) {
+                self.push(RegistryEvent::Added, &path);
+            } else {
+                self.push(RegistryEvent::Modified, &path);
+            }
+        } else if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 && self.known.remove(&path) {
+            self.push(RegistryEvent::Removed, &path);
+        }
+    }
+
+    /// Start over after lost events: watch the trees again and report a full rescan
+    ///
+    /// Directories created while events were lost get watched, and the pending
+    /// events are dropped since the rescan covers them.
+    fn rescan(&mut self) {
+        self.known.clear();
+        for root in self.roots.clone() {
+            let _ = self.watch_tree(&root, false);
+        }
+        self.pending.clear();
+        self.pending.push_back(RegistryEvent::Rescan);
+    }
+
+    fn push(&mut self, event: fn(String) -> RegistryEvent, path: &Path) {
+        if let Some(id) = id::desktop_file_id(path, &self.roots) {
+            self.pending.push_back(event(id));
+        }
+    }
+}
+
+/// Check if a created path is a symlink or a hard link to an existing file
+fn is_link(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::symlink_metadata(path).is_ok_and(|m| !m.file_type().is_file() || m.nlink() > 1)
+}
+
+impl Iterator for RegistryWatcher {
+    type Item = io::Result<RegistryEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_event())
+    }
+}
+
+impl AsFd for RegistryWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for RegistryWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// A change to the entry followed by an [`EntryWatcher`]
+#[derive(Debug, Clone)]
+pub enum EntryChange {
+    /// The entry now in effect, after its file changed or another copy took over
+    Changed(ApplicationEntry),
+    /// No copy that can be parsed is left, or the one in effect has Hidden=true
+    Removed,
+}
+
+/// Watches the entry behind one desktop file ID as copies of it come and go
+///
+/// A user's copy in `$XDG_DATA_HOME/applications` overrides the system one, and
+/// removing it brings the system copy back. Changes are only reported when the
+/// copy in effect or its contents change, so edits to a shadowed copy are
+/// ignored. Like [`RegistryWatcher`], only directories that exist when the watch
+/// starts are monitored.
+#[derive(Debug)]
+pub struct EntryWatcher {
+    watcher: RegistryWatcher,
+    id: String,
+    /// Where copies of the entry are looked for, from highest to lowest precedence
+    dirs: Vec<PathBuf>,
+    application_dirs: Option<Arc<[PathBuf]>>,
+    current: Option<ApplicationEntry>,
+    /// Path and contents of the file `current` was read from
+    source: Option<(PathBuf, Vec<u8>)>,
+}
+
+impl ApplicationEntry {
+    /// Start watching this entry's file and the other copies of its desktop file ID
+    ///
+    /// An entry without an ID, such as a file outside the applications
+    /// directories, is watched on its own.
+    pub fn watch(&self) -> io::Result<EntryWatcher> {
+        let (id, dirs, recursive) = match (self.id(), &self.application_dirs) {
+            (Some(id), Some(dirs)) => (id, dirs.to_vec(), true),
+            (Some(id), None) => (id, application_entry_paths_by_precedence(), true),
+            (None, _) => {
+                let path = self.path();
+                let parent = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+                let id = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+                (id, vec![parent], false)
+            }
+        };
+
+        let mut watcher = EntryWatcher {
+            watcher: RegistryWatcher::new(dirs.clone(), recursive)?,
+            id,
+            dirs,
+            application_dirs: self.application_dirs.clone(),
+            current: None,
+            source: None,
+        };
+        watcher.refresh();
+        Ok(watcher)
+    }
+}
+
+impl EntryWatcher {
+    /// Get the desktop file ID being watched
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the entry currently in effect, if any
+    pub fn current(&self) -> Option<&ApplicationEntry> {
+        self.current.as_ref()
+    }
+
+    /// Block until the entry in effect changes and return the change
+    pub fn next_change(&mut self) -> io::Result<EntryChange> {
+        loop {
+            if let Some(change) = self.drain() {
+                return Ok(change);
+            }
+            self.watcher.read_events()?;
+        }
+    }
+
+    /// Wait up to `timeout` for the entry in effect to change
+    ///
+    /// Returns `None` when the time runs out without a change.
+    pub fn next_change_timeout(&mut self, timeout: Duration) -> io::Result<Option<EntryChange>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(change) = self.drain() {
+                return Ok(Some(change));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !self.watcher.wait_readable(remaining)? {
+                return Ok(None);
+            }
+            self.watcher.read_events()?;
+        }
+    }
+
+    /// Look at the pending file events, re-reading the entry if one is about its ID
+    fn drain(&mut self) -> Option<EntryChange> {
+        let mut relevant = false;
+        while let Some(event) = self.watcher.pending.pop_front() {
+            relevant |= event.id().is_none_or(|id| id == self.id);
+        }
+        if relevant {
+            self.refresh()
+        } else {
+            None
+        }
+    }
+
+    /// Find the copy in effect and report it if it differs from the known one
+    ///
+    /// Like [`ApplicationEntry::find_by_id`], copies that can't be parsed are
+    /// skipped in favor of the next one.
+    fn refresh(&mut self) -> Option<EntryChange> {
+        let parsed = self
+            .dirs
+            .iter()
+            .filter_map(|dir| find_id_in(dir, &self.id))
+            .find_map(|path| {
+                let contents = std::fs::read(&path).ok()?;
+                let inner = DesktopEntry::from_reader_with(&path, contents.as_slice(), &ParseOptions::default()).ok()?;
+                Some(((path, contents), inner))
+            });
+        let (found, inner) = parsed.unzip();
+        if found == self.source {
+            return None;
+        }
+
+        let entry = inner.and_then(|inner| {
+            let entry = ApplicationEntry {
+                inner,
+                application_dirs: self.application_dirs.clone(),
+                full: None,
+            };
+            (!entry.is_hidden()).then_some(entry)
+        });
+        self.source = found;
+
+        match (entry, self.current.is_some()) {
+            (Some(entry), _) => {
+                self.current = Some(entry.clone());
+                Some(EntryChange::Changed(entry))
+            }
+            (None, true) => {
+                self.current = None;
+                Some(EntryChange::Removed)
+            }
+            (None, false) => None,
+        }
+    }
+}
+
+impl Iterator for EntryWatcher {
+    type Item = io::Result<EntryChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_change())
+    }
+}
+
+impl AsFd for EntryWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.watcher.as_fd()
+    }
+}
+
+impl AsRawFd for EntryWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.watcher.as_raw_fd()
+    }
+}
+
+/// Merge an event into a batch, keeping one event per ID
+///
+/// The latest event wins, except that a file added and then modified within the
+/// batch is still reported as added. A rescan replaces the whole batch, since it
+/// reads every entry anyway.
+fn coalesce(batch: &mut Vec<RegistryEvent>, event: RegistryEvent) {
+    if batch.first() == Some(&RegistryEvent::Rescan) {
+        return;
+    }
+    if event == RegistryEvent::Rescan {
+        batch.clear();
+        batch.push(event);
+        return;
+    }
+    let Some(existing) = batch.iter_mut().find(|e| e.id() == event.id()) else {
+        batch.push(event);
+        return;
+    };
+
+    if !matches!((&*existing, &event), (RegistryEvent::Added(_), RegistryEvent::Modified(_))) {
+        *existing = event;
+    }
+}
+
+fn is_desktop_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "desktop")
+}
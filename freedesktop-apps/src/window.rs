@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use crate::{ApplicationEntry, Launched};
+
+/// A window reported by the caller, identified the way compositors expose it
+///
+/// Implemented by callers on top of whatever window list their compositor or
+/// window manager provides, for use with [`Registry::focus_or_launch`](crate::Registry::focus_or_launch).
+pub trait WindowHint {
+    /// The X11 `WM_CLASS` of the window
+    fn wm_class(&self) -> Option<&str> {
+        None
+    }
+
+    /// The Wayland app ID of the window
+    fn app_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Ask the compositor to activate the window, returning whether the request was made
+    fn activate(&self) -> bool;
+}
+
+/// What [`Registry::focus_or_launch`](crate::Registry::focus_or_launch) did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FocusOutcome {
+    /// An existing window was activated
+    Focused,
+    /// The application has a single main window that is still running from an earlier launch
+    AlreadyRunning(Launched),
+    /// A new instance was launched
+    Launched(Launched),
+}
+
+impl ApplicationEntry {
+    /// Get the WM class or Wayland app ID the application's windows are expected to have
+    pub fn startup_wm_class(&self) -> Option<String> {
+        self.get_string("StartupWMClass")
+    }
+
+    /// Check whether a window belongs to this entry
+    ///
+    /// `wm_class` is the X11 `WM_CLASS` class (or instance) name and `app_id` the Wayland
+    /// app ID; either may be omitted. StartupWMClass is matched exactly first, then the
+    /// desktop file ID and the Exec binary name are compared case-insensitively, which is
+    /// how most toolkits derive their window identifiers.
+    pub fn matches_window(&self, wm_class: Option<&str>, app_id: Option<&str>) -> bool {
+        let candidates: Vec<&str> = wm_class.into_iter().chain(app_id).collect();
+        if candidates.is_empty() {
+            return false;
+        }
+
+        if let Some(startup_class) = self.startup_wm_class() {
+            if candidates.iter().any(|c| *c == startup_class) {
+                return true;
+            }
+        }
+
+        let mut names = Vec::new();
+        if let Some(startup_class) = self.startup_wm_class() {
+            names.push(startup_class);
+        }
+        if let Some(id) = self.id() {
+            names.push(id);
+        }
+        if let Some(binary) = self.exec_binary_name() {
+            names.push(binary);
+        }
+
+        candidates
+            .iter()
+            .any(|candidate| names.iter().any(|name| name.eq_ignore_ascii_case(candidate)))
+    }
+
+    /// Find the entry whose windows have the given WM class
+    ///
+    /// Entries declaring a matching StartupWMClass win over entries matched by ID or binary name.
+    pub fn find_by_wm_class(wm_class: &str) -> Option<ApplicationEntry> {
+        let mut fallback = None;
+
+        for entry in ApplicationEntry::all() {
+            if entry.startup_wm_class().as_deref() == Some(wm_class) {
+                return Some(entry);
+            }
+            if fallback.is_none() && entry.matches_window(Some(wm_class), None) {
+                fallback = Some(entry);
+            }
+        }
+
+        fallback
+    }
+
+    /// Get the file name of the program in the Exec key
+    pub(crate) fn exec_binary_name(&self) -> Option<String> {
+        let exec = self.exec()?;
+        let program = exec.split_whitespace().next()?.trim_matches('"');
+        Path::new(program)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    }
+}
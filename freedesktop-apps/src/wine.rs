@@ -0,0 +1,91 @@
+//! Compatibility handling for entries generated by Wine and Proton
+//!
+//! Wine writes its menu entries as shell command lines rather than following the
+//! Exec quoting rules: arguments are left unquoted with backslash escapes
+//! (`C:\\windows\\command\\start.exe`, `My\ Game.lnk`) and the program is often
+//! wrapped in `env WINEPREFIX=...`. Windows programs also expect `Z:\` style paths
+//! unless they are started through `start.exe /unix`.
+
+use crate::{basename, parse_command_line, ApplicationEntry};
+
+/// Program names that run Windows executables
+const WINE_PROGRAMS: &[&str] = &[
+    "wine",
+    "wine64",
+    "wine-stable",
+    "wine-staging",
+    "wine-development",
+    "proton",
+];
+
+impl ApplicationEntry {
+    /// Check if the entry runs its program through Wine or Proton
+    ///
+    /// Recognizes `wine ...` as well as `env NAME=value ... wine ...` command lines.
+    pub fn is_wine(&self) -> bool {
+        self.wine_argv().is_some()
+    }
+
+    /// Get the WINEPREFIX set by the Exec line, if any
+    pub fn wine_prefix(&self) -> Option<String> {
+        let argv = self.wine_argv()?;
+        env_assignments(&argv)
+            .iter()
+            .find_map(|arg| arg.strip_prefix("WINEPREFIX="))
+            .map(str::to_string)
+    }
+
+    /// Parse the Exec line with shell escapes if it starts a Wine program
+    fn wine_argv(&self) -> Option<Vec<String>> {
+        let (program, args) = parse_command_line(&self.exec()?, true).ok()?;
+        let argv: Vec<String> = std::iter::once(program).chain(args).collect();
+        runs_wine(&argv).then_some(argv)
+    }
+}
+
+/// Check if an argument vector runs a Wine program, possibly through `env`
+fn runs_wine(argv: &[String]) -> bool {
+    let index = if argv.first().is_some_and(|arg| basename(arg) == "env") {
+        1 + env_assignments(argv).len()
+    } else {
+        0
+    };
+
+    argv.get(index)
+        .is_some_and(|program| WINE_PROGRAMS.contains(&basename(program)))
+}
+
+/// Get the `NAME=value` arguments that follow a leading `env`
+fn env_assignments(argv: &[String]) -> &[String] {
+    let rest = argv.get(1..).unwrap_or_default();
+    let count = rest
+        .iter()
+        .take_while(|arg| !arg.starts_with('-') && arg.contains('='))
+        .count();
+    &rest[..count]
+}
+
+/// Check if a Wine command line passes file arguments as Unix paths
+///
+/// `start.exe /unix` converts Unix paths itself; everything else expects Windows paths.
+pub(crate) fn takes_unix_paths(exec: &str) -> bool {
+    exec.split_whitespace().any(|arg| arg.eq_ignore_ascii_case("/unix"))
+}
+
+/// Convert a path to the form Windows programs under Wine understand
+///
+/// Absolute paths go through the `Z:` drive, which Wine maps to `/`. URLs are
+/// left unchanged.
+pub(crate) fn to_windows_path(path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+
+    let windows = path.replace('/', "\\");
+    if path.starts_with('/') {
+        format!("Z:{}", windows)
+    } else {
+        windows
+    }
+}
+
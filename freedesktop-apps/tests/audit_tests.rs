@@ -0,0 +1,46 @@
+use freedesktop_apps::{ApplicationEntry, AuditLog, LaunchOptions};
+use freedesktop_core::time::Timestamp;
+use std::fs;
+
+#[test]
+fn test_audit_log_records_and_rotates() {
+    let temp_dir = "/tmp/test_audit_log";
+    fs::remove_dir_all(temp_dir).ok();
+    fs::create_dir_all(temp_dir).unwrap();
+    let path = format!("{}/audit.desktop", temp_dir);
+    fs::write(&path, "[Desktop Entry]\nType=Application\nName=Audit\nExec=audit-app %f\nTryExec=/nonexistent/audit-app").unwrap();
+    let entry = ApplicationEntry::try_from_path(&path).unwrap();
+
+    let log_path = format!("{}/state/launch.log", temp_dir);
+    AuditLog::new(&log_path).max_size(1).max_files(2).enable();
+    for _ in 0..4 {
+        assert!(entry.execute_with_files(&["/tmp/a file.txt"]).is_err());
+    }
+    AuditLog::disable();
+    assert!(entry.execute().is_err());
+
+    let content = fs::read_to_string(&log_path).unwrap();
+    let fields: Vec<&str> = content.trim_end_matches('\n').split('\t').collect();
+    assert_eq!(fields.len(), 5);
+    assert!(fields[0].parse::<Timestamp>().is_ok());
+    assert!(fields[1].ends_with("audit.desktop"));
+    assert!(fields[2].starts_with("error: Validation failed"));
+    // Nothing was spawned, so there is no command line
+    assert_eq!(fields[3], "");
+    assert_eq!(fields[4], "");
+
+    // Launch IDs are recorded so launches can be traced back to what started them
+    AuditLog::new(&log_path).enable();
+    assert!(entry.launch_with(&LaunchOptions::new().launch_id("menu-click-7")).is_err());
+    AuditLog::disable();
+    let content = fs::read_to_string(&log_path).unwrap();
+    let last = content.lines().last().unwrap();
+    assert_eq!(last.split('\t').nth(4), Some("menu-click-7"));
+
+    // Rotation keeps at most two old files
+    assert!(fs::metadata(format!("{}.1", log_path)).is_ok());
+    assert!(fs::metadata(format!("{}.2", log_path)).is_ok());
+    assert!(fs::metadata(format!("{}.3", log_path)).is_err());
+
+    fs::remove_dir_all(temp_dir).ok();
+}
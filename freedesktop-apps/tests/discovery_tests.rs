@@ -0,0 +1,1429 @@
+use freedesktop_apps::{AppPolicy, ApplicationEntry, AutostartHandling, Desktop, DuplicateStrategy, EntryChange, EntryQuery, ExecuteError, LaunchAllOptions, MenuSection, MimeAssociations, MimeInfoCache, ParseError, RefreshPolicy, Registry, RegistryEvent, Role, Session, SharedRegistry, ValidationIssue, Visibility, InstallationIssue, applications_for_mime, autostart_report, default_application_for, default_handler_for_scheme, defaults_list_paths, handlers_for_scheme, migrate_defaults_list, mimeapps_list_paths_for, opener_for, OpenError, scheme_mime_type, set_default_application, validate_dir, verify_installation};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Discovery reads XDG_DATA_HOME and XDG_DATA_DIRS, so tests that change
+// them must not run concurrently
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+fn write_entry(dir: &Path, file: &str, content: &str) {
+    let path = dir.join("applications").join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+#[test]
+fn test_hidden_entry_shadows_lower_precedence() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_hidden");
+    let home = root.join("home");
+    let system = root.join("system");
+
+    let app = "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor";
+    write_entry(&system, "editor.desktop", app);
+    write_entry(&system, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer");
+    write_entry(&home, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor\nHidden=true");
+    // A Hidden entry with lower precedence doesn't remove the user's copy
+    write_entry(&home, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=My Viewer\nExec=viewer");
+    write_entry(&system, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\nHidden=true");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+
+    let entries = ApplicationEntry::all();
+    let ids: Vec<String> = entries.iter().filter_map(|e| e.id()).collect();
+    assert!(!ids.contains(&"editor".to_string()));
+    assert_eq!(ids, vec!["viewer".to_string()]);
+    assert_eq!(entries[0].name(), Some("My Viewer".to_string()));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_duplicate_ids_use_highest_precedence() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_precedence");
+    let home = root.join("home");
+    let first = root.join("first");
+    let second = root.join("second");
+
+    write_entry(&second, "app.desktop", "[Desktop Entry]\nType=Application\nName=Second\nExec=app");
+    write_entry(&first, "app.desktop", "[Desktop Entry]\nType=Application\nName=First\nExec=app");
+    write_entry(&second, "tool.desktop", "[Desktop Entry]\nType=Application\nName=Tool\nExec=tool");
+    write_entry(&home, "tool.desktop", "[Desktop Entry]\nType=Application\nName=My Tool\nExec=tool");
+    write_entry(&second, "vendor/nested.desktop", "[Desktop Entry]\nType=Application\nName=Nested\nExec=nested");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", format!("{}:{}", first.display(), second.display()));
+
+    let names: Vec<(String, String)> = ApplicationEntry::all()
+        .iter()
+        .map(|e| (e.id().unwrap(), e.name().unwrap()))
+        .collect();
+    assert_eq!(names.len(), 3);
+    assert!(names.contains(&("app".to_string(), "First".to_string())));
+    assert!(names.contains(&("tool".to_string(), "My Tool".to_string())));
+    assert!(names.contains(&("vendor-nested".to_string(), "Nested".to_string())));
+
+    let shadowed: Vec<String> = ApplicationEntry::all_including_shadowed()
+        .iter()
+        .filter(|e| e.id().as_deref() == Some("app"))
+        .map(|e| e.name().unwrap())
+        .collect();
+    assert_eq!(shadowed, vec!["First".to_string(), "Second".to_string()]);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_session_save_and_restore() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_session");
+    let data = root.join("data");
+
+    write_entry(&data, "org.example.Editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=example-editor\nTryExec=/nonexistent/example-editor");
+    write_entry(&data, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\nStartupWMClass=ImageViewer");
+    write_entry(&data, "helper.desktop", "[Desktop Entry]\nType=Application\nName=Helper\nExec=example-editor\nNoDisplay=true");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let registry = Registry::load();
+
+    let session = Session::from_windows(&registry, &[(Some("example-editor"), None), (None, Some("ImageViewer")), (Some("terminal"), None)]);
+    let mut ids = session.ids().to_vec();
+    ids.sort();
+    assert_eq!(ids, vec!["org.example.Editor".to_string(), "viewer".to_string()]);
+
+    let session = Session::new(vec!["org.example.Editor".to_string(), "org.example.Editor".to_string()]);
+    let file = root.join("state/session");
+    session.save_to(&file).unwrap();
+    assert_eq!(Session::load_from(&file).unwrap(), session);
+
+    let results = session.restore(&registry, &LaunchAllOptions::new());
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0].result, Err(ExecuteError::ValidationFailed(_))));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_targeted_lookups() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_lookup");
+    let home = root.join("home");
+    let system = root.join("system");
+
+    write_entry(&system, "org/example/Viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\nMimeType=image/png;image/jpeg;");
+    write_entry(&system, "text-editor.desktop", "[Desktop Entry]\nType=Application\nName=Text Editor\nExec=editor\nMimeType=text/plain;");
+    write_entry(&system, "removed.desktop", "[Desktop Entry]\nType=Application\nName=Removed\nExec=removed");
+    write_entry(&home, "removed.desktop", "[Desktop Entry]\nType=Application\nName=Removed\nExec=removed\nHidden=true");
+    // A copy that can't be parsed doesn't hide the next one
+    write_entry(&home, "text-editor.desktop", "Name=No group");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+
+    let viewer = ApplicationEntry::find_by_id("org-example-Viewer.desktop").unwrap();
+    assert_eq!(viewer.name(), Some("Viewer".to_string()));
+    assert_eq!(viewer.id(), Some("org-example-Viewer".to_string()));
+    let editor = ApplicationEntry::find_by_id("text-editor").unwrap();
+    assert_eq!(editor.name(), Some("Text Editor".to_string()));
+    assert!(ApplicationEntry::find_by_id("removed").is_none());
+    assert!(ApplicationEntry::find_by_id("missing").is_none());
+
+    let editors = ApplicationEntry::find_by_name("text EDITOR");
+    assert_eq!(editors.len(), 1);
+
+    let images: Vec<String> = ApplicationEntry::find_by_mime_type("image/jpeg")
+        .iter()
+        .filter_map(|e| e.id())
+        .collect();
+    assert_eq!(images, vec!["org-example-Viewer".to_string()]);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_lazy_iteration_matches_all() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_iter");
+    let home = root.join("home");
+    let system = root.join("system");
+
+    write_entry(&system, "a.desktop", "[Desktop Entry]\nType=Application\nName=A\nExec=a");
+    write_entry(&system, "b.desktop", "[Desktop Entry]\nType=Application\nName=B\nExec=b");
+    write_entry(&system, "sub/c.desktop", "[Desktop Entry]\nType=Application\nName=C\nExec=c");
+    write_entry(&home, "b.desktop", "[Desktop Entry]\nType=Application\nName=B\nExec=b\nHidden=true");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+
+    let iterated: Vec<Option<String>> = ApplicationEntry::iter().map(|e| e.id()).collect();
+    let all: Vec<Option<String>> = ApplicationEntry::all().iter().map(|e| e.id()).collect();
+    assert_eq!(iterated, all);
+    assert_eq!(iterated, vec![Some("a".to_string()), Some("sub-c".to_string())]);
+
+    #[cfg(feature = "rayon")]
+    {
+        let parallel: Vec<Option<String>> = ApplicationEntry::all_parallel().iter().map(|e| e.id()).collect();
+        assert_eq!(parallel, all);
+    }
+
+    let first = ApplicationEntry::iter().find(|e| e.name().as_deref() == Some("C"));
+    assert_eq!(first.and_then(|e| e.id()), Some("sub-c".to_string()));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_recently_added_entries() {
+    use freedesktop_core::time::Timestamp;
+    use std::time::{Duration, SystemTime};
+
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_recent");
+    let data = root.join("data");
+
+    write_entry(&data, "old.desktop", "[Desktop Entry]\nType=Application\nName=Old\nExec=old");
+    write_entry(&data, "new.desktop", "[Desktop Entry]\nType=Application\nName=New\nExec=new");
+    // Pretend the second file comes from a later install
+    let later = SystemTime::now() + Duration::from_secs(3600);
+    fs::File::options()
+        .write(true)
+        .open(data.join("applications/new.desktop"))
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let registry = Registry::load();
+
+    let since = Timestamp::from(SystemTime::now() - Duration::from_secs(60));
+    let ids: Vec<String> = registry.recently_added(since).iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["new".to_string(), "old".to_string()]);
+
+    let since = Timestamp::from(later - Duration::from_secs(1));
+    assert_eq!(registry.recently_added(since).len(), 1);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_watch_reports_changes() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_watch");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    let apps = data.join("applications");
+    write_entry(&data, "existing.desktop", "[Desktop Entry]\nType=Application\nName=Existing\nExec=existing");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let mut registry = Registry::load();
+    let mut watcher = registry.watch().unwrap();
+
+    fs::write(apps.join("new.desktop"), "[Desktop Entry]\nType=Application\nName=New\nExec=new").unwrap();
+    let added = watcher.next_event().unwrap();
+    assert_eq!(added, RegistryEvent::Added("new".to_string()));
+    registry.apply_event(&added);
+    assert!(registry.get("new").is_some());
+
+    fs::write(apps.join("existing.desktop"), "[Desktop Entry]\nType=Application\nName=Changed\nExec=existing").unwrap();
+    let modified = watcher.next_event().unwrap();
+    assert_eq!(modified, RegistryEvent::Modified("existing".to_string()));
+    registry.apply_event(&modified);
+    assert_eq!(registry.get("existing").unwrap().name(), Some("Changed".to_string()));
+
+    fs::create_dir_all(apps.join("vendor")).unwrap();
+    fs::write(apps.join("vendor/tool.desktop"), "[Desktop Entry]\nType=Application\nName=Tool\nExec=tool").unwrap();
+    assert_eq!(watcher.next_event().unwrap(), RegistryEvent::Added("vendor-tool".to_string()));
+
+    // Links are never written, so they are reported as soon as they appear
+    let target = root.join("linked.desktop");
+    fs::write(&target, "[Desktop Entry]\nType=Application\nName=Linked\nExec=linked").unwrap();
+    std::os::unix::fs::symlink(&target, apps.join("symlinked.desktop")).unwrap();
+    assert_eq!(watcher.next_event().unwrap(), RegistryEvent::Added("symlinked".to_string()));
+    fs::hard_link(&target, apps.join("hardlinked.desktop")).unwrap();
+    assert_eq!(watcher.next_event().unwrap(), RegistryEvent::Added("hardlinked".to_string()));
+
+    fs::remove_file(apps.join("new.desktop")).unwrap();
+    let removed = watcher.next_event().unwrap();
+    assert_eq!(removed, RegistryEvent::Removed("new".to_string()));
+    registry.apply_event(&removed);
+    assert!(registry.get("new").is_none());
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_apply_event_uses_registry_dirs() {
+    let root = Path::new("/tmp/test_discovery_apply_dirs");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    let apps = data.join("applications");
+    write_entry(&data, "first.desktop", "[Desktop Entry]\nType=Application\nName=First\nExec=first");
+
+    let mut registry = Registry::load_from(std::slice::from_ref(&apps));
+    assert!(registry.get("first").is_some());
+    let mut watcher = registry.watch().unwrap();
+
+    write_entry(&data, "second.desktop", "[Desktop Entry]\nType=Application\nName=Second\nExec=second");
+    let added = watcher.next_event().unwrap();
+    assert_eq!(added, RegistryEvent::Added("second".to_string()));
+    registry.apply_event(&added);
+    assert_eq!(registry.get("second").and_then(|e| e.id()), Some("second".to_string()));
+
+    // A rescan after lost events reads every entry again
+    fs::remove_file(apps.join("first.desktop")).unwrap();
+    write_entry(&data, "third.desktop", "[Desktop Entry]\nType=Application\nName=Third\nExec=third");
+    registry.apply_event(&RegistryEvent::Rescan);
+    let mut ids: Vec<String> = registry.entries().iter().filter_map(|e| e.id()).collect();
+    ids.sort();
+    assert_eq!(ids, ["second", "third"]);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_entry_watch_follows_overrides() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_entry_watch");
+    fs::remove_dir_all(root).ok();
+    let home = root.join("home");
+    let system = root.join("system");
+    fs::create_dir_all(home.join("applications")).unwrap();
+    write_entry(&system, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor");
+    write_entry(&system, "other.desktop", "[Desktop Entry]\nType=Application\nName=Other\nExec=other");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+    let entry = ApplicationEntry::find_by_id("editor").unwrap();
+    let mut watcher = entry.watch().unwrap();
+    assert_eq!(watcher.id(), "editor");
+    assert_eq!(watcher.current().unwrap().name(), Some("Editor".to_string()));
+
+    let name = |change: EntryChange| match change {
+        EntryChange::Changed(entry) => entry.name(),
+        EntryChange::Removed => None,
+    };
+
+    // Other entries and unchanged rewrites aren't reported
+    write_entry(&system, "other.desktop", "[Desktop Entry]\nType=Application\nName=Changed\nExec=other");
+    write_entry(&system, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor");
+    assert!(watcher.next_change_timeout(Duration::from_millis(200)).unwrap().is_none());
+
+    write_entry(&home, "editor.desktop", "[Desktop Entry]\nType=Application\nName=My Editor\nExec=editor");
+    assert_eq!(name(watcher.next_change().unwrap()), Some("My Editor".to_string()));
+
+    // The shadowed system copy no longer matters
+    write_entry(&system, "editor.desktop", "[Desktop Entry]\nType=Application\nName=New Editor\nExec=editor");
+    assert!(watcher.next_change_timeout(Duration::from_millis(200)).unwrap().is_none());
+
+    fs::remove_file(home.join("applications/editor.desktop")).unwrap();
+    assert_eq!(name(watcher.next_change().unwrap()), Some("New Editor".to_string()));
+
+    // A broken override falls back to the system copy, as lookups do
+    write_entry(&home, "editor.desktop", "[Desktop Entry]\nName=Broken");
+    assert!(watcher.next_change_timeout(Duration::from_millis(200)).unwrap().is_none());
+    assert_eq!(ApplicationEntry::find_by_id("editor").unwrap().name(), Some("New Editor".to_string()));
+
+    write_entry(&home, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor\nHidden=true");
+    assert!(matches!(watcher.next_change().unwrap(), EntryChange::Removed));
+    assert!(watcher.current().is_none());
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_mimeapps_precedence() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_mimeapps");
+    fs::remove_dir_all(root).ok();
+    let config_home = root.join("config");
+    let config_dir = root.join("xdg");
+    let data = root.join("data");
+    let app = |name: &str, types: &str| format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\nMimeType={}", name, name, types);
+    write_entry(&data, "viewer.desktop", &app("viewer", "image/png;"));
+    write_entry(&data, "editor.desktop", &app("editor", "image/png;text/plain;"));
+    write_entry(&data, "paint.desktop", &app("paint", "image/jpeg;"));
+
+    let write = |path: PathBuf, content: &str| {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    };
+    write(
+        config_home.join("kde-mimeapps.list"),
+        "[Default Applications]\nimage/png=missing.desktop;editor.desktop;\n",
+    );
+    write(
+        config_home.join("mimeapps.list"),
+        "[Added Associations]\nimage/png=paint.desktop;\n[Removed Associations]\nimage/png=viewer.desktop;\n",
+    );
+    // Less important files can't undo what the user added
+    write(
+        config_dir.join("mimeapps.list"),
+        "[Default Applications]\nImage/PNG=viewer.desktop\ntext/plain=viewer.desktop\n[Removed Associations]\nimage/png=paint.desktop;\n",
+    );
+    write(data.join("applications/mimeapps.list"), "[Default Applications]\nimage/jpeg=paint.desktop\n");
+
+    std::env::set_var("XDG_CONFIG_HOME", &config_home);
+    std::env::set_var("XDG_CONFIG_DIRS", &config_dir);
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+
+    let paths = mimeapps_list_paths_for("KDE:GNOME");
+    assert_eq!(
+        paths,
+        vec![
+            config_home.join("kde-mimeapps.list"),
+            config_home.join("mimeapps.list"),
+            config_dir.join("mimeapps.list"),
+            data.join("applications/mimeapps.list"),
+        ]
+    );
+    assert_eq!(mimeapps_list_paths_for("").len(), 3);
+
+    let associations = MimeAssociations::from_paths(&paths);
+    assert_eq!(associations.default_applications("image/png"), ["missing", "editor", "viewer"]);
+    assert_eq!(associations.added_associations("image/png"), ["paint"]);
+    assert_eq!(associations.removed_associations("IMAGE/PNG"), ["viewer", "paint"]);
+
+    let id = |entry: Option<ApplicationEntry>| entry.and_then(|e| e.id());
+    // missing isn't installed, so the next default is used
+    assert_eq!(id(associations.default_application("image/png")), Some("editor".to_string()));
+    // viewer doesn't handle text/plain, so the associations decide
+    assert_eq!(id(associations.default_application("text/plain")), Some("editor".to_string()));
+    assert_eq!(id(associations.default_application("image/jpeg")), Some("paint".to_string()));
+    let ids: Vec<String> = associations.applications_for("image/png").into_iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["paint".to_string(), "editor".to_string()]);
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_default_application_falls_back() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_default_app");
+    fs::remove_dir_all(root).ok();
+    let config = root.join("config");
+    let data = root.join("data");
+    write_entry(&data, "gone.desktop", "[Desktop Entry]\nType=Application\nName=Gone\nExec=gone\nTryExec=/nonexistent/gone\nMimeType=text/html;");
+    write_entry(&data, "link.desktop", "[Desktop Entry]\nType=Link\nName=Link\nURL=https://example.com\nMimeType=text/html;");
+    write_entry(&data, "browser.desktop", "[Desktop Entry]\nType=Application\nName=Browser\nExec=browser %u\nMimeType=text/html;");
+    write_entry(&data, "reader.desktop", "[Desktop Entry]\nType=Application\nName=Reader\nExec=reader %u\nMimeType=text/html;");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("gnome-mimeapps.list"),
+        "[Default Applications]\ntext/html=uninstalled.desktop;gone.desktop;link.desktop;reader.desktop;\n",
+    )
+    .unwrap();
+    fs::write(config.join("mimeapps.list"), "[Default Applications]\ntext/html=browser.desktop;\n").unwrap();
+
+    std::env::set_var("XDG_CONFIG_HOME", &config);
+    std::env::set_var("XDG_CONFIG_DIRS", root.join("missing"));
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let id = |entry: Option<ApplicationEntry>| entry.and_then(|e| e.id());
+
+    // The desktop-specific list comes first, and its unusable candidates are skipped
+    std::env::set_var("XDG_CURRENT_DESKTOP", "GNOME");
+    assert_eq!(id(default_application_for("text/html")), Some("reader".to_string()));
+    std::env::set_var("XDG_CURRENT_DESKTOP", "KDE");
+    assert_eq!(id(default_application_for("text/html")), Some("browser".to_string()));
+    // Without a default, an application that handles the type is picked
+    fs::remove_file(config.join("mimeapps.list")).unwrap();
+    let fallback = id(default_application_for("text/html")).unwrap();
+    assert!(fallback == "browser" || fallback == "reader");
+    assert_eq!(default_application_for("image/png").map(|e| e.id()), None);
+
+    let ids: Vec<String> = MimeAssociations::load().applications_for("text/html").into_iter().filter_map(|e| e.id()).collect();
+    assert!(!ids.contains(&"gone".to_string()) && !ids.contains(&"link".to_string()));
+    assert_eq!(ids.len(), 2);
+
+    std::env::remove_var("XDG_CURRENT_DESKTOP");
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_legacy_defaults_list() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_defaults_list");
+    fs::remove_dir_all(root).ok();
+    let config = root.join("config");
+    let home = root.join("home");
+    let system = root.join("system");
+    write_entry(&system, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\nMimeType=image/png;application/pdf;");
+    write_entry(&system, "reader.desktop", "[Desktop Entry]\nType=Application\nName=Reader\nExec=reader\nMimeType=application/pdf;");
+    fs::create_dir_all(home.join("applications")).unwrap();
+    fs::write(
+        home.join("applications/defaults.list"),
+        "[Default Applications]\nimage/png=viewer.desktop\napplication/pdf=viewer.desktop\n",
+    )
+    .unwrap();
+    fs::write(system.join("applications/defaults.list"), "[Default Applications]\napplication/pdf=reader.desktop\n").unwrap();
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("mimeapps.list"), "[Default Applications]\nApplication/PDF=reader.desktop\n").unwrap();
+
+    std::env::set_var("XDG_CONFIG_HOME", &config);
+    std::env::set_var("XDG_CONFIG_DIRS", root.join("missing"));
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+    std::env::remove_var("XDG_CURRENT_DESKTOP");
+
+    assert_eq!(
+        defaults_list_paths(),
+        vec![home.join("applications/defaults.list"), system.join("applications/defaults.list")]
+    );
+    // defaults.list only fills in after mimeapps.list
+    let associations = MimeAssociations::load();
+    assert_eq!(associations.default_applications("application/pdf"), ["reader", "viewer"]);
+    assert_eq!(associations.default_applications("image/png"), ["viewer"]);
+
+    assert_eq!(migrate_defaults_list().unwrap(), vec!["image/png".to_string()]);
+    let migrated = fs::read_to_string(config.join("mimeapps.list")).unwrap();
+    assert_eq!(migrated, "[Default Applications]\nApplication/PDF=reader.desktop\nimage/png=viewer.desktop\n");
+    assert!(home.join("applications/defaults.list").exists());
+    assert!(migrate_defaults_list().unwrap().is_empty());
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_set_default_application() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_set_default");
+    fs::remove_dir_all(root).ok();
+    let config = root.join("config");
+    let data = root.join("data");
+    write_entry(&data, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\nMimeType=image/png;");
+    write_entry(&data, "paint.desktop", "[Desktop Entry]\nType=Application\nName=Paint\nExec=paint\nMimeType=image/png;");
+
+    std::env::set_var("XDG_CONFIG_HOME", &config);
+    std::env::set_var("XDG_CONFIG_DIRS", root.join("missing"));
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    std::env::remove_var("XDG_CURRENT_DESKTOP");
+
+    // The file and its directory are created when missing
+    set_default_application("image/png", "viewer").unwrap();
+    assert_eq!(
+        fs::read_to_string(config.join("mimeapps.list")).unwrap(),
+        "[Default Applications]\nimage/png=viewer.desktop\n\n[Added Associations]\nimage/png=viewer.desktop;\n"
+    );
+
+    fs::write(
+        config.join("mimeapps.list"),
+        "[Default Applications]\ntext/plain=editor.desktop\nImage/PNG=viewer.desktop\n\n[Added Associations]\nimage/png=viewer.desktop;paint.desktop;\n\n[Removed Associations]\nimage/png=paint.desktop;\ntext/plain=viewer.desktop;\n\n[X-Other]\nkey=value\n",
+    )
+    .unwrap();
+    set_default_application("image/png", "paint.desktop").unwrap();
+    assert_eq!(
+        fs::read_to_string(config.join("mimeapps.list")).unwrap(),
+        "[Default Applications]\ntext/plain=editor.desktop\nImage/PNG=paint.desktop\n\n[Added Associations]\nimage/png=paint.desktop;viewer.desktop;\n\n[Removed Associations]\ntext/plain=viewer.desktop;\n\n[X-Other]\nkey=value\n"
+    );
+    assert_eq!(default_application_for("image/png").and_then(|e| e.id()), Some("paint".to_string()));
+
+    assert!(set_default_application("png", "paint").is_err());
+    assert!(set_default_application("image/png", ".desktop").is_err());
+    let leftovers = fs::read_dir(&config).unwrap().count();
+    assert_eq!(leftovers, 1);
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_ranked_applications_for_mime() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_ranked");
+    fs::remove_dir_all(root).ok();
+    let config = root.join("config");
+    let data = root.join("data");
+    let app = |name: &str, types: &str| format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\nMimeType={}", name, name, types);
+    write_entry(&data, "ide.desktop", &app("ide", "text/x-csrc;"));
+    write_entry(&data, "cpp.desktop", &app("cpp", "text/x-c++src;"));
+    write_entry(&data, "notepad.desktop", &app("notepad", "text/plain;"));
+    write_entry(&data, "pager.desktop", &app("pager", "text/plain;"));
+    write_entry(&data, "hex.desktop", &app("hex", "application/octet-stream;"));
+    write_entry(&data, "mdview.desktop", &app("mdview", "text/markdown;"));
+    write_entry(&data, "mdedit.desktop", &app("mdedit", "text/x-markdown;"));
+    fs::create_dir_all(data.join("mime")).unwrap();
+    fs::write(data.join("mime/subclasses"), "text/x-c++src text/x-csrc\ntext/x-csrc text/plain\n").unwrap();
+    fs::write(data.join("mime/aliases"), "text/x-markdown text/markdown\ntext/x-web-markdown text/markdown\n").unwrap();
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("mimeapps.list"),
+        "[Default Applications]\ntext/x-c++src=notepad.desktop\n[Added Associations]\ntext/x-c++src=notepad.desktop;\n[Removed Associations]\ntext/x-c++src=pager.desktop;\n",
+    )
+    .unwrap();
+
+    std::env::set_var("XDG_CONFIG_HOME", &config);
+    std::env::set_var("XDG_CONFIG_DIRS", root.join("missing"));
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    std::env::remove_var("XDG_CURRENT_DESKTOP");
+
+    let ids = |mime: &str| -> Vec<String> { applications_for_mime(mime).into_iter().filter_map(|e| e.id()).collect() };
+    // Default first, then the type's own handlers, then those of its parents; pager was removed
+    assert_eq!(ids("text/x-c++src"), vec!["notepad", "cpp", "ide"]);
+    let mut csrc = ids("text/x-csrc");
+    assert_eq!(csrc.remove(0), "ide");
+    csrc.sort();
+    assert_eq!(csrc, vec!["notepad", "pager"]);
+    // An alias finds the handlers of both names, then those of text/plain
+    let markdown = ids("text/x-markdown");
+    assert_eq!(markdown[..2], ["mdedit", "mdview"]);
+    assert_eq!(markdown.len(), 4);
+    assert_eq!(ids("text/markdown")[..2], ["mdview", "mdedit"]);
+    // Defaults and plain lookups resolve aliases too, though no entry lists this one
+    let default = |mime: &str| default_application_for(mime).and_then(|e| e.id());
+    assert_eq!(default("text/x-web-markdown"), Some("mdview".to_string()));
+    assert_eq!(default("text/x-markdown"), Some("mdedit".to_string()));
+    let associations = MimeAssociations::load();
+    let web: Vec<String> = associations.applications_for("text/x-web-markdown").into_iter().filter_map(|e| e.id()).collect();
+    assert_eq!(web, ["mdview", "mdedit"]);
+    // Any text type falls back to text/plain handlers
+    assert_eq!(ids("text/x-log").len(), 2);
+    assert!(ids("image/png").is_empty());
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_desktop_owns_watcher() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_desktop");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    let apps = data.join("applications");
+    write_entry(&data, "existing.desktop", "[Desktop Entry]\nType=Application\nName=Existing\nExec=existing");
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+
+    let mut desktop = Desktop::new();
+    assert!(desktop.tasks().is_empty());
+    let policy = RefreshPolicy::new().quiet_period(Duration::from_millis(50));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let first = sender.clone();
+    desktop.watch_registry(policy, move |events| { let _ = first.send(("first", events.len())); }).unwrap();
+    // Watching again replaces the running watcher instead of adding one
+    desktop.watch_registry(policy, move |events| { let _ = sender.send(("second", events.len())); }).unwrap();
+    assert_eq!(desktop.tasks(), vec!["registry-watcher"]);
+
+    // Files other than desktop files don't hold up the watcher
+    fs::write(apps.join("notes.txt"), "not an entry").unwrap();
+    fs::write(apps.join("new.desktop"), "[Desktop Entry]\nType=Application\nName=New\nExec=new").unwrap();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), ("second", 1));
+    assert!(desktop.registry().get("new").is_some());
+    assert!(desktop.task_error("registry-watcher").is_none());
+
+    // Stopping wakes the idle watcher at once
+    let stopping = std::time::Instant::now();
+    desktop.shutdown();
+    assert!(stopping.elapsed() < Duration::from_secs(1));
+    assert!(desktop.tasks().is_empty());
+    fs::remove_file(apps.join("new.desktop")).unwrap();
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    assert!(desktop.registry().get("new").is_some());
+
+    desktop.reload();
+    assert!(desktop.registry().get("new").is_none());
+    let mime = desktop.mime_database();
+    assert!(Arc::ptr_eq(&mime, &desktop.mime_database()));
+    desktop.reload();
+    assert!(!Arc::ptr_eq(&mime, &desktop.mime_database()));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_mimeinfo_cache_with_fallback() {
+    let root = std::env::temp_dir().join(format!("fd-apps-mimeinfo-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let cached = root.join("cached");
+    let stale = root.join("stale");
+    let uncached = root.join("uncached");
+    for dir in [&cached, &stale, &uncached] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    let viewer = "[Desktop Entry]\nType=Application\nName=Viewer\nExec=true\nMimeType=image/png;\n";
+    fs::write(cached.join("viewer.desktop"), viewer).unwrap();
+    fs::write(cached.join("ghost.desktop"), viewer.replace("image/png;", "text/plain;")).unwrap();
+    fs::write(
+        cached.join("mimeinfo.cache"),
+        "[MIME Cache]\nimage/png=viewer.desktop;deleted.desktop;ghost.desktop;\n",
+    )
+    .unwrap();
+    fs::write(stale.join("mimeinfo.cache"), "[MIME Cache]\nimage/png=old.desktop;\n").unwrap();
+    let editor = "[Desktop Entry]\nType=Application\nName=Editor\nExec=true\nMimeType=image/png;\n";
+    fs::write(stale.join("editor.desktop"), editor).unwrap();
+    fs::write(uncached.join("paint.desktop"), editor.replace("Editor", "Paint")).unwrap();
+    let past = std::time::SystemTime::now() - Duration::from_secs(60);
+    let set_modified = |path: &Path, time| fs::File::open(path).unwrap().set_modified(time).unwrap();
+    set_modified(&stale.join("mimeinfo.cache"), past);
+    for file in [cached.join("viewer.desktop"), cached.join("ghost.desktop")] {
+        set_modified(&file, past - Duration::from_secs(10));
+    }
+    set_modified(&cached.join("mimeinfo.cache"), past);
+    set_modified(&cached, past);
+
+    let cache = MimeInfoCache::load_from(&[cached.clone(), stale.clone(), uncached.clone()]);
+    assert_eq!(cache.stale_dirs(), vec![stale.as_path(), uncached.as_path()]);
+    assert_eq!(cache.ids_for("IMAGE/PNG"), vec!["viewer", "deleted", "ghost", "editor", "paint"]);
+    let ids: Vec<String> = cache.applications_for("image/png").iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["viewer", "editor", "paint"]);
+
+    // A file installed with its original, older time still makes the cache stale
+    fs::write(cached.join("archived.desktop"), viewer.replace("Viewer", "Archived")).unwrap();
+    set_modified(&cached.join("archived.desktop"), past - Duration::from_secs(3600));
+    let cache = MimeInfoCache::load_from(std::slice::from_ref(&cached));
+    assert_eq!(cache.stale_dirs(), vec![cached.as_path()]);
+    assert!(cache.ids_for("image/png").contains(&"archived"));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_scheme_handlers() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_schemes");
+    fs::remove_dir_all(root).ok();
+    let config = root.join("config");
+    let data = root.join("data");
+    let app = |name: &str, types: &str| format!("[Desktop Entry]\nType=Application\nName={}\nExec={} %u\nMimeType={}", name, name, types);
+    write_entry(&data, "browser.desktop", &app("browser", "x-scheme-handler/http;x-scheme-handler/https;text/html;"));
+    write_entry(&data, "other.desktop", &app("other", "x-scheme-handler/https;"));
+    write_entry(&data, "torrent.desktop", &app("torrent", "x-scheme-handler/magnet;"));
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("mimeapps.list"), "[Default Applications]\nx-scheme-handler/https=other.desktop\n").unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &config);
+    std::env::set_var("XDG_CONFIG_DIRS", root.join("missing"));
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    std::env::remove_var("XDG_CURRENT_DESKTOP");
+
+    assert_eq!(scheme_mime_type("HTTPS:").as_deref(), Some("x-scheme-handler/https"));
+    assert_eq!(scheme_mime_type("web+app").as_deref(), Some("x-scheme-handler/web+app"));
+    assert_eq!(scheme_mime_type("1http"), None);
+    assert_eq!(scheme_mime_type("a/b"), None);
+    assert_eq!(scheme_mime_type(""), None);
+
+    let id = |entry: Option<ApplicationEntry>| entry.and_then(|e| e.id());
+    assert_eq!(id(default_handler_for_scheme("https")).as_deref(), Some("other"));
+    assert_eq!(id(default_handler_for_scheme("http")).as_deref(), Some("browser"));
+    assert_eq!(id(default_handler_for_scheme("magnet")).as_deref(), Some("torrent"));
+    assert!(default_handler_for_scheme("gopher").is_none());
+    assert!(default_handler_for_scheme("not a scheme").is_none());
+
+    let ids: Vec<String> = handlers_for_scheme("https").into_iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["other", "browser"]);
+    assert!(handlers_for_scheme("").is_empty());
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_opener_for_files_and_urls() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_open");
+    fs::remove_dir_all(root).ok();
+    let config = root.join("config");
+    let data = root.join("data");
+    let app = |name: &str, exec: &str, types: &str| format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\nMimeType={}", name, exec, types);
+    write_entry(&data, "viewer.desktop", &app("viewer", "true %f", "application/pdf;"));
+    write_entry(&data, "browser.desktop", &app("browser", "true %u", "x-scheme-handler/https;"));
+    fs::create_dir_all(data.join("mime")).unwrap();
+    fs::write(data.join("mime/globs2"), "50:application/pdf:*.pdf\n").unwrap();
+    fs::create_dir_all(&config).unwrap();
+    let pdf = root.join("my report.pdf");
+    fs::write(&pdf, "%PDF-1.7").unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &config);
+    std::env::set_var("XDG_CONFIG_DIRS", root.join("missing"));
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    std::env::remove_var("XDG_CURRENT_DESKTOP");
+
+    let (entry, url) = opener_for(pdf.to_str().unwrap()).unwrap();
+    assert_eq!(entry.id().as_deref(), Some("viewer"));
+    assert_eq!(url, "file:///tmp/test_discovery_open/my%20report.pdf");
+    // Entries taking files get the path back
+    let (_, args) = entry.prepare_command(&[], &[&url]).unwrap();
+    assert_eq!(args, vec![pdf.to_string_lossy().to_string()]);
+    assert_eq!(opener_for(&url).unwrap().1, url);
+
+    let (entry, url) = opener_for("https://example.com/a?b=c").unwrap();
+    assert_eq!(entry.id().as_deref(), Some("browser"));
+    assert_eq!(url, "https://example.com/a?b=c");
+
+    assert!(matches!(opener_for("gopher://example.com"), Err(OpenError::NoApplication(t)) if t == "x-scheme-handler/gopher"));
+    assert!(matches!(opener_for(root.join("missing.pdf").to_str().unwrap()), Err(OpenError::Io(_))));
+    let notes = root.join("notes.txt");
+    fs::write(&notes, "plain").unwrap();
+    assert!(matches!(opener_for(notes.to_str().unwrap()), Err(OpenError::NoApplication(t)) if t == "text/plain"));
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_can_open_files() {
+    let root = Path::new("/tmp/test_discovery_can_open");
+    fs::remove_dir_all(root).ok();
+    let mime = root.join("mime");
+    fs::create_dir_all(&mime).unwrap();
+    fs::write(mime.join("globs2"), "50:text/x-csrc:*.c\n50:text/markdown:*.md\n50:image/png:*.png\n").unwrap();
+    fs::write(mime.join("aliases"), "text/x-markdown text/markdown\n").unwrap();
+    let database = freedesktop_mime::MimeDatabase::load_text_from(&[mime]);
+    let file = |name: &str| {
+        let path = root.join(name);
+        fs::write(&path, "content").unwrap();
+        path
+    };
+    let (source, markdown, image) = (file("main.c"), file("README.md"), file("logo.png"));
+    let entry = |name: &str, types: &str| {
+        let path = root.join(format!("{}.desktop", name));
+        fs::write(&path, format!("[Desktop Entry]\nType=Application\nName={}\nExec=true\nMimeType={}", name, types)).unwrap();
+        ApplicationEntry::try_from_path(path).unwrap()
+    };
+
+    // text/plain handlers open every text type
+    let editor = entry("editor", "text/plain;");
+    assert!(editor.can_open_with(&source, &database));
+    assert!(editor.can_open_with(&markdown, &database));
+    assert!(!editor.can_open_with(&image, &database));
+    // Either name of an aliased type matches
+    let preview = entry("preview", "TEXT/X-MARKDOWN;");
+    assert!(preview.can_open_with(&markdown, &database));
+    assert!(!preview.can_open_with(&source, &database));
+    assert!(preview.can_open_type("text/markdown", &database));
+    // application/octet-stream isn't declared a parent here
+    let hex = entry("hex", "application/octet-stream;");
+    assert!(!hex.can_open_with(&image, &database));
+    assert!(!entry("plain", "").can_open_with(&source, &database));
+    assert!(!editor.can_open_with(root.join("missing.c"), &database));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_localized_menu_section_names() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_sections");
+    let data = root.join("data");
+    let directories = data.join("desktop-directories");
+    fs::create_dir_all(&directories).unwrap();
+    fs::write(
+        directories.join("Utility.directory"),
+        "[Desktop Entry]\nType=Directory\nName=Accessories\nName[de]=Werkzeuge\n",
+    )
+    .unwrap();
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+
+    // Installed .directory files win over the built-in table
+    assert_eq!(MenuSection::Accessories.localized_name(Some("de_DE.UTF-8")), "Werkzeuge");
+    assert_eq!(MenuSection::Accessories.localized_name(Some("fr_FR")), "Accessories");
+    assert_eq!(MenuSection::Games.localized_name(Some("de_AT")), "Spiele");
+    assert_eq!(MenuSection::Multimedia.localized_name(Some("pt_BR")), "Multimídia");
+    assert_eq!(MenuSection::Multimedia.localized_name(Some("pt_PT")), "Multimédia");
+    assert_eq!(MenuSection::Other.localized_name(Some("de")), "Sonstige");
+    assert_eq!(MenuSection::Office.localized_name(Some("xx")), "Office");
+    assert_eq!(MenuSection::Other.directory_file(), None);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_registry_search_ranking() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_search");
+    let data = root.join("data");
+
+    write_entry(&data, "firefox.desktop", "[Desktop Entry]\nType=Application\nName=Firefox\nGenericName=Web Browser\nGenericName[de]=Webbrowser\nKeywords=internet;www;\nCategories=Network;WebBrowser;\nExec=firefox %u");
+    write_entry(&data, "files.desktop", "[Desktop Entry]\nType=Application\nName=Files\nName[de]=Dateien\nKeywords=folder;manager;explore;\nCategories=System;\nExec=nautilus");
+    write_entry(&data, "fire-tool.desktop", "[Desktop Entry]\nType=Application\nName=Camp Fire Tool\nCategories=Utility;\nExec=campfire");
+    write_entry(&data, "hidden.desktop", "[Desktop Entry]\nType=Application\nName=Firewall\nExec=firewall\nNoDisplay=true");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let registry = Registry::load();
+    let ids = |results: Vec<&ApplicationEntry>| -> Vec<String> { results.iter().filter_map(|e| e.id()).collect() };
+
+    assert_eq!(ids(registry.search("fire", None)), vec!["firefox".to_string(), "fire-tool".to_string()]);
+    assert_eq!(ids(registry.search("browser", None)), vec!["firefox".to_string()]);
+    assert_eq!(ids(registry.search("www", None)), vec!["firefox".to_string()]);
+    assert_eq!(ids(registry.search("nautilus", None)), vec!["files".to_string()]);
+    assert_eq!(ids(registry.search("system", None)), vec!["files".to_string()]);
+    assert_eq!(ids(registry.search("dateien", Some("de_DE"))), vec!["files".to_string()]);
+    assert_eq!(ids(registry.search("camp tool", None)), vec!["fire-tool".to_string()]);
+    assert!(registry.search("camp browser", None).is_empty());
+    assert!(registry.search("   ", None).is_empty());
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_entry_query_filters() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_query");
+    let data = root.join("data");
+
+    write_entry(&data, "gedit.desktop", "[Desktop Entry]\nType=Application\nName=Text Editor\nExec=gedit\nCategories=Development;TextEditor;\nMimeType=text/plain;\nOnlyShowIn=GNOME;");
+    write_entry(&data, "kate.desktop", "[Desktop Entry]\nType=Application\nName=Kate\nExec=kate\nCategories=Development;TextEditor;\nMimeType=text/plain;\nNotShowIn=GNOME;");
+    write_entry(&data, "vim.desktop", "[Desktop Entry]\nType=Application\nName=Vim\nExec=vim\nTerminal=true\nCategories=Development;TextEditor;\nMimeType=text/plain;");
+    write_entry(&data, "hidden.desktop", "[Desktop Entry]\nType=Application\nName=Hidden\nExec=hidden\nCategories=Development;\nNoDisplay=true");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let ids = |entries: Vec<ApplicationEntry>| -> Vec<String> { entries.iter().filter_map(|e| e.id()).collect() };
+
+    let query = EntryQuery::new()
+        .category("Development")
+        .shows_in("ubuntu:GNOME")
+        .terminal(false)
+        .mime_type("text/plain");
+    assert_eq!(ids(query.run()), vec!["gedit".to_string()]);
+
+    let query = EntryQuery::new().category("Development").shows_in("KDE");
+    assert_eq!(ids(query.run()), vec!["kate".to_string(), "vim".to_string()]);
+    assert_eq!(query.first().and_then(|e| e.id()), Some("kate".to_string()));
+
+    let query = EntryQuery::new().category("Development").include_hidden(true);
+    assert_eq!(query.run().len(), 4);
+
+    let registry = Registry::load();
+    assert_eq!(registry.query(&EntryQuery::new().terminal(true)).len(), 1);
+    assert_eq!(registry.query(&EntryQuery::new().role(Role::TextEditor)).len(), 3);
+    assert_eq!(registry.with_role(Role::TextEditor).len(), 3);
+    assert!(registry.with_role(Role::WebBrowser).is_empty());
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_discovery_reports_parse_errors() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_errors");
+    let home = root.join("home");
+    let system = root.join("system");
+
+    write_entry(&system, "good.desktop", "[Desktop Entry]\nType=Application\nName=Good\nExec=good");
+    write_entry(&system, "broken.desktop", "[Desktop Entry]\nType=Application\nName=Broken\nExec=broken");
+    write_entry(&home, "broken.desktop", "[Desktop Entry]\nType=Application\nExec=broken");
+    write_entry(&system, "empty.desktop", "");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+
+    let (entries, errors) = ApplicationEntry::all_with_errors();
+    let ids: Vec<String> = entries.iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["broken".to_string(), "good".to_string()]);
+    assert_eq!(entries[0].path(), system.join("applications/broken.desktop"));
+
+    let mut failed: Vec<&Path> = errors.iter().map(|(path, _)| path.as_path()).collect();
+    failed.sort();
+    assert_eq!(failed, vec![home.join("applications/broken.desktop"), system.join("applications/empty.desktop")]);
+    assert!(matches!(errors[0].1, ParseError::MissingRequiredKey { .. }));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_validate_dir_reports_each_file() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_validate");
+    let home = root.join("home");
+    let system = root.join("system");
+
+    write_entry(&system, "good.desktop", "[Desktop Entry]\nType=Application\nName=Good\nExec=good");
+    write_entry(&system, "empty.desktop", "");
+    write_entry(&system, "twice.desktop", "[Desktop Entry]\nType=Application\nName=Twice\nName=Again\nExec=twice");
+    write_entry(&system, "old.desktop", "[Desktop Entry]\nType=Application\nName=Old\nEncoding=UTF-8\nExec=old");
+    write_entry(&system, "vendor-tool.desktop", "[Desktop Entry]\nType=Application\nName=Tool\nExec=tool");
+    write_entry(&system, "vendor/tool.desktop", "[Desktop Entry]\nType=Application\nName=Tool\nExec=tool");
+    write_entry(&system, "mine.desktop", "[Desktop Entry]\nType=Application\nName=Mine\nExec=mine");
+    write_entry(&home, "mine.desktop", "[Desktop Entry]\nType=Application\nName=My Mine\nExec=mine");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+
+    let report = validate_dir(system.join("applications"));
+    assert_eq!(report.files().len(), 7);
+    let issues = |name: &str| {
+        report.files().iter().find(|file| file.path().ends_with(name)).unwrap().issues().to_vec()
+    };
+
+    assert!(issues("good.desktop").is_empty());
+    assert!(matches!(issues("empty.desktop")[..], [ValidationIssue::Unparsable(_)]));
+    assert!(matches!(issues("twice.desktop")[..], [ValidationIssue::Strict(_)]));
+    assert!(matches!(issues("old.desktop")[..], [ValidationIssue::Deprecated(_)]));
+    assert!(matches!(&issues("vendor/tool.desktop")[..], [ValidationIssue::DuplicateId { id, .. }] if id == "vendor-tool"));
+    assert!(matches!(&issues("mine.desktop")[..], [ValidationIssue::Shadowed { by, .. }] if *by == home.join("applications/mine.desktop")));
+
+    assert_eq!(report.problems().count(), 6);
+    assert_eq!(report.error_count(), 3);
+    assert_eq!(report.warning_count(), 3);
+    assert!(!report.passed());
+
+    // The highest-precedence directory can't be shadowed
+    assert!(validate_dir(home.join("applications")).passed());
+    assert_eq!(validate_dir(home.join("applications")).warning_count(), 0);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_flatpak_exports_are_opt_in() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_exports");
+    let home = root.join("home");
+    let exports = home.join("flatpak/exports/share");
+
+    write_entry(&home, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor");
+    write_entry(&exports, "org.example.Game.desktop", "[Desktop Entry]\nType=Application\nName=Game\nExec=flatpak run org.example.Game");
+    // The export copy of an ID found the usual way is shadowed
+    write_entry(&exports, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Flatpak Editor\nExec=editor");
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", root.join("missing"));
+    let ids = |entries: Vec<ApplicationEntry>| -> Vec<String> { entries.iter().filter_map(|e| e.id()).collect() };
+
+    assert_eq!(ids(ApplicationEntry::all()), vec!["editor".to_string()]);
+    // Outside the discovery that asked for them, export files have no ID
+    let game = ApplicationEntry::from_path(exports.join("applications/org.example.Game.desktop"));
+    assert_eq!(game.id(), None);
+
+    let entries = ApplicationEntry::all_with_exports();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name(), Some("Editor".to_string()));
+    assert_eq!(ids(entries), vec!["editor".to_string(), "org.example.Game".to_string()]);
+
+    let query = EntryQuery::new().include_exports(true);
+    assert_eq!(ids(query.run()), vec!["editor".to_string(), "org.example.Game".to_string()]);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_discovery_in_custom_dirs() {
+    let root = Path::new("/tmp/test_discovery_custom_dirs");
+    let first = root.join("first");
+    let second = root.join("second");
+
+    write_entry(&first, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor");
+    write_entry(&first, "tools/shell.desktop", "[Desktop Entry]\nType=Application\nName=Shell\nExec=sh\nTerminal=true");
+    write_entry(&second, "editor.desktop", "[Desktop Entry]\nType=Application\nName=Old Editor\nExec=editor");
+    write_entry(&second, "viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\nHidden=true");
+
+    let dirs = vec![first.join("applications"), second.join("applications")];
+    let entries = ApplicationEntry::all_in(&dirs);
+    let ids: Vec<String> = entries.iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["editor".to_string(), "tools-shell".to_string()]);
+    assert_eq!(entries[0].name(), Some("Editor".to_string()));
+
+    let shell = EntryQuery::new().dirs(&dirs).terminal(true).first().unwrap();
+    assert_eq!(shell.id(), Some("tools-shell".to_string()));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_watch_coalesces_bursts() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_watch_batch");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    let apps = data.join("applications");
+    write_entry(&data, "existing.desktop", "[Desktop Entry]\nType=Application\nName=Existing\nExec=existing");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let mut registry = Registry::load();
+    let mut watcher = registry.watch().unwrap();
+
+    for i in 0..20 {
+        fs::write(apps.join(format!("app{}.desktop", i)), "[Desktop Entry]\nType=Application\nName=App\nExec=app").unwrap();
+    }
+    fs::write(apps.join("app0.desktop"), "[Desktop Entry]\nType=Application\nName=First\nExec=app").unwrap();
+    fs::remove_file(apps.join("existing.desktop")).unwrap();
+
+    let policy = RefreshPolicy::new().quiet_period(Duration::from_millis(200));
+    let batch = watcher.next_batch(&policy).unwrap();
+    assert_eq!(batch.len(), 21);
+    assert_eq!(batch[0], RegistryEvent::Added("app0".to_string()));
+    assert_eq!(batch[20], RegistryEvent::Removed("existing".to_string()));
+
+    let mut reported = Vec::new();
+    registry.apply_events(&batch, |done, total| reported.push((done, total)));
+    assert_eq!(reported.last(), Some(&(21, 21)));
+    assert_eq!(registry.entries().len(), 20);
+    assert_eq!(registry.get("app0").unwrap().name(), Some("First".to_string()));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_shared_registry_swaps_on_refresh() {
+    fn assert_shareable<T: Send + Sync>() {}
+    assert_shareable::<Registry>();
+    assert_shareable::<SharedRegistry>();
+
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_shared");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    write_entry(&data, "first.desktop", "[Desktop Entry]\nType=Application\nName=First\nExec=first");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    let shared = Arc::new(SharedRegistry::load());
+    let before = shared.snapshot();
+
+    // Readers keep working on their snapshot while the registry is refreshed
+    let reader = {
+        let shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            for _ in 0..1000 {
+                let snapshot = shared.snapshot();
+                assert!(snapshot.get("first").is_some());
+                assert!(matches!(snapshot.entries().len(), 1 | 2));
+            }
+        })
+    };
+
+    write_entry(&data, "second.desktop", "[Desktop Entry]\nType=Application\nName=Second\nExec=second");
+    shared.apply_events(&[RegistryEvent::Added("second".to_string())], |_, _| {});
+    reader.join().unwrap();
+
+    assert_eq!(before.entries().len(), 1);
+    assert!(before.get("second").is_none());
+    assert!(shared.snapshot().get("second").is_some());
+
+    fs::remove_file(data.join("applications/first.desktop")).unwrap();
+    shared.reload();
+    let ids: Vec<String> = shared.snapshot().entries().iter().filter_map(|e| e.id()).collect();
+    assert_eq!(ids, vec!["second".to_string()]);
+    assert_eq!(before.entries().len(), 1);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_registry_export_to_directory() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_export");
+    fs::remove_dir_all(root).ok();
+    let home = root.join("home");
+    let system = root.join("system");
+    let target = root.join("target");
+
+    write_entry(&system, "editor.desktop", "[Desktop Entry]\nType=Application\nName=System Editor\nExec=editor");
+    write_entry(&home, "editor.desktop", "[Desktop Entry]\nType=Application\nName=My Editor\nExec=editor --mine");
+    write_entry(&system, "vendor/tool.desktop", "[Desktop Entry]\nType=Application\nName=Tool\nExec=tool");
+    fs::create_dir_all(&target).unwrap();
+    fs::write(target.join("mine.desktop"), "[Desktop Entry]\nType=Application\nName=Mine\nExec=mine").unwrap();
+
+    std::env::set_var("XDG_DATA_HOME", &home);
+    std::env::set_var("XDG_DATA_DIRS", &system);
+    let registry = Registry::load();
+    let written = registry.export_to(&target).unwrap();
+    assert_eq!(written.len(), 2);
+
+    let editor = fs::read_to_string(target.join("editor.desktop")).unwrap();
+    let source = home.join("applications/editor.desktop");
+    assert!(editor.starts_with(&format!("# Exported by freedesktop-apps from {}\n", source.display())));
+    assert!(editor.ends_with("[Desktop Entry]\nType=Application\nName=My Editor\nExec=editor --mine\n"));
+    let tool = ApplicationEntry::try_from_path(target.join("vendor-tool.desktop")).unwrap();
+    assert_eq!(tool.name(), Some("Tool".to_string()));
+
+    // A line break in the source path can't add keys
+    let odd = home.join("applications/odd\nExec=evil");
+    fs::create_dir_all(&odd).unwrap();
+    fs::write(odd.join("app.desktop"), "[Desktop Entry]\nType=Application\nName=Odd\nExec=odd").unwrap();
+    let exported = Registry::from_entries(vec![ApplicationEntry::try_from_path(odd.join("app.desktop")).unwrap()])
+        .export_to(root.join("odd"))
+        .unwrap();
+    let content = fs::read_to_string(&exported[0]).unwrap();
+    assert_eq!(content.lines().filter(|line| line.starts_with("Exec=")).collect::<Vec<_>>(), ["Exec=odd"]);
+    fs::remove_dir_all(home.join("applications/odd\nExec=evil")).unwrap();
+
+    // A later export drops entries that are gone but keeps files it didn't write
+    fs::remove_file(system.join("applications/vendor/tool.desktop")).unwrap();
+    Registry::load().export_to(&target).unwrap();
+    assert!(!target.join("vendor-tool.desktop").exists());
+    assert!(target.join("editor.desktop").exists());
+    assert!(target.join("mine.desktop").exists());
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_visibility_preview_for_other_desktops() {
+    let root = Path::new("/tmp/test_discovery_preview");
+    fs::remove_dir_all(root).ok();
+    let entry = |file: &str, keys: &str| {
+        write_entry(root, file, &format!("[Desktop Entry]\nType=Application\nName={}\nExec=app\n{}", file, keys));
+        ApplicationEntry::try_from_path(root.join("applications").join(file)).unwrap()
+    };
+    let registry = Registry::from_entries(vec![
+        entry("everywhere.desktop", ""),
+        entry("gnome-only.desktop", "OnlyShowIn=GNOME;"),
+        entry("not-kde.desktop", "NotShowIn=KDE;"),
+        entry("listed.desktop", "NoDisplay=true"),
+        entry("deleted.desktop", "Hidden=true"),
+    ]);
+
+    let names = |entries: Vec<&ApplicationEntry>| -> Vec<String> { entries.iter().filter_map(|e| e.name()).collect() };
+    assert_eq!(names(registry.visible_in("GNOME")), ["everywhere.desktop", "gnome-only.desktop", "not-kde.desktop"]);
+    assert_eq!(names(registry.visible_in("sway")), ["everywhere.desktop", "not-kde.desktop"]);
+    assert_eq!(names(registry.visible_in("ubuntu:GNOME")).len(), 3);
+
+    let kde: Vec<Visibility> = registry.preview("KDE").into_iter().map(|(_, visibility)| visibility).collect();
+    assert_eq!(kde, [
+        Visibility::Shown,
+        Visibility::OnlyShownElsewhere,
+        Visibility::NotShownIn,
+        Visibility::NoDisplay,
+        Visibility::Hidden,
+    ]);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_registry_policy_restricts_entries() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_policy");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    write_entry(&data, "writer.desktop", "[Desktop Entry]\nType=Application\nName=Writer\nExec=writer\nCategories=Office;");
+    write_entry(&data, "browser.desktop", "[Desktop Entry]\nType=Application\nName=Browser\nExec=browser\nCategories=Network;");
+    write_entry(&data, "shell.desktop", "[Desktop Entry]\nType=Application\nName=Shell\nExec=shell\nCategories=System;");
+    write_entry(&data, "mines.desktop", "[Desktop Entry]\nType=Application\nName=Mines\nExec=mines\nCategories=Office;Game;");
+
+    let config = root.join("config");
+    fs::create_dir_all(config.join("freedesktop")).unwrap();
+    fs::write(
+        config.join("freedesktop/app-policy.conf"),
+        "[Policy]\nAllowCategories=Office;\nAllowIds=browser.desktop;\nDenyCategories=Game;\n",
+    )
+    .unwrap();
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+    std::env::set_var("XDG_CONFIG_DIRS", &config);
+    let policy = AppPolicy::load().unwrap();
+    assert_eq!(AppPolicy::default_path(), Some(config.join("freedesktop/app-policy.conf")));
+    std::env::remove_var("XDG_CONFIG_DIRS");
+
+    let mut registry = Registry::load().with_policy(policy);
+    let mut ids: Vec<String> = registry.entries().iter().filter_map(|e| e.id()).collect();
+    ids.sort();
+    assert_eq!(ids, ["browser", "writer"]);
+    assert!(registry.get("shell").is_none());
+    assert!(matches!(registry.launch("mines"), Err(ExecuteError::NotFound(_))));
+
+    // Entries that show up later are checked too
+    write_entry(&data, "solitaire.desktop", "[Desktop Entry]\nType=Application\nName=Solitaire\nExec=sol\nCategories=Game;");
+    write_entry(&data, "sheets.desktop", "[Desktop Entry]\nType=Application\nName=Sheets\nExec=sheets\nCategories=Office;");
+    registry.apply_event(&RegistryEvent::Added("solitaire".to_string()));
+    registry.apply_event(&RegistryEvent::Added("sheets".to_string()));
+    assert!(registry.get("solitaire").is_none());
+    assert!(registry.get("sheets").is_some());
+
+    let shared = SharedRegistry::new(registry);
+    shared.reload();
+    assert_eq!(shared.snapshot().entries().len(), 3);
+
+    assert!(AppPolicy::new().is_empty());
+    assert_eq!(Registry::load().with_policy(AppPolicy::new().deny_id("shell")).entries().len(), 5);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_verify_installation() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_verify");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    write_entry(&data, "org.example.Viewer.desktop", "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer %F\nIcon=org.example.Viewer\nDBusActivatable=true\nMimeType=text/plain;image/png;\n");
+    write_entry(&data, "viewer-tool.desktop", "[Desktop Entry]\nType=Application\nName=Tool\nExec=tool\nIcon=viewer-tool\nDBusActivatable=true\n");
+    fs::create_dir_all(data.join("icons/hicolor/48x48/apps")).unwrap();
+    fs::write(data.join("icons/hicolor/48x48/apps/org.example.Viewer.png"), "").unwrap();
+    let cache = data.join("applications/mimeinfo.cache");
+    fs::write(&cache, "[MIME Cache]\ntext/plain=other.desktop;org.example.Viewer.desktop;\n").unwrap();
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+
+    let report = verify_installation("org.example.Viewer.desktop");
+    assert_eq!(report.id(), "org.example.Viewer");
+    assert_eq!(report.path(), Some(data.join("applications/org.example.Viewer.desktop").as_path()));
+    assert!(matches!(report.issues(), [InstallationIssue::MimeTypeNotCached(mime)] if mime == "image/png"));
+    assert!(!report.passed());
+
+    fs::write(&cache, "[MIME Cache]\ntext/plain=org.example.Viewer.desktop;\nimage/png=org.example.Viewer.desktop;\n").unwrap();
+    assert!(verify_installation("org.example.Viewer").passed());
+
+    let report = verify_installation("viewer-tool");
+    assert!(matches!(report.issues(), [
+        InstallationIssue::BusNameMismatch,
+        InstallationIssue::MissingIcon { size: 48, .. },
+    ]), "{:?}", report.issues());
+
+    // A scalable icon covers every size
+    fs::create_dir_all(data.join("icons/hicolor/scalable/apps")).unwrap();
+    fs::write(data.join("icons/hicolor/scalable/apps/viewer-tool.svg"), "").unwrap();
+    assert!(matches!(verify_installation("viewer-tool").issues(), [InstallationIssue::BusNameMismatch]));
+
+    assert!(matches!(verify_installation("not-installed").issues(), [InstallationIssue::NotInstalled]));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_registry_merges_duplicate_entries() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_duplicates");
+    fs::remove_dir_all(root).ok();
+    let data = root.join("data");
+    write_entry(&data, "gnome-calculator.desktop", "[Desktop Entry]\nType=Application\nName=Calculator\nExec=gnome-calculator\n");
+    write_entry(&data, "org.gnome.Calculator.desktop", "[Desktop Entry]\nType=Application\nName=Calculator\nExec=gnome-calculator\n");
+    write_entry(&data, "calc.desktop", "[Desktop Entry]\nType=Application\nName=Calculator\nExec=other-calculator\n");
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+
+    let report = Registry::load().with_duplicate_strategy(DuplicateStrategy::Report);
+    assert_eq!(report.entries().len(), 3);
+    assert_eq!(report.duplicates().len(), 1);
+    let group = &report.duplicates()[0];
+    assert_eq!((group.name(), group.exec()), ("Calculator", "gnome-calculator"));
+    assert_eq!(group.kept().id().as_deref(), Some("org.gnome.Calculator"));
+    assert_eq!(group.duplicate_ids(), ["gnome-calculator"]);
+
+    let mut registry = Registry::load().with_duplicate_strategy(DuplicateStrategy::Merge);
+    let mut ids: Vec<String> = registry.entries().iter().filter_map(|e| e.id()).collect();
+    ids.sort();
+    assert_eq!(ids, ["calc", "org.gnome.Calculator"]);
+    // The old ID still works and refers to the kept entry
+    assert_eq!(registry.get("gnome-calculator.desktop").and_then(|e| e.id()).as_deref(), Some("org.gnome.Calculator"));
+
+    // Removing the kept file brings the duplicate back
+    fs::remove_file(data.join("applications/org.gnome.Calculator.desktop")).unwrap();
+    registry.apply_event(&RegistryEvent::Removed("org.gnome.Calculator".to_string()));
+    assert!(registry.duplicates().is_empty());
+    assert!(registry.entries().iter().any(|e| e.id().as_deref() == Some("gnome-calculator")));
+    assert!(fs::metadata(data.join("applications/gnome-calculator.desktop")).is_ok());
+
+    write_entry(&data, "org.gnome.Calculator.desktop", "[Desktop Entry]\nType=Application\nName=Calculator\nExec=gnome-calculator\n");
+    let shared = SharedRegistry::new(registry);
+    shared.reload();
+    assert_eq!(shared.snapshot().entries().len(), 2);
+    assert_eq!(shared.snapshot().duplicates().len(), 1);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_autostart_systemd_handling() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_autostart");
+    fs::remove_dir_all(root).ok();
+    let home = root.join("home");
+    let system = root.join("system");
+    let write = |dir: &Path, file: &str, content: &str| {
+        fs::create_dir_all(dir.join("autostart")).unwrap();
+        fs::write(dir.join("autostart").join(file), content).unwrap();
+    };
+    write(&system, "sync.desktop", "[Desktop Entry]\nType=Application\nName=Sync\nExec=sync-daemon\nOnlyShowIn=GNOME;\n");
+    write(&system, "panel.desktop", "[Desktop Entry]\nType=Application\nName=Panel\nExec=panel\nX-systemd-skip=true\n");
+    write(&system, "keyring.desktop", "[Desktop Entry]\nType=Application\nName=Keyring\nExec=keyring\nX-GNOME-Autostart-Phase=PreDisplayServer\n");
+    write(&system, "tracker.desktop", "[Desktop Entry]\nType=Application\nName=Tracker\nExec=tracker\nX-GNOME-HiddenUnderSystemd=true\n");
+    write(&system, "missing.desktop", "[Desktop Entry]\nType=Application\nName=Missing\nExec=missing\nTryExec=/nonexistent/missing\n");
+    write(&system, "off.desktop", "[Desktop Entry]\nType=Application\nName=Off\nExec=off\nX-GNOME-Autostart-enabled=false\n");
+    write(&system, "chat.desktop", "[Desktop Entry]\nType=Application\nName=Chat\nExec=chat\n");
+    // The user disabled chat
+    write(&home, "chat.desktop", "[Desktop Entry]\nType=Application\nName=Chat\nExec=chat\nHidden=true\n");
+
+    std::env::set_var("XDG_CONFIG_HOME", &home);
+    std::env::set_var("XDG_CONFIG_DIRS", &system);
+    let mut report: Vec<(String, AutostartHandling)> = autostart_report()
+        .into_iter()
+        .map(|(entry, handling)| (entry.id().unwrap(), handling))
+        .collect();
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_CONFIG_DIRS");
+    report.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(
+        report,
+        [
+            ("keyring".to_string(), AutostartHandling::SessionPhase("PreDisplayServer".to_string())),
+            ("missing".to_string(), AutostartHandling::Disabled),
+            ("off".to_string(), AutostartHandling::Disabled),
+            ("panel".to_string(), AutostartHandling::SystemdSkip),
+            ("sync".to_string(), AutostartHandling::Generated),
+            ("tracker".to_string(), AutostartHandling::ReplacedByUnit),
+        ]
+    );
+    let manual: Vec<&str> = report
+        .iter()
+        .filter(|(_, handling)| handling.needs_manual_launch())
+        .map(|(id, _)| id.as_str())
+        .collect();
+    assert_eq!(manual, ["keyring", "panel"]);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_trust_resolves_paths_out_of_data_dirs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = Path::new("/tmp/test_discovery_trust");
+    let data = root.join("data");
+    let downloads = root.join("downloads");
+    let app = "[Desktop Entry]\nType=Application\nName=Evil\nExec=evil";
+
+    write_entry(&data, "installed.desktop", app);
+    fs::create_dir_all(&downloads).unwrap();
+    let evil = downloads.join("evil.desktop");
+    fs::write(&evil, app).unwrap();
+    fs::set_permissions(&evil, fs::Permissions::from_mode(0o644)).unwrap();
+
+    std::env::set_var("XDG_DATA_HOME", root.join("missing"));
+    std::env::set_var("XDG_DATA_DIRS", &data);
+
+    let installed = ApplicationEntry::try_from_path(data.join("applications/installed.desktop")).unwrap();
+    assert!(installed.is_trusted());
+
+    // A path that only starts with a data dir before resolving `..`
+    let dotted = data.join("applications/../../downloads/evil.desktop");
+    assert!(!ApplicationEntry::try_from_path(&dotted).unwrap().is_trusted());
+
+    // A symlink in a data dir pointing out of it is judged by its target
+    let link = data.join("applications/evil.desktop");
+    std::os::unix::fs::symlink(&evil, &link).unwrap();
+    assert!(!ApplicationEntry::try_from_path(&link).unwrap().is_trusted());
+
+    fs::remove_dir_all(root).ok();
+}
@@ -1,7 +1,9 @@
-use freedesktop_apps::{ApplicationEntry, ParseError};
+use freedesktop_apps::{ApplicationEntry, EntryType, Limit, ParseError, ParseLimits, ParseOptions};
 use std::fs;
+#[allow(unused_imports)]
 use std::path::Path;
 
+#[allow(dead_code)]
 fn fixture_path(name: &str) -> String {
     format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
 }
@@ -51,6 +53,31 @@ fn test_key_without_equals() {
     fs::remove_file(temp_file).ok();
 }
 
+#[test]
+fn test_strict_mode_rejects_spec_violations() {
+    let cases = [
+        ("/tmp/strict_no_equals_test.desktop", "[Desktop Entry]\nType=Application\nName=Test\nGarbageLine\nExec=test"),
+        ("/tmp/strict_dup_group_test.desktop", "[Desktop Entry]\nType=Application\nName=Test\nExec=test\n[Desktop Entry]\nComment=Again"),
+        ("/tmp/strict_dup_key_test.desktop", "[Desktop Entry]\nType=Application\nName=Test\nName=Other\nExec=test"),
+        ("/tmp/strict_escape_test.desktop", "[Desktop Entry]\nType=Application\nName=Test\\q\nExec=test"),
+    ];
+
+    for (temp_file, content) in cases {
+        fs::write(temp_file, content).unwrap();
+
+        // Permissive parsing (the default) accepts these files
+        assert!(ApplicationEntry::try_from_path(temp_file).is_ok(), "{}", temp_file);
+        assert!(ApplicationEntry::try_from_path_with(temp_file, &ParseOptions::permissive()).is_ok());
+
+        match ApplicationEntry::try_from_path_with(temp_file, &ParseOptions::strict()) {
+            Err(ParseError::InvalidFormat { .. }) => {}
+            other => panic!("Expected InvalidFormat for {}, got: {:?}", temp_file, other),
+        }
+
+        fs::remove_file(temp_file).ok();
+    }
+}
+
 #[test]
 fn test_empty_key_name() {
     let temp_file = "/tmp/empty_key_test.desktop";
@@ -117,7 +144,7 @@ fn test_whitespace_variations() {
     fs::write(temp_file, "[Desktop Entry]\n  Type  =  Application  \n\tName\t=\tTest App\t\nExec =test-app   \n   Comment=   A test app   ").unwrap();
     
     let entry = ApplicationEntry::try_from_path(temp_file).expect("Should parse whitespace variations");
-    assert_eq!(entry.entry_type(), Some("Application".to_string()));
+    assert_eq!(entry.entry_type(), Some(EntryType::Application));
     assert_eq!(entry.name(), Some("Test App".to_string()));
     assert_eq!(entry.exec(), Some("test-app".to_string()));
     assert_eq!(entry.comment(), Some("A test app".to_string()));
@@ -217,6 +244,73 @@ fn test_performance_large_file() {
 }
 
 #[test]
+fn test_parse_limits() {
+    let temp_file = "/tmp/parse_limits_test.desktop";
+    let limited = |limits: ParseLimits| ParseOptions::default().limits(limits);
+    let header = "[Desktop Entry]\nType=Application\nName=Limits\nExec=limits\n";
+
+    fs::write(temp_file, format!("{}X-Padding={}\n", header, "a".repeat(200))).unwrap();
+    assert!(ApplicationEntry::try_from_path(temp_file).is_ok());
+    match ApplicationEntry::try_from_path_with(temp_file, &limited(ParseLimits::new().max_line_length(100))) {
+        Err(ParseError::LimitExceeded { line, limit, .. }) => {
+            assert_eq!(line, Some(5));
+            assert_eq!(limit, Limit::LineLength(100));
+        }
+        other => panic!("Expected LimitExceeded, got: {:?}", other),
+    }
+    assert!(matches!(
+        ApplicationEntry::try_from_path_with(temp_file, &limited(ParseLimits::new().max_file_size(128))),
+        Err(ParseError::LimitExceeded { line: None, limit: Limit::FileSize(128), .. })
+    ));
+
+    let keys: String = (0..50).map(|i| format!("X-Key{}=value\n", i)).collect();
+    fs::write(temp_file, format!("{}{}", header, keys)).unwrap();
+    assert!(matches!(
+        ApplicationEntry::try_from_path_with(temp_file, &limited(ParseLimits::new().max_keys(20))),
+        Err(ParseError::LimitExceeded { limit: Limit::Keys(20), .. })
+    ));
+    assert!(ApplicationEntry::try_from_path_with(temp_file, &limited(ParseLimits::unlimited())).is_ok());
+
+    fs::remove_file(temp_file).ok();
+
+    // Endless files without a size are stopped by the line length limit
+    let err = ApplicationEntry::try_from_path("/dev/zero").unwrap_err();
+    assert!(matches!(err, ParseError::LimitExceeded { limit: Limit::LineLength(_), .. }));
+    assert!(err.to_string().contains("line is longer than"));
+}
+
+#[test]
+fn test_recover_damaged_file() {
+    let temp_file = "/tmp/recover_test.desktop";
+    let mut contents = b"[Desktop Entry]\nType=Application\nName=Salvaged\n".to_vec();
+    contents.extend_from_slice(b"Exec=sal\xff\xfe\x00vaged\n\x01\x02garbage\n");
+    contents.extend_from_slice(b"Icon=salvaged\nComment=Cut off in the mid");
+    fs::write(temp_file, &contents).unwrap();
+
+    assert!(ApplicationEntry::try_from_path(temp_file).is_err());
+
+    let recovered = ApplicationEntry::recover(temp_file).unwrap();
+    assert_eq!(recovered.entry().name(), Some("Salvaged".to_string()));
+    assert_eq!(recovered.entry().icon(), Some("salvaged".to_string()));
+    assert_eq!(recovered.entry().exec(), None);
+    assert_eq!(recovered.total_lines(), 7);
+    assert_eq!(recovered.kept_lines(), 5);
+    assert!(recovered.salvage_ratio() < 1.0);
+    assert!(!recovered.is_complete(), "Exec was lost");
+
+    // Overlong lines are skipped and the file is cut off at the size limit
+    fs::write(temp_file, format!("[Desktop Entry]\nX-Long={}\nName=Short\nType=Application\n", "a".repeat(200))).unwrap();
+    let limits = ParseLimits::new().max_line_length(100).max_file_size(236);
+    let recovered = ApplicationEntry::recover_with(temp_file, limits).unwrap();
+    assert_eq!(recovered.entry().name(), Some("Short".to_string()));
+    assert!(!recovered.is_complete());
+
+    fs::remove_file(temp_file).ok();
+    assert!(matches!(ApplicationEntry::recover(temp_file), Err(ParseError::IoError { .. })));
+}
+
+#[test]
+#[allow(unused_comparisons, clippy::absurd_extreme_comparisons)]
 fn test_application_all_filtering() {
     // Test that ApplicationEntry::all() properly filters desktop files
     // and handles parsing errors gracefully
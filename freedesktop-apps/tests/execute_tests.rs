@@ -1,10 +1,26 @@
-use freedesktop_apps::{ApplicationEntry, ExecuteError};
+use freedesktop_apps::{ActivationTokenProvider, ApplicationEntry, DetachMode, DownloadFetcher, Elevation, ExecuteError, FlatpakSpawnLauncher, FocusOutcome, LaunchAllOptions, LaunchOptions, LaunchFailures, LaunchHooks, LaunchRequest, Launcher, MailtoBuilder, Registry, SpawnLauncher, StartupNotifier, StartupSequence, TerminalProfile, UrlFetcher, WindowHint, escape_value, quote_exec_arg, register_terminal, which};
+use std::cell::Cell;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+// Tests that set TERMINAL hold this so they don't race
+static TERMINAL_LOCK: Mutex<()> = Mutex::new(());
+
+#[allow(dead_code)]
 fn fixture_path(name: &str) -> String {
     format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
 }
 
+/// Create an empty file that passes the executable check, standing in for a program
+fn write_executable(path: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::write(path, "").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
 #[test]
 fn test_execute_validation_no_exec() {
     let temp_file = "/tmp/no_exec_test.desktop";
@@ -117,6 +133,40 @@ fn test_try_exec_with_valid_program() {
     fs::remove_file(temp_file).ok();
 }
 
+#[test]
+fn test_try_exec_requires_executable_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = "/tmp/try_exec_permissions_test";
+    fs::remove_dir_all(dir).ok();
+    fs::create_dir_all(format!("{}/subdir", dir)).unwrap();
+    let plain = format!("{}/plain", dir);
+    let program = format!("{}/program", dir);
+    fs::write(&plain, "#!/bin/sh\n").unwrap();
+    fs::write(&program, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert_eq!(which(&program), Some(PathBuf::from(&program)));
+    assert_eq!(which(&plain), None);
+    assert_eq!(which(format!("{}/subdir", dir)), None);
+    assert_eq!(which(""), None);
+    assert!(which("sh").is_some_and(|sh| sh.is_absolute() || sh.starts_with(".")));
+    assert_eq!(which("try-exec-permissions-test-missing"), None);
+
+    let temp_file = format!("{}/entry.desktop", dir);
+    let entry_with = |try_exec: &str| {
+        fs::write(&temp_file, format!("[Desktop Entry]\nType=Application\nName=Test\nExec=echo test\nTryExec={}\n", try_exec)).unwrap();
+        ApplicationEntry::try_from_path(&temp_file).unwrap()
+    };
+    assert!(entry_with(&program).prepare_command(&[], &[]).is_ok());
+    for try_exec in [plain.as_str(), &format!("{}/subdir", dir)] {
+        let result = entry_with(try_exec).prepare_command(&[], &[]);
+        assert!(matches!(result, Err(ExecuteError::ValidationFailed(_))), "{}: {:?}", try_exec, result);
+    }
+
+    fs::remove_dir_all(dir).ok();
+}
+
 #[test]
 fn test_terminal_application() {
     let temp_file = "/tmp/terminal_test.desktop";
@@ -154,6 +204,7 @@ fn test_terminal_application() {
 }
 
 #[test]
+#[allow(clippy::single_match)]
 fn test_execute_with_files() {
     let temp_file = "/tmp/files_test.desktop";
     fs::write(temp_file, 
@@ -181,6 +232,7 @@ fn test_execute_with_files() {
 }
 
 #[test]
+#[allow(clippy::single_match)]
 fn test_execute_with_urls() {
     let temp_file = "/tmp/urls_test.desktop";
     fs::write(temp_file, 
@@ -208,6 +260,7 @@ fn test_execute_with_urls() {
 }
 
 #[test]
+#[allow(clippy::single_match)]
 fn test_working_directory() {
     let temp_file = "/tmp/workdir_test.desktop";
     fs::write(temp_file, 
@@ -260,4 +313,1222 @@ Exec=echo %f
     }
     
     fs::remove_file(temp_file).ok();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_field_codes_expand_to_whole_arguments() {
+    let temp_file = "/tmp/argv_expansion_test.desktop";
+    fs::write(temp_file,
+        "[Desktop Entry]\nType=Application\nName=My App\nIcon=my-icon\nExec=viewer %i --title %c --open=%f \"100%%\" %F\n"
+    ).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+
+    // Values are inserted as arguments and never re-parsed, whatever they contain
+    let files = ["/tmp/a b.txt", "/tmp/it's \"quoted\" $(touch pwned); `id`"];
+    let (program, args) = entry.prepare_command(&files, &[]).unwrap();
+    assert_eq!(program, "viewer");
+    assert_eq!(args, vec![
+        "--icon", "my-icon",
+        "--title", "My App",
+        "--open=/tmp/a b.txt",
+        "100%",
+        files[0], files[1],
+    ]);
+
+    // Codes without values disappear instead of leaving empty arguments
+    let (_, args) = entry.prepare_command(&[], &[]).unwrap();
+    assert_eq!(args, vec!["--icon", "my-icon", "--title", "My App", "--open=", "100%"]);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_exec_quoting_rules() {
+    let temp_file = "/tmp/exec_quoting_test.desktop";
+    // String escapes are decoded first, so a literal backslash takes four
+    fs::write(temp_file, r#"[Desktop Entry]
+Type=Application
+Name=Quoting
+Exec=sh "a\\\\b" "say \"hi\"" "\$HOME" 'not quoted' "" last
+"#).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let (program, args) = entry.prepare_command(&[], &[]).unwrap();
+    assert_eq!(program, "sh");
+    assert_eq!(args, vec!["a\\b", "say \"hi\"", "$HOME", "'not", "quoted'", "", "last"]);
+
+    // Quoted arguments survive being written to a file and read back
+    let original = ["plain", "two words", "it's", "a\\b", "$(id) `id`", "say \"hi\"", ""];
+    assert_eq!(quote_exec_arg("plain"), "plain");
+    assert_eq!(quote_exec_arg("$x"), "\"\\$x\"");
+    let exec: Vec<String> = original.iter().map(|arg| quote_exec_arg(arg)).collect();
+    fs::write(temp_file, format!(
+        "[Desktop Entry]\nType=Application\nName=Quoting\nExec=sh {}\n",
+        escape_value(&exec.join(" "))
+    )).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let (_, args) = entry.prepare_command(&[], &[]).unwrap();
+    assert_eq!(args, original);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[derive(Debug, Default)]
+struct TempFetcher {
+    fetched: Mutex<Vec<String>>,
+}
+
+impl UrlFetcher for TempFetcher {
+    fn fetch(&self, url: &str) -> std::io::Result<PathBuf> {
+        self.fetched.lock().unwrap().push(url.to_string());
+        let path = PathBuf::from("/tmp/url_fetch_test_download.txt");
+        fs::write(&path, url)?;
+        Ok(path)
+    }
+}
+
+#[test]
+fn test_remote_urls_for_file_only_entries() {
+    let temp_file = "/tmp/url_fetch_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer %F\nPath=/tmp\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+
+    // Local URLs are passed as paths without any fetcher
+    let (_, args) = entry.prepare_command(&["/tmp/a.txt"], &["file:///tmp/my%20file.txt"]).unwrap();
+    assert_eq!(args, vec!["/tmp/a.txt", "/tmp/my file.txt"]);
+
+    let url = "https://example.com/report.pdf";
+    match entry.prepare_command(&[], &[url]) {
+        Err(ExecuteError::UnsupportedUrl(unsupported)) => assert_eq!(unsupported, url),
+        other => panic!("Expected UnsupportedUrl, got: {:?}", other),
+    }
+
+    let fetcher = Arc::new(TempFetcher::default());
+    let options = LaunchOptions::new().url_fetcher(fetcher.clone());
+    let (_, args) = entry.prepare_command_with(&[], &[url], &options).unwrap();
+    assert_eq!(args, vec!["/tmp/url_fetch_test_download.txt"]);
+    assert_eq!(*fetcher.fetched.lock().unwrap(), vec![url.to_string()]);
+
+    // Launches hand downloads to the caller, and remove them if they fail
+    let download = PathBuf::from("/tmp/url_fetch_test_download.txt");
+    let launched = entry
+        .launch_with_urls(&[url], &options.clone().launcher(Arc::new(RecordingLauncher::default())))
+        .unwrap();
+    assert_eq!(launched.len(), 1);
+    assert_eq!(launched[0].downloads(), std::slice::from_ref(&download));
+    assert!(download.exists());
+    let failing = options.clone().launcher(Arc::new(OutputLauncher { fail: true, ..Default::default() }));
+    assert!(entry.launch_with_urls(&[url], &failing).is_err());
+    assert!(!download.exists());
+
+    // Entries that take URLs get them unchanged
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer %U\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let (_, args) = entry.prepare_command(&[], &[url]).unwrap();
+    assert_eq!(args, vec![url]);
+
+    fs::remove_file(temp_file).ok();
+    fs::remove_file("/tmp/url_fetch_test_download.txt").ok();
+}
+
+#[test]
+fn test_download_fetcher() {
+    let dir = "/tmp/test_download_fetcher";
+    let source = "/tmp/test_download_fetcher_source.txt";
+    fs::remove_dir_all(dir).ok();
+    fs::write(source, "downloaded").unwrap();
+
+    let fetcher = DownloadFetcher::in_dir(dir);
+    match fetcher.fetch(&format!("file://{}", source)) {
+        Ok(path) => {
+            assert!(path.starts_with(dir));
+            assert_eq!(path.file_name().unwrap(), "test_download_fetcher_source.txt");
+            assert_eq!(fs::read_to_string(&path).unwrap(), "downloaded");
+            let download_dir = path.parent().unwrap();
+            assert_ne!(download_dir, std::path::Path::new(dir));
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(download_dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        // Neither curl nor wget is installed
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => panic!("Download failed: {}", e),
+    }
+    assert!(fetcher.fetch("file:///tmp/test_download_fetcher_missing").is_err());
+    // The failed download doesn't leave its directory behind
+    assert!(fs::read_dir(dir).unwrap().count() <= 1);
+
+    fs::remove_dir_all(dir).ok();
+    fs::remove_file(source).ok();
+}
+
+/// Starts a number of instances, then refuses
+#[derive(Debug)]
+struct LimitedLauncher {
+    remaining: Mutex<usize>,
+}
+
+impl Launcher for LimitedLauncher {
+    fn launch(&self, _request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let mut remaining = self.remaining.lock().unwrap();
+        if *remaining == 0 {
+            return Err(ExecuteError::InvalidCommand("refused".to_string()));
+        }
+        *remaining -= 1;
+        Ok(Some(100 + *remaining as u32))
+    }
+}
+
+#[test]
+fn test_partial_launch_keeps_started_instances() {
+    let temp_file = "/tmp/partial_launch_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Viewer\nExec=sh %f").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let launcher = |remaining| LaunchOptions::new().launcher(Arc::new(LimitedLauncher { remaining: Mutex::new(remaining) }));
+
+    let files = ["/tmp/a.txt", "/tmp/b.txt", "/tmp/c.txt"];
+    match entry.launch_with_files(&files, &launcher(2)) {
+        Err(ExecuteError::PartialLaunch { launched, error }) => {
+            assert_eq!(launched.iter().map(|l| l.pid()).collect::<Vec<_>>(), vec![Some(101), Some(100)]);
+            assert!(matches!(*error, ExecuteError::InvalidCommand(_)));
+        }
+        other => panic!("Expected a partial launch, got {:?}", other),
+    }
+    assert!(matches!(entry.launch_with_files(&files, &launcher(0)), Err(ExecuteError::InvalidCommand(_))));
+    assert_eq!(entry.launch_with_files(&files, &launcher(3)).unwrap().len(), 3);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_registry_launch_all_results() {
+    let temp_dir = "/tmp/test_registry_launch_all";
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let apps = PathBuf::from(format!("{}/applications", temp_dir));
+    let path = apps.join("broken.desktop");
+    fs::write(&path, "[Desktop Entry]\nType=Application\nName=Broken\nExec=launch-all-binary\nTryExec=/nonexistent/launch-all-binary").unwrap();
+
+    let registry = Registry::load_from(&[apps]);
+    assert!(registry.get("broken.desktop").is_some());
+
+    let opts = LaunchAllOptions::new()
+        .concurrency(2)
+        .entry_delay("broken", Duration::from_millis(10));
+    let results = registry.launch_all(&["missing", "broken.desktop", "broken"], &opts);
+
+    let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, vec!["missing", "broken.desktop", "broken"]);
+    assert!(matches!(results[0].result, Err(ExecuteError::NotFound(_))));
+    assert!(matches!(results[1].result, Err(ExecuteError::ValidationFailed(_))));
+    assert!(matches!(results[2].result, Err(ExecuteError::ValidationFailed(_))));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_registry_debounces_repeated_launches() {
+    let temp_dir = "/tmp/test_registry_debounce";
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let apps = [PathBuf::from(format!("{}/applications", temp_dir))];
+    fs::write(apps[0].join("quick.desktop"), "[Desktop Entry]\nType=Application\nName=Quick\nExec=/bin/true").unwrap();
+
+    let registry = Registry::load_from(&apps).with_debounce(Duration::from_secs(60));
+    let first = registry.launch("quick").unwrap();
+    let second = registry.launch("quick.desktop").unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first.id(), Some("quick"));
+
+    let registry = Registry::load_from(&apps);
+    let first = registry.launch("quick").unwrap();
+    let second = registry.launch("quick").unwrap();
+    assert_ne!(first.pid(), second.pid());
+
+    assert!(matches!(registry.launch("missing"), Err(ExecuteError::NotFound(_))));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+struct TestWindow {
+    app_id: &'static str,
+    activated: Cell<bool>,
+}
+
+impl WindowHint for TestWindow {
+    fn app_id(&self) -> Option<&str> {
+        Some(self.app_id)
+    }
+
+    fn activate(&self) -> bool {
+        self.activated.set(true);
+        true
+    }
+}
+
+#[test]
+fn test_registry_focus_or_launch() {
+    let temp_dir = "/tmp/test_registry_focus";
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let apps = PathBuf::from(format!("{}/applications", temp_dir));
+    fs::write(apps.join("org.example.Single.desktop"), "[Desktop Entry]\nType=Application\nName=Single\nExec=/bin/sleep 5\nSingleMainWindow=true").unwrap();
+
+    let registry = Registry::load_from(&[apps]);
+
+    let windows = [
+        TestWindow { app_id: "org.other.App", activated: Cell::new(false) },
+        TestWindow { app_id: "org.example.Single", activated: Cell::new(false) },
+    ];
+    assert_eq!(registry.focus_or_launch("org.example.Single", &windows).unwrap(), FocusOutcome::Focused);
+    assert!(!windows[0].activated.get());
+    assert!(windows[1].activated.get());
+
+    let no_windows: [TestWindow; 0] = [];
+    let launched = match registry.focus_or_launch("org.example.Single", &no_windows).unwrap() {
+        FocusOutcome::Launched(launched) => launched,
+        other => panic!("Expected a launch, got: {:?}", other),
+    };
+    assert_eq!(
+        registry.focus_or_launch("org.example.Single", &no_windows).unwrap(),
+        FocusOutcome::AlreadyRunning(launched.clone())
+    );
+
+    std::process::Command::new("kill").arg(launched.pid().unwrap().to_string()).status().ok();
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_registry_tracks_repeated_failures() {
+    let temp_dir = "/tmp/test_registry_failures";
+    fs::remove_dir_all(temp_dir).ok();
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let apps = PathBuf::from(format!("{}/applications", temp_dir));
+    fs::write(apps.join("flaky.desktop"), "[Desktop Entry]\nType=Application\nName=Flaky\nExec=flaky\nTryExec=/nonexistent/flaky").unwrap();
+
+    let failures = LaunchFailures::new(format!("{}/state/launch-failures", temp_dir)).threshold(2);
+    let registry = Registry::load_from(&[apps]).with_failure_tracking(failures.clone());
+
+    assert!(registry.launch("flaky").is_err());
+    assert!(!registry.is_failing("flaky"));
+    assert!(registry.launch("flaky").is_err());
+    assert!(registry.is_failing("flaky.desktop"));
+
+    let failure = registry.last_failure("flaky").unwrap();
+    assert_eq!(failure.count, 2);
+    assert!(failure.last_error.starts_with("Validation failed"));
+
+    // The state is persisted, so a fresh store sees it too
+    let reopened = LaunchFailures::new(failures.path()).threshold(2);
+    assert!(reopened.is_failing("flaky"));
+    reopened.clear("flaky").unwrap();
+    assert!(!registry.is_failing("flaky"));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_registry_tracks_early_exits() {
+    let temp_dir = "/tmp/test_registry_early_exit";
+    fs::remove_dir_all(temp_dir).ok();
+    let apps = PathBuf::from(format!("{}/applications", temp_dir));
+    fs::create_dir_all(&apps).unwrap();
+    fs::write(apps.join("crashy.desktop"), "[Desktop Entry]\nType=Application\nName=Crashy\nExec=sh -c \"exit 3\"").unwrap();
+
+    let failures = LaunchFailures::new(format!("{}/state/launch-failures", temp_dir));
+    let registry = Registry::load_from(&[apps]).with_failure_tracking(failures);
+
+    // The program starts, then exits with an error right away
+    assert!(matches!(registry.launch("crashy"), Err(ExecuteError::ExitedEarly { code: Some(3), .. })));
+    let failure = registry.last_failure("crashy").unwrap();
+    assert_eq!(failure.count, 1);
+    assert!(failure.last_error.contains("code 3"));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_registry_launches_in_parallel() {
+    let temp_dir = "/tmp/test_registry_parallel";
+    fs::remove_dir_all(temp_dir).ok();
+    let apps = PathBuf::from(format!("{}/applications", temp_dir));
+    fs::create_dir_all(&apps).unwrap();
+    for name in ["one", "two"] {
+        fs::write(apps.join(format!("{}.desktop", name)), "[Desktop Entry]\nType=Application\nName=Sleeper\nExec=sleep 5").unwrap();
+    }
+
+    let failures = LaunchFailures::new(format!("{}/state/launch-failures", temp_dir));
+    let registry = Registry::load_from(&[apps])
+        .with_debounce(Duration::from_secs(60))
+        .with_failure_tracking(failures)
+        .with_failure_grace(Duration::from_millis(500));
+
+    // Each launch waits out the grace period, which different entries do side by side
+    let start = std::time::Instant::now();
+    let launched: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = ["one", "two", "one"]
+            .into_iter()
+            .map(|id| {
+                let registry = &registry;
+                scope.spawn(move || registry.launch(id).unwrap())
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    assert!(start.elapsed() < Duration::from_millis(900));
+
+    // A concurrent launch of the same entry waits for the first one and reuses it
+    assert_eq!(launched[0], launched[2]);
+    assert_ne!(launched[0].pid(), launched[1].pid());
+
+    for pid in launched[..2].iter().filter_map(|launched| launched.pid()) {
+        unsafe { libc::kill(pid as i32, libc::SIGTERM) };
+    }
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_wine_entry_compatibility() {
+    let temp_file = "/tmp/wine_start_test.desktop";
+    fs::write(temp_file,
+        r#"[Desktop Entry]
+Type=Application
+Name=Some Game
+Exec=env WINEPREFIX="/home/user/.wine" wine C:\\\\windows\\\\command\\\\start.exe /Unix /home/user/Desktop/Some\\ Game.lnk
+"#).unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.is_wine());
+    assert_eq!(entry.wine_prefix().as_deref(), Some("/home/user/.wine"));
+
+    let (program, args) = entry.prepare_command(&[], &[]).unwrap();
+    assert_eq!(program, "env");
+    assert_eq!(args, vec![
+        "WINEPREFIX=/home/user/.wine",
+        "wine",
+        r"C:\windows\command\start.exe",
+        "/Unix",
+        "/home/user/Desktop/Some Game.lnk",
+    ]);
+
+    // Programs run directly by Wine get files as Windows paths
+    fs::write(temp_file,
+        "[Desktop Entry]\nType=Application\nName=Editor\nExec=wine notepad.exe %f\n"
+    ).unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.is_wine());
+    assert_eq!(entry.wine_prefix(), None);
+
+    let (program, args) = entry.prepare_command(&["/home/user/my notes.txt"], &[]).unwrap();
+    assert_eq!(program, "wine");
+    assert_eq!(args, vec!["notepad.exe", r"Z:\home\user\my notes.txt"]);
+
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Wine Config\nExec=winecfg\n").unwrap();
+    assert!(!ApplicationEntry::try_from_path(temp_file).unwrap().is_wine());
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_terminal_hold_option() {
+    let _guard = TERMINAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let temp_dir = "/tmp/test_terminal_hold";
+    fs::create_dir_all(temp_dir).unwrap();
+    let temp_file = format!("{}/hold.desktop", temp_dir);
+    fs::write(&temp_file,
+        "[Desktop Entry]\nType=Application\nName=Hold Test\nExec=htop -d 10\nTerminal=true\n"
+    ).unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+    let hold = LaunchOptions::new().hold_terminal(true);
+
+    // Emulators with a hold flag get it before -e
+    let xterm = format!("{}/xterm", temp_dir);
+    write_executable(&xterm);
+    std::env::set_var("TERMINAL", &xterm);
+    let (program, args) = entry.prepare_command_with(&[], &[], &hold).unwrap();
+    assert_eq!(program, xterm);
+    assert_eq!(args, vec!["-hold", "-e", "htop", "-d", "10"]);
+
+    // Others run the program through a shell that waits for Enter
+    let gnome_terminal = format!("{}/gnome-terminal", temp_dir);
+    write_executable(&gnome_terminal);
+    std::env::set_var("TERMINAL", &gnome_terminal);
+    let (program, args) = entry.prepare_command_with(&[], &[], &hold).unwrap();
+    assert_eq!(program, gnome_terminal);
+    assert_eq!(&args[..3], &["--", "sh", "-c"]);
+    assert_eq!(&args[4..], &["sh", "htop", "-d", "10"]);
+
+    std::env::remove_var("TERMINAL");
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_launch_plan() {
+    let _guard = TERMINAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let temp_dir = "/tmp/test_launch_plan";
+    fs::create_dir_all(temp_dir).unwrap();
+    let temp_file = format!("{}/plan.desktop", temp_dir);
+    fs::write(&temp_file,
+        "[Desktop Entry]\nType=Application\nName=Plan\nExec=htop --open %f\nPath=/srv\nTerminal=true\nStartupNotify=true\n"
+    ).unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+    let xterm = format!("{}/xterm", temp_dir);
+    write_executable(&xterm);
+    std::env::set_var("TERMINAL", &xterm);
+
+    let options = LaunchOptions::new()
+        .env("LANG", "C")
+        .env_remove("http_proxy")
+        .activation_token("token")
+        .detach(false);
+    let plan = entry.plan(&["/tmp/a.txt"], &[], &options).unwrap();
+    assert_eq!(plan.program(), xterm);
+    assert_eq!(plan.args(), ["-e", "htop", "--open", "/tmp/a.txt"]);
+    assert_eq!(plan.terminal_command().unwrap(), ["htop", "--open", "/tmp/a.txt"]);
+    assert_eq!(plan.working_dir(), Some(std::path::Path::new("/srv")));
+    assert_eq!(plan.env_remove().collect::<Vec<_>>(), ["http_proxy"]);
+    assert!(!plan.detaches() && !plan.clears_env());
+
+    // The token, launched desktop file and startup ID are part of the environment
+    let startup = plan.startup().unwrap();
+    let env: Vec<(&str, &str)> = plan.env().collect();
+    assert_eq!(env, [
+        ("LANG", "C"),
+        ("XDG_ACTIVATION_TOKEN", "token"),
+        ("GIO_LAUNCHED_DESKTOP_FILE", temp_file.as_str()),
+        ("DESKTOP_STARTUP_ID", startup.id()),
+    ]);
+    let command = plan.command();
+    assert_eq!(command.get_program(), xterm.as_str());
+    assert_eq!(command.get_current_dir(), Some(std::path::Path::new("/srv")));
+
+    // Plans fail the same way launches do
+    fs::write(&temp_file, "[Desktop Entry]\nType=Application\nName=Plan\nExec=plan\nTryExec=definitely-not-installed-program\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+    assert!(entry.plan(&[], &[], &options).is_err());
+
+    std::env::remove_var("TERMINAL");
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_gio_launched_env() {
+    let temp_file = "/tmp/gio_launched_env_test.desktop";
+    fs::write(temp_file, r#"[Desktop Entry]
+Type=Application
+Name=GIO
+Exec=sh -c "echo \$GIO_LAUNCHED_DESKTOP_FILE \$GIO_LAUNCHED_DESKTOP_FILE_PID \$\$"
+"#).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let launcher = Arc::new(OutputLauncher::default());
+    let options = LaunchOptions::new().detach(false).launcher(launcher.clone());
+
+    // The PID is only set when asked for
+    entry.launch_with(&options).unwrap();
+    let output = launcher.output.lock().unwrap().clone();
+    let fields: Vec<&str> = output.split(' ').collect();
+    assert_eq!(fields.len(), 2, "{}", output);
+    assert_eq!(fields[0], temp_file);
+
+    // The PID is that of the program itself
+    entry.launch_with(&options.clone().gio_launched_pid(true)).unwrap();
+    let output = launcher.output.lock().unwrap().clone();
+    let fields: Vec<&str> = output.split(' ').collect();
+    assert_eq!(fields.len(), 3, "{}", output);
+    assert_eq!(fields[0], temp_file);
+    assert_eq!(fields[1], fields[2]);
+
+    // Only the program's own PID is left when turned off
+    entry.launch_with(&options.clone().gio_launched_env(false)).unwrap();
+    assert!(launcher.output.lock().unwrap().parse::<u32>().is_ok());
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_launch_id_env() {
+    let temp_file = "/tmp/launch_id_env_test.desktop";
+    fs::write(temp_file, r#"[Desktop Entry]
+Type=Application
+Name=Launch ID
+Exec=sh -c "echo \$XDG_LAUNCH_ID"
+"#).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let launcher = Arc::new(OutputLauncher::default());
+    let options = LaunchOptions::new().detach(false).launcher(launcher.clone());
+
+    entry.launch_with(&options.clone().launch_id("menu-click-7")).unwrap();
+    assert_eq!(*launcher.output.lock().unwrap(), "menu-click-7");
+
+    // A variable set directly takes precedence, like XDG_ACTIVATION_TOKEN
+    entry.launch_with(&options.clone().launch_id("menu-click-7").env("XDG_LAUNCH_ID", "set")).unwrap();
+    assert_eq!(*launcher.output.lock().unwrap(), "set");
+
+    let plan = entry.plan(&[], &[], &LaunchOptions::new().launch_id("menu-click-8")).unwrap();
+    assert!(plan.env().any(|var| var == ("XDG_LAUNCH_ID", "menu-click-8")));
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_exited_children_are_reaped() {
+    let temp_file = "/tmp/reap_children_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Quick\nExec=/bin/true\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+
+    for options in [LaunchOptions::new(), LaunchOptions::new().detach(false)] {
+        let launched = entry.launch_with(&options.launcher(Arc::new(SpawnLauncher))).unwrap();
+        let proc = format!("/proc/{}", launched.pid().unwrap());
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        // A zombie keeps its /proc entry until it is waited for
+        while Path::new(&proc).exists() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!Path::new(&proc).exists());
+    }
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_diagnose_startup_reports_early_exit() {
+    let temp_file = "/tmp/diagnose_startup_test.desktop";
+    let options = LaunchOptions::new()
+        .launcher(Arc::new(SpawnLauncher))
+        .diagnose_startup(Duration::from_secs(5));
+
+    fs::write(temp_file, r#"[Desktop Entry]
+Type=Application
+Name=Broken
+Exec=sh -c "echo 'libfoo.so.1: cannot open shared object file' >&2 && exit 127"
+"#).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    match entry.launch_with(&options) {
+        Err(ExecuteError::ExitedEarly { code, stderr }) => {
+            assert_eq!(code, Some(127));
+            assert_eq!(stderr, "libfoo.so.1: cannot open shared object file\n");
+        }
+        other => panic!("Expected ExitedEarly, got: {:?}", other),
+    }
+
+    // Programs that finish successfully or keep running are fine
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Quick\nExec=true --quick\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.launch_with(&options).is_ok());
+
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Slow\nExec=sh -c \"echo starting >&2 && sleep 1\"\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let started = std::time::Instant::now();
+    let launched = entry.launch_with(&options.clone().diagnose_startup(Duration::from_millis(200))).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert!(launched.is_running());
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_pkexec_elevation() {
+    let temp_file = "/tmp/pkexec_elevation_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Partitions\nExec=partitions --all\nPath=/tmp\nX-KDE-RootOnly=true\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.requires_root());
+
+    let argv = |plan: &freedesktop_apps::LaunchPlan| -> Vec<String> {
+        let command = plan.command();
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    };
+    let options = LaunchOptions::new()
+        .gio_launched_env(false)
+        .env("LANG", "C")
+        .launch_id("settings-button");
+
+    let plan = entry.plan(&[], &[], &options.clone().elevation(Elevation::IfRequired)).unwrap();
+    assert!(plan.elevates());
+    assert_eq!(plan.program(), "partitions");
+    let elevated = argv(&plan);
+    assert_eq!(elevated[0], "pkexec");
+    assert!(elevated[1].ends_with("env"));
+    assert!(elevated.contains(&"--chdir=/tmp".to_string()));
+    assert!(elevated.contains(&"LANG=C".to_string()));
+    assert!(elevated.contains(&"XDG_LAUNCH_ID=settings-button".to_string()));
+    assert_eq!(elevated[elevated.len() - 2..], ["partitions", "--all"]);
+
+    // Entries that don't need root run as the user unless elevation is forced
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let plan = entry.plan(&[], &[], &options.clone().elevation(Elevation::IfRequired)).unwrap();
+    assert!(!plan.elevates());
+    assert_eq!(argv(&plan), ["viewer"]);
+    let plan = entry.plan(&[], &[], &options.clone().elevation(Elevation::Always)).unwrap();
+    assert_eq!(argv(&plan)[0], "pkexec");
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_detach_modes() {
+    let temp_file = "/tmp/detach_modes_test.desktop";
+    let stat_file = "/tmp/detach_modes_test.stat";
+    fs::write(temp_file, format!(r#"[Desktop Entry]
+Type=Application
+Name=Detach
+Exec=sh -c "cat /proc/\$\$/stat > {stat_file}.tmp && mv {stat_file}.tmp {stat_file}"
+"#)).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+
+    // Returns the PID, process group and session of the started shell
+    let ids = |mode: DetachMode| -> (Option<u32>, [u32; 3]) {
+        fs::remove_file(stat_file).ok();
+        let options = LaunchOptions::new().gio_launched_env(false).detach_mode(mode);
+        let pid = entry.launch_with(&options).unwrap().pid();
+        for _ in 0..200 {
+            if let Ok(stat) = fs::read_to_string(stat_file) {
+                let fields: Vec<u32> = stat.rsplit(')').next().unwrap().split_whitespace().skip(2).take(3).map(|f| f.parse().unwrap()).collect();
+                let pid_field = stat.split_whitespace().next().unwrap().parse().unwrap();
+                return (pid, [pid_field, fields[0], fields[1]]);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("{:?} launch didn't run", mode);
+    };
+    let own_session = unsafe { libc::getsid(0) } as u32;
+
+    let (pid, [shell, group, session]) = ids(DetachMode::ProcessGroup);
+    assert_eq!(pid, Some(shell));
+    assert_eq!(group, shell);
+    assert_eq!(session, own_session);
+
+    let (pid, [shell, group, session]) = ids(DetachMode::Session);
+    assert_eq!(pid, Some(shell));
+    assert_eq!((group, session), (shell, shell));
+
+    let (pid, [shell, _, session]) = ids(DetachMode::DoubleFork);
+    assert_eq!(pid, None);
+    assert_eq!(session, shell);
+
+    fs::remove_file(temp_file).ok();
+    fs::remove_file(stat_file).ok();
+}
+
+#[derive(Debug, Default)]
+struct RecordingHooks {
+    refuse: bool,
+    pids: Mutex<Vec<u32>>,
+}
+
+impl LaunchHooks for RecordingHooks {
+    fn before_spawn(&self, entry: &ApplicationEntry, command: &mut std::process::Command) -> Result<(), ExecuteError> {
+        if self.refuse {
+            return Err(ExecuteError::ValidationFailed(format!("{} is not allowed", entry.name().unwrap())));
+        }
+        command.env("HOOKED", "yes");
+        command.stdout(fs::File::create("/tmp/launch_hooks_test.out").unwrap());
+        Ok(())
+    }
+
+    fn after_spawn(&self, _entry: &ApplicationEntry, pid: u32) {
+        self.pids.lock().unwrap().push(pid);
+    }
+}
+
+#[test]
+fn test_launch_hooks() {
+    let temp_file = "/tmp/launch_hooks_test.desktop";
+    let output = "/tmp/launch_hooks_test.out";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Hooked\nExec=sh -c \"echo \\$HOOKED\"\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    fs::remove_file(output).ok();
+
+    let hooks = Arc::new(RecordingHooks::default());
+    let options = LaunchOptions::new().launcher(Arc::new(SpawnLauncher)).hooks(hooks.clone());
+    let launched = entry.launch_with(&options).unwrap();
+    assert_eq!(*hooks.pids.lock().unwrap(), [launched.pid().unwrap()]);
+    let mut written = String::new();
+    for _ in 0..200 {
+        written = fs::read_to_string(output).unwrap_or_default();
+        if !written.is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(written, "yes\n");
+
+    // Hooks apply to spawn_with too, and can cancel the launch
+    let mut child = entry.spawn_with(&[], &[], &options).unwrap();
+    child.wait().unwrap();
+    assert_eq!(hooks.pids.lock().unwrap().len(), 2);
+    let refusing = LaunchOptions::new().hooks(Arc::new(RecordingHooks { refuse: true, ..Default::default() }));
+    match entry.launch_with(&refusing) {
+        Err(ExecuteError::ValidationFailed(message)) => assert_eq!(message, "Hooked is not allowed"),
+        other => panic!("Expected the hook to refuse, got: {:?}", other),
+    }
+
+    fs::remove_file(temp_file).ok();
+    fs::remove_file(output).ok();
+}
+
+#[test]
+fn test_terminal_profiles() {
+    let _guard = TERMINAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let temp_dir = "/tmp/test_terminal_profiles";
+    fs::create_dir_all(temp_dir).unwrap();
+    let temp_file = format!("{}/profiles.desktop", temp_dir);
+    fs::write(&temp_file,
+        "[Desktop Entry]\nType=Application\nName=Profile Test\nExec=htop -d 10\nTerminal=true\n"
+    ).unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+    let plain = LaunchOptions::new();
+    let hold = LaunchOptions::new().hold_terminal(true);
+
+    let terminal = |name: &str| {
+        let path = format!("{}/{}", temp_dir, name);
+        write_executable(&path);
+        path
+    };
+
+    // Each built-in terminal takes the command its own way
+    std::env::set_var("TERMINAL", terminal("gnome-terminal"));
+    assert_eq!(entry.prepare_command_with(&[], &[], &plain).unwrap().1, vec!["--", "htop", "-d", "10"]);
+    std::env::set_var("TERMINAL", terminal("xfce4-terminal"));
+    assert_eq!(entry.prepare_command_with(&[], &[], &plain).unwrap().1, vec!["-x", "htop", "-d", "10"]);
+    std::env::set_var("TERMINAL", terminal("kitty"));
+    assert_eq!(entry.prepare_command_with(&[], &[], &hold).unwrap().1, vec!["--hold", "htop", "-d", "10"]);
+    std::env::set_var("TERMINAL", terminal("wezterm"));
+    assert_eq!(entry.prepare_command_with(&[], &[], &plain).unwrap().1, vec!["start", "--", "htop", "-d", "10"]);
+
+    // Unknown terminals get -e
+    std::env::set_var("TERMINAL", terminal("unknown-term"));
+    assert_eq!(entry.prepare_command_with(&[], &[], &plain).unwrap().1, vec!["-e", "htop", "-d", "10"]);
+
+    // Registered terminals replace the built-in handling
+    register_terminal(Arc::new(
+        TerminalProfile::new("my-term").exec_args(["--command"]).hold_args(["--keep-open"]),
+    ));
+    let my_term = terminal("my-term");
+    std::env::set_var("TERMINAL", &my_term);
+    let (program, args) = entry.prepare_command_with(&[], &[], &hold).unwrap();
+    assert_eq!(program, my_term);
+    assert_eq!(args, vec!["--keep-open", "--command", "htop", "-d", "10"]);
+
+    std::env::remove_var("TERMINAL");
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_launch_options_environment() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = "/tmp/test_launch_options_env";
+    let work_dir = format!("{}/work", temp_dir);
+    fs::create_dir_all(&work_dir).unwrap();
+    let script = format!("{}/report.sh", temp_dir);
+    let out = format!("{}/out", temp_dir);
+    fs::remove_file(&out).ok();
+    fs::write(&script, "#!/bin/sh\nprintf '%s|%s|%s' \"$GREETING\" \"$HOME\" \"$(pwd)\" > \"$OUT.tmp\"\nmv \"$OUT.tmp\" \"$OUT\"\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let temp_file = format!("{}/report.desktop", temp_dir);
+    fs::write(&temp_file, format!("[Desktop Entry]\nType=Application\nName=Report\nExec={}\nPath=/\n", script)).unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+
+    let options = LaunchOptions::new()
+        .env("GREETING", "hello")
+        .env("OUT", &out)
+        .env_remove("HOME")
+        .working_dir(&work_dir)
+        .detach(false);
+    entry.launch_with(&options).unwrap();
+
+    let mut report = None;
+    for _ in 0..100 {
+        if let Ok(content) = fs::read_to_string(&out) {
+            report = Some(content);
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(report, Some(format!("hello||{}", work_dir)));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[derive(Debug, Default)]
+struct OutputLauncher {
+    fail: bool,
+    output: Mutex<String>,
+}
+
+impl Launcher for OutputLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        if self.fail {
+            return Err(ExecuteError::InvalidCommand("refused".to_string()));
+        }
+        let output = request.command().stdout(std::process::Stdio::piped()).output()?;
+        *self.output.lock().unwrap() = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Default)]
+struct RecordingNotifier {
+    messages: Mutex<Vec<String>>,
+}
+
+impl StartupNotifier for RecordingNotifier {
+    fn begin(&self, sequence: &StartupSequence) {
+        self.messages.lock().unwrap().push(sequence.new_message());
+    }
+
+    fn complete(&self, sequence: &StartupSequence) {
+        self.messages.lock().unwrap().push(sequence.remove_message());
+    }
+}
+
+#[test]
+fn test_startup_notification() {
+    let temp_file = "/tmp/startup_notify_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Startup \"Test\"\nExec=printenv DESKTOP_STARTUP_ID\nIcon=startup-test\nStartupNotify=true\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.startup_notify());
+
+    let launcher = Arc::new(OutputLauncher::default());
+    let notifier = Arc::new(RecordingNotifier::default());
+    let options = LaunchOptions::new()
+        .detach(false)
+        .launcher(launcher.clone())
+        .startup_notifier(notifier.clone())
+        .startup_time(1234);
+
+    // The program gets the ID and the sequence is announced before it starts
+    let launched = entry.launch_with(&options).unwrap();
+    let startup = launched.startup().unwrap();
+    assert!(startup.id().ends_with("_TIME1234"));
+    assert_eq!(*launcher.output.lock().unwrap(), startup.id());
+    let new_message = notifier.messages.lock().unwrap()[0].clone();
+    assert!(new_message.starts_with(&format!("new: ID=\"{}\" NAME=\"Startup \\\"Test\\\"\"", startup.id())));
+    assert!(new_message.contains(" BIN=\"printenv\" ICON=\"startup-test\""));
+    assert_eq!(notifier.messages.lock().unwrap().len(), 1);
+
+    // A failed launch ends its sequence
+    let failing = options.clone().launcher(Arc::new(OutputLauncher { fail: true, ..Default::default() }));
+    assert!(entry.launch_with(&failing).is_err());
+    let messages = notifier.messages.lock().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert!(messages[2].starts_with("remove: ID=\""));
+    assert_ne!(messages[2], format!("remove: ID=\"{}\"", startup.id()));
+    drop(messages);
+
+    // Caller-provided IDs and entries without StartupNotify get no sequence
+    let launched = entry.launch_with(&options.clone().env("DESKTOP_STARTUP_ID", "given")).unwrap();
+    assert!(launched.startup().is_none());
+    assert_eq!(*launcher.output.lock().unwrap(), "given");
+
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Quiet\nExec=printenv DESKTOP_STARTUP_ID\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.launch_with(&options).unwrap().startup().is_none());
+
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Quiet\nExec=printenv DESKTOP_STARTUP_ID\nStartupWMClass=Quiet\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.launch_with(&options).unwrap().startup().is_none());
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[derive(Debug, Default)]
+struct CountingTokens {
+    issued: Mutex<u32>,
+}
+
+impl ActivationTokenProvider for CountingTokens {
+    fn token(&self, entry: &ApplicationEntry) -> Option<String> {
+        let mut issued = self.issued.lock().unwrap();
+        *issued += 1;
+        Some(format!("{}-{}", entry.name()?, issued))
+    }
+}
+
+#[test]
+fn test_activation_tokens() {
+    let temp_file = "/tmp/activation_token_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Token\nExec=printenv XDG_ACTIVATION_TOKEN\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+
+    let launcher = Arc::new(OutputLauncher::default());
+    let options = LaunchOptions::new().detach(false).launcher(launcher.clone());
+    let output = || launcher.output.lock().unwrap().clone();
+
+    entry.launch_with(&options.clone().activation_token("given")).unwrap();
+    assert_eq!(output(), "given");
+
+    // The provider hands out a fresh token for each launch
+    let tokens = Arc::new(CountingTokens::default());
+    let provided = options.clone().activation_token_provider(tokens.clone());
+    entry.launch_with(&provided).unwrap();
+    assert_eq!(output(), "Token-1");
+    entry.launch_with(&provided).unwrap();
+    assert_eq!(output(), "Token-2");
+
+    // An explicit token or environment variable wins without asking the provider
+    entry.launch_with(&provided.clone().activation_token("given")).unwrap();
+    assert_eq!(output(), "given");
+    entry.launch_with(&provided.clone().env("XDG_ACTIVATION_TOKEN", "from-env")).unwrap();
+    assert_eq!(output(), "from-env");
+    assert_eq!(*tokens.issued.lock().unwrap(), 2);
+
+    // No token is requested for launches that fail validation
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Token\nExec=missing\nTryExec=definitely-not-installed-program\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.launch_with(&provided).is_err());
+    assert_eq!(*tokens.issued.lock().unwrap(), 2);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_single_file_exec_launches_per_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = "/tmp/test_exec_per_file";
+    fs::create_dir_all(temp_dir).unwrap();
+    let script = format!("{}/record.sh", temp_dir);
+    let out = format!("{}/out", temp_dir);
+    fs::write(&script, format!("#!/bin/sh\necho \"$*\" >> {}\n", out)).unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let wait_for_lines = |count: usize| {
+        for _ in 0..100 {
+            let lines: Vec<String> = fs::read_to_string(&out).unwrap_or_default().lines().map(str::to_string).collect();
+            if lines.len() >= count {
+                std::thread::sleep(Duration::from_millis(50));
+                let mut lines: Vec<String> = fs::read_to_string(&out).unwrap().lines().map(str::to_string).collect();
+                lines.sort();
+                fs::remove_file(&out).ok();
+                return lines;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Expected {} launches", count);
+    };
+
+    let temp_file = format!("{}/single.desktop", temp_dir);
+    fs::write(&temp_file, format!("[Desktop Entry]\nType=Application\nName=Single\nExec={} %f\n", script)).unwrap();
+    ApplicationEntry::try_from_path(&temp_file).unwrap().execute_with_files(&["/tmp/a", "/tmp/b"]).unwrap();
+    assert_eq!(wait_for_lines(2), vec!["/tmp/a", "/tmp/b"]);
+
+    fs::write(&temp_file, format!("[Desktop Entry]\nType=Application\nName=Single\nExec={} %u\n", script)).unwrap();
+    ApplicationEntry::try_from_path(&temp_file).unwrap().execute_with_urls(&["https://a.example", "https://b.example"]).unwrap();
+    assert_eq!(wait_for_lines(2), vec!["https://a.example", "https://b.example"]);
+
+    // %F takes all files in one instance
+    fs::write(&temp_file, format!("[Desktop Entry]\nType=Application\nName=Multi\nExec={} %F\n", script)).unwrap();
+    ApplicationEntry::try_from_path(&temp_file).unwrap().execute_with_files(&["/tmp/a", "/tmp/b"]).unwrap();
+    assert_eq!(wait_for_lines(1), vec!["/tmp/a /tmp/b"]);
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_spawn_returns_child() {
+    let temp_file = "/tmp/spawn_child_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Exit\nExec=sh -c \"exit 3\"\n").unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let mut child = entry.spawn_with(&[], &[], &LaunchOptions::new()).unwrap();
+    assert!(child.id() > 0);
+    assert_eq!(child.wait().unwrap().code(), Some(3));
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[derive(Debug, Default)]
+struct RecordingLauncher {
+    argv: Mutex<Vec<String>>,
+}
+
+impl Launcher for RecordingLauncher {
+    fn launch(&self, request: &LaunchRequest<'_>) -> Result<Option<u32>, ExecuteError> {
+        let mut argv = self.argv.lock().unwrap();
+        argv.push(request.program().to_string());
+        argv.extend(request.args().iter().cloned());
+        assert_eq!(request.working_dir(), Some(std::path::Path::new("/tmp")));
+        Ok(Some(42))
+    }
+}
+
+#[test]
+fn test_custom_launcher_backend() {
+    let temp_file = "/tmp/custom_launcher_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Echo\nExec=echo --open %f\nPath=/tmp\n").unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let launcher = Arc::new(RecordingLauncher::default());
+    let options = LaunchOptions::new().launcher(launcher.clone());
+
+    let launched = entry.launch_with(&options).unwrap();
+    assert_eq!(launched.pid(), Some(42));
+    assert_eq!(*launcher.argv.lock().unwrap(), ["echo", "--open"]);
+
+    // Validation still happens before the backend is called
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Missing\nExec=missing\nTryExec=definitely-not-installed-program\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.launch_with(&options).is_err());
+    assert_eq!(launcher.argv.lock().unwrap().len(), 2);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_flatpak_spawn_launcher() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Stands in for flatpak-spawn and records the arguments it was given
+    let script = "/tmp/flatpak_spawn_test.sh";
+    let output = "/tmp/flatpak_spawn_test.out";
+    fs::remove_file(output).ok();
+    fs::write(script, format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > {}.tmp && mv {}.tmp {}\n", output, output, output)).unwrap();
+    fs::set_permissions(script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let temp_file = "/tmp/flatpak_spawn_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Host App\nExec=sh --open %f\nPath=/srv\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let options = LaunchOptions::new()
+        .launcher(Arc::new(FlatpakSpawnLauncher::with_executable(script)))
+        .env("LANG", "C")
+        .env_remove("http_proxy")
+        .working_dir("/tmp");
+    assert!(entry.launch_with(&options).unwrap().pid().is_some());
+
+    let mut args = None;
+    for _ in 0..100 {
+        if let Ok(content) = fs::read_to_string(output) {
+            args = Some(content);
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(
+        args.unwrap().lines().collect::<Vec<_>>(),
+        [
+            "--host",
+            "--directory=/tmp",
+            "--unset-env=http_proxy",
+            "--env=LANG=C",
+            "--env=GIO_LAUNCHED_DESKTOP_FILE=/tmp/flatpak_spawn_test.desktop",
+            "sh",
+            "--open",
+        ]
+    );
+
+    fs::remove_file(temp_file).ok();
+    fs::remove_file(script).ok();
+    fs::remove_file(output).ok();
+}
+
+#[cfg(feature = "dbus")]
+#[test]
+fn test_transient_scope_launcher_starts_program() {
+    use freedesktop_apps::TransientScopeLauncher;
+
+    let temp_file = "/tmp/transient_scope_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Scoped\nExec=true --scoped\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+
+    // The program runs whether or not a user manager takes it into a scope
+    let options = LaunchOptions::new().launcher(Arc::new(TransientScopeLauncher::new()));
+    assert!(entry.launch_with(&options).unwrap().pid().is_some());
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[cfg(feature = "dbus")]
+#[test]
+fn test_activation_falls_back_to_exec() {
+    let temp_file = "/tmp/activation_fallback_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Activatable\nExec=true %U\nDBusActivatable=true\n").unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert!(entry.dbus_activatable());
+
+    // The file has no desktop file ID, so there is no bus name to activate
+    let launched = entry.activate_with(&["https://example.com"], &LaunchOptions::new()).unwrap();
+    assert!(launched.is_some());
+    assert!(matches!(
+        entry.activate_action("new-window", &LaunchOptions::new()),
+        Err(ExecuteError::NotExecutable(_))
+    ));
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_require_trusted() {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_file = "/tmp/untrusted_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Untrusted\nExec=echo hello\n").unwrap();
+    fs::set_permissions(temp_file, fs::Permissions::from_mode(0o644)).unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let options = LaunchOptions::new().require_trusted(true);
+
+    assert!(entry.id().is_none());
+    assert!(!entry.is_trusted());
+    match entry.prepare_command_with(&[], &[], &options) {
+        Err(ExecuteError::Untrusted(path)) => assert_eq!(path, PathBuf::from(temp_file)),
+        other => panic!("Expected an untrusted file, got: {:?}", other),
+    }
+    // Without the option the file is run as before
+    assert!(entry.prepare_command_with(&[], &[], &LaunchOptions::new()).is_ok());
+
+    fs::set_permissions(temp_file, fs::Permissions::from_mode(0o755)).unwrap();
+    assert!(entry.is_trusted());
+    assert!(entry.prepare_command_with(&[], &[], &options).is_ok());
+
+    // A downloaded file stays untrusted, where the filesystem supports user attributes
+    let path = std::ffi::CString::new(std::path::Path::new(temp_file).as_os_str().as_bytes()).unwrap();
+    let value = b"https://example.com/app.desktop";
+    let tagged = unsafe {
+        libc::setxattr(path.as_ptr(), c"user.xdg.origin.url".as_ptr(), value.as_ptr().cast(), value.len(), 0) == 0
+    };
+    if tagged {
+        assert!(!entry.is_trusted());
+    }
+
+    // A custom directory gives the entry an ID but doesn't make it trusted
+    let custom_dir = "/tmp/untrusted_test_dir";
+    fs::create_dir_all(custom_dir).unwrap();
+    let custom_file = format!("{}/custom.desktop", custom_dir);
+    fs::write(&custom_file, "[Desktop Entry]\nType=Application\nName=Custom\nExec=echo hello\n").unwrap();
+    fs::set_permissions(&custom_file, fs::Permissions::from_mode(0o644)).unwrap();
+    let entries = ApplicationEntry::all_in(&[PathBuf::from(custom_dir)]);
+    assert_eq!(entries[0].id().as_deref(), Some("custom"));
+    assert!(!entries[0].is_trusted());
+
+    fs::remove_file(temp_file).ok();
+    fs::remove_dir_all(custom_dir).ok();
+}
+
+#[test]
+fn test_mailto_uri() {
+    assert_eq!(MailtoBuilder::new().to_uri(), "mailto:");
+    assert_eq!(MailtoBuilder::new().to("a@example.com").to_uri(), "mailto:a@example.com");
+
+    let mail = MailtoBuilder::new()
+        .to("a@example.com")
+        .to("b+c@example.com")
+        .cc("d@example.com")
+        .bcc("e@example.com")
+        .subject("Q&A? 100%")
+        .body("Line one\nLine two\r\nDone")
+        .attach("/tmp/my report.pdf");
+    assert_eq!(
+        mail.to_uri(),
+        "mailto:a@example.com,b%2Bc@example.com?cc=d@example.com&bcc=e@example.com&subject=Q%26A%3F%20100%25\
+         &body=Line%20one%0D%0ALine%20two%0D%0ADone&attach=file%3A%2F%2F%2Ftmp%2Fmy%2520report.pdf"
+    );
+    assert_eq!(mail.to_string(), mail.to_uri());
+
+    // Relative attachments are made absolute
+    let relative = MailtoBuilder::new().attach("notes.txt").to_uri();
+    assert!(relative.starts_with("mailto:?attach=file%3A%2F%2F%2F"));
+    assert!(relative.ends_with("%2Fnotes.txt"));
+}
@@ -1,5 +1,17 @@
-use freedesktop_apps::ApplicationEntry;
+use freedesktop_apps::{normalize_desktop_id, resolve_desktop_id_in, ApplicationEntry, IdResolveError};
 use std::fs;
+use std::sync::{Mutex, MutexGuard};
+
+// IDs depend on XDG_DATA_DIRS, so tests that change it must not run concurrently
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Make `data_dir` the only XDG data directory until the guard is dropped
+fn use_data_dir(data_dir: &str) -> MutexGuard<'static, ()> {
+    let guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::env::set_var("XDG_DATA_DIRS", data_dir);
+    std::env::remove_var("XDG_DATA_HOME");
+    guard
+}
 
 fn fixture_path(name: &str) -> String {
     format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
@@ -10,8 +22,8 @@ fn test_simple_desktop_file_id() {
     let path = fixture_path("minimal_app.desktop");
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse minimal app");
     
-    // For files not in XDG directories, should use filename without extension
-    assert_eq!(entry.id(), Some("minimal_app".to_string()));
+    // Files outside the XDG data directories have no desktop file ID
+    assert_eq!(entry.id(), None);
 }
 
 #[test]
@@ -35,10 +47,7 @@ Exec=test-app"#;
     fs::write(&simple_file, desktop_content).unwrap();
     fs::write(&nested_file, desktop_content).unwrap();
     
-    // Mock the XDG base directories to include our temp dir
-    // Note: This test shows the concept, but in practice we'd need to mock
-    // freedesktop_core::base_directories() or set XDG environment variables
-    
+    let _guard = use_data_dir(temp_dir);
     let simple_entry = ApplicationEntry::try_from_path(&simple_file).unwrap();
     let nested_entry = ApplicationEntry::try_from_path(&nested_file).unwrap();
     
@@ -71,6 +80,7 @@ Exec=foo-viewer"#;
     
     fs::write(&desktop_file, desktop_content).unwrap();
     
+    let _guard = use_data_dir(&format!("{}/share", temp_base));
     let entry = ApplicationEntry::try_from_path(&desktop_file).unwrap();
     
     // In applications directory, should generate spec-compliant ID
@@ -82,28 +92,44 @@ Exec=foo-viewer"#;
 
 #[test]
 fn test_desktop_file_id_edge_cases() {
+    let temp_dir = "/tmp/test_xdg_edge";
+    let apps_dir = format!("{}/applications", temp_dir);
+    fs::create_dir_all(&apps_dir).unwrap();
+    let _guard = use_data_dir(temp_dir);
+
     // Test with files that have no extension
-    let temp_file = "/tmp/no_extension_test";
-    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Test\nExec=test").unwrap();
-    
-    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
-    assert_eq!(entry.id(), Some("no_extension_test".to_string()));
+    let temp_file = format!("{}/no_extension_test", apps_dir);
+    fs::write(&temp_file, "[Desktop Entry]\nType=Application\nName=Test\nExec=test").unwrap();
     
-    fs::remove_file(temp_file).ok();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+    assert_eq!(entry.id(), None);
     
     // Test with files that have multiple dots
-    let temp_file = "/tmp/complex.name.desktop";
-    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Test\nExec=test").unwrap();
+    let temp_file = format!("{}/complex.name.desktop", apps_dir);
+    fs::write(&temp_file, "[Desktop Entry]\nType=Application\nName=Test\nExec=test").unwrap();
     
-    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
     assert_eq!(entry.id(), Some("complex.name".to_string()));
-    
+
+    // Files outside the data dirs don't get an ID guessed from their name
+    let temp_file = "/tmp/outside_xdg_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Test\nExec=test").unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert_eq!(entry.id(), None);
+
     fs::remove_file(temp_file).ok();
+    fs::remove_dir_all(temp_dir).ok();
 }
 
 #[test]
 fn test_desktop_file_id_real_world_examples() {
     // Test some real-world style desktop file IDs
+    let temp_dir = "/tmp/test_xdg_real_world";
+    let apps_dir = format!("{}/applications", temp_dir);
+    fs::create_dir_all(&apps_dir).unwrap();
+    let _guard = use_data_dir(temp_dir);
+
     let test_cases = vec![
         ("org.gnome.Calculator.desktop", "org.gnome.Calculator"),
         ("firefox.desktop", "firefox"),
@@ -112,12 +138,104 @@ fn test_desktop_file_id_real_world_examples() {
     ];
     
     for (filename, expected_id) in test_cases {
-        let temp_file = format!("/tmp/{}", filename);
+        let temp_file = format!("{}/{}", apps_dir, filename);
         fs::write(&temp_file, "[Desktop Entry]\nType=Application\nName=Test\nExec=test").unwrap();
         
         let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
         assert_eq!(entry.id(), Some(expected_id.to_string()));
-        
-        fs::remove_file(&temp_file).ok();
     }
-}
\ No newline at end of file
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_appstream_component_mapping() {
+    use freedesktop_apps::appstream;
+
+    assert_eq!(appstream::desktop_id_for_component("org.gnome.Nautilus"), "org.gnome.Nautilus");
+    assert_eq!(appstream::desktop_id_for_component("firefox.desktop"), "firefox");
+    assert_eq!(
+        appstream::component_id_candidates("firefox.desktop"),
+        vec!["firefox".to_string(), "firefox.desktop".to_string()]
+    );
+    assert!(appstream::component_matches_desktop_id("firefox.desktop", "firefox"));
+    assert!(!appstream::component_matches_desktop_id("org.mozilla.firefox", "firefox"));
+
+    let metainfo = r#"<component type="desktop-application">
+  <id>org.example.Viewer</id>
+  <launchable type="desktop-id">org.example.Viewer.desktop</launchable>
+  <launchable type="url">https://example.org</launchable>
+</component>"#;
+    assert_eq!(appstream::launchable_desktop_ids(metainfo), vec!["org.example.Viewer".to_string()]);
+
+    let temp_dir = "/tmp/test_xdg_appstream";
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let _guard = use_data_dir(temp_dir);
+
+    let temp_file = format!("{}/applications/org.example.Viewer.desktop", temp_dir);
+    fs::write(&temp_file, "[Desktop Entry]\nType=Application\nName=Viewer\nExec=viewer").unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+    assert!(entry.matches_component_id("org.example.Viewer"));
+    assert!(entry.matches_component_id("org.example.Viewer.desktop"));
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_window_matching() {
+    let temp_dir = "/tmp/test_xdg_window";
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let _guard = use_data_dir(temp_dir);
+
+    let temp_file = format!("{}/applications/org.example.Editor.desktop", temp_dir);
+    fs::write(
+        &temp_file,
+        "[Desktop Entry]\nType=Application\nName=Editor\nExec=/usr/bin/example-editor %F\nStartupWMClass=ExampleEditor\n",
+    )
+    .unwrap();
+    let entry = ApplicationEntry::try_from_path(&temp_file).unwrap();
+
+    assert_eq!(entry.startup_wm_class(), Some("ExampleEditor".to_string()));
+    assert!(entry.matches_window(Some("ExampleEditor"), None));
+    assert!(entry.matches_window(None, Some("org.example.editor")));
+    assert!(entry.matches_window(Some("example-editor"), None));
+    assert!(!entry.matches_window(Some("firefox"), Some("org.mozilla.firefox")));
+    assert!(!entry.matches_window(None, None));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
+
+#[test]
+fn test_desktop_id_normalization() {
+    assert_eq!(normalize_desktop_id(" firefox.desktop "), "firefox");
+    assert_eq!(normalize_desktop_id("/usr/share/applications/org.gnome.Calculator.desktop"), "org.gnome.Calculator");
+    assert_eq!(normalize_desktop_id("org.mozilla.firefox"), "org.mozilla.firefox");
+
+    let temp_dir = "/tmp/test_resolve_ids";
+    fs::create_dir_all(format!("{}/applications", temp_dir)).unwrap();
+    let _guard = use_data_dir(temp_dir);
+    let files = [
+        ("org.mozilla.firefox.desktop", "Firefox"),
+        ("org.gnome.Calc.desktop", "Calculator"),
+        ("org.kde.kcalc.desktop", "Calculator"),
+    ];
+    let entries: Vec<ApplicationEntry> = files
+        .iter()
+        .map(|(file, name)| {
+            let path = format!("{}/applications/{}", temp_dir, file);
+            fs::write(&path, format!("[Desktop Entry]\nType=Application\nName={}\nExec=test", name)).unwrap();
+            ApplicationEntry::try_from_path(&path).unwrap()
+        })
+        .collect();
+
+    assert_eq!(resolve_desktop_id_in("org.mozilla.firefox.desktop", &entries), Ok("org.mozilla.firefox".to_string()));
+    assert_eq!(resolve_desktop_id_in("ORG.MOZILLA.FIREFOX", &entries), Ok("org.mozilla.firefox".to_string()));
+    assert_eq!(resolve_desktop_id_in("firefox", &entries), Ok("org.mozilla.firefox".to_string()));
+    assert_eq!(resolve_desktop_id_in("kcalc", &entries), Ok("org.kde.kcalc".to_string()));
+    assert_eq!(
+        resolve_desktop_id_in("calculator", &entries),
+        Err(IdResolveError::Ambiguous(vec!["org.gnome.Calc".to_string(), "org.kde.kcalc".to_string()]))
+    );
+    assert!(matches!(resolve_desktop_id_in("missing", &entries), Err(IdResolveError::NotFound(_))));
+
+    fs::remove_dir_all(temp_dir).ok();
+}
@@ -1,4 +1,5 @@
-use freedesktop_apps::{ApplicationEntry, ParseError};
+use freedesktop_apps::{ApplicationEntry, Category, DirectoryEntry, EntryQuery, EntryType, LinkEntry, MenuSection, ParseError, Role};
+use std::fs;
 use std::path::Path;
 
 fn fixture_path(name: &str) -> String {
@@ -11,7 +12,7 @@ fn test_complete_application_entry() {
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse complete app");
 
     // Basic required fields
-    assert_eq!(entry.entry_type(), Some("Application".to_string()));
+    assert_eq!(entry.entry_type(), Some(EntryType::Application));
     assert_eq!(entry.name(), Some("Complete Test Application".to_string()));
     assert_eq!(entry.exec(), Some("test-app --mode=%f %F".to_string()));
     
@@ -57,12 +58,76 @@ fn test_complete_application_entry() {
     );
 }
 
+#[test]
+fn test_typed_categories() {
+    let path = fixture_path("complete_app.desktop");
+    let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse complete app");
+
+    assert_eq!(
+        entry.categories_typed(),
+        vec![Category::Development, Category::Utility, Category::Education]
+    );
+    assert!(Category::Development.is_main());
+    assert!(!Category::IDE.is_main());
+    assert_eq!(Category::from("2DGraphics"), Category::Graphics2D);
+    assert_eq!(Category::Graphics2D.as_str(), "2DGraphics");
+    assert_eq!(Category::from("X-Custom"), Category::Other("X-Custom".to_string()));
+    assert!(Category::TrayIcon.is_reserved());
+}
+
+#[test]
+fn test_menu_sections() {
+    let entry = ApplicationEntry::try_from_path(fixture_path("complete_app.desktop")).unwrap();
+    assert_eq!(entry.menu_section(), MenuSection::Development);
+
+    let cases = [
+        ("Audio;Player;", MenuSection::Multimedia),
+        ("Network;WebBrowser;", MenuSection::Internet),
+        ("WebBrowser;", MenuSection::Internet),
+        ("X-Unknown;", MenuSection::Other),
+    ];
+    for (categories, expected) in cases {
+        let temp_file = "/tmp/menu_section_test.desktop";
+        fs::write(
+            temp_file,
+            format!("[Desktop Entry]\nType=Application\nName=Test\nExec=test\nCategories={}\n", categories),
+        )
+        .unwrap();
+        let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+        assert_eq!(entry.menu_section(), expected, "{}", categories);
+        fs::remove_file(temp_file).ok();
+    }
+}
+
+#[test]
+fn test_roles() {
+    let cases = [
+        ("Categories=Network;WebBrowser;", vec![Role::WebBrowser]),
+        ("MimeType=text/html;x-scheme-handler/https;", vec![Role::WebBrowser]),
+        ("Categories=Utility;TextEditor;\nMimeType=text/plain;", vec![Role::TextEditor]),
+        ("Categories=System;TerminalEmulator;", vec![Role::TerminalEmulator]),
+        ("Categories=Graphics;Viewer;\nMimeType=image/png;image/jpeg;", vec![Role::ImageViewer]),
+        ("Categories=Graphics;RasterGraphics;\nMimeType=image/png;", vec![]),
+        ("Categories=AudioVideo;Player;\nMimeType=audio/mpeg;video/mp4;", vec![Role::AudioPlayer]),
+        ("Categories=Audio;\nMimeType=audio/flac;\nImplements=org.mpris.MediaPlayer2;", vec![Role::AudioPlayer]),
+        ("Categories=Audio;Recorder;\nMimeType=audio/flac;", vec![]),
+    ];
+    for (keys, expected) in cases {
+        let temp_file = "/tmp/role_test.desktop";
+        fs::write(temp_file, format!("[Desktop Entry]\nType=Application\nName=Test\nExec=test\n{}\n", keys)).unwrap();
+        let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+        assert_eq!(entry.roles(), expected, "{}", keys);
+        fs::remove_file(temp_file).ok();
+    }
+    assert_eq!(Role::ImageViewer.to_string(), "image-viewer");
+}
+
 #[test]
 fn test_minimal_application_entry() {
     let path = fixture_path("minimal_app.desktop");
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse minimal app");
 
-    assert_eq!(entry.entry_type(), Some("Application".to_string()));
+    assert_eq!(entry.entry_type(), Some(EntryType::Application));
     assert_eq!(entry.name(), Some("Minimal App".to_string()));
     assert_eq!(entry.exec(), Some("minimal-app".to_string()));
     
@@ -82,7 +147,7 @@ fn test_link_entry() {
     let path = fixture_path("link_entry.desktop");
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse link entry");
 
-    assert_eq!(entry.entry_type(), Some("Link".to_string()));
+    assert_eq!(entry.entry_type(), Some(EntryType::Link));
     assert_eq!(entry.name(), Some("Test Website Link".to_string()));
     assert_eq!(entry.comment(), Some("A test link to example.com".to_string()));
     assert_eq!(entry.icon(), Some("web-browser".to_string()));
@@ -92,12 +157,80 @@ fn test_link_entry() {
     assert_eq!(entry.exec(), None);
 }
 
+#[test]
+fn test_typed_entry_wrappers() {
+    let link = LinkEntry::try_from_path(fixture_path("link_entry.desktop")).expect("Failed to parse link entry");
+    assert_eq!(link.url(), Some("https://example.com".to_string()));
+    assert_eq!(link.name(), Some("Test Website Link".to_string()));
+
+    let directory = DirectoryEntry::try_from_path(fixture_path("directory_entry.directory"))
+        .expect("Failed to parse directory entry");
+    assert_eq!(directory.entry_type(), Some(EntryType::Directory));
+    assert_eq!(directory.icon(), Some("folder".to_string()));
+
+    // Wrappers refuse entries of a different type
+    assert!(LinkEntry::try_from_path(fixture_path("minimal_app.desktop")).is_err());
+    let app = ApplicationEntry::try_from_path(fixture_path("minimal_app.desktop")).unwrap();
+    assert!(DirectoryEntry::try_from(app).is_err());
+
+    assert_eq!(EntryType::from("X-Custom"), EntryType::Other("X-Custom".to_string()));
+    assert!(!EntryType::from("X-Custom").is_known());
+    assert_eq!("Link".parse::<EntryType>(), Ok(EntryType::Link));
+}
+
+#[test]
+fn test_entry_type_ignores_case() {
+    let temp_file = "/tmp/entry_type_case_test.desktop";
+    // Exec is still required, because the type is recognized as Application
+    fs::write(temp_file, "[Desktop Entry]\nType=application\nName=Lowercase\n").unwrap();
+    assert!(matches!(
+        ApplicationEntry::try_from_path(temp_file),
+        Err(ParseError::MissingRequiredKey { ref key, .. }) if key == "Exec"
+    ));
+
+    fs::write(temp_file, "[Desktop Entry]\nType=application\nName=Lowercase\nExec=lowercase\n").unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert_eq!(entry.entry_type(), Some(EntryType::Application));
+    assert_eq!(entry.entry_type().unwrap().as_str(), "Application");
+    assert!(EntryQuery::new().entry_type(EntryType::Application).matches(&entry));
+    assert!(!EntryQuery::new().entry_type(EntryType::Link).matches(&entry));
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_version_and_deprecations() {
+    let temp_file = "/tmp/deprecated_keys_test.desktop";
+    fs::write(
+        temp_file,
+        "[Desktop Entry]\nVersion=1.0\nEncoding=UTF-8\nType=Application\nName=Old App\nTerminalOptions=--hold\nExec=old-app %f %d %m\n",
+    )
+    .unwrap();
+
+    let entry = ApplicationEntry::try_from_path(temp_file).expect("Failed to parse deprecated entry");
+    assert_eq!(entry.version(), Some("1.0".to_string()));
+    assert!(!entry.targets_newer_spec());
+    assert_eq!(entry.deprecated_keys(), vec!["Encoding".to_string(), "TerminalOptions".to_string()]);
+    assert_eq!(entry.deprecated_field_codes(), vec!['d', 'm']);
+    assert_eq!(entry.deprecations().len(), 4);
+
+    let modern = ApplicationEntry::try_from_path(fixture_path("minimal_app.desktop")).unwrap();
+    assert!(modern.deprecations().is_empty());
+
+    fs::write(temp_file, "[Desktop Entry]\nVersion=1.10\nType=Application\nName=Future App\nExec=future-app\n").unwrap();
+    let future = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert_eq!(future.version(), Some("1.10".to_string()));
+    assert!(future.targets_newer_spec());
+
+    fs::remove_file(temp_file).ok();
+}
+
 #[test]
 fn test_dbus_activatable_entry() {
     let path = fixture_path("dbus_activatable.desktop");
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse D-Bus activatable app");
 
-    assert_eq!(entry.entry_type(), Some("Application".to_string()));
+    assert_eq!(entry.entry_type(), Some(EntryType::Application));
     assert_eq!(entry.name(), Some("D-Bus Activatable App".to_string()));
     assert_eq!(entry.get_bool("DBusActivatable"), Some(true));
     assert_eq!(entry.exec(), Some("org.example.DBusApp".to_string()));
@@ -118,6 +251,7 @@ fn test_boolean_parsing() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_numeric_parsing() {
     let path = fixture_path("numeric_test.desktop");
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse numeric test");
@@ -167,7 +301,7 @@ fn test_malformed_missing_required_fields() {
     
     assert!(result.is_err());
     match result.unwrap_err() {
-        ParseError::MissingRequiredKey(_) => {}, // Expected
+        ParseError::MissingRequiredKey { key, .. } => assert_eq!(key, "Type"),
         other => panic!("Expected MissingRequiredKey error, got: {:?}", other),
     }
 }
@@ -179,18 +313,35 @@ fn test_malformed_no_group() {
     
     assert!(result.is_err());
     match result.unwrap_err() {
-        ParseError::InvalidFormat(_) => {}, // Expected
+        ParseError::InvalidFormat { line, .. } => assert!(line.is_some()),
         other => panic!("Expected InvalidFormat error, got: {:?}", other),
     }
 }
 
+#[test]
+fn test_parse_error_context() {
+    let path = fixture_path("malformed_no_group.desktop");
+    let err = ApplicationEntry::try_from_path(&path).unwrap_err();
+
+    assert_eq!(err.path(), Path::new(&path));
+    assert_eq!(err.line(), Some(2));
+    assert!(err.to_string().starts_with(&format!("{}:2: ", path)));
+
+    // Usable with `?` into boxed errors, with the I/O cause available as source
+    fn load(path: &str) -> Result<ApplicationEntry, Box<dyn std::error::Error>> {
+        Ok(ApplicationEntry::try_from_path(path)?)
+    }
+    let err = load("/nonexistent/file.desktop").unwrap_err();
+    assert!(err.source().is_some());
+}
+
 #[test]
 fn test_nonexistent_file() {
     let result = ApplicationEntry::try_from_path("/nonexistent/file.desktop");
     
     assert!(result.is_err());
     match result.unwrap_err() {
-        ParseError::IoError(_) => {}, // Expected
+        ParseError::IoError { .. } => {}, // Expected
         other => panic!("Expected IoError, got: {:?}", other),
     }
 }
@@ -212,4 +363,88 @@ fn test_path_method() {
     let entry = ApplicationEntry::try_from_path(&path).expect("Failed to parse");
     
     assert_eq!(entry.path(), Path::new(&path));
-}
\ No newline at end of file
+}
+#[test]
+fn test_metadata_only_parse() {
+    let temp_file = "/tmp/metadata_only_test.desktop";
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Editor\nName[de]=Bearbeiter\nIcon=editor\nKeywords=text;notes;\nCategories=Utility;\nExec=editor %F\nStartupWMClass=EditorWindow\n\n[Desktop Action new-window]\nName=New Window\nExec=editor --new-window\n").unwrap();
+
+    let entry = ApplicationEntry::metadata_from_path(temp_file).unwrap();
+    assert!(entry.is_metadata_only());
+    assert_eq!(entry.name(), Some("Editor".to_string()));
+    assert_eq!(entry.get_localized_string("Name", Some("de")), Some("Bearbeiter".to_string()));
+    assert_eq!(entry.keywords(), Some(vec!["text".to_string(), "notes".to_string()]));
+    assert!(entry.should_show());
+    assert!(entry.is_metadata_only());
+
+    // Anything else is read from the full parse, which happens once
+    assert_eq!(entry.get_string("StartupWMClass"), Some("EditorWindow".to_string()));
+    assert!(!entry.is_metadata_only());
+    let (program, _) = entry.prepare_command(&["/tmp/a.txt"], &[]).unwrap();
+    assert_eq!(program, "editor");
+
+    // Invalid entries are rejected like a full parse would
+    fs::write(temp_file, "[Desktop Entry]\nType=Application\nName=Broken\n").unwrap();
+    assert!(matches!(ApplicationEntry::metadata_from_path(temp_file), Err(ParseError::MissingRequiredKey { .. })));
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_gio_app_info_conversion() {
+    let temp_file = "/tmp/gio_conversion_test.desktop";
+    fs::write(
+        temp_file,
+        "[Desktop Entry]\nType=Application\nName=Files\nName[de]=Dateien\nX-GNOME-FullName=GNOME Files\nComment=Access files\\sand folders\nExec=\"/opt/My Files/files\" --new-window %U\nMimeType=inode/directory;\nActions=new;\n\n[Desktop Action new]\nName=New Window\nExec=files --new\n",
+    )
+    .unwrap();
+    let entry = ApplicationEntry::try_from_path(temp_file).unwrap();
+    assert_eq!(entry.gio_display_name(), Some("GNOME Files".to_string()));
+    assert_eq!(entry.gio_executable(), Some("/opt/My Files/files".to_string()));
+    assert_eq!(entry.gio_id(), None);
+
+    // Values are handed over exactly as written
+    let keyfile = entry.to_gio_keyfile().unwrap();
+    assert_eq!(keyfile.get("Desktop Entry", "Comment"), Some("Access files\\sand folders"));
+    assert_eq!(keyfile.get("Desktop Entry", "Name[de]"), Some("Dateien"));
+    assert_eq!(keyfile.get("Desktop Action new", "Exec"), Some("files --new"));
+
+    // And read back as GLib would serialize them
+    let parsed = ApplicationEntry::from_gio_data(&keyfile.to_string(), "/nonexistent/files.desktop").unwrap();
+    assert_eq!(parsed.path(), Path::new("/nonexistent/files.desktop"));
+    assert_eq!(parsed.name(), entry.name());
+    assert_eq!(parsed.comment(), Some("Access files and folders".to_string()));
+    assert_eq!(parsed.exec(), entry.exec());
+    assert_eq!(parsed.mime_types(), Some(vec!["inode/directory".to_string()]));
+    assert!(matches!(
+        ApplicationEntry::from_gio_data("[Desktop Entry]\nType=Application\nExec=files\n", "/nonexistent/files.desktop"),
+        Err(ParseError::MissingRequiredKey { ref key, .. }) if key == "Name"
+    ));
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+fn test_ordered_groups_and_keys() {
+    let temp_file = "/tmp/ordered_keys_test.desktop";
+    let content = "[Desktop Entry]\nType=Application\nName=Notes\nName[de]=Notizen\nExec=notes\nActions=new;\nComment=Take\\snotes\nVersion=1.5\nTerminal=false\n\n[X-Vendor]\nZeta=1\nAlpha=2\n\n[Desktop Action new]\nExec=notes --new\nName=New Note\n";
+    fs::write(temp_file, content).unwrap();
+
+    let entry = ApplicationEntry::metadata_from_path(temp_file).unwrap();
+    assert_eq!(entry.group_names(), vec!["Desktop Entry", "X-Vendor", "Desktop Action new"]);
+    let keys: Vec<&str> = entry.group_entries("Desktop Entry").unwrap().iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, vec!["Type", "Name", "Name[de]", "Exec", "Actions", "Comment", "Version", "Terminal"]);
+    assert_eq!(
+        entry.group_entries("X-Vendor").unwrap(),
+        [("Zeta".to_string(), "1".to_string()), ("Alpha".to_string(), "2".to_string())]
+    );
+    assert!(entry.group_entries("Missing").is_none());
+
+    // Values come back as written, not as parsed
+    let keyfile = entry.to_keyfile();
+    assert_eq!(keyfile.get("Desktop Entry", "Comment"), Some("Take\\snotes"));
+    assert_eq!(keyfile.get("Desktop Entry", "Version"), Some("1.5"));
+    assert_eq!(keyfile.to_string(), content);
+
+    fs::remove_file(temp_file).ok();
+}
@@ -8,5 +8,7 @@ fn main() {
     }
     let app =
         ApplicationEntry::from_path("/home/javi/.nix-profile/share/applications/obsidian.desktop");
-    app.execute();
+    if let Err(e) = app.execute() {
+        eprintln!("Failed to launch: {}", e);
+    }
 }
@@ -0,0 +1,223 @@
+//! Timeouts and cancellation for D-Bus calls
+//!
+//! A service that is stuck but still owns its bus name never answers, and zbus
+//! waits for the reply forever. Every D-Bus client in these crates takes a
+//! [`CallOptions`] so a GUI can give up after a while, or when the user closes the
+//! window that asked, instead of hanging.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use freedesktop_core::call::{CallOptions, CancellationToken};
+//! use freedesktop_core::login1::Login1;
+//!
+//! let token = CancellationToken::new();
+//! let options = CallOptions::new()
+//!     .timeout(Duration::from_secs(2))
+//!     .cancellation(token.clone());
+//! let login1 = Login1::new().unwrap().with_call_options(options);
+//! // token.cancel() from another thread makes the call return CallError::Cancelled
+//! let _ = login1.can_suspend();
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// How often a waiting call checks its cancellation token
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub enum CallError {
+    /// The D-Bus call failed (service not running, bus unavailable, ...)
+    DBus(zbus::Error),
+    /// No reply arrived within the timeout
+    Timeout(Duration),
+    /// The call was cancelled through its [`CancellationToken`]
+    Cancelled,
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::DBus(e) => write!(f, "D-Bus error: {}", e),
+            CallError::Timeout(timeout) => write!(f, "D-Bus call timed out after {:?}", timeout),
+            CallError::Cancelled => write!(f, "D-Bus call was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for CallError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CallError::DBus(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for CallError {
+    fn from(e: zbus::Error) -> Self {
+        CallError::DBus(e)
+    }
+}
+
+/// Cancels the calls using it, from any thread
+///
+/// Clones share the same state, so keep one and pass clones to [`CallOptions`].
+/// Once cancelled a token stays cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PartialEq for CancellationToken {
+    /// Tokens are equal when they are clones of each other
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make pending and future calls using this token return [`CallError::Cancelled`]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Limits on how long a D-Bus call may take
+///
+/// The timeout covers the whole operation, including waiting for the signal that
+/// carries a portal's or prompt's result, so it also bounds how long the user may
+/// take to answer a dialog. By default calls wait forever.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallOptions {
+    timeout: Option<Duration>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl CallOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give up with [`CallError::Timeout`] when the call takes longer than this
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up with [`CallError::Cancelled`] once the token is cancelled
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Run an async zbus operation on the current thread within these limits
+    ///
+    /// The operation is dropped when it times out or is cancelled, which abandons
+    /// the pending call; a reply arriving later is discarded.
+    pub fn block_on<T, E, F>(&self, future: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: From<CallError>,
+    {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+
+        loop {
+            if self.is_cancelled() {
+                return Err(CallError::Cancelled.into());
+            }
+            if let Poll::Ready(result) = future.as_mut().poll(&mut context) {
+                return result;
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(CallError::Timeout(self.timeout.unwrap_or_default()).into()),
+                },
+                None => None,
+            };
+            match (remaining, self.cancellation.is_some()) {
+                (Some(remaining), true) => thread::park_timeout(remaining.min(CANCEL_CHECK_INTERVAL)),
+                (Some(remaining), false) => thread::park_timeout(remaining),
+                (None, true) => thread::park_timeout(CANCEL_CHECK_INTERVAL),
+                (None, false) => thread::park(),
+            }
+        }
+    }
+}
+
+/// Wakes the thread blocked in [`CallOptions::block_on`]
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Wait for the next item of a zbus stream, such as the signals of a proxy
+pub(crate) async fn next<S>(stream: &mut S) -> Option<S::Item>
+where
+    S: futures_core::Stream + Unpin,
+{
+    std::future::poll_fn(|cx| std::pin::Pin::new(&mut *stream).poll_next(cx)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_on_times_out() {
+        let options = CallOptions::new().timeout(Duration::from_millis(20));
+        let result: Result<(), CallError> = options.block_on(std::future::pending());
+        assert!(matches!(result, Err(CallError::Timeout(t)) if t == Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn test_block_on_cancels() {
+        let token = CancellationToken::new();
+        let options = CallOptions::new().cancellation(token.clone());
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+        let result: Result<(), CallError> = options.block_on(std::future::pending());
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(CallError::Cancelled)));
+    }
+
+    #[test]
+    fn test_block_on_returns_result() {
+        let options = CallOptions::new().timeout(Duration::from_secs(5));
+        let result: Result<u32, CallError> = options.block_on(async { Ok(7) });
+        assert_eq!(result.unwrap(), 7);
+    }
+}
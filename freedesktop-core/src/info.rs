@@ -1,4 +1,9 @@
 use std::env;
+#[cfg(feature = "dbus")]
+use std::time::Duration;
+
+#[cfg(feature = "dbus")]
+use crate::call::{CallError, CallOptions};
 
 pub struct Info;
 
@@ -10,4 +15,54 @@ impl Info {
 
         None
     }
+
+    /// Get how long the session has been idle
+    ///
+    /// Queries Mutter's IdleMonitor first, which reports milliseconds, and falls back
+    /// to `org.freedesktop.ScreenSaver` (KDE, Xfce and others), which reports seconds.
+    /// Returns None when no idle service is reachable on the session bus.
+    #[cfg(feature = "dbus")]
+    pub fn idle_time() -> Option<Duration> {
+        let connection = zbus::blocking::Connection::session().ok()?;
+        Self::idle_time_with_connection(&connection, &CallOptions::default()).ok()
+    }
+
+    /// Get how long the session has been idle, using an existing session bus
+    /// connection, within the limits of `options`
+    ///
+    /// The timeout covers both services. Fails with the ScreenSaver error when
+    /// neither service answers.
+    #[cfg(feature = "dbus")]
+    pub fn idle_time_with_connection(
+        connection: &zbus::blocking::Connection,
+        options: &CallOptions,
+    ) -> Result<Duration, CallError> {
+        use zbus::Proxy;
+
+        options.block_on(async {
+            let mutter = async {
+                let proxy = Proxy::new(
+                    connection.inner(),
+                    "org.gnome.Mutter.IdleMonitor",
+                    "/org/gnome/Mutter/IdleMonitor/Core",
+                    "org.gnome.Mutter.IdleMonitor",
+                )
+                .await?;
+                proxy.call::<_, _, u64>("GetIdletime", &()).await
+            };
+            if let Ok(millis) = mutter.await {
+                return Ok(Duration::from_millis(millis));
+            }
+
+            let proxy = Proxy::new(
+                connection.inner(),
+                "org.freedesktop.ScreenSaver",
+                "/org/freedesktop/ScreenSaver",
+                "org.freedesktop.ScreenSaver",
+            )
+            .await?;
+            let secs: u32 = proxy.call("GetSessionIdleTime", &()).await?;
+            Ok(Duration::from_secs(secs.into()))
+        })
+    }
 }
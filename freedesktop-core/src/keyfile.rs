@@ -0,0 +1,224 @@
+//! Reading and merging layered keyfiles
+//!
+//! Many freedesktop config files (mimeapps.list, xdg-terminals.list, ...) may exist in
+//! several config dirs at once. [`KeyFile::merge_layered`] reads every copy and merges
+//! them with one simple rule: files are applied in order, and a key in a later file
+//! replaces the same key of the same group from an earlier one. Groups and keys keep
+//! the position where they first appeared. Comments and blank lines are not kept.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An ordered set of `[Group]` sections holding `key=value` pairs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyFile {
+    groups: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl KeyFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse keyfile text, skipping comments and lines that aren't groups or keys
+    ///
+    /// Keys before the first group header are ignored. Duplicate keys keep the last value.
+    pub fn parse(content: &str) -> Self {
+        let mut keyfile = KeyFile::new();
+        let mut group: Option<String> = None;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                keyfile.add_group(name);
+                group = Some(name.to_string());
+            } else if let (Some(group), Some((key, value))) = (&group, line.split_once('=')) {
+                keyfile.set(group, key.trim(), value.trim());
+            }
+        }
+
+        keyfile
+    }
+
+    /// Read and parse a keyfile
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        std::fs::read_to_string(path).map(|content| Self::parse(&content))
+    }
+
+    /// Read every existing file in order and merge them, later files overriding earlier keys
+    ///
+    /// Missing or unreadable files are skipped.
+    pub fn merge_layered<I, P>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut merged = KeyFile::new();
+        for path in paths {
+            if let Ok(layer) = Self::from_path(path) {
+                merged.merge(&layer);
+            }
+        }
+        merged
+    }
+
+    /// Apply the keys of `other` on top of this keyfile
+    pub fn merge(&mut self, other: &KeyFile) {
+        for (group, entries) in &other.groups {
+            self.add_group(group);
+            for (key, value) in entries {
+                self.set(group, key, value);
+            }
+        }
+    }
+
+    /// Get the value of a key
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.entries(group)?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the value of a `;`-separated list key, without empty items
+    pub fn get_list(&self, group: &str, key: &str) -> Option<Vec<String>> {
+        self.get(group, key).map(|value| {
+            value
+                .split(';')
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    /// Set a key, adding the group if needed
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        let entries = self.add_group(group);
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => entries.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// Remove a key, returning its value
+    pub fn remove(&mut self, group: &str, key: &str) -> Option<String> {
+        let (_, entries) = self.groups.iter_mut().find(|(g, _)| g == group)?;
+        let index = entries.iter().position(|(k, _)| k == key)?;
+        Some(entries.remove(index).1)
+    }
+
+    /// Get the group names in order
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|(group, _)| group.as_str())
+    }
+
+    /// Get the key-value pairs of a group in order
+    pub fn entries(&self, group: &str) -> Option<&[(String, String)]> {
+        self.groups
+            .iter()
+            .find(|(g, _)| g == group)
+            .map(|(_, entries)| entries.as_slice())
+    }
+
+    fn add_group(&mut self, group: &str) -> &mut Vec<(String, String)> {
+        let index = match self.groups.iter().position(|(g, _)| g == group) {
+            Some(index) => index,
+            None => {
+                self.groups.push((group.to_string(), Vec::new()));
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[index].1
+    }
+}
+
+impl fmt::Display for KeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (group, entries)) in self.groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", group)?;
+            for (key, value) in entries {
+                writeln!(f, "{}={}", key, escape_control_chars(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write control characters as escape sequences so every value stays on one line
+///
+/// Values are stored raw, so existing escapes are left alone. Control characters
+/// without an escape sequence in the specification are dropped.
+fn escape_control_chars(value: &str) -> std::borrow::Cow<'_, str> {
+    if !value.chars().any(char::is_control) {
+        return value.into();
+    }
+    let mut result = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            c if c.is_control() => {}
+            c => result.push(c),
+        }
+    }
+    result.into()
+}
+
+/// Get the copies of a config file in merge order, lowest precedence first
+///
+/// Lists `name` in each XDG_CONFIG_DIRS entry from last to first, followed by
+/// XDG_CONFIG_HOME, so the result can be passed straight to [`KeyFile::merge_layered`].
+/// Only existing files are returned.
+pub fn config_layers(name: &str) -> Vec<PathBuf> {
+    let mut dirs = crate::config_dirs();
+    dirs.reverse();
+    dirs.extend(crate::config_home());
+
+    dirs.into_iter()
+        .map(|dir| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let keyfile = KeyFile::parse("ignored=1\n# comment\n[A]\nx = 1\ny=2;3;\n\n[B]\nz=\n");
+        assert_eq!(keyfile.groups().collect::<Vec<_>>(), vec!["A", "B"]);
+        assert_eq!(keyfile.get("A", "x"), Some("1"));
+        assert_eq!(keyfile.get_list("A", "y"), Some(vec!["2".to_string(), "3".to_string()]));
+        assert_eq!(keyfile.get("B", "z"), Some(""));
+        assert_eq!(keyfile.get("A", "ignored"), None);
+        assert_eq!(keyfile.to_string(), "[A]\nx=1\ny=2;3;\n\n[B]\nz=\n");
+    }
+
+    #[test]
+    fn test_merge_overrides_later() {
+        let mut base = KeyFile::parse("[A]\nx=system\ny=system\n[B]\nz=system\n");
+        base.merge(&KeyFile::parse("[C]\nw=user\n[A]\ny=user\n"));
+
+        assert_eq!(base.get("A", "x"), Some("system"));
+        assert_eq!(base.get("A", "y"), Some("user"));
+        assert_eq!(base.get("B", "z"), Some("system"));
+        assert_eq!(base.groups().collect::<Vec<_>>(), vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_format_escapes_control_chars() {
+        let mut keyfile = KeyFile::new();
+        keyfile.set("A", "x", "one\ntwo\tthree\u{7}\\s");
+        assert_eq!(keyfile.to_string(), "[A]\nx=one\\ntwo\\tthree\\s\n");
+    }
+}
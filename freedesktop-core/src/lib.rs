@@ -1,5 +1,22 @@
+#[cfg(feature = "dbus")]
+pub mod call;
 pub mod info;
-use std::path::PathBuf;
+pub mod keyfile;
+#[cfg(feature = "dbus")]
+pub mod login1;
+#[cfg(feature = "dbus")]
+pub mod portal;
+#[cfg(feature = "dbus")]
+pub mod power;
+#[cfg(feature = "dbus")]
+pub mod secrets;
+pub mod time;
+use std::path::{Path, PathBuf};
+
+/// The zbus version used by the D-Bus features, for building connections to pass
+/// to their `with_connection` constructors
+#[cfg(feature = "dbus")]
+pub use zbus;
 
 /// The base directories all other searches are
 /// based on. Data comes from XDG_DATA_DIRS
@@ -26,3 +43,145 @@ pub fn base_directories() -> Vec<PathBuf> {
 
     dirs
 }
+
+/// The system configuration directories, most important first.
+/// Data comes from XDG_CONFIG_DIRS, defaulting to /etc/xdg
+pub fn config_dirs() -> Vec<PathBuf> {
+    match std::env::var("XDG_CONFIG_DIRS") {
+        Ok(var_str) if !var_str.is_empty() => var_str
+            .split(':')
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        _ => vec![PathBuf::from("/etc/xdg")],
+    }
+}
+
+/// The user's configuration directory. Data comes from
+/// XDG_CONFIG_HOME, defaulting to ~/.config
+pub fn config_home() -> Option<PathBuf> {
+    if let Ok(var_str) = std::env::var("XDG_CONFIG_HOME") {
+        if !var_str.is_empty() {
+            return Some(PathBuf::from(var_str));
+        }
+    }
+
+    dirs::home_dir().map(|home| home.join(".config"))
+}
+
+/// The user's state directory for data that should persist between
+/// restarts but isn't important enough for XDG_DATA_HOME. Data comes
+/// from XDG_STATE_HOME, defaulting to ~/.local/state
+pub fn state_home() -> Option<PathBuf> {
+    if let Ok(var_str) = std::env::var("XDG_STATE_HOME") {
+        if !var_str.is_empty() {
+            return Some(PathBuf::from(var_str));
+        }
+    }
+
+    dirs::home_dir().map(|home| home.join(".local").join("state"))
+}
+
+/// The user's data directory. Data comes from XDG_DATA_HOME,
+/// defaulting to ~/.local/share
+pub fn data_home() -> Option<PathBuf> {
+    if let Ok(var_str) = std::env::var("XDG_DATA_HOME") {
+        if !var_str.is_empty() {
+            return Some(PathBuf::from(var_str));
+        }
+    }
+
+    dirs::home_dir().map(|home| home.join(".local").join("share"))
+}
+
+/// The data directories Flatpak and Snap export installed applications to,
+/// most important first. Sessions that don't set up XDG_DATA_DIRS properly
+/// miss these. Only existing directories are returned.
+pub fn sandbox_export_directories() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    if let Some(home) = data_home() {
+        dirs.push(home.join("flatpak").join("exports").join("share"));
+    }
+    dirs.push(PathBuf::from("/var/lib/flatpak/exports/share"));
+    dirs.push(PathBuf::from("/var/lib/snapd/desktop"));
+
+    dirs.into_iter().filter(|dir| dir.exists()).collect()
+}
+
+/// Get the locale messages are shown in, from LC_ALL, LC_MESSAGES or LANG
+///
+/// The first one that is set wins. Returns None for the C and POSIX locales,
+/// which mean untranslated text.
+pub fn message_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .filter(|value| value != "C" && value != "POSIX" && !value.starts_with("C."))
+}
+
+/// Build a `file://` URI for an absolute path, percent-encoding everything
+/// but unreserved characters and `/`
+pub fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Get the local path of a `file://` URI, decoding percent-encoded bytes
+///
+/// Returns None for other schemes and for URIs naming another host.
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let rest = uri.strip_prefix("file://")?;
+    let path = match rest.strip_prefix("localhost") {
+        Some(path) => path,
+        None => rest,
+    };
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut input = path.bytes();
+    while let Some(byte) = input.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = [input.next()?, input.next()?];
+        bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+    }
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(file_uri(Path::new("/home/user/Pictures/sea.png")), "file:///home/user/Pictures/sea.png");
+        assert_eq!(file_uri(Path::new("/tmp/my wallpaper #1.jpg")), "file:///tmp/my%20wallpaper%20%231.jpg");
+    }
+
+    #[test]
+    fn test_file_uri_to_path() {
+        let path = Path::new("/tmp/my wallpaper #1.jpg");
+        assert_eq!(file_uri_to_path(&file_uri(path)).as_deref(), Some(path));
+        assert_eq!(file_uri_to_path("file://localhost/etc/hosts").as_deref(), Some(Path::new("/etc/hosts")));
+        assert_eq!(file_uri_to_path("file://server/share/a.txt"), None);
+        assert_eq!(file_uri_to_path("https://example.com/a.txt"), None);
+        assert_eq!(file_uri_to_path("file:///tmp/bad%2"), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Power actions through systemd-logind (`org.freedesktop.login1`)
+//!
+//! Each action has a `can_*` query reflecting the polkit policy for the calling
+//! user, so session bars can hide or mark actions that aren't permitted.
+
+use zbus::blocking::Connection;
+use zbus::Proxy;
+
+use crate::call::{CallError, CallOptions};
+
+const DESTINATION: &str = "org.freedesktop.login1";
+const PATH: &str = "/org/freedesktop/login1";
+const INTERFACE: &str = "org.freedesktop.login1.Manager";
+
+/// Whether a power action may be performed, as reported by logind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerCapability {
+    /// The action is permitted without authentication
+    Yes,
+    /// The action is not permitted
+    No,
+    /// The action is permitted after authenticating through polkit
+    Challenge,
+    /// The hardware or configuration doesn't support the action
+    NotApplicable,
+}
+
+impl PowerCapability {
+    fn from_logind(value: &str) -> Self {
+        match value {
+            "yes" => PowerCapability::Yes,
+            "challenge" => PowerCapability::Challenge,
+            "no" => PowerCapability::No,
+            _ => PowerCapability::NotApplicable,
+        }
+    }
+
+    /// Check whether the action can be offered to the user
+    pub fn is_available(&self) -> bool {
+        matches!(self, PowerCapability::Yes | PowerCapability::Challenge)
+    }
+}
+
+/// A connection to the logind manager on the system bus
+pub struct Login1 {
+    connection: Connection,
+    options: CallOptions,
+}
+
+impl Login1 {
+    /// Connect to logind on the system bus
+    pub fn new() -> zbus::Result<Self> {
+        Ok(Self::with_connection(Connection::system()?))
+    }
+
+    /// Use an existing system bus connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Limit how long calls may take and allow cancelling them
+    pub fn with_call_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn can_suspend(&self) -> Result<PowerCapability, CallError> {
+        self.capability("CanSuspend")
+    }
+
+    /// Suspend the system; `interactive` allows polkit to prompt for authentication
+    pub fn suspend(&self, interactive: bool) -> Result<(), CallError> {
+        self.action("Suspend", interactive)
+    }
+
+    pub fn can_hibernate(&self) -> Result<PowerCapability, CallError> {
+        self.capability("CanHibernate")
+    }
+
+    /// Hibernate the system; `interactive` allows polkit to prompt for authentication
+    pub fn hibernate(&self, interactive: bool) -> Result<(), CallError> {
+        self.action("Hibernate", interactive)
+    }
+
+    pub fn can_poweroff(&self) -> Result<PowerCapability, CallError> {
+        self.capability("CanPowerOff")
+    }
+
+    /// Power off the system; `interactive` allows polkit to prompt for authentication
+    pub fn poweroff(&self, interactive: bool) -> Result<(), CallError> {
+        self.action("PowerOff", interactive)
+    }
+
+    pub fn can_reboot(&self) -> Result<PowerCapability, CallError> {
+        self.capability("CanReboot")
+    }
+
+    /// Reboot the system; `interactive` allows polkit to prompt for authentication
+    pub fn reboot(&self, interactive: bool) -> Result<(), CallError> {
+        self.action("Reboot", interactive)
+    }
+
+    fn capability(&self, method: &str) -> Result<PowerCapability, CallError> {
+        self.options.block_on(async {
+            let proxy = Proxy::new(self.connection.inner(), DESTINATION, PATH, INTERFACE).await?;
+            let value: String = proxy.call(method, &()).await?;
+            Ok(PowerCapability::from_logind(&value))
+        })
+    }
+
+    fn action(&self, method: &str, interactive: bool) -> Result<(), CallError> {
+        self.options.block_on(async {
+            let proxy = Proxy::new(self.connection.inner(), DESTINATION, PATH, INTERFACE).await?;
+            proxy.call_method(method, &(interactive,)).await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,85 @@
+//! The Account portal (`org.freedesktop.portal.Account`)
+//!
+//! Gives applications the user's name and picture after the user agrees to share them.
+
+use std::collections::HashMap;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+use crate::call::CallOptions;
+
+use super::{request, PortalError, PortalResults};
+
+const INTERFACE: &str = "org.freedesktop.portal.Account";
+
+/// Basic information about the user, as shared through the portal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserInformation {
+    /// The user's login name
+    pub id: String,
+    /// The user's real name
+    pub name: String,
+    /// URI of the user's picture, if they have one
+    pub image: Option<String>,
+}
+
+/// Client for the Account portal
+pub struct Account {
+    connection: Connection,
+    options: CallOptions,
+}
+
+impl Account {
+    /// Connect to the session bus
+    pub fn new() -> Result<Self, PortalError> {
+        Ok(Self::with_connection(Connection::session()?))
+    }
+
+    /// Use an existing session bus connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Limit how long requests may take, including the user's answer, and allow cancelling them
+    pub fn with_call_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Ask the user to share their account information
+    ///
+    /// `reason` is shown in the consent dialog. Declining returns
+    /// [`PortalError::Cancelled`]. `parent_window` identifies the requesting window
+    /// (e.g. `wayland:<handle>`) and may be empty.
+    pub fn user_information(&self, reason: Option<&str>, parent_window: &str) -> Result<UserInformation, PortalError> {
+        let results = self.options.block_on(request(&self.connection, INTERFACE, "GetUserInformation", |token| {
+            let mut options: HashMap<&str, Value> = HashMap::new();
+            options.insert("handle_token", Value::from(token.to_string()));
+            if let Some(reason) = reason {
+                options.insert("reason", Value::from(reason));
+            }
+            (parent_window, options)
+        }))?;
+
+        parse_user_information(results)
+    }
+}
+
+fn parse_user_information(results: PortalResults) -> Result<UserInformation, PortalError> {
+    let string = |key: &str| {
+        results
+            .get(key)
+            .and_then(|v| String::try_from(v.clone()).ok())
+            .filter(|s| !s.is_empty())
+    };
+
+    Ok(UserInformation {
+        id: string("id").ok_or_else(|| PortalError::InvalidResponse("Missing id".to_string()))?,
+        name: string("name").unwrap_or_default(),
+        image: string("image"),
+    })
+}
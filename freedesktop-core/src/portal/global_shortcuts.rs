@@ -0,0 +1,222 @@
+//! The GlobalShortcuts portal (`org.freedesktop.portal.GlobalShortcuts`)
+//!
+//! Lets applications register system-wide hotkeys on compositors where clients
+//! can't grab keys themselves, most notably under Wayland.
+
+use std::collections::HashMap;
+
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type as MessageType;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{MatchRule, Proxy};
+
+use crate::call::CallOptions;
+
+use super::{handle_token, request, PortalError, PortalResults, PORTAL_DESTINATION, PORTAL_PATH};
+
+const INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+const SESSION_INTERFACE: &str = "org.freedesktop.portal.Session";
+
+/// A shortcut the application wants to register
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    /// Application-defined identifier reported back on activation
+    pub id: String,
+    /// Human readable description shown in the desktop's shortcut settings
+    pub description: String,
+    /// Suggested trigger, e.g. `CTRL+ALT+t`, which the desktop may ignore
+    pub preferred_trigger: Option<String>,
+}
+
+impl Shortcut {
+    pub fn new<I: Into<String>, D: Into<String>>(id: I, description: D) -> Self {
+        Self {
+            id: id.into(),
+            description: description.into(),
+            preferred_trigger: None,
+        }
+    }
+
+    /// Suggest a trigger for this shortcut
+    pub fn preferred_trigger<T: Into<String>>(mut self, trigger: T) -> Self {
+        self.preferred_trigger = Some(trigger.into());
+        self
+    }
+}
+
+/// A shortcut as it was bound by the desktop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundShortcut {
+    pub id: String,
+    pub description: String,
+    /// Human readable description of the trigger the user assigned
+    pub trigger_description: Option<String>,
+}
+
+/// A shortcut was pressed or released
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShortcutEvent {
+    Activated { id: String, timestamp: u64 },
+    Deactivated { id: String, timestamp: u64 },
+}
+
+/// A GlobalShortcuts portal session
+///
+/// The session is closed when this value is dropped, which unregisters its shortcuts.
+pub struct GlobalShortcuts {
+    connection: Connection,
+    session: OwnedObjectPath,
+    options: CallOptions,
+}
+
+impl GlobalShortcuts {
+    /// Connect to the session bus and create a new shortcuts session
+    pub fn new() -> Result<Self, PortalError> {
+        Self::with_connection(Connection::session()?)
+    }
+
+    /// Create a new shortcuts session on an existing session bus connection
+    pub fn with_connection(connection: Connection) -> Result<Self, PortalError> {
+        Self::with_connection_and_options(connection, CallOptions::default())
+    }
+
+    /// Create a new shortcuts session whose calls are limited by `options`
+    ///
+    /// The limits apply to creating the session, binding and listing shortcuts and
+    /// closing the session, not to waiting for [`GlobalShortcuts::events`].
+    pub fn with_connection_and_options(connection: Connection, options: CallOptions) -> Result<Self, PortalError> {
+        let session_token = handle_token();
+
+        let results = options.block_on(request(&connection, INTERFACE, "CreateSession", |token| {
+            let mut options: HashMap<&str, Value> = HashMap::new();
+            options.insert("handle_token", Value::from(token.to_string()));
+            options.insert("session_handle_token", Value::from(session_token.clone()));
+            (options,)
+        }))?;
+
+        let session = results
+            .get("session_handle")
+            .and_then(object_path_value)
+            .ok_or_else(|| PortalError::InvalidResponse("Missing session_handle".to_string()))?;
+
+        Ok(Self {
+            connection,
+            session,
+            options,
+        })
+    }
+
+    /// Ask the desktop to bind the given shortcuts, possibly showing a dialog
+    ///
+    /// `parent_window` identifies the requesting window (e.g. `wayland:<handle>`)
+    /// and may be empty.
+    pub fn bind_shortcuts(&self, shortcuts: &[Shortcut], parent_window: &str) -> Result<Vec<BoundShortcut>, PortalError> {
+        let shortcuts: Vec<(String, HashMap<String, Value>)> = shortcuts
+            .iter()
+            .map(|shortcut| {
+                let mut data: HashMap<String, Value> = HashMap::new();
+                data.insert("description".to_string(), Value::from(shortcut.description.clone()));
+                if let Some(trigger) = &shortcut.preferred_trigger {
+                    data.insert("preferred_trigger".to_string(), Value::from(trigger.clone()));
+                }
+                (shortcut.id.clone(), data)
+            })
+            .collect();
+
+        let results = self.options.block_on(request(&self.connection, INTERFACE, "BindShortcuts", |token| {
+            let mut options: HashMap<&str, Value> = HashMap::new();
+            options.insert("handle_token", Value::from(token.to_string()));
+            (self.session_path(), shortcuts, parent_window, options)
+        }))?;
+
+        parse_shortcuts(results)
+    }
+
+    /// List the shortcuts currently bound for this session
+    pub fn list_shortcuts(&self) -> Result<Vec<BoundShortcut>, PortalError> {
+        let results = self.options.block_on(request(&self.connection, INTERFACE, "ListShortcuts", |token| {
+            let mut options: HashMap<&str, Value> = HashMap::new();
+            options.insert("handle_token", Value::from(token.to_string()));
+            (self.session_path(), options)
+        }))?;
+
+        parse_shortcuts(results)
+    }
+
+    /// Block on activation events for this session's shortcuts
+    pub fn events(&self) -> Result<impl Iterator<Item = ShortcutEvent>, PortalError> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(INTERFACE)?
+            .path(PORTAL_PATH)?
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, &self.connection, None)?;
+        let session = self.session.clone();
+
+        Ok(messages.filter_map(move |message| {
+            let message = message.ok()?;
+            let header = message.header();
+            let member = header.member()?.to_string();
+            let (handle, id, timestamp, _options): (OwnedObjectPath, String, u64, HashMap<String, OwnedValue>) =
+                message.body().deserialize().ok()?;
+            if handle != session {
+                return None;
+            }
+            match member.as_str() {
+                "Activated" => Some(ShortcutEvent::Activated { id, timestamp }),
+                "Deactivated" => Some(ShortcutEvent::Deactivated { id, timestamp }),
+                _ => None,
+            }
+        }))
+    }
+
+    fn session_path(&self) -> ObjectPath<'_> {
+        self.session.as_ref()
+    }
+}
+
+impl Drop for GlobalShortcuts {
+    fn drop(&mut self) {
+        let _: Result<(), PortalError> = self.options.block_on(async {
+            let proxy = Proxy::new(
+                self.connection.inner(),
+                PORTAL_DESTINATION,
+                self.session.as_str(),
+                SESSION_INTERFACE,
+            )
+            .await?;
+            proxy.call_method("Close", &()).await?;
+            Ok(())
+        });
+    }
+}
+
+/// Read an object path that some portal versions send as a plain string
+fn object_path_value(value: &OwnedValue) -> Option<OwnedObjectPath> {
+    match &**value {
+        Value::ObjectPath(path) => Some(path.clone().into()),
+        Value::Str(s) => OwnedObjectPath::try_from(s.as_str()).ok(),
+        _ => None,
+    }
+}
+
+fn parse_shortcuts(mut results: PortalResults) -> Result<Vec<BoundShortcut>, PortalError> {
+    let Some(value) = results.remove("shortcuts") else {
+        return Ok(Vec::new());
+    };
+    let shortcuts: Vec<(String, HashMap<String, OwnedValue>)> = value.try_into()?;
+
+    Ok(shortcuts
+        .into_iter()
+        .map(|(id, data)| BoundShortcut {
+            id,
+            description: data
+                .get("description")
+                .and_then(|v| String::try_from(v.clone()).ok())
+                .unwrap_or_default(),
+            trigger_description: data
+                .get("trigger_description")
+                .and_then(|v| String::try_from(v.clone()).ok()),
+        })
+        .collect())
+}
@@ -0,0 +1,153 @@
+//! Wrappers for the XDG desktop portals (`org.freedesktop.portal.*`)
+//!
+//! Portal methods that need user interaction return a request object whose
+//! `Response` signal carries the actual result. The helpers here take care of
+//! that handshake so each portal wrapper only deals with its own data.
+
+pub mod account;
+pub mod global_shortcuts;
+pub mod settings;
+pub mod wallpaper;
+
+pub use account::{Account, UserInformation};
+pub use global_shortcuts::{BoundShortcut, GlobalShortcuts, Shortcut, ShortcutEvent};
+pub use settings::{AccentColor, Font, Settings};
+pub use wallpaper::{Wallpaper, WallpaperTarget};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::{DynamicType, OwnedValue};
+use zbus::Proxy;
+
+use crate::call::{self, CallError};
+
+pub(crate) const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+pub(crate) const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+
+/// Results of a portal request, keyed by result name
+pub type PortalResults = HashMap<String, OwnedValue>;
+
+#[derive(Debug)]
+pub enum PortalError {
+    /// The D-Bus call itself failed (no portal running, bus unavailable, ...)
+    DBus(zbus::Error),
+    /// The user cancelled the interaction
+    Cancelled,
+    /// The portal ended the interaction for another reason
+    Failed,
+    /// The portal answered with data we could not interpret
+    InvalidResponse(String),
+    /// No answer arrived within the timeout of the client's
+    /// [`CallOptions`](crate::call::CallOptions)
+    Timeout(Duration),
+    /// The caller cancelled the call through its
+    /// [`CancellationToken`](crate::call::CancellationToken)
+    Aborted,
+}
+
+impl fmt::Display for PortalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortalError::DBus(e) => write!(f, "D-Bus error: {}", e),
+            PortalError::Cancelled => write!(f, "Portal request was cancelled"),
+            PortalError::Failed => write!(f, "Portal request failed"),
+            PortalError::InvalidResponse(msg) => write!(f, "Invalid portal response: {}", msg),
+            PortalError::Timeout(timeout) => write!(f, "Portal did not answer within {:?}", timeout),
+            PortalError::Aborted => write!(f, "Portal call was aborted"),
+        }
+    }
+}
+
+impl std::error::Error for PortalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PortalError::DBus(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for PortalError {
+    fn from(e: zbus::Error) -> Self {
+        PortalError::DBus(e)
+    }
+}
+
+impl From<CallError> for PortalError {
+    fn from(e: CallError) -> Self {
+        match e {
+            CallError::DBus(e) => PortalError::DBus(e),
+            CallError::Timeout(timeout) => PortalError::Timeout(timeout),
+            CallError::Cancelled => PortalError::Aborted,
+        }
+    }
+}
+
+impl From<zbus::zvariant::Error> for PortalError {
+    fn from(e: zbus::zvariant::Error) -> Self {
+        PortalError::InvalidResponse(e.to_string())
+    }
+}
+
+/// Generate a token unique to this process for request and session handles
+pub(crate) fn handle_token() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "freedesktop_rs_{}_{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Build the object path the portal will use for a request or session with the given token
+pub(crate) fn handle_path(connection: &Connection, kind: &str, token: &str) -> Result<String, PortalError> {
+    let sender = connection
+        .unique_name()
+        .ok_or_else(|| PortalError::InvalidResponse("Connection has no unique name".to_string()))?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    Ok(format!("{}/{}/{}/{}", PORTAL_PATH, kind, sender, token))
+}
+
+/// Call a portal method that returns a request handle and wait for its `Response`
+///
+/// `build_body` receives the handle token which must be passed in the method's
+/// options as `handle_token`. Run it with the client's
+/// [`CallOptions::block_on`](crate::call::CallOptions::block_on).
+pub(crate) async fn request<B, F>(
+    connection: &Connection,
+    interface: &str,
+    method: &str,
+    build_body: F,
+) -> Result<PortalResults, PortalError>
+where
+    B: serde::Serialize + DynamicType,
+    F: FnOnce(&str) -> B,
+{
+    let token = handle_token();
+    let request_path = handle_path(connection, "request", &token)?;
+    let connection = connection.inner();
+
+    // Subscribe before calling so a fast response can't be missed
+    let request_proxy = Proxy::new(connection, PORTAL_DESTINATION, request_path.as_str(), REQUEST_INTERFACE).await?;
+    let mut responses = request_proxy.receive_signal("Response").await?;
+
+    let proxy = Proxy::new(connection, PORTAL_DESTINATION, PORTAL_PATH, interface).await?;
+    proxy.call_method(method, &build_body(&token)).await?;
+
+    let message = call::next(&mut responses)
+        .await
+        .ok_or_else(|| PortalError::InvalidResponse("Response stream ended".to_string()))?;
+    let (code, results): (u32, PortalResults) = message.body().deserialize()?;
+
+    match code {
+        0 => Ok(results),
+        1 => Err(PortalError::Cancelled),
+        _ => Err(PortalError::Failed),
+    }
+}
@@ -0,0 +1,295 @@
+//! The Settings portal (`org.freedesktop.portal.Settings`)
+//!
+//! Typed accessors try the portal first and fall back to `gsettings` and KDE's
+//! configuration files, so they also work outside of a portal-enabled session.
+
+use std::path::Path;
+use std::process::Command;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::Proxy;
+
+use crate::call::CallOptions;
+
+use super::{PortalError, PORTAL_DESTINATION, PORTAL_PATH};
+
+const INTERFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE: &str = "org.freedesktop.appearance";
+const GNOME_INTERFACE: &str = "org.gnome.desktop.interface";
+
+/// An RGB color with components in the `0.0..=1.0` range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccentColor {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+}
+
+/// A font family and point size
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    pub family: String,
+    pub size: f64,
+}
+
+impl Font {
+    /// Parse a Pango-style description such as `Cantarell 11`
+    pub fn from_pango(description: &str) -> Option<Self> {
+        let description = description.trim();
+        let (family, size) = description.rsplit_once(' ')?;
+        Some(Self {
+            family: family.trim_end_matches(',').to_string(),
+            size: size.parse().ok()?,
+        })
+    }
+
+    /// Parse a Qt-style description such as `Noto Sans,10,-1,5,50,0,0,0,0,0`
+    pub fn from_qt(description: &str) -> Option<Self> {
+        let mut parts = description.trim().split(',');
+        let family = parts.next()?.to_string();
+        let size = parts.next()?.parse().ok()?;
+        Some(Self { family, size })
+    }
+}
+
+/// Reader for desktop-wide appearance settings
+pub struct Settings {
+    connection: Option<Connection>,
+    options: CallOptions,
+}
+
+impl Settings {
+    /// Create a reader, connecting to the session bus if one is available
+    pub fn new() -> Self {
+        Self {
+            connection: Connection::session().ok(),
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Create a reader using an existing session bus connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection: Some(connection),
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Limit how long reading from the portal may take and allow cancelling it
+    ///
+    /// The typed accessors fall back to the other sources when the portal times out.
+    pub fn with_call_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read a raw setting from the portal
+    pub fn read(&self, namespace: &str, key: &str) -> Result<OwnedValue, PortalError> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or_else(|| PortalError::InvalidResponse("No session bus connection".to_string()))?;
+        self.options.block_on(async {
+            let proxy = Proxy::new(connection.inner(), PORTAL_DESTINATION, PORTAL_PATH, INTERFACE).await?;
+            match proxy.call::<_, _, OwnedValue>("ReadOne", &(namespace, key)).await {
+                Ok(value) => Ok(value),
+                // Portals before version 2 only offer Read, which wraps the value in another variant
+                Err(_) => {
+                    let value: OwnedValue = proxy.call("Read", &(namespace, key)).await?;
+                    match &*value {
+                        Value::Value(inner) => Ok(OwnedValue::try_from(&**inner)?),
+                        _ => Ok(value),
+                    }
+                }
+            }
+        })
+    }
+
+    /// Get the user's accent color
+    pub fn accent_color(&self) -> Option<AccentColor> {
+        if let Ok(value) = self.read(APPEARANCE, "accent-color") {
+            if let Ok((red, green, blue)) = <(f64, f64, f64)>::try_from(value) {
+                // Out of range values mean "no preference"
+                if [red, green, blue].iter().all(|c| (0.0..=1.0).contains(c)) {
+                    return Some(AccentColor { red, green, blue });
+                }
+            }
+        }
+
+        if let Some(name) = gsettings_get(GNOME_INTERFACE, "accent-color") {
+            if let Some(color) = gnome_accent_color(&name) {
+                return Some(color);
+            }
+        }
+
+        let kdeglobals = crate::config_home()?.join("kdeglobals");
+        read_ini_value(&kdeglobals, "General", "AccentColor").and_then(|rgb| kde_accent_color(&rgb))
+    }
+
+    /// Get the default interface font
+    pub fn interface_font(&self) -> Option<Font> {
+        if let Ok(value) = self.read(GNOME_INTERFACE, "font-name") {
+            if let Some(font) = String::try_from(value).ok().and_then(|s| Font::from_pango(&s)) {
+                return Some(font);
+            }
+        }
+
+        if let Some(font) = gsettings_get(GNOME_INTERFACE, "font-name").and_then(|s| Font::from_pango(&s)) {
+            return Some(font);
+        }
+
+        let kdeglobals = crate::config_home()?.join("kdeglobals");
+        read_ini_value(&kdeglobals, "General", "font").and_then(|s| Font::from_qt(&s))
+    }
+
+    /// Get the cursor size in pixels
+    pub fn cursor_size(&self) -> Option<u32> {
+        if let Ok(value) = self.read(GNOME_INTERFACE, "cursor-size") {
+            if let Ok(size) = i32::try_from(value) {
+                return u32::try_from(size).ok();
+            }
+        }
+
+        if let Some(size) = gsettings_get(GNOME_INTERFACE, "cursor-size").and_then(|s| s.parse().ok()) {
+            return Some(size);
+        }
+
+        let kcminputrc = crate::config_home().map(|dir| dir.join("kcminputrc"));
+        if let Some(size) = kcminputrc
+            .and_then(|path| read_ini_value(&path, "Mouse", "cursorSize"))
+            .and_then(|s| s.parse().ok())
+        {
+            return Some(size);
+        }
+
+        std::env::var("XCURSOR_SIZE").ok().and_then(|s| s.parse().ok())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read a key with the `gsettings` tool, stripping GVariant string quoting
+fn gsettings_get(schema: &str, key: &str) -> Option<String> {
+    let output = Command::new("gsettings").args(["get", schema, key]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let value = value.strip_prefix("uint32 ").unwrap_or(&value);
+    Some(value.trim_matches('\'').to_string())
+}
+
+/// Map GNOME's named accent colors to their RGB values
+fn gnome_accent_color(name: &str) -> Option<AccentColor> {
+    let (red, green, blue) = match name {
+        "blue" => (0x35, 0x84, 0xe4),
+        "teal" => (0x21, 0x90, 0xa4),
+        "green" => (0x3a, 0x94, 0x4a),
+        "yellow" => (0xc8, 0x88, 0x00),
+        "orange" => (0xed, 0x5b, 0x00),
+        "red" => (0xe6, 0x2d, 0x42),
+        "pink" => (0xd5, 0x61, 0x99),
+        "purple" => (0x91, 0x41, 0xac),
+        "slate" => (0x6f, 0x83, 0x96),
+        _ => return None,
+    };
+    Some(AccentColor {
+        red: f64::from(red) / 255.0,
+        green: f64::from(green) / 255.0,
+        blue: f64::from(blue) / 255.0,
+    })
+}
+
+/// Parse KDE's `r,g,b` accent color with components in the `0..=255` range
+fn kde_accent_color(rgb: &str) -> Option<AccentColor> {
+    let mut components = rgb.split(',').map(|c| c.trim().parse::<u8>().ok());
+    Some(AccentColor {
+        red: f64::from(components.next()??) / 255.0,
+        green: f64::from(components.next()??) / 255.0,
+        blue: f64::from(components.next()??) / 255.0,
+    })
+}
+
+/// Read a single value from an INI-style configuration file
+fn read_ini_value(path: &Path, group: &str, key: &str) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let header = format!("[{}]", group);
+    let mut in_group = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_group = line == header;
+        } else if in_group {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim() == key {
+                    return Some(v.trim().to_string());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_from_pango() {
+        let font = Font::from_pango("Cantarell 11").unwrap();
+        assert_eq!(font.family, "Cantarell");
+        assert_eq!(font.size, 11.0);
+
+        let font = Font::from_pango(" Noto Sans, 10.5 ").unwrap();
+        assert_eq!(font.family, "Noto Sans");
+        assert_eq!(font.size, 10.5);
+
+        assert_eq!(Font::from_pango("Cantarell"), None);
+        assert_eq!(Font::from_pango("Cantarell Bold"), None);
+        assert_eq!(Font::from_pango(""), None);
+    }
+
+    #[test]
+    fn test_font_from_qt() {
+        let font = Font::from_qt("Noto Sans,10,-1,5,50,0,0,0,0,0").unwrap();
+        assert_eq!(font.family, "Noto Sans");
+        assert_eq!(font.size, 10.0);
+
+        assert_eq!(Font::from_qt("Noto Sans"), None);
+        assert_eq!(Font::from_qt("Noto Sans,large,-1"), None);
+        assert_eq!(Font::from_qt(""), None);
+    }
+
+    #[test]
+    fn test_gnome_accent_color() {
+        let color = gnome_accent_color("blue").unwrap();
+        assert_eq!(color.red, f64::from(0x35) / 255.0);
+        assert_eq!(color.green, f64::from(0x84) / 255.0);
+        assert_eq!(color.blue, f64::from(0xe4) / 255.0);
+
+        assert_eq!(gnome_accent_color("'blue'"), None);
+        assert_eq!(gnome_accent_color("Blue"), None);
+        assert_eq!(gnome_accent_color(""), None);
+    }
+
+    #[test]
+    fn test_kde_accent_color() {
+        let color = kde_accent_color("61, 174, 233").unwrap();
+        assert_eq!(color.red, 61.0 / 255.0);
+        assert_eq!(color.green, 174.0 / 255.0);
+        assert_eq!(color.blue, 233.0 / 255.0);
+
+        assert_eq!(kde_accent_color("61,174"), None);
+        assert_eq!(kde_accent_color("61,174,blue"), None);
+        assert_eq!(kde_accent_color("61,174,300"), None);
+        assert_eq!(kde_accent_color(""), None);
+    }
+}
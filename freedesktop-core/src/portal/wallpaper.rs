@@ -0,0 +1,98 @@
+//! The Wallpaper portal (`org.freedesktop.portal.Wallpaper`)
+//!
+//! Sets the desktop background or lock screen picture. The desktop may show a
+//! preview first and lets the user decline.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+use crate::call::CallOptions;
+
+use super::{request, PortalError};
+
+const INTERFACE: &str = "org.freedesktop.portal.Wallpaper";
+
+/// Where a wallpaper should be shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WallpaperTarget {
+    Background,
+    Lockscreen,
+    #[default]
+    Both,
+}
+
+impl WallpaperTarget {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WallpaperTarget::Background => "background",
+            WallpaperTarget::Lockscreen => "lockscreen",
+            WallpaperTarget::Both => "both",
+        }
+    }
+}
+
+/// Client for the Wallpaper portal
+pub struct Wallpaper {
+    connection: Connection,
+    options: CallOptions,
+}
+
+impl Wallpaper {
+    /// Connect to the session bus
+    pub fn new() -> Result<Self, PortalError> {
+        Ok(Self::with_connection(Connection::session()?))
+    }
+
+    /// Use an existing session bus connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Limit how long requests may take, including the user's answer, and allow cancelling them
+    pub fn with_call_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Ask the desktop to use the image at `uri` as wallpaper
+    ///
+    /// With `show_preview` the desktop shows the image and asks the user to confirm;
+    /// declining returns [`PortalError::Cancelled`]. `parent_window` identifies the
+    /// requesting window (e.g. `wayland:<handle>`) and may be empty.
+    pub fn set_uri(
+        &self,
+        uri: &str,
+        target: WallpaperTarget,
+        show_preview: bool,
+        parent_window: &str,
+    ) -> Result<(), PortalError> {
+        self.options.block_on(request(&self.connection, INTERFACE, "SetWallpaperURI", |token| {
+            let mut options: HashMap<&str, Value> = HashMap::new();
+            options.insert("handle_token", Value::from(token.to_string()));
+            options.insert("show-preview", Value::from(show_preview));
+            options.insert("set-on", Value::from(target.as_str()));
+            (parent_window, uri, options)
+        }))?;
+
+        Ok(())
+    }
+
+    /// Ask the desktop to use a local image file as wallpaper, see [`Wallpaper::set_uri`]
+    pub fn set_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        target: WallpaperTarget,
+        show_preview: bool,
+        parent_window: &str,
+    ) -> Result<(), PortalError> {
+        let path = std::fs::canonicalize(path)
+            .map_err(|e| PortalError::InvalidResponse(format!("Invalid wallpaper path: {}", e)))?;
+        self.set_uri(&crate::file_uri(&path), target, show_preview, parent_window)
+    }
+}
@@ -0,0 +1,106 @@
+//! Power state through UPower and power-profiles-daemon
+//!
+//! Lets callers hold off heavy work such as full rescans or thumbnail generation
+//! while the machine runs on battery or the user picked the power saver profile.
+
+use zbus::blocking::Connection;
+use zbus::Proxy;
+
+use crate::call::{CallError, CallOptions};
+
+const UPOWER_DESTINATION: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const UPOWER_INTERFACE: &str = "org.freedesktop.UPower";
+
+/// power-profiles-daemon names, newest first; versions before 0.20 only use the second
+const PROFILE_SERVICES: [(&str, &str); 2] = [
+    ("org.freedesktop.UPower.PowerProfiles", "/org/freedesktop/UPower/PowerProfiles"),
+    ("net.hadess.PowerProfiles", "/net/hadess/PowerProfiles"),
+];
+
+/// The active power profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PowerProfile {
+    PowerSaver,
+    Balanced,
+    Performance,
+    /// A profile this crate doesn't know about
+    Other(String),
+}
+
+impl PowerProfile {
+    fn from_daemon(value: &str) -> Self {
+        match value {
+            "power-saver" => PowerProfile::PowerSaver,
+            "balanced" => PowerProfile::Balanced,
+            "performance" => PowerProfile::Performance,
+            other => PowerProfile::Other(other.to_string()),
+        }
+    }
+}
+
+/// A connection to the power services on the system bus
+pub struct Power {
+    connection: Connection,
+    options: CallOptions,
+}
+
+impl Power {
+    /// Connect to the system bus
+    pub fn new() -> zbus::Result<Self> {
+        Ok(Self::with_connection(Connection::system()?))
+    }
+
+    /// Use an existing system bus connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Limit how long calls may take and allow cancelling them
+    ///
+    /// The limits apply to each query on its own.
+    pub fn with_call_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Check whether the machine is running on battery, as reported by UPower
+    pub fn on_battery(&self) -> Result<bool, CallError> {
+        self.options.block_on(async {
+            let proxy = Proxy::new(self.connection.inner(), UPOWER_DESTINATION, UPOWER_PATH, UPOWER_INTERFACE).await?;
+            Ok(proxy.get_property("OnBattery").await?)
+        })
+    }
+
+    /// Get the active profile from power-profiles-daemon
+    pub fn active_profile(&self) -> Result<PowerProfile, CallError> {
+        self.options.block_on(async {
+            let mut last_error = None;
+            for (name, path) in PROFILE_SERVICES {
+                let proxy = match Proxy::new(self.connection.inner(), name, path, name).await {
+                    Ok(proxy) => proxy,
+                    Err(e) => {
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                match proxy.get_property::<String>("ActiveProfile").await {
+                    Ok(profile) => return Ok(PowerProfile::from_daemon(&profile)),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            Err(last_error.unwrap_or(zbus::Error::Unsupported).into())
+        })
+    }
+
+    /// Check whether heavy background work should be put off
+    ///
+    /// True on battery or with the power saver profile. Services that aren't
+    /// running count as no reason to wait.
+    pub fn prefers_deferring_work(&self) -> bool {
+        self.on_battery().unwrap_or(false) || self.active_profile().ok() == Some(PowerProfile::PowerSaver)
+    }
+}
@@ -0,0 +1,273 @@
+//! A thin client for the Secret Service API (`org.freedesktop.secrets`)
+//!
+//! Items are stored in the default collection and transferred using the `plain`
+//! algorithm, so secrets travel unencrypted over the session bus. That matches what
+//! most desktop utilities need; the bus is private to the user's session.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::Proxy;
+
+use crate::call::{self, CallError, CallOptions};
+
+const DESTINATION: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+
+/// The wire format of a secret: session, parameters, value and content type
+type RawSecret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[derive(Debug)]
+pub enum SecretError {
+    /// The D-Bus call failed (no secret service running, bus unavailable, ...)
+    DBus(zbus::Error),
+    /// The user dismissed an unlock or confirmation prompt
+    Dismissed,
+    /// The service answered with data we could not interpret
+    InvalidResponse(String),
+    /// No answer arrived within the timeout of the session's [`CallOptions`]
+    Timeout(Duration),
+    /// The caller cancelled the call through its
+    /// [`CancellationToken`](crate::call::CancellationToken)
+    Aborted,
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::DBus(e) => write!(f, "D-Bus error: {}", e),
+            SecretError::Dismissed => write!(f, "Secret service prompt was dismissed"),
+            SecretError::InvalidResponse(msg) => write!(f, "Invalid secret service response: {}", msg),
+            SecretError::Timeout(timeout) => write!(f, "Secret service did not answer within {:?}", timeout),
+            SecretError::Aborted => write!(f, "Secret service call was aborted"),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SecretError::DBus(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for SecretError {
+    fn from(e: zbus::Error) -> Self {
+        SecretError::DBus(e)
+    }
+}
+
+impl From<zbus::fdo::Error> for SecretError {
+    fn from(e: zbus::fdo::Error) -> Self {
+        SecretError::DBus(e.into())
+    }
+}
+
+impl From<CallError> for SecretError {
+    fn from(e: CallError) -> Self {
+        match e {
+            CallError::DBus(e) => SecretError::DBus(e),
+            CallError::Timeout(timeout) => SecretError::Timeout(timeout),
+            CallError::Cancelled => SecretError::Aborted,
+        }
+    }
+}
+
+/// An item stored by the secret service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretItem {
+    path: OwnedObjectPath,
+}
+
+impl SecretItem {
+    /// The object path of the item on the bus
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+}
+
+/// A session with the secret service
+pub struct SecretService {
+    connection: Connection,
+    session: OwnedObjectPath,
+    options: CallOptions,
+}
+
+impl SecretService {
+    /// Connect to the session bus and open a `plain` session
+    pub fn connect() -> Result<Self, SecretError> {
+        Self::with_connection(Connection::session()?)
+    }
+
+    /// Open a `plain` session on an existing session bus connection
+    pub fn with_connection(connection: Connection) -> Result<Self, SecretError> {
+        Self::with_connection_and_options(connection, CallOptions::default())
+    }
+
+    /// Open a `plain` session whose calls are limited by `options`
+    ///
+    /// The limits apply to each operation on its own, including the time the user
+    /// takes to answer an unlock prompt.
+    pub fn with_connection_and_options(connection: Connection, options: CallOptions) -> Result<Self, SecretError> {
+        let session = options.block_on(async {
+            let service = Proxy::new(connection.inner(), DESTINATION, SERVICE_PATH, SERVICE_INTERFACE).await?;
+            let (_output, session): (OwnedValue, OwnedObjectPath) =
+                service.call("OpenSession", &("plain", Value::from(""))).await?;
+            Ok::<_, SecretError>(session)
+        })?;
+
+        Ok(Self {
+            connection,
+            session,
+            options,
+        })
+    }
+
+    /// Find items whose attributes match all of the given ones, unlocking them if needed
+    pub fn search(&self, attributes: &HashMap<&str, &str>) -> Result<Vec<SecretItem>, SecretError> {
+        self.options.block_on(async {
+            let (mut unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) =
+                self.service().await?.call("SearchItems", &(attributes,)).await?;
+
+            if !locked.is_empty() {
+                unlocked.extend(self.unlock(locked).await?);
+            }
+
+            Ok(unlocked.into_iter().map(|path| SecretItem { path }).collect())
+        })
+    }
+
+    /// Retrieve the secret value of an item
+    pub fn get_secret(&self, item: &SecretItem) -> Result<Vec<u8>, SecretError> {
+        self.options.block_on(async {
+            let (_session, _parameters, value, _content_type): RawSecret =
+                self.item_proxy(item).await?.call("GetSecret", &(self.session_path(),)).await?;
+            Ok(value)
+        })
+    }
+
+    /// Get the human readable label of an item
+    pub fn label(&self, item: &SecretItem) -> Result<String, SecretError> {
+        self.options
+            .block_on(async { Ok(self.item_proxy(item).await?.get_property("Label").await?) })
+    }
+
+    /// Get the lookup attributes of an item
+    pub fn attributes(&self, item: &SecretItem) -> Result<HashMap<String, String>, SecretError> {
+        self.options
+            .block_on(async { Ok(self.item_proxy(item).await?.get_property("Attributes").await?) })
+    }
+
+    /// Store a secret in the default collection, replacing an item with the same attributes
+    pub fn store(
+        &self,
+        label: &str,
+        attributes: &HashMap<&str, &str>,
+        secret: &[u8],
+        content_type: &str,
+    ) -> Result<SecretItem, SecretError> {
+        let mut properties: HashMap<&str, Value> = HashMap::new();
+        properties.insert("org.freedesktop.Secret.Item.Label", Value::from(label));
+        properties.insert("org.freedesktop.Secret.Item.Attributes", Value::from(attributes.clone()));
+        let secret = (self.session_path(), Vec::<u8>::new(), secret.to_vec(), content_type);
+
+        self.options.block_on(async {
+            let collection =
+                Proxy::new(self.connection.inner(), DESTINATION, DEFAULT_COLLECTION, COLLECTION_INTERFACE).await?;
+            let (item, prompt): (OwnedObjectPath, OwnedObjectPath) =
+                collection.call("CreateItem", &(properties, secret, true)).await?;
+
+            if item.as_str() != "/" {
+                return Ok(SecretItem { path: item });
+            }
+
+            // The collection is locked; the prompt result holds the created item
+            let result = self.prompt(&prompt).await?;
+            let path = OwnedObjectPath::try_from(result)
+                .map_err(|e| SecretError::InvalidResponse(e.to_string()))?;
+            Ok(SecretItem { path })
+        })
+    }
+
+    /// Delete an item
+    pub fn delete(&self, item: &SecretItem) -> Result<(), SecretError> {
+        self.options.block_on(async {
+            let prompt: OwnedObjectPath = self.item_proxy(item).await?.call("Delete", &()).await?;
+            if prompt.as_str() != "/" {
+                self.prompt(&prompt).await?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn unlock(&self, objects: Vec<OwnedObjectPath>) -> Result<Vec<OwnedObjectPath>, SecretError> {
+        let (mut unlocked, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) =
+            self.service().await?.call("Unlock", &(objects,)).await?;
+
+        if prompt.as_str() != "/" {
+            let result = self.prompt(&prompt).await?;
+            let prompted: Vec<OwnedObjectPath> = result
+                .try_into()
+                .map_err(|e: zbus::zvariant::Error| SecretError::InvalidResponse(e.to_string()))?;
+            unlocked.extend(prompted);
+        }
+
+        Ok(unlocked)
+    }
+
+    /// Show a prompt and wait for it to complete, returning its result
+    async fn prompt(&self, path: &OwnedObjectPath) -> Result<OwnedValue, SecretError> {
+        let proxy = Proxy::new(self.connection.inner(), DESTINATION, path.as_str(), PROMPT_INTERFACE).await?;
+        let mut completed = proxy.receive_signal("Completed").await?;
+        proxy.call_method("Prompt", &("",)).await?;
+
+        let message = call::next(&mut completed)
+            .await
+            .ok_or_else(|| SecretError::InvalidResponse("Prompt signal stream ended".to_string()))?;
+        let (dismissed, result): (bool, OwnedValue) = message.body().deserialize()?;
+
+        if dismissed {
+            Err(SecretError::Dismissed)
+        } else {
+            Ok(result)
+        }
+    }
+
+    async fn service(&self) -> Result<Proxy<'_>, SecretError> {
+        Ok(Proxy::new(self.connection.inner(), DESTINATION, SERVICE_PATH, SERVICE_INTERFACE).await?)
+    }
+
+    async fn item_proxy<'a>(&'a self, item: &'a SecretItem) -> Result<Proxy<'a>, SecretError> {
+        Ok(Proxy::new(self.connection.inner(), DESTINATION, item.path.as_str(), ITEM_INTERFACE).await?)
+    }
+
+    fn session_path(&self) -> ObjectPath<'_> {
+        self.session.as_ref()
+    }
+}
+
+impl Drop for SecretService {
+    fn drop(&mut self) {
+        let _: Result<(), SecretError> = self.options.block_on(async {
+            let proxy = Proxy::new(
+                self.connection.inner(),
+                DESTINATION,
+                self.session.as_str(),
+                "org.freedesktop.Secret.Session",
+            )
+            .await?;
+            proxy.call_method("Close", &()).await?;
+            Ok(())
+        });
+    }
+}
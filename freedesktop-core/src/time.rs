@@ -0,0 +1,289 @@
+//! Timestamps used across the specifications
+//!
+//! [`Timestamp`] wraps [`SystemTime`] and converts to and from the text forms the
+//! specifications use: RFC 3339 with an explicit offset for data written by this crate
+//! and recent files, and the zone-less local `YYYY-MM-DDThh:mm:ss` form of trashinfo
+//! `DeletionDate` keys.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A point in time, formatted as RFC 3339 in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(SystemTime);
+
+impl Timestamp {
+    /// The current time
+    pub fn now() -> Self {
+        Timestamp(SystemTime::now())
+    }
+
+    /// Create a timestamp from seconds since the Unix epoch
+    pub fn from_unix_secs(secs: i64) -> Self {
+        let time = if secs >= 0 {
+            UNIX_EPOCH + Duration::from_secs(secs as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+        };
+        Timestamp(time)
+    }
+
+    /// Get the seconds since the Unix epoch, rounded down
+    pub fn unix_secs(&self) -> i64 {
+        match self.0.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => {
+                let d = e.duration();
+                -(d.as_secs() as i64) - i64::from(d.subsec_nanos() > 0)
+            }
+        }
+    }
+
+    /// Get the underlying system time
+    pub fn system_time(&self) -> SystemTime {
+        self.0
+    }
+
+    /// Parse an RFC 3339 date-time such as `2024-05-01T12:30:00+02:00`
+    ///
+    /// Fractional seconds are accepted and a lowercase `t`/`z` or a space separator
+    /// are tolerated. Leap seconds are folded into the following second.
+    pub fn parse_rfc3339(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (date, rest) = s.split_at_checked(10)?;
+        let rest = rest.strip_prefix(['T', 't', ' '])?;
+
+        let (time, offset) = match rest.find(['Z', 'z', '+', '-']) {
+            Some(pos) => rest.split_at(pos),
+            None => return None,
+        };
+        let offset_secs = match offset {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let (hours, minutes) = offset[1..].split_once(':')?;
+                sign * (parse_digits(hours, 2)? * 3600 + parse_digits(minutes, 2)? * 60)
+            }
+        };
+
+        let (time, nanos) = match time.split_once('.') {
+            Some((time, fraction)) => (time, parse_fraction(fraction)?),
+            None => (time, 0),
+        };
+
+        let local = parse_date(date)? * SECS_PER_DAY + parse_time(time)?;
+        let mut timestamp = Self::from_unix_secs(local - offset_secs);
+        timestamp.0 += Duration::from_nanos(u64::from(nanos));
+        Some(timestamp)
+    }
+
+    /// Format as RFC 3339 in UTC, e.g. `2024-05-01T10:30:00Z`
+    pub fn to_rfc3339(&self) -> String {
+        format_civil(self.unix_secs(), "Z")
+    }
+
+    /// Parse a zone-less `YYYY-MM-DDThh:mm:ss` date-time in the system's local time zone,
+    /// as used by the trash specification
+    pub fn parse_local(s: &str) -> Option<Self> {
+        let (date, time) = s.trim().split_once('T')?;
+        let naive = parse_date(date)? * SECS_PER_DAY + parse_time(time)?;
+
+        // The offset depends on the instant itself, so refine it once around DST changes
+        let guess = naive - local_offset(naive);
+        Some(Self::from_unix_secs(naive - local_offset(guess)))
+    }
+
+    /// Format as a zone-less `YYYY-MM-DDThh:mm:ss` date-time in the system's local time zone
+    pub fn to_local(&self) -> String {
+        let secs = self.unix_secs();
+        format_civil(secs + local_offset(secs), "")
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        Timestamp(time)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rfc3339())
+    }
+}
+
+/// Error returned when a string isn't a valid RFC 3339 date-time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimestampError(String);
+
+impl fmt::Display for ParseTimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid RFC 3339 timestamp: '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseTimestampError {}
+
+impl FromStr for Timestamp {
+    type Err = ParseTimestampError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_rfc3339(s).ok_or_else(|| ParseTimestampError(s.to_string()))
+    }
+}
+
+fn parse_digits(s: &str, len: usize) -> Option<i64> {
+    if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn parse_fraction(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = s.chars().chain(std::iter::repeat('0')).take(9).collect();
+    digits.parse().ok()
+}
+
+/// Parse `YYYY-MM-DD` into days since the Unix epoch
+fn parse_date(s: &str) -> Option<i64> {
+    let mut parts = s.split('-');
+    let year = parse_digits(parts.next()?, 4)?;
+    let month = parse_digits(parts.next()?, 2)?;
+    let day = parse_digits(parts.next()?, 2)?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// Parse `hh:mm:ss` into seconds since midnight
+fn parse_time(s: &str) -> Option<i64> {
+    let mut parts = s.split(':');
+    let hour = parse_digits(parts.next()?, 2)?;
+    let minute = parse_digits(parts.next()?, 2)?;
+    let second = parse_digits(parts.next()?, 2)?;
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(hour * 3600 + minute * 60 + second)
+}
+
+fn format_civil(secs: i64, suffix: &str) -> String {
+    let days = secs.div_euclid(SECS_PER_DAY);
+    let time = secs.rem_euclid(SECS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        suffix
+    )
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between proleptic Gregorian dates and days since 1970-01-01,
+// after Howard Hinnant's `days_from_civil` and `civil_from_days`
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Offset of the local time zone from UTC in seconds at the given instant
+fn local_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    if result.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc3339_round_trip() {
+        let timestamp = Timestamp::parse_rfc3339("2024-02-29T23:59:58Z").unwrap();
+        assert_eq!(timestamp.unix_secs(), 1_709_251_198);
+        assert_eq!(timestamp.to_rfc3339(), "2024-02-29T23:59:58Z");
+        assert_eq!(Timestamp::from_unix_secs(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(Timestamp::from_unix_secs(-1).to_string(), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_rfc3339_offsets_and_fractions() {
+        let utc = Timestamp::parse_rfc3339("2024-05-01T10:30:00Z").unwrap();
+        assert_eq!(Timestamp::parse_rfc3339("2024-05-01T12:30:00+02:00"), Some(utc));
+        assert_eq!(Timestamp::parse_rfc3339("2024-05-01 05:30:00-05:00"), Some(utc));
+
+        let fractional: Timestamp = "2024-05-01T10:30:00.25Z".parse().unwrap();
+        assert_eq!(
+            fractional.system_time().duration_since(utc.system_time()).unwrap(),
+            Duration::from_millis(250)
+        );
+    }
+
+    #[test]
+    fn test_rfc3339_rejects_invalid() {
+        for invalid in ["", "2024-05-01", "2024-05-01T10:30:00", "2023-02-29T00:00:00Z", "2024-13-01T00:00:00Z", "2024-05-01T24:00:00Z", "2024-05-01T10:30:00+0200"] {
+            assert!(invalid.parse::<Timestamp>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_local_round_trip() {
+        let timestamp = Timestamp::from_unix_secs(1_700_000_000);
+        let local = timestamp.to_local();
+        assert_eq!(local.len(), 19);
+        assert_eq!(Timestamp::parse_local(&local), Some(timestamp));
+    }
+}
@@ -0,0 +1,441 @@
+//! The binary `mime.cache` of a `mime` directory
+//!
+//! Besides the text files, `update-mime-database` writes everything it knows to
+//! `mime.cache`, as sorted tables that are searched where they lie. The file is
+//! mapped into memory, so opening it costs nothing and a lookup only touches the
+//! entries a binary search visits, where the text files have to be parsed in full
+//! before the first lookup.
+//!
+//! Numbers are big-endian 32-bit values and strings are offsets to NUL-terminated
+//! text. The header holds the format version followed by the offsets of the tables.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::glob::{best_matches, pattern_matches};
+
+/// The major version of the format; minor versions only add tables
+const MAJOR_VERSION: u32 = 1;
+
+/// Header fields holding the offsets of the tables used here
+const ALIAS_LIST: usize = 4;
+const PARENT_LIST: usize = 8;
+const LITERAL_LIST: usize = 12;
+const REVERSE_SUFFIX_TREE: usize = 16;
+const GLOB_LIST: usize = 20;
+const MAGIC_LIST: usize = 24;
+
+/// Sizes of the table entries
+const ALIAS_ENTRY: usize = 8;
+const PARENT_ENTRY: usize = 8;
+const GLOB_ENTRY: usize = 12;
+const SUFFIX_NODE: usize = 12;
+const MAGIC_MATCH: usize = 16;
+const MATCHLET: usize = 32;
+
+/// Bit of a glob's weight field set for case-sensitive patterns
+const CASE_SENSITIVE: u32 = 0x100;
+
+/// Deepest matchlet nesting followed, so a corrupt file can't recurse forever
+const MAX_MATCHLET_DEPTH: usize = 32;
+
+/// The bytes of a cache file
+enum Buffer {
+    #[cfg(unix)]
+    Mapped { address: *mut libc::c_void, len: usize },
+    Owned(Vec<u8>),
+}
+
+// SAFETY: the mapping is private and read-only and lives as long as the buffer
+#[cfg(unix)]
+unsafe impl Send for Buffer {}
+#[cfg(unix)]
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    #[cfg(unix)]
+    fn map(file: &File) -> io::Result<Buffer> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len()).map_err(|_| invalid("file too large"))?;
+        if len == 0 {
+            return Ok(Buffer::Owned(Vec::new()));
+        }
+        // SAFETY: a new read-only mapping of the whole file, unmapped on drop.
+        // update-mime-database replaces the file rather than rewriting it, so the
+        // mapped pages don't change underneath us.
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Buffer::Mapped { address, len })
+    }
+
+    /// Read the whole file where it can't be mapped
+    #[cfg(not(unix))]
+    fn map(mut file: &File) -> io::Result<Buffer> {
+        use std::io::Read;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(Buffer::Owned(bytes))
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self {
+            // SAFETY: the mapping is `len` bytes long and valid until drop
+            #[cfg(unix)]
+            Buffer::Mapped { address, len } => unsafe { std::slice::from_raw_parts(*address as *const u8, *len) },
+            Buffer::Owned(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if let Buffer::Mapped { address, len } = self {
+            // SAFETY: unmapping the mapping created in Buffer::map
+            unsafe {
+                libc::munmap(*address, *len);
+            }
+        }
+    }
+}
+
+/// A `mime.cache` file, queried without parsing it
+///
+/// Clones share the same mapping.
+#[derive(Clone)]
+pub struct MimeCache {
+    buffer: Arc<Buffer>,
+}
+
+impl fmt::Debug for MimeCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MimeCache").field("len", &self.bytes().len()).finish()
+    }
+}
+
+impl MimeCache {
+    /// Map a `mime.cache` file into memory
+    ///
+    /// Fails if the file can't be read or isn't a cache of a supported version.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_buffer(Buffer::map(&file)?)
+    }
+
+    /// Use the contents of a `mime.cache` file already in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        Self::from_buffer(Buffer::Owned(bytes))
+    }
+
+    fn from_buffer(buffer: Buffer) -> io::Result<Self> {
+        let cache = MimeCache {
+            buffer: Arc::new(buffer),
+        };
+        let version = cache.u32_at(0).ok_or_else(|| invalid("missing header"))?;
+        if version >> 16 != MAJOR_VERSION {
+            return Err(invalid(&format!("unsupported version {}.{}", version >> 16, version & 0xffff)));
+        }
+        for field in [ALIAS_LIST, PARENT_LIST, LITERAL_LIST, REVERSE_SUFFIX_TREE, GLOB_LIST, MAGIC_LIST] {
+            let table = cache.offset_at(field).ok_or_else(|| invalid("missing header"))?;
+            if cache.u32_at(table).is_none() {
+                return Err(invalid("table offset past the end"));
+            }
+        }
+        Ok(cache)
+    }
+
+    /// Get the MIME type of a file from its name, like [`GlobDatabase::mime_type_for_filename`](crate::GlobDatabase::mime_type_for_filename)
+    pub fn mime_type_for_filename<P: AsRef<Path>>(&self, path: P) -> Option<&str> {
+        self.mime_types_for_filename(path).into_iter().next()
+    }
+
+    /// Get every MIME type tied for the best match of a file name, like
+    /// [`GlobDatabase::mime_types_for_filename`](crate::GlobDatabase::mime_types_for_filename)
+    pub fn mime_types_for_filename<P: AsRef<Path>>(&self, path: P) -> Vec<&str> {
+        match path.as_ref().file_name() {
+            Some(name) => best_matches(self.glob_matches(&name.to_string_lossy())),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the MIME type of a file from the start of its contents
+    pub fn mime_type_for_data(&self, data: &[u8]) -> Option<&str> {
+        self.sniff(data, &HashSet::new()).map(|(_, mime_type)| mime_type)
+    }
+
+    /// Get the number of bytes at the start of a file the magic rules look at
+    pub fn max_extent(&self) -> usize {
+        self.offset_at(MAGIC_LIST)
+            .and_then(|table| self.offset_at(table + 4))
+            .unwrap_or(0)
+    }
+
+    /// Get the type an alias stands for, or `None` when `mime_type` isn't an alias
+    pub fn unalias(&self, mime_type: &str) -> Option<&str> {
+        let (count, entries) = self.table(ALIAS_LIST)?;
+        let key = |i: usize| self.str_field(entries + i * ALIAS_ENTRY);
+        let index = self.equal_range(count, key, mime_type).next()?;
+        self.str_field(entries + index * ALIAS_ENTRY + 4)
+    }
+
+    /// Get the aliases of a type
+    ///
+    /// The alias table is sorted by alias, so every entry is read.
+    pub fn aliases(&self, mime_type: &str) -> Vec<&str> {
+        let Some((count, entries)) = self.table(ALIAS_LIST) else {
+            return Vec::new();
+        };
+        (0..count)
+            .map(|i| entries + i * ALIAS_ENTRY)
+            .filter(|&entry| self.str_field(entry + 4) == Some(mime_type))
+            .filter_map(|entry| self.str_field(entry))
+            .collect()
+    }
+
+    /// Get the types `mime_type` is a direct subclass of
+    pub fn parents(&self, mime_type: &str) -> Vec<&str> {
+        let Some((count, entries)) = self.table(PARENT_LIST) else {
+            return Vec::new();
+        };
+        let key = |i: usize| self.str_field(entries + i * PARENT_ENTRY);
+        let Some(index) = self.equal_range(count, key, mime_type).next() else {
+            return Vec::new();
+        };
+        let Some(list) = self.offset_at(entries + index * PARENT_ENTRY + 4) else {
+            return Vec::new();
+        };
+        let count = self.offset_at(list).unwrap_or(0);
+        (0..count).filter_map(|i| self.str_field(list + 4 + i * 4)).collect()
+    }
+
+    /// Get the `(weight, pattern length, type)` of every glob matching a file name
+    pub(crate) fn glob_matches(&self, name: &str) -> Vec<(u32, usize, &str)> {
+        let mut matches = Vec::new();
+        let lowercase = name.to_lowercase();
+
+        // Literal names, such as Makefile; case-insensitive ones are stored lowercase
+        if let Some((count, entries)) = self.table(LITERAL_LIST) {
+            let key = |i: usize| self.str_field(entries + i * GLOB_ENTRY);
+            let length = name.chars().count();
+            for (lookup, case_sensitive) in [(lowercase.as_str(), false), (name, true)] {
+                for i in self.equal_range(count, key, lookup) {
+                    if let Some((_, mime_type, weight, cs)) = self.glob_entry(entries + i * GLOB_ENTRY) {
+                        if cs == case_sensitive {
+                            matches.push((weight, length, mime_type));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Patterns of the form *suffix, stored as a tree of characters read backwards
+        if let Some((count, first)) = self.table(REVERSE_SUFFIX_TREE) {
+            if let Some(roots) = self.offset_at(first) {
+                self.suffix_matches(&lowercase, false, count, roots, &mut matches);
+                self.suffix_matches(name, true, count, roots, &mut matches);
+            }
+        }
+
+        // Everything else is matched one pattern at a time
+        if let Some((count, entries)) = self.table(GLOB_LIST) {
+            for i in 0..count {
+                if let Some((pattern, mime_type, weight, cs)) = self.glob_entry(entries + i * GLOB_ENTRY) {
+                    if pattern_matches(pattern, name, cs) {
+                        matches.push((weight, pattern.chars().count(), mime_type));
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    /// Walk the suffix tree along the name from its end, collecting the types of
+    /// every suffix passed
+    fn suffix_matches<'a>(
+        &'a self,
+        name: &str,
+        case_sensitive: bool,
+        mut count: usize,
+        mut nodes: usize,
+        matches: &mut Vec<(u32, usize, &'a str)>,
+    ) {
+        for (depth, ch) in name.chars().rev().enumerate() {
+            let Some(node) = self.find_node(count, nodes, u32::from(ch)) else {
+                return;
+            };
+            let (Some(children), Some(first_child)) = (self.offset_at(node + 4), self.offset_at(node + 8)) else {
+                return;
+            };
+            count = children;
+            nodes = first_child;
+
+            // Leaves have character 0, so they sort before the other children
+            for leaf in (0..count).map(|i| nodes + i * SUFFIX_NODE) {
+                if self.u32_at(leaf) != Some(0) {
+                    break;
+                }
+                let (Some(mime_type), Some(flags)) = (self.str_field(leaf + 4), self.u32_at(leaf + 8)) else {
+                    continue;
+                };
+                if (flags & CASE_SENSITIVE != 0) == case_sensitive {
+                    // The pattern is '*' followed by the suffix matched so far
+                    matches.push((flags & 0xff, depth + 2, mime_type));
+                }
+            }
+        }
+    }
+
+    /// Binary search the sorted nodes of one tree level for a character
+    fn find_node(&self, count: usize, nodes: usize, ch: u32) -> Option<usize> {
+        let index = partition(count, |i| self.u32_at(nodes + i * SUFFIX_NODE).is_some_and(|c| c < ch));
+        let node = nodes + index * SUFFIX_NODE;
+        (index < count && self.u32_at(node) == Some(ch)).then_some(node)
+    }
+
+    /// Read a literal or glob entry: pattern, type, weight and case sensitivity
+    fn glob_entry(&self, entry: usize) -> Option<(&str, &str, u32, bool)> {
+        let flags = self.u32_at(entry + 8)?;
+        Some((
+            self.str_field(entry)?,
+            self.str_field(entry + 4)?,
+            flags & 0xff,
+            flags & CASE_SENSITIVE != 0,
+        ))
+    }
+
+    /// Find the highest priority magic rule matching the data, as `(priority, type)`
+    ///
+    /// Rules for the `excluded` types are skipped.
+    pub(crate) fn sniff(&self, data: &[u8], excluded: &HashSet<String>) -> Option<(u32, &str)> {
+        let table = self.offset_at(MAGIC_LIST)?;
+        let count = self.offset_at(table)?;
+        let matches = self.offset_at(table + 8)?;
+        // Sorted by priority, highest first
+        (0..count).map(|i| matches + i * MAGIC_MATCH).find_map(|entry| {
+            let matchlets = self.offset_at(entry + 8)?;
+            let first = self.offset_at(entry + 12)?;
+            let mime_type = self.str_field(entry + 4)?;
+            if excluded.contains(mime_type) || !self.matchlets_match(matchlets, first, data, 0) {
+                return None;
+            }
+            Some((self.u32_at(entry)?, mime_type))
+        })
+    }
+
+    /// Check if one of the matchlets matches along with, if it has any, one of its children
+    fn matchlets_match(&self, count: usize, first: usize, data: &[u8], depth: usize) -> bool {
+        if depth > MAX_MATCHLET_DEPTH {
+            return false;
+        }
+        (0..count).any(|i| {
+            let matchlet = first + i * MATCHLET;
+            let field = |n: usize| self.offset_at(matchlet + n * 4);
+            let (Some(start), Some(range), Some(length), Some(value), Some(mask), Some(children), Some(first_child)) =
+                (field(0), field(1), field(3), field(4), field(5), field(6), field(7))
+            else {
+                return false;
+            };
+            let Some(value) = self.bytes().get(value..value + length) else {
+                return false;
+            };
+            // Offset 0 means there is no mask
+            let mask = match mask {
+                0 => None,
+                mask => match self.bytes().get(mask..mask + length) {
+                    Some(mask) => Some(mask),
+                    None => return false,
+                },
+            };
+
+            let found = (start..start + range.max(1)).any(|offset| {
+                let Some(window) = data.get(offset..offset + length) else {
+                    return false;
+                };
+                match mask {
+                    Some(mask) => window
+                        .iter()
+                        .zip(value)
+                        .zip(mask)
+                        .all(|((byte, value), mask)| byte & mask == value & mask),
+                    None => window == value,
+                }
+            });
+            found && (children == 0 || self.matchlets_match(children, first_child, data, depth + 1))
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.buffer.bytes()
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes().get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?))
+    }
+
+    fn offset_at(&self, offset: usize) -> Option<usize> {
+        self.u32_at(offset).and_then(|value| usize::try_from(value).ok())
+    }
+
+    /// Read the string a field points to
+    fn str_field(&self, offset: usize) -> Option<&str> {
+        let rest = self.bytes().get(self.offset_at(offset)?..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&rest[..end]).ok()
+    }
+
+    /// Get the entry count and the offset of the first entry of a table that
+    /// starts with its count
+    fn table(&self, field: usize) -> Option<(usize, usize)> {
+        let table = self.offset_at(field)?;
+        Some((self.offset_at(table)?, table + 4))
+    }
+
+    /// Get the indices of the entries whose key equals `target` in a table sorted by key
+    fn equal_range<'a, F>(&'a self, count: usize, key: F, target: &str) -> Range<usize>
+    where
+        F: Fn(usize) -> Option<&'a str>,
+    {
+        let target = target.as_bytes();
+        let start = partition(count, |i| key(i).is_some_and(|k| k.as_bytes() < target));
+        let end = partition(count, |i| key(i).is_some_and(|k| k.as_bytes() <= target));
+        start..end.max(start)
+    }
+}
+
+/// Find the first index in `0..count` for which `before` is false, given that it
+/// is true for a prefix of the range and false after
+fn partition<F: Fn(usize) -> bool>(count: usize, before: F) -> usize {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if before(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid mime.cache: {}", message))
+}
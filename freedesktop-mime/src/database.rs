@@ -0,0 +1,339 @@
+//! Combining glob and magic results as the shared-mime-info specification describes
+//!
+//! A file's name is checked first. When the globs agree on one type that is the
+//! answer; otherwise the file's contents are sniffed. A glob type that is the
+//! sniffed type or one of its subclasses wins over the sniffed type: a Word
+//! document sniffs as `application/x-ole-storage`, and the `.doc` glob's
+//! `application/msword`, a subclass of it, is the more specific answer.
+//!
+//! Types are compared after resolving aliases, so `text/x-markdown` is treated as
+//! the `text/markdown` it stands for.
+//!
+//! Directories with a `mime.cache` are queried through it and their text files
+//! are never read. `__NOGLOBS__` and `__NOMAGIC__` in the text files of a more
+//! important directory still hide a type's globs and magic rules in the caches.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::glob::best_matches;
+use crate::{GlobDatabase, MagicDatabase, MimeCache};
+
+/// Type of data that isn't text and matches no rule
+pub const OCTET_STREAM: &str = "application/octet-stream";
+/// Type of text that matches no rule
+pub const TEXT_PLAIN: &str = "text/plain";
+/// Type of empty files
+pub const ZERO_SIZE: &str = "application/x-zerosize";
+/// Type of directories
+pub const DIRECTORY: &str = "inode/directory";
+
+/// How much of a file is read when the magic rules don't say
+const DEFAULT_SNIFF_LENGTH: usize = 4096;
+
+/// The glob, magic, alias and subclass data of the installed MIME database
+#[derive(Debug, Clone, Default)]
+pub struct MimeDatabase {
+    globs: GlobDatabase,
+    magic: MagicDatabase,
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, Vec<String>>,
+    caches: Vec<MimeCache>,
+    /// What the text files added before each cache cleared, one per cache
+    cleared: Vec<Cleared>,
+}
+
+/// Types whose globs and magic rules a more important directory removed
+#[derive(Debug, Clone, Default)]
+struct Cleared {
+    globs: HashSet<String>,
+    magic: HashSet<String>,
+}
+
+impl MimeDatabase {
+    /// Load the installed MIME database, see [`mime_dirs`](crate::mime_dirs)
+    pub fn load() -> Self {
+        Self::load_from(&crate::mime_dirs())
+    }
+
+    /// Load the given `mime` directories, most important first
+    ///
+    /// A directory's `mime.cache` is used when it can be opened, otherwise its
+    /// `globs2`, `magic`, `aliases` and `subclasses` files are parsed.
+    pub fn load_from(dirs: &[PathBuf]) -> Self {
+        let mut database = MimeDatabase::default();
+        for dir in dirs {
+            match MimeCache::open(dir.join("mime.cache")) {
+                Ok(cache) => database.add_cache(cache),
+                Err(_) => database.add_text_files(dir),
+            }
+        }
+        database
+    }
+
+    /// Load the given `mime` directories from their text files, ignoring any `mime.cache`
+    pub fn load_text_from(dirs: &[PathBuf]) -> Self {
+        let mut database = MimeDatabase::default();
+        for dir in dirs {
+            database.add_text_files(dir);
+        }
+        database
+    }
+
+    fn add_text_files(&mut self, dir: &Path) {
+        if let Ok(content) = std::fs::read_to_string(dir.join("globs2")) {
+            self.globs.add_globs2(&content);
+        }
+        if let Ok(content) = std::fs::read(dir.join("magic")) {
+            let _ = self.magic.add_magic(&content);
+        }
+        if let Ok(content) = std::fs::read_to_string(dir.join("aliases")) {
+            for (alias, mime_type) in content.lines().filter_map(|line| line.split_once(' ')) {
+                // More important directories were added first
+                self.aliases.entry(alias.to_string()).or_insert_with(|| mime_type.to_string());
+            }
+        }
+        let Ok(content) = std::fs::read_to_string(dir.join("subclasses")) else {
+            return;
+        };
+        for (child, parent) in content.lines().filter_map(|line| line.split_once(' ')) {
+            let known = self.parents.entry(child.to_string()).or_default();
+            if !known.iter().any(|p| p == parent) {
+                known.push(parent.to_string());
+            }
+        }
+    }
+
+    /// Build a database from already loaded parts and `(type, parent type)` pairs
+    pub fn from_parts(globs: GlobDatabase, magic: MagicDatabase, subclasses: &[(&str, &str)]) -> Self {
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        for (child, parent) in subclasses {
+            parents.entry(child.to_string()).or_default().push(parent.to_string());
+        }
+        MimeDatabase {
+            globs,
+            magic,
+            aliases: HashMap::new(),
+            parents,
+            caches: Vec::new(),
+            cleared: Vec::new(),
+        }
+    }
+
+    /// Add a cache that is less important than the data added before
+    ///
+    /// Types cleared by `__NOGLOBS__` or `__NOMAGIC__` in the text files added
+    /// before keep their globs or magic rules in the cache hidden.
+    pub fn add_cache(&mut self, cache: MimeCache) {
+        self.cleared.push(Cleared {
+            globs: self.globs.cleared_types().map(String::from).collect(),
+            magic: self.magic.cleared_types().map(String::from).collect(),
+        });
+        self.caches.push(cache);
+    }
+
+    /// Get the globs read from the text files of directories without a cache
+    pub fn globs(&self) -> &GlobDatabase {
+        &self.globs
+    }
+
+    /// Get the magic rules read from the text files of directories without a cache
+    pub fn magic(&self) -> &MagicDatabase {
+        &self.magic
+    }
+
+    /// Get the caches of the directories that have one
+    pub fn caches(&self) -> &[MimeCache] {
+        &self.caches
+    }
+
+    /// Get the MIME type of a file from its name, see [`GlobDatabase::mime_type_for_filename`]
+    pub fn mime_type_for_filename<P: AsRef<Path>>(&self, path: P) -> Option<&str> {
+        self.mime_types_for_filename(path).into_iter().next()
+    }
+
+    /// Get every MIME type tied for the best match of a file name, see
+    /// [`GlobDatabase::mime_types_for_filename`]
+    pub fn mime_types_for_filename<P: AsRef<Path>>(&self, path: P) -> Vec<&str> {
+        let Some(name) = path.as_ref().file_name().map(|name| name.to_string_lossy()) else {
+            return Vec::new();
+        };
+        let mut matches = Vec::new();
+        for (cache, cleared) in self.caches.iter().zip(&self.cleared) {
+            let found = cache.glob_matches(&name);
+            matches.extend(found.into_iter().filter(|(_, _, mime_type)| !cleared.globs.contains(*mime_type)));
+        }
+        matches.extend(self.globs.glob_matches(&name));
+        best_matches(matches)
+    }
+
+    /// Get the type an alias stands for, or `mime_type` itself when it isn't an alias
+    pub fn unalias<'a>(&'a self, mime_type: &'a str) -> &'a str {
+        self.caches
+            .iter()
+            .find_map(|cache| cache.unalias(mime_type))
+            .or_else(|| self.aliases.get(mime_type).map(String::as_str))
+            .unwrap_or(mime_type)
+    }
+
+    /// Get the aliases of a type, from caches and text files
+    pub fn aliases(&self, mime_type: &str) -> Vec<&str> {
+        let cached = self.caches.iter().flat_map(|cache| cache.aliases(mime_type));
+        let parsed = self
+            .aliases
+            .iter()
+            .filter(|(_, canonical)| *canonical == mime_type)
+            .map(|(alias, _)| alias.as_str());
+        let mut aliases: Vec<&str> = Vec::new();
+        for alias in cached.chain(parsed) {
+            if !aliases.contains(&alias) {
+                aliases.push(alias);
+            }
+        }
+        aliases
+    }
+
+    /// Get the types `mime_type` is a direct subclass of, from caches and text files
+    ///
+    /// An alias has the parents of the type it stands for.
+    pub fn parents(&self, mime_type: &str) -> Vec<&str> {
+        let mime_type = self.unalias(mime_type);
+        let cached = self.caches.iter().flat_map(|cache| cache.parents(mime_type));
+        let parsed = self.parents.get(mime_type).into_iter().flatten().map(String::as_str);
+        let mut parents: Vec<&str> = Vec::new();
+        for parent in cached.chain(parsed) {
+            if !parents.contains(&parent) {
+                parents.push(parent);
+            }
+        }
+        parents
+    }
+
+    /// Get every type `mime_type` is a subclass of, closest first
+    ///
+    /// `text/plain` is included for text types. `application/octet-stream`, which
+    /// nearly every type is a subclass of, is only included when declared.
+    pub fn ancestors(&self, mime_type: &str) -> Vec<&str> {
+        let mime_type = self.unalias(mime_type);
+        let mut ancestors: Vec<&str> = Vec::new();
+        let mut next = 0;
+        let mut current = mime_type;
+        loop {
+            for parent in self.parents(current) {
+                if parent != mime_type && !ancestors.contains(&parent) {
+                    ancestors.push(parent);
+                }
+            }
+            let Some(&ancestor) = ancestors.get(next) else {
+                break;
+            };
+            current = ancestor;
+            next += 1;
+        }
+        if mime_type.starts_with("text/") && mime_type != TEXT_PLAIN && !ancestors.contains(&TEXT_PLAIN) {
+            ancestors.push(TEXT_PLAIN);
+        }
+        ancestors
+    }
+
+    /// Check if `mime_type` is `parent` or a subclass of it
+    ///
+    /// Besides the database's subclasses, every `text/*` type is a subclass of
+    /// `text/plain` and every type but `inode/*` ones of `application/octet-stream`.
+    /// Aliases are resolved first.
+    pub fn is_subclass(&self, mime_type: &str, parent: &str) -> bool {
+        let (mime_type, parent) = (self.unalias(mime_type), self.unalias(parent));
+        if mime_type == parent {
+            return true;
+        }
+        if parent == TEXT_PLAIN && mime_type.starts_with("text/") {
+            return true;
+        }
+        if parent == OCTET_STREAM && !mime_type.starts_with("inode/") {
+            return true;
+        }
+        self.parents(mime_type).into_iter().any(|p| self.is_subclass(p, parent))
+    }
+
+    /// Get the MIME type of data from its contents alone
+    ///
+    /// Falls back to `text/plain` for data that looks like text and to
+    /// `application/octet-stream` for anything else.
+    pub fn mime_type_for_data(&self, data: &[u8]) -> &str {
+        self.sniff(data).unwrap_or_else(|| fallback(data))
+    }
+
+    /// Get the MIME type of a file from its name and, when that isn't enough, its contents
+    ///
+    /// Directories are `inode/directory` and empty files `application/x-zerosize`.
+    pub fn mime_type_for_file<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)?;
+        if metadata.is_dir() {
+            return Ok(DIRECTORY.to_string());
+        }
+
+        let globs = self.mime_types_for_filename(path);
+        if let [mime_type] = globs.as_slice() {
+            return Ok(mime_type.to_string());
+        }
+        if metadata.len() == 0 {
+            return Ok(ZERO_SIZE.to_string());
+        }
+
+        let mut data = Vec::new();
+        let length = self
+            .caches
+            .iter()
+            .map(MimeCache::max_extent)
+            .fold(self.magic.max_extent(), usize::max)
+            .max(DEFAULT_SNIFF_LENGTH);
+        File::open(path)?.take(length as u64).read_to_end(&mut data)?;
+        Ok(self.resolve(&globs, &data).to_string())
+    }
+
+    /// Find the highest priority magic rule matching the data, from caches and text files
+    fn sniff(&self, data: &[u8]) -> Option<&str> {
+        let cached = self
+            .caches
+            .iter()
+            .zip(&self.cleared)
+            .filter_map(|(cache, cleared)| cache.sniff(data, &cleared.magic));
+        let parsed = self.magic.sniff(data).map(|rule| (rule.priority(), rule.mime_type()));
+        let mut best: Option<(u32, &str)> = None;
+        for (priority, mime_type) in cached.chain(parsed) {
+            // Earlier data wins ties
+            if best.is_none_or(|(best_priority, _)| priority > best_priority) {
+                best = Some((priority, mime_type));
+            }
+        }
+        best.map(|(_, mime_type)| mime_type)
+    }
+
+    /// Pick the type among conflicting or missing glob results using the file's contents
+    fn resolve<'a>(&'a self, globs: &[&'a str], data: &[u8]) -> &'a str {
+        match self.sniff(data) {
+            Some(sniffed) => globs
+                .iter()
+                .find(|glob| self.is_subclass(glob, sniffed))
+                .copied()
+                .unwrap_or(sniffed),
+            None => globs.first().copied().unwrap_or_else(|| fallback(data)),
+        }
+    }
+}
+
+/// Tell text from binary data when no rule matched
+fn fallback(data: &[u8]) -> &'static str {
+    if data.contains(&0) {
+        return OCTET_STREAM;
+    }
+    match std::str::from_utf8(data) {
+        Ok(_) => TEXT_PLAIN,
+        // A multi-byte character may be cut off at the end of the sample
+        Err(e) if e.error_len().is_none() => TEXT_PLAIN,
+        Err(_) => OCTET_STREAM,
+    }
+}
@@ -0,0 +1,285 @@
+//! The shared-mime-info glob database
+//!
+//! `update-mime-database` writes every glob of a `mime` directory to its `globs2`
+//! file, one `weight:type:pattern[:flags]` line each. The only flag is `cs`, for
+//! patterns that match case-sensitively. A `__NOGLOBS__` pattern drops the type's
+//! globs from less important directories, so a user can take a type's patterns
+//! away from the system database.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Pattern that removes a type's globs from less important directories
+pub(crate) const NO_GLOBS: &str = "__NOGLOBS__";
+
+/// One filename pattern of a MIME type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Glob {
+    mime_type: String,
+    pattern: String,
+    weight: u32,
+    case_sensitive: bool,
+}
+
+impl Glob {
+    /// Get the MIME type the pattern identifies
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// Get the pattern, such as `*.tar.gz` or `Makefile`
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Get the weight, from 0 to 100; higher weights win
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Check if the pattern only matches names with the same case
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    /// Check whether a file name matches the pattern
+    pub fn matches(&self, name: &str) -> bool {
+        pattern_matches(&self.pattern, name, self.case_sensitive)
+    }
+
+    pub(crate) fn new(mime_type: String, pattern: String, weight: u32, case_sensitive: bool) -> Glob {
+        Glob {
+            mime_type,
+            pattern,
+            weight,
+            case_sensitive,
+        }
+    }
+
+    /// Parse a `weight:type:pattern[:flags]` line, `None` for comments and invalid lines
+    fn parse(line: &str) -> Option<Glob> {
+        if line.starts_with('#') {
+            return None;
+        }
+        let mut fields = line.splitn(4, ':');
+        let weight = fields.next()?.trim().parse().ok()?;
+        let mime_type = fields.next()?.to_string();
+        let pattern = fields.next().filter(|pattern| !pattern.is_empty())?.to_string();
+        let case_sensitive = fields.next().is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+        Some(Glob {
+            mime_type,
+            pattern,
+            weight,
+            case_sensitive,
+        })
+    }
+}
+
+/// The globs of every `mime` directory, for finding a file's type from its name
+#[derive(Debug, Clone, Default)]
+pub struct GlobDatabase {
+    globs: Vec<Glob>,
+}
+
+impl GlobDatabase {
+    /// An empty database
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the `globs2` files of the installed MIME database, see [`mime_dirs`](crate::mime_dirs)
+    pub fn load() -> Self {
+        Self::load_from(&crate::mime_dirs())
+    }
+
+    /// Load the `globs2` files of the given `mime` directories, most important first
+    ///
+    /// Directories without a `globs2` file are skipped.
+    pub fn load_from(dirs: &[PathBuf]) -> Self {
+        let mut database = GlobDatabase::new();
+        for dir in dirs {
+            if let Ok(content) = std::fs::read_to_string(dir.join("globs2")) {
+                database.add_globs2(&content);
+            }
+        }
+        database
+    }
+
+    /// Read a single `globs2` file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut database = GlobDatabase::new();
+        database.add_globs2(&std::fs::read_to_string(path)?);
+        Ok(database)
+    }
+
+    /// Add the globs of a `globs2` file that is less important than those added before
+    ///
+    /// Patterns already known for a type are ignored, as are all patterns of types
+    /// an earlier file cleared with `__NOGLOBS__`.
+    pub fn add_globs2(&mut self, content: &str) {
+        let parsed: Vec<Glob> = content.lines().filter_map(Glob::parse).collect();
+        let cleared: HashSet<&str> = self.cleared_types().collect();
+        let mut seen: HashSet<(&str, &str)> = self
+            .globs
+            .iter()
+            .map(|glob| (glob.mime_type.as_str(), glob.pattern.as_str()))
+            .collect();
+        let keep: Vec<bool> = parsed
+            .iter()
+            .map(|glob| {
+                !cleared.contains(glob.mime_type.as_str())
+                    && seen.insert((glob.mime_type.as_str(), glob.pattern.as_str()))
+            })
+            .collect();
+        self.globs.extend(parsed.into_iter().zip(keep).filter_map(|(glob, keep)| keep.then_some(glob)));
+    }
+
+    /// Get the types whose globs `__NOGLOBS__` removed from less important directories
+    pub(crate) fn cleared_types(&self) -> impl Iterator<Item = &str> {
+        self.globs.iter().filter(|glob| glob.pattern == NO_GLOBS).map(|glob| glob.mime_type.as_str())
+    }
+
+    /// Get every glob, `__NOGLOBS__` markers excluded
+    pub fn globs(&self) -> impl Iterator<Item = &Glob> {
+        self.globs.iter().filter(|glob| glob.pattern != NO_GLOBS)
+    }
+
+    /// Get the MIME type of a file from its name
+    ///
+    /// Only the last component of `path` is used and the file doesn't have to
+    /// exist. The glob with the highest weight wins, and among those the longest
+    /// pattern, so `archive.tar.gz` is a compressed tarball rather than a gzip
+    /// file. When several types tie, the first one listed is returned; see
+    /// [`GlobDatabase::mime_types_for_filename`].
+    pub fn mime_type_for_filename<P: AsRef<Path>>(&self, path: P) -> Option<&str> {
+        self.mime_types_for_filename(path).into_iter().next()
+    }
+
+    /// Get every MIME type tied for the best match of a file name
+    ///
+    /// More than one type means the name alone is ambiguous and the file's
+    /// contents have to decide.
+    pub fn mime_types_for_filename<P: AsRef<Path>>(&self, path: P) -> Vec<&str> {
+        match path.as_ref().file_name() {
+            Some(name) => best_matches(self.glob_matches(&name.to_string_lossy())),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the `(weight, pattern length, type)` of every glob matching a file name
+    pub(crate) fn glob_matches(&self, name: &str) -> Vec<(u32, usize, &str)> {
+        self.globs()
+            .filter(|glob| glob.matches(name))
+            .map(|glob| (glob.weight, glob.pattern.chars().count(), glob.mime_type.as_str()))
+            .collect()
+    }
+}
+
+/// Get the types of the best `(weight, pattern length, type)` matches, in order
+///
+/// The highest weight wins, and among those the longest pattern.
+pub(crate) fn best_matches(matches: Vec<(u32, usize, &str)>) -> Vec<&str> {
+    let Some(best) = matches.iter().map(|(weight, length, _)| (*weight, *length)).max() else {
+        return Vec::new();
+    };
+
+    let mut types: Vec<&str> = Vec::new();
+    for (_, _, mime_type) in matches.into_iter().filter(|(weight, length, _)| (*weight, *length) == best) {
+        if !types.contains(&mime_type) {
+            types.push(mime_type);
+        }
+    }
+    types
+}
+
+/// Match a file name against a glob pattern, ignoring case unless `case_sensitive`
+pub(crate) fn pattern_matches(pattern: &str, name: &str, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        return fnmatch(pattern, name);
+    }
+    fnmatch(&pattern.to_lowercase(), &name.to_lowercase())
+}
+
+/// Match a name against a shell glob with `*`, `?` and `[...]` sets
+///
+/// Walks both strings once, going back only to the most recent `*`, which then
+/// takes one more character of the name.
+fn fnmatch(pattern: &str, name: &str) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // The pattern position after the last '*' and the name position it resumes at
+    let mut star: Option<(usize, usize)> = None;
+
+    loop {
+        match pattern[p..].chars().next() {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+                continue;
+            }
+            Some(_) => {
+                if let Some(ch) = name[n..].chars().next() {
+                    if let Some(length) = match_char(&pattern[p..], ch) {
+                        p += length;
+                        n += ch.len_utf8();
+                        continue;
+                    }
+                }
+            }
+            None if n == name.len() => return true,
+            None => {}
+        }
+
+        let Some((star_p, star_n)) = star else {
+            return false;
+        };
+        let Some(skipped) = name[star_n..].chars().next() else {
+            return false;
+        };
+        star = Some((star_p, star_n + skipped.len_utf8()));
+        p = star_p;
+        n = star_n + skipped.len_utf8();
+    }
+}
+
+/// Match one character against the start of a pattern, returning the pattern bytes used
+fn match_char(pattern: &str, ch: char) -> Option<usize> {
+    match pattern.chars().next()? {
+        '?' => Some(1),
+        '[' => match set_end(pattern) {
+            Some(end) => in_set(&pattern[1..end], ch).then_some(end + 1),
+            // An unterminated set is a literal '['
+            None => (ch == '[').then_some(1),
+        },
+        literal => (literal == ch).then_some(literal.len_utf8()),
+    }
+}
+
+/// Find the `]` closing the set that starts the pattern
+fn set_end(pattern: &str) -> Option<usize> {
+    // A ']' right after the '[' or '[!' is part of the set
+    let start = if pattern[1..].starts_with('!') { 2 } else { 1 };
+    let from = start + pattern[start..].chars().next()?.len_utf8();
+    pattern[from..].find(']').map(|i| from + i)
+}
+
+/// Check if a character is in a set's contents, such as `!a-z0`
+fn in_set(set: &str, ch: char) -> bool {
+    let (negated, set) = match set.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, set),
+    };
+    let mut found = false;
+    let mut chars = set.chars();
+    while let Some(first) = chars.next() {
+        let mut ahead = chars.clone();
+        match (ahead.next(), ahead.next()) {
+            (Some('-'), Some(last)) => {
+                found |= first <= ch && ch <= last;
+                chars = ahead;
+            }
+            _ => found |= first == ch,
+        }
+    }
+    found != negated
+}
@@ -0,0 +1,412 @@
+//! Descriptions and icons of MIME types from the shared-mime-info XML
+//!
+//! Packages install their definitions to `mime/packages/*.xml`, and
+//! `update-mime-database` merges them into one file per type, such as
+//! `mime/text/markdown.xml`. Both have the same `<mime-type>` elements, with
+//! translated comments, acronyms and icon names. The per-type file of the most
+//! important directory that has one is read; without any, the packages are.
+//!
+//! The XML is scanned for the few elements used here rather than fully parsed.
+
+use std::path::{Path, PathBuf};
+
+use crate::glob::NO_GLOBS;
+use crate::{Glob, MimeDatabase};
+
+/// Weight of globs that don't give one
+const DEFAULT_WEIGHT: u32 = 50;
+
+/// Values of an element that may be repeated with an `xml:lang` attribute
+type Translations = Vec<(Option<String>, String)>;
+
+/// What the XML says about one MIME type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeTypeInfo {
+    mime_type: String,
+    comments: Translations,
+    acronyms: Translations,
+    expanded_acronyms: Translations,
+    pub(crate) generic_icon: Option<String>,
+    pub(crate) icon: Option<String>,
+    globs: Vec<Glob>,
+    aliases: Vec<String>,
+    parents: Vec<String>,
+}
+
+impl MimeTypeInfo {
+    /// Read what the installed database says about a type, see [`mime_dirs`](crate::mime_dirs)
+    pub fn load(mime_type: &str) -> Option<Self> {
+        Self::load_from(&crate::mime_dirs(), mime_type)
+    }
+
+    /// Read what the given `mime` directories say about a type, most important first
+    ///
+    /// An alias is looked up as the type it stands for. Returns `None` when no
+    /// file describes the type.
+    pub fn load_from(dirs: &[PathBuf], mime_type: &str) -> Option<Self> {
+        let mime_type = mime_type.to_ascii_lowercase();
+        if !is_type_name(&mime_type) {
+            return None;
+        }
+        let canonical = MimeDatabase::load_from(dirs).unalias(&mime_type).to_string();
+
+        for dir in dirs {
+            let path = dir.join(format!("{}.xml", canonical));
+            if let Ok(content) = std::fs::read_to_string(path) {
+                if let Some(info) = Self::parse(&content).into_iter().find(|info| info.mime_type == canonical) {
+                    return Some(info);
+                }
+            }
+        }
+
+        // Packages may each add to a type; more important ones come first
+        let mut found: Option<MimeTypeInfo> = None;
+        for path in dirs.iter().flat_map(|dir| package_files(dir)) {
+            let Ok(content) = std::fs::read_to_string(path) else {
+                continue;
+            };
+            for info in Self::parse(&content) {
+                if info.mime_type == canonical || info.aliases.contains(&mime_type) {
+                    match &mut found {
+                        Some(found) => found.merge(info),
+                        None => found = Some(info),
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Get every `<mime-type>` element of a document
+    pub fn parse(xml: &str) -> Vec<Self> {
+        let mut types = Vec::new();
+        let mut current: Option<MimeTypeInfo> = None;
+        let mut rest = xml;
+
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+            if let Some(comment) = rest.strip_prefix("!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let Some(end) = rest.find('>') else {
+                break;
+            };
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+            if tag.starts_with(['?', '!']) {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                if name.trim() == "mime-type" {
+                    types.extend(current.take());
+                }
+                continue;
+            }
+
+            let tag = tag.strip_suffix('/').unwrap_or(tag);
+            let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            if name == "mime-type" {
+                types.extend(current.take());
+                current = attribute(attributes, "type").map(|mime_type| MimeTypeInfo {
+                    mime_type: mime_type.to_ascii_lowercase(),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let Some(info) = current.as_mut() else {
+                continue;
+            };
+            let text = || unescape(rest[..rest.find('<').unwrap_or(rest.len())].trim());
+            let lang = || attribute(attributes, "xml:lang");
+            match name {
+                "comment" => info.comments.push((lang(), text())),
+                "acronym" => info.acronyms.push((lang(), text())),
+                "expanded-acronym" => info.expanded_acronyms.push((lang(), text())),
+                "generic-icon" => info.generic_icon = attribute(attributes, "name"),
+                "icon" => info.icon = attribute(attributes, "name"),
+                "glob" => {
+                    let Some(pattern) = attribute(attributes, "pattern") else {
+                        continue;
+                    };
+                    let weight = attribute(attributes, "weight")
+                        .and_then(|weight| weight.parse().ok())
+                        .unwrap_or(DEFAULT_WEIGHT);
+                    let case_sensitive = attribute(attributes, "case-sensitive").is_some_and(|cs| cs == "true");
+                    info.globs.push(Glob::new(info.mime_type.clone(), pattern, weight, case_sensitive));
+                }
+                "glob-deleteall" => {
+                    // Drops the globs listed before it, besides those of less important directories
+                    info.globs.clear();
+                    info.globs.push(Glob::new(info.mime_type.clone(), NO_GLOBS.to_string(), DEFAULT_WEIGHT, false))
+                }
+                "alias" => info.aliases.extend(attribute(attributes, "type").map(|t| t.to_ascii_lowercase())),
+                "sub-class-of" => info.parents.extend(attribute(attributes, "type").map(|t| t.to_ascii_lowercase())),
+                _ => {}
+            }
+        }
+        types.extend(current);
+        types
+    }
+
+    /// Get the type described
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// Get the human-readable description translated for `locale`, or the current
+    /// locale if None, such as "Markdown document"
+    pub fn comment(&self, locale: Option<&str>) -> Option<&str> {
+        translated(&self.comments, locale)
+    }
+
+    /// Get the acronym of the type's name, such as "PDF"
+    pub fn acronym(&self, locale: Option<&str>) -> Option<&str> {
+        translated(&self.acronyms, locale)
+    }
+
+    /// Get what the acronym stands for, such as "Portable Document Format"
+    pub fn expanded_acronym(&self, locale: Option<&str>) -> Option<&str> {
+        translated(&self.expanded_acronyms, locale)
+    }
+
+    /// Get the icon name to use when the theme has no icon for the type itself
+    ///
+    /// Without a `<generic-icon>` element this is the media type followed by
+    /// `-x-generic`, such as `text-x-generic`, as the specification says.
+    pub fn generic_icon(&self) -> String {
+        self.generic_icon.clone().unwrap_or_else(|| {
+            let media = self.mime_type.split('/').next().unwrap_or_default();
+            format!("{}-x-generic", media)
+        })
+    }
+
+    /// Get the icon name of the type
+    ///
+    /// Without an `<icon>` element this is the type with `/` replaced by `-`,
+    /// such as `text-markdown`.
+    pub fn icon(&self) -> String {
+        self.icon.clone().unwrap_or_else(|| self.mime_type.replace('/', "-"))
+    }
+
+    /// Get the filename patterns of the type
+    ///
+    /// A `<glob-deleteall/>` element is a `__NOGLOBS__` pattern, as in `globs2`.
+    pub fn globs(&self) -> &[Glob] {
+        &self.globs
+    }
+
+    /// Get the other names of the type
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// Get the types this one is declared a subclass of
+    pub fn parents(&self) -> &[String] {
+        &self.parents
+    }
+
+    /// Write the type as `update-mime-database` writes its per-type file
+    pub(crate) fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str(&format!(
+            "<mime-type xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\" type=\"{}\">\n",
+            escape(&self.mime_type)
+        ));
+        let elements = [
+            ("comment", &self.comments),
+            ("acronym", &self.acronyms),
+            ("expanded-acronym", &self.expanded_acronyms),
+        ];
+        for (name, values) in elements {
+            for (lang, value) in values {
+                match lang {
+                    Some(lang) => xml.push_str(&format!("  <{} xml:lang=\"{}\">", name, escape(lang))),
+                    None => xml.push_str(&format!("  <{}>", name)),
+                }
+                xml.push_str(&format!("{}</{}>\n", escape(value), name));
+            }
+        }
+        for (name, value) in [("generic-icon", &self.generic_icon), ("icon", &self.icon)] {
+            if let Some(value) = value {
+                xml.push_str(&format!("  <{} name=\"{}\"/>\n", name, escape(value)));
+            }
+        }
+        for parent in &self.parents {
+            xml.push_str(&format!("  <sub-class-of type=\"{}\"/>\n", escape(parent)));
+        }
+        for glob in &self.globs {
+            if glob.pattern() == NO_GLOBS {
+                xml.push_str("  <glob-deleteall/>\n");
+                continue;
+            }
+            xml.push_str(&format!("  <glob pattern=\"{}\"", escape(glob.pattern())));
+            if glob.weight() != DEFAULT_WEIGHT {
+                xml.push_str(&format!(" weight=\"{}\"", glob.weight()));
+            }
+            if glob.is_case_sensitive() {
+                xml.push_str(" case-sensitive=\"true\"");
+            }
+            xml.push_str("/>\n");
+        }
+        for alias in &self.aliases {
+            xml.push_str(&format!("  <alias type=\"{}\"/>\n", escape(alias)));
+        }
+        xml.push_str("</mime-type>\n");
+        xml
+    }
+
+    /// Add what a later package of the same directory says about the type
+    ///
+    /// A `<glob-deleteall/>` in it drops the globs collected so far.
+    pub(crate) fn merge_later(&mut self, later: MimeTypeInfo) {
+        if later.globs.iter().any(|glob| glob.pattern() == NO_GLOBS) {
+            self.globs.clear();
+        }
+        self.merge(later);
+    }
+
+    /// Add what a less important package says about the same type
+    pub(crate) fn merge(&mut self, other: MimeTypeInfo) {
+        self.comments.extend(other.comments);
+        self.acronyms.extend(other.acronyms);
+        self.expanded_acronyms.extend(other.expanded_acronyms);
+        self.generic_icon = self.generic_icon.take().or(other.generic_icon);
+        self.icon = self.icon.take().or(other.icon);
+        for glob in other.globs {
+            if !self.globs.iter().any(|known| known.pattern() == glob.pattern()) {
+                self.globs.push(glob);
+            }
+        }
+        for alias in other.aliases {
+            if !self.aliases.contains(&alias) {
+                self.aliases.push(alias);
+            }
+        }
+        for parent in other.parents {
+            if !self.parents.contains(&parent) {
+                self.parents.push(parent);
+            }
+        }
+    }
+}
+
+/// Check for a `media/subtype` name that can't point outside the directory
+pub(crate) fn is_type_name(mime_type: &str) -> bool {
+    let Some((media, subtype)) = mime_type.split_once('/') else {
+        return false;
+    };
+    [media, subtype]
+        .iter()
+        .all(|part| !part.is_empty() && !part.starts_with('.') && !part.contains('/'))
+}
+
+/// Get the XML files of a directory's `packages`, in name order
+pub(crate) fn package_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir.join("packages"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "xml"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Get the value of an attribute from the text between a tag's name and its end
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        let (key, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let (value, remainder) = after[1..].split_once(quote)?;
+        if key.trim() == name {
+            return Some(unescape(value));
+        }
+        rest = remainder;
+    }
+}
+
+/// Escape text for an element or a double-quoted attribute
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replace the predefined and numeric character references
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => reference
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| reference.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(ch) => {
+                result.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Pick the translation for a locale, trying `lang_COUNTRY@MODIFIER`,
+/// `lang_COUNTRY`, `lang@MODIFIER` and `lang` before the untranslated value
+fn translated<'a>(values: &'a Translations, locale: Option<&str>) -> Option<&'a str> {
+    let find = |lang: Option<&str>| {
+        values
+            .iter()
+            .find(|(value_lang, _)| value_lang.as_deref() == lang)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let locale = locale.map(str::to_string).or_else(freedesktop_core::message_locale);
+    if let Some(locale) = locale {
+        let (locale, modifier) = match locale.split_once('@') {
+            Some((locale, modifier)) => (locale, Some(modifier)),
+            None => (locale.as_str(), None),
+        };
+        let locale = locale.split('.').next().unwrap_or(locale);
+        let lang = locale.split('_').next().unwrap_or(locale);
+
+        let mut candidates = Vec::new();
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{}@{}", locale, modifier));
+        }
+        candidates.push(locale.to_string());
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{}@{}", lang, modifier));
+        }
+        candidates.push(lang.to_string());
+
+        if let Some(value) = candidates.iter().find_map(|candidate| find(Some(candidate))) {
+            return Some(value);
+        }
+    }
+    find(None)
+}
@@ -0,0 +1,74 @@
+//! # freedesktop-mime
+//!
+//! The shared-mime-info database, which tells which MIME type a file has. Together
+//! with the MimeType keys of desktop entries it answers which applications open a
+//! file.
+//!
+//! ```rust
+//! use freedesktop_mime::{mime_type_for_data, mime_type_for_filename};
+//!
+//! if let Some(mime) = mime_type_for_filename("report.pdf") {
+//!     println!("report.pdf is {}", mime);
+//! }
+//! println!("{}", mime_type_for_data(b"%PDF-1.7"));
+//! ```
+
+mod cache;
+mod database;
+mod glob;
+mod info;
+mod magic;
+mod packages;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use cache::MimeCache;
+pub use database::{MimeDatabase, DIRECTORY, OCTET_STREAM, TEXT_PLAIN, ZERO_SIZE};
+pub use glob::{Glob, GlobDatabase};
+pub use info::MimeTypeInfo;
+pub use magic::{MagicDatabase, MagicRule};
+pub use packages::MimePackages;
+
+/// The `mime` directories of the XDG data dirs, most important first
+///
+/// `$XDG_DATA_HOME/mime` comes first, followed by `mime` in each XDG_DATA_DIRS
+/// entry, which defaults to `/usr/local/share:/usr/share` when unset. Only
+/// existing directories are returned.
+pub fn mime_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = freedesktop_core::data_home().into_iter().collect();
+    let system = match std::env::var("XDG_DATA_DIRS") {
+        Ok(value) if !value.is_empty() => freedesktop_core::base_directories(),
+        _ => vec![PathBuf::from("/usr/local/share"), PathBuf::from("/usr/share")],
+    };
+    for dir in system {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs.into_iter()
+        .map(|dir| dir.join("mime"))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Get the MIME type of a file from its name, using the installed database
+///
+/// Loads the database on every call; keep a [`MimeDatabase`] to look up many names.
+pub fn mime_type_for_filename<P: AsRef<Path>>(path: P) -> Option<String> {
+    MimeDatabase::load().mime_type_for_filename(path).map(str::to_string)
+}
+
+/// Get the MIME type of data from its contents, using the installed magic database
+///
+/// See [`MimeDatabase::mime_type_for_data`].
+pub fn mime_type_for_data(data: &[u8]) -> String {
+    MimeDatabase::load().mime_type_for_data(data).to_string()
+}
+
+/// Get the MIME type of a file from its name and contents, using the installed database
+///
+/// See [`MimeDatabase::mime_type_for_file`]. Loads the database on every call.
+pub fn mime_type_for_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    MimeDatabase::load().mime_type_for_file(path)
+}
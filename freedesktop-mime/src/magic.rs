@@ -0,0 +1,290 @@
+//! The shared-mime-info magic database
+//!
+//! `update-mime-database` writes the content rules of every type to the binary
+//! `magic` file of a `mime` directory. Each `[priority:type]` section holds
+//! matchlets, one per line:
+//!
+//! ```text
+//! [indent]>start-offset=<2-byte length><value>[&<mask>][~word-size][+range-length]
+//! ```
+//!
+//! A matchlet indented one level deeper than the one before it only counts when
+//! that one matched too. A section matches when one of its top-level matchlets
+//! matches along with, if it has any, one of its children. A section holding
+//! just `__NOMAGIC__` drops the type's rules from less important directories.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Header every magic file starts with
+const HEADER: &[u8] = b"MIME-Magic\0\n";
+
+/// Section content that removes a type's rules from less important directories
+const NO_MAGIC: &[u8] = b"__NOMAGIC__";
+
+/// One comparison of a magic rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Matchlet {
+    indent: usize,
+    offset: usize,
+    range: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+}
+
+impl Matchlet {
+    /// Check whether the value appears at one of the offsets the matchlet covers
+    fn matches(&self, data: &[u8]) -> bool {
+        (self.offset..self.offset + self.range).any(|start| {
+            let Some(window) = data.get(start..start + self.value.len()) else {
+                return false;
+            };
+            match &self.mask {
+                Some(mask) => window
+                    .iter()
+                    .zip(&self.value)
+                    .zip(mask)
+                    .all(|((byte, value), mask)| byte & mask == value & mask),
+                None => window == self.value.as_slice(),
+            }
+        })
+    }
+
+    /// Get how many bytes of data the matchlet looks at
+    fn extent(&self) -> usize {
+        self.offset + self.range - 1 + self.value.len()
+    }
+}
+
+/// The content rules of one MIME type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MagicRule {
+    priority: u32,
+    mime_type: String,
+    matchlets: Vec<Matchlet>,
+}
+
+impl MagicRule {
+    /// Get the MIME type the rule identifies
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    /// Get the priority, from 0 to 100; rules with higher priorities are tried first
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    /// Check whether the start of a file's contents matches the rule
+    pub fn matches(&self, data: &[u8]) -> bool {
+        matches_level(&self.matchlets, 0, data)
+    }
+
+    fn is_no_magic(&self) -> bool {
+        self.matchlets.is_empty()
+    }
+}
+
+/// Check if one of the matchlets at `indent` matches, along with one of its children
+fn matches_level(matchlets: &[Matchlet], indent: usize, data: &[u8]) -> bool {
+    let mut i = 0;
+    while i < matchlets.len() {
+        let matchlet = &matchlets[i];
+        // The children run until the next matchlet at this level or above
+        let children_end = matchlets[i + 1..]
+            .iter()
+            .position(|m| m.indent <= indent)
+            .map_or(matchlets.len(), |end| i + 1 + end);
+        let children = &matchlets[i + 1..children_end];
+        if matchlet.indent == indent
+            && matchlet.matches(data)
+            && (children.is_empty() || matches_level(children, indent + 1, data))
+        {
+            return true;
+        }
+        i = children_end;
+    }
+    false
+}
+
+/// The magic rules of every `mime` directory, for finding a file's type from its contents
+#[derive(Debug, Clone, Default)]
+pub struct MagicDatabase {
+    rules: Vec<MagicRule>,
+}
+
+impl MagicDatabase {
+    /// An empty database
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the `magic` files of the installed MIME database, see [`mime_dirs`](crate::mime_dirs)
+    pub fn load() -> Self {
+        Self::load_from(&crate::mime_dirs())
+    }
+
+    /// Load the `magic` files of the given `mime` directories, most important first
+    ///
+    /// Directories without a `magic` file and files that can't be parsed are skipped.
+    pub fn load_from(dirs: &[PathBuf]) -> Self {
+        let mut database = MagicDatabase::new();
+        for dir in dirs {
+            if let Ok(content) = std::fs::read(dir.join("magic")) {
+                let _ = database.add_magic(&content);
+            }
+        }
+        database
+    }
+
+    /// Read a single `magic` file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut database = MagicDatabase::new();
+        database.add_magic(&std::fs::read(path)?)?;
+        Ok(database)
+    }
+
+    /// Add the rules of a `magic` file that is less important than those added before
+    ///
+    /// Rules for types an earlier file cleared with `__NOMAGIC__` are ignored.
+    /// Fails without adding anything if the file is malformed.
+    pub fn add_magic(&mut self, content: &[u8]) -> io::Result<()> {
+        let rules = parse_magic(content)?;
+        let cleared: Vec<String> = self.cleared_types().map(String::from).collect();
+        self.rules.extend(rules.into_iter().filter(|rule| !cleared.contains(&rule.mime_type)));
+        // Stable, so equal priorities keep the order of the files
+        self.rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        Ok(())
+    }
+
+    /// Get the types whose rules `__NOMAGIC__` removed from less important directories
+    pub(crate) fn cleared_types(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().filter(|rule| rule.is_no_magic()).map(|rule| rule.mime_type.as_str())
+    }
+
+    /// Get every rule, highest priority first
+    pub fn rules(&self) -> impl Iterator<Item = &MagicRule> {
+        self.rules.iter().filter(|rule| !rule.is_no_magic())
+    }
+
+    /// Get the number of bytes at the start of a file the rules look at
+    pub fn max_extent(&self) -> usize {
+        self.rules
+            .iter()
+            .flat_map(|rule| &rule.matchlets)
+            .map(Matchlet::extent)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Find the best matching rule for the start of a file's contents
+    pub fn sniff(&self, data: &[u8]) -> Option<&MagicRule> {
+        self.rules().find(|rule| rule.matches(data))
+    }
+
+    /// Get the MIME type of a file from the start of its contents
+    pub fn mime_type_for_data(&self, data: &[u8]) -> Option<&str> {
+        self.sniff(data).map(MagicRule::mime_type)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid magic file: {}", message))
+}
+
+fn parse_magic(content: &[u8]) -> io::Result<Vec<MagicRule>> {
+    let mut input = content.strip_prefix(HEADER).ok_or_else(|| invalid("missing header"))?;
+    let mut rules: Vec<MagicRule> = Vec::new();
+
+    while !input.is_empty() {
+        if input[0] == b'[' {
+            let end = input.iter().position(|&b| b == b'\n').ok_or_else(|| invalid("unterminated section"))?;
+            let header = std::str::from_utf8(&input[1..end])
+                .ok()
+                .and_then(|header| header.strip_suffix(']'))
+                .ok_or_else(|| invalid("bad section header"))?;
+            let (priority, mime_type) = header.split_once(':').ok_or_else(|| invalid("bad section header"))?;
+            rules.push(MagicRule {
+                priority: priority.parse().map_err(|_| invalid("bad priority"))?,
+                mime_type: mime_type.to_string(),
+                matchlets: Vec::new(),
+            });
+            input = &input[end + 1..];
+            continue;
+        }
+
+        let rule = rules.last_mut().ok_or_else(|| invalid("matchlet outside a section"))?;
+        if let Some(rest) = input.strip_prefix(NO_MAGIC) {
+            input = rest.strip_prefix(b"\n").unwrap_or(rest);
+            continue;
+        }
+        let (matchlet, rest) = parse_matchlet(input)?;
+        rule.matchlets.push(matchlet);
+        input = rest;
+    }
+    Ok(rules)
+}
+
+/// Parse one matchlet line, returning it and the input after it
+fn parse_matchlet(input: &[u8]) -> io::Result<(Matchlet, &[u8])> {
+    let (indent, input) = take_number(input);
+    let input = input.strip_prefix(b">").ok_or_else(|| invalid("expected '>'"))?;
+    let (offset, input) = take_number(input);
+    let offset = offset.ok_or_else(|| invalid("missing offset"))?;
+    let input = input.strip_prefix(b"=").ok_or_else(|| invalid("expected '='"))?;
+
+    let length = match input {
+        [high, low, ..] => usize::from(*high) << 8 | usize::from(*low),
+        _ => return Err(invalid("missing value length")),
+    };
+    let value = input.get(2..2 + length).ok_or_else(|| invalid("value runs past the end"))?.to_vec();
+    let mut input = &input[2 + length..];
+
+    let mut mask = None;
+    if let Some(rest) = input.strip_prefix(b"&") {
+        mask = Some(rest.get(..length).ok_or_else(|| invalid("mask runs past the end"))?.to_vec());
+        input = &rest[length..];
+    }
+    let mut word_size = 1;
+    if let Some(rest) = input.strip_prefix(b"~") {
+        let (size, rest) = take_number(rest);
+        word_size = size.ok_or_else(|| invalid("missing word size"))?;
+        input = rest;
+    }
+    let mut range = 1;
+    if let Some(rest) = input.strip_prefix(b"+") {
+        let (length, rest) = take_number(rest);
+        range = length.ok_or_else(|| invalid("missing range length"))?.max(1);
+        input = rest;
+    }
+    let input = input.strip_prefix(b"\n").ok_or_else(|| invalid("expected end of line"))?;
+
+    let mut matchlet = Matchlet {
+        indent: indent.unwrap_or(0),
+        offset,
+        range,
+        value,
+        mask,
+    };
+    // Values are stored big-endian; multi-byte words are compared in host order
+    if cfg!(target_endian = "little") && word_size > 1 {
+        swap_words(&mut matchlet.value, word_size);
+        if let Some(mask) = &mut matchlet.mask {
+            swap_words(mask, word_size);
+        }
+    }
+    Ok((matchlet, input))
+}
+
+fn swap_words(bytes: &mut [u8], word_size: usize) {
+    for word in bytes.chunks_exact_mut(word_size) {
+        word.reverse();
+    }
+}
+
+/// Read a decimal number from the start of the input, if there is one
+fn take_number(input: &[u8]) -> (Option<usize>, &[u8]) {
+    let digits = input.iter().take_while(|b| b.is_ascii_digit()).count();
+    let number = std::str::from_utf8(&input[..digits]).ok().and_then(|digits| digits.parse().ok());
+    (number, &input[digits..])
+}
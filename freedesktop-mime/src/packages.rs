@@ -0,0 +1,257 @@
+//! Installing MIME type definitions, like `xdg-mime install`
+//!
+//! A package is an XML file of `<mime-type>` elements in `mime/packages`. The
+//! database is generated from the packages of its directory, so installing or
+//! removing one is followed by an update: `update-mime-database` when it is on
+//! PATH, or else [`MimePackages::rebuild`].
+//!
+//! ```no_run
+//! use freedesktop_mime::MimePackages;
+//!
+//! let packages = MimePackages::user().unwrap();
+//! packages.install("example-notes.xml").unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::info::{is_type_name, package_files};
+use crate::MimeTypeInfo;
+
+/// Program that regenerates a `mime` directory from its packages
+const UPDATE_TOOL: &str = "update-mime-database";
+
+/// Files generated from the packages that [`MimePackages::rebuild`] can't
+/// regenerate, removed so they don't outlive the packages they came from
+const STALE_FILES: [&str; 2] = ["mime.cache", "magic"];
+
+/// The packages of one `mime` directory
+#[derive(Debug, Clone)]
+pub struct MimePackages {
+    dir: PathBuf,
+    update_tool: Option<PathBuf>,
+}
+
+impl MimePackages {
+    /// Use the user's `$XDG_DATA_HOME/mime`, which is created when needed
+    ///
+    /// Returns `None` when the home directory can't be determined.
+    pub fn user() -> Option<Self> {
+        freedesktop_core::data_home().map(|dir| Self::in_dir(dir.join("mime")))
+    }
+
+    /// Use the given `mime` directory
+    pub fn in_dir<P: Into<PathBuf>>(dir: P) -> Self {
+        MimePackages {
+            dir: dir.into(),
+            update_tool: find_program(UPDATE_TOOL),
+        }
+    }
+
+    /// Set the program run to update the database, or `None` to always use
+    /// [`MimePackages::rebuild`]
+    pub fn update_tool(mut self, tool: Option<PathBuf>) -> Self {
+        self.update_tool = tool;
+        self
+    }
+
+    /// Get the `mime` directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the installed package files, in name order
+    pub fn installed(&self) -> Vec<PathBuf> {
+        package_files(&self.dir)
+    }
+
+    /// Copy a package file into the directory and update the database
+    ///
+    /// The file keeps its name, which must have a vendor prefix such as
+    /// `example-notes.xml` so packages of different vendors don't collide.
+    /// Returns the installed path.
+    pub fn install<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| invalid_input(format!("{} is not a file", path.display())))?;
+        self.install_xml(&name, &fs::read_to_string(path)?)
+    }
+
+    /// Write a package from its contents and update the database
+    ///
+    /// Fails without writing anything when the name has no vendor prefix or the
+    /// XML defines no valid type.
+    pub fn install_xml(&self, name: &str, xml: &str) -> io::Result<PathBuf> {
+        check_package_name(name)?;
+        let types = MimeTypeInfo::parse(xml);
+        if types.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} defines no MIME types", name)));
+        }
+        if let Some(info) = types.iter().find(|info| !is_type_name(info.mime_type())) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a valid MIME type", info.mime_type()),
+            ));
+        }
+
+        let packages = self.dir.join("packages");
+        fs::create_dir_all(&packages)?;
+        let path = packages.join(name);
+        fs::write(&path, xml)?;
+        self.update()?;
+        Ok(path)
+    }
+
+    /// Remove a package by file name and update the database
+    ///
+    /// Returns false when the package wasn't installed.
+    pub fn uninstall(&self, name: &str) -> io::Result<bool> {
+        check_package_name(name)?;
+        match fs::remove_file(self.dir.join("packages").join(name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        self.update()?;
+        Ok(true)
+    }
+
+    /// Regenerate the database from the packages
+    ///
+    /// Runs the update tool when there is one, otherwise [`MimePackages::rebuild`].
+    pub fn update(&self) -> io::Result<()> {
+        let Some(tool) = &self.update_tool else {
+            return self.rebuild();
+        };
+        let status = Command::new(tool).arg(&self.dir).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("{} failed with {}", tool.display(), status)));
+        }
+        Ok(())
+    }
+
+    /// Regenerate the database from the packages without `update-mime-database`
+    ///
+    /// Writes `globs2`, `aliases`, `subclasses`, `icons`, `generic-icons`,
+    /// `types` and a file per type. Magic rules aren't compiled, so types defined
+    /// here are found by name only; `mime.cache` and `magic` are removed, as they
+    /// would hide the new files or describe removed packages.
+    pub fn rebuild(&self) -> io::Result<()> {
+        let mut types: BTreeMap<String, MimeTypeInfo> = BTreeMap::new();
+        for path in self.installed() {
+            for info in MimeTypeInfo::parse(&fs::read_to_string(path)?) {
+                if !is_type_name(info.mime_type()) {
+                    continue;
+                }
+                match types.get_mut(info.mime_type()) {
+                    Some(known) => known.merge_later(info),
+                    None => {
+                        types.insert(info.mime_type().to_string(), info);
+                    }
+                }
+            }
+        }
+
+        // Types of removed packages would otherwise keep their files
+        if let Ok(previous) = fs::read_to_string(self.dir.join("types")) {
+            for mime_type in previous.lines().filter(|line| is_type_name(line)) {
+                if !types.contains_key(mime_type) {
+                    let _ = fs::remove_file(self.dir.join(format!("{}.xml", mime_type)));
+                }
+            }
+        }
+        for name in STALE_FILES {
+            match fs::remove_file(self.dir.join(name)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+
+        let mut globs = vec!["# Generated from the packages directory. DO NOT EDIT!".to_string()];
+        let mut globs_by_weight: Vec<_> = types.values().flat_map(MimeTypeInfo::globs).collect();
+        globs_by_weight.sort_by_key(|glob| std::cmp::Reverse(glob.weight()));
+        for glob in globs_by_weight {
+            let flags = if glob.is_case_sensitive() { ":cs" } else { "" };
+            globs.push(format!("{}:{}:{}{}", glob.weight(), glob.mime_type(), glob.pattern(), flags));
+        }
+        let mut aliases: Vec<String> = Vec::new();
+        let mut subclasses: Vec<String> = Vec::new();
+        let mut icons: Vec<String> = Vec::new();
+        let mut generic_icons: Vec<String> = Vec::new();
+        for (mime_type, info) in &types {
+            aliases.extend(info.aliases().iter().map(|alias| format!("{} {}", alias, mime_type)));
+            subclasses.extend(info.parents().iter().map(|parent| format!("{} {}", mime_type, parent)));
+            icons.extend(info.icon.iter().map(|icon| format!("{}:{}", mime_type, icon)));
+            generic_icons.extend(info.generic_icon.iter().map(|icon| format!("{}:{}", mime_type, icon)));
+        }
+        aliases.sort();
+
+        fs::create_dir_all(&self.dir)?;
+        write_lines(&self.dir.join("globs2"), &globs)?;
+        write_lines(&self.dir.join("aliases"), &aliases)?;
+        write_lines(&self.dir.join("subclasses"), &subclasses)?;
+        write_lines(&self.dir.join("icons"), &icons)?;
+        write_lines(&self.dir.join("generic-icons"), &generic_icons)?;
+        write_lines(&self.dir.join("types"), &types.keys().cloned().collect::<Vec<_>>())?;
+        for (mime_type, info) in &types {
+            let path = self.dir.join(format!("{}.xml", mime_type));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, info.to_xml())?;
+        }
+        Ok(())
+    }
+}
+
+/// Check for a plain `vendor-name.xml` file name
+fn check_package_name(name: &str) -> io::Result<()> {
+    let Some(stem) = name.strip_suffix(".xml") else {
+        return Err(invalid_input(format!("{} doesn't end in .xml", name)));
+    };
+    if stem.contains('/') || stem.starts_with('.') {
+        return Err(invalid_input(format!("{} is not a file name", name)));
+    }
+    if !stem.contains('-') || stem.starts_with('-') {
+        return Err(invalid_input(format!("{} has no vendor prefix, such as vendor-{}", name, name)));
+    }
+    Ok(())
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> io::Result<()> {
+    let mut content = String::new();
+    for line in lines {
+        content.push_str(line);
+        content.push('\n');
+    }
+    fs::write(path, content)
+}
+
+/// Find a program in PATH
+fn find_program(name: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Without permission bits, any file counts
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
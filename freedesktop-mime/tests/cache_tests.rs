@@ -0,0 +1,299 @@
+use freedesktop_mime::{MimeCache, MimeDatabase};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A node of the reverse suffix tree: a character with children, or a leaf
+/// holding a type and its weight and flags
+enum Node {
+    Char(char, Vec<Node>),
+    Leaf(&'static str, u32),
+}
+
+/// Writes the tables of a mime.cache file
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+    strings: HashMap<&'static str, u32>,
+}
+
+impl Writer {
+    fn here(&self) -> u32 {
+        self.buf.len() as u32
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buf.extend(value.to_be_bytes());
+    }
+
+    fn patch(&mut self, at: u32, value: u32) {
+        let at = at as usize;
+        self.buf[at..at + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    fn string(&mut self, s: &'static str) -> u32 {
+        if let Some(&offset) = self.strings.get(s) {
+            return offset;
+        }
+        let offset = self.bytes(format!("{}\0", s).as_bytes());
+        self.strings.insert(s, offset);
+        offset
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> u32 {
+        let offset = self.here();
+        self.buf.extend(bytes);
+        while !self.buf.len().is_multiple_of(4) {
+            self.buf.push(0);
+        }
+        offset
+    }
+
+    /// Write `(pattern, type, weight and flags)` entries, sorted by the caller
+    fn glob_list(&mut self, entries: &[(&'static str, &'static str, u32)]) -> u32 {
+        let strings: Vec<(u32, u32, u32)> = entries
+            .iter()
+            .map(|(pattern, mime_type, weight)| (self.string(pattern), self.string(mime_type), *weight))
+            .collect();
+        let offset = self.here();
+        self.u32(strings.len() as u32);
+        for (pattern, mime_type, weight) in strings {
+            self.u32(pattern);
+            self.u32(mime_type);
+            self.u32(weight);
+        }
+        offset
+    }
+
+    /// Write one level of the suffix tree and, after it, the levels below
+    fn nodes(&mut self, nodes: &[Node]) -> u32 {
+        let offset = self.here();
+        for _ in nodes {
+            self.u32(0);
+            self.u32(0);
+            self.u32(0);
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            let at = offset + i as u32 * 12;
+            match node {
+                Node::Leaf(mime_type, flags) => {
+                    let mime_type = self.string(mime_type);
+                    self.patch(at + 4, mime_type);
+                    self.patch(at + 8, *flags);
+                }
+                Node::Char(ch, children) => {
+                    let first_child = self.nodes(children);
+                    self.patch(at, *ch as u32);
+                    self.patch(at + 4, children.len() as u32);
+                    self.patch(at + 8, first_child);
+                }
+            }
+        }
+        offset
+    }
+}
+
+/// Build the branch of the suffix tree for `*` followed by `suffix`
+fn suffix(suffix: &str, leaves: Vec<Node>) -> Node {
+    // The tree is read from the end of the name, so the first character is deepest
+    let mut children = leaves;
+    for ch in suffix.chars() {
+        children = vec![Node::Char(ch, children)];
+    }
+    children.pop().unwrap()
+}
+
+fn cache_file() -> Vec<u8> {
+    let mut w = Writer::default();
+    w.buf.extend([0, 1, 0, 2]);
+    w.buf.resize(40, 0);
+
+    // Header fields 7 and up (namespaces, icons) are left empty
+    let empty = w.here();
+    w.u32(0);
+    for field in [28, 32, 36] {
+        w.patch(field, empty);
+    }
+
+    // Sorted by alias
+    let aliases: Vec<(u32, u32)> = [
+        ("application/vnd.ms-word", "application/msword"),
+        ("application/x-msword", "application/msword"),
+        ("text/x-pdf", "application/pdf"),
+    ]
+    .iter()
+    .map(|(alias, mime_type)| (w.string(alias), w.string(mime_type)))
+    .collect();
+    let alias_list = w.here();
+    w.u32(aliases.len() as u32);
+    for (alias, mime_type) in aliases {
+        w.u32(alias);
+        w.u32(mime_type);
+    }
+    w.patch(4, alias_list);
+
+    let ole = w.string("application/x-ole-storage");
+    let parents = w.here();
+    w.u32(1);
+    w.u32(ole);
+    let msword = w.string("application/msword");
+    let parent_list = w.here();
+    w.u32(1);
+    w.u32(msword);
+    w.u32(parents);
+    w.patch(8, parent_list);
+
+    let literals = w.glob_list(&[("Makefile", "text/x-makefile", 50 | 0x100), ("readme", "text/x-readme", 60)]);
+    w.patch(12, literals);
+
+    // *.doc and *.txt ignoring case, *.C only in upper case
+    let roots = w.nodes(&[
+        suffix(".C", vec![Node::Leaf("text/x-c++src", 50 | 0x100)]),
+        suffix(
+            ".doc",
+            vec![
+                Node::Leaf("application/msword", 50),
+                Node::Leaf("text/x-document", 50),
+            ],
+        ),
+        suffix(".txt", vec![Node::Leaf("text/plain", 50)]),
+    ]);
+    let tree = w.here();
+    w.u32(3);
+    w.u32(roots);
+    w.patch(16, tree);
+
+    let globs = w.glob_list(&[("README*", "text/x-readme", 10), ("[0-9]*.log", "text/x-log", 50)]);
+    w.patch(20, globs);
+
+    // %PDF- at the start, and the OLE signature with a child that must match too
+    let pdf_value = w.bytes(b"%PDF-");
+    let ole_value = w.bytes(b"\xd0\xcf\x11\xe0");
+    let child_value = w.bytes(b"Word");
+    let child_mask = w.bytes(b"\xff\xff\xff\xdf");
+    let child = w.here();
+    for value in [8, 8, 1, 4, child_value, child_mask, 0, 0] {
+        w.u32(value);
+    }
+    let pdf_matchlet = w.here();
+    for value in [0, 1, 1, 5, pdf_value, 0, 0, 0] {
+        w.u32(value);
+    }
+    let ole_matchlet = w.here();
+    for value in [0, 1, 1, 4, ole_value, 0, 1, child] {
+        w.u32(value);
+    }
+    let pdf = w.string("application/pdf");
+    let matches = w.here();
+    for value in [80, pdf, 1, pdf_matchlet, 30, msword, 1, ole_matchlet] {
+        w.u32(value);
+    }
+    let magic = w.here();
+    w.u32(2);
+    w.u32(16);
+    w.u32(matches);
+    w.patch(24, magic);
+
+    w.buf
+}
+
+#[test]
+fn test_mime_cache_lookups() {
+    let cache = MimeCache::from_bytes(cache_file()).unwrap();
+
+    assert_eq!(cache.mime_type_for_filename("notes.txt"), Some("text/plain"));
+    assert_eq!(cache.mime_type_for_filename("/some/dir/NOTES.TXT"), Some("text/plain"));
+    assert_eq!(cache.mime_type_for_filename("main.C"), Some("text/x-c++src"));
+    assert_eq!(cache.mime_type_for_filename("main.c"), None);
+    assert_eq!(cache.mime_type_for_filename("Makefile"), Some("text/x-makefile"));
+    assert_eq!(cache.mime_type_for_filename("makefile"), None);
+    assert_eq!(cache.mime_type_for_filename("README"), Some("text/x-readme"));
+    assert_eq!(cache.mime_type_for_filename("README.md"), Some("text/x-readme"));
+    assert_eq!(cache.mime_type_for_filename("2024-01.log"), Some("text/x-log"));
+    assert_eq!(cache.mime_type_for_filename("txt"), None);
+    assert_eq!(
+        cache.mime_types_for_filename("letter.doc"),
+        vec!["application/msword", "text/x-document"]
+    );
+
+    assert_eq!(cache.mime_type_for_data(b"%PDF-1.7"), Some("application/pdf"));
+    assert_eq!(cache.mime_type_for_data(b"\xd0\xcf\x11\xe0...."), None);
+    assert_eq!(cache.mime_type_for_data(b"\xd0\xcf\x11\xe0....WorD"), Some("application/msword"));
+    assert_eq!(cache.max_extent(), 16);
+
+    assert_eq!(cache.parents("application/msword"), vec!["application/x-ole-storage"]);
+    assert!(cache.parents("application/pdf").is_empty());
+
+    assert_eq!(cache.unalias("application/x-msword"), Some("application/msword"));
+    assert_eq!(cache.unalias("text/x-pdf"), Some("application/pdf"));
+    assert_eq!(cache.unalias("application/msword"), None);
+    assert_eq!(cache.aliases("application/msword"), vec!["application/vnd.ms-word", "application/x-msword"]);
+
+    assert!(MimeCache::from_bytes(Vec::new()).is_err());
+    let mut future = cache_file();
+    future[1] = 2;
+    assert!(MimeCache::from_bytes(future).is_err());
+    let mut truncated = cache_file();
+    truncated.truncate(60);
+    assert!(MimeCache::from_bytes(truncated).is_err());
+}
+
+#[test]
+fn test_database_prefers_cache() {
+    let root = Path::new("/tmp/test_mime_cache");
+    fs::remove_dir_all(root).ok();
+    let cached = root.join("cached/mime");
+    let text = root.join("text/mime");
+    fs::create_dir_all(&cached).unwrap();
+    fs::create_dir_all(&text).unwrap();
+    fs::write(cached.join("mime.cache"), cache_file()).unwrap();
+    // Never read while the cache next to it can be used
+    fs::write(cached.join("globs2"), "50:text/x-stale:*.txt\n").unwrap();
+    fs::write(text.join("globs2"), "50:text/markdown:*.md\n").unwrap();
+
+    assert!(MimeCache::open(cached.join("mime.cache")).is_ok());
+    assert!(MimeCache::open(text.join("mime.cache")).is_err());
+
+    let database = MimeDatabase::load_from(&[cached.clone(), text.clone()]);
+    assert_eq!(database.caches().len(), 1);
+    assert_eq!(database.mime_type_for_filename("notes.txt"), Some("text/plain"));
+    assert_eq!(database.mime_type_for_filename("notes.md"), Some("text/markdown"));
+    assert!(database.is_subclass("application/msword", "application/x-ole-storage"));
+    assert!(database.is_subclass("application/x-msword", "application/x-ole-storage"));
+    assert_eq!(database.unalias("application/vnd.ms-word"), "application/msword");
+
+    let doc = root.join("letter.doc");
+    fs::write(&doc, b"\xd0\xcf\x11\xe0....Word").unwrap();
+    assert_eq!(database.mime_type_for_file(&doc).unwrap(), "application/msword");
+
+    let text_only = MimeDatabase::load_text_from(&[cached, text]);
+    assert!(text_only.caches().is_empty());
+    assert_eq!(text_only.mime_type_for_filename("notes.txt"), Some("text/x-stale"));
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_text_files_clear_cached_types() {
+    let root = Path::new("/tmp/test_mime_cache_cleared");
+    fs::remove_dir_all(root).ok();
+    let user = root.join("user/mime");
+    let system = root.join("system/mime");
+    fs::create_dir_all(&user).unwrap();
+    fs::create_dir_all(&system).unwrap();
+    fs::write(system.join("mime.cache"), cache_file()).unwrap();
+    fs::write(user.join("globs2"), "50:text/plain:__NOGLOBS__\n50:text/x-notes:*.txt\n").unwrap();
+    fs::write(user.join("magic"), b"MIME-Magic\0\n[50:application/pdf]\n__NOMAGIC__\n").unwrap();
+
+    let database = MimeDatabase::load_from(&[user, system.clone()]);
+    assert_eq!(database.mime_types_for_filename("notes.txt"), vec!["text/x-notes"]);
+    assert_eq!(database.mime_type_for_data(b"%PDF-1.7"), "text/plain");
+    // Types nothing cleared still come from the cache
+    assert_eq!(database.mime_type_for_filename("Makefile"), Some("text/x-makefile"));
+
+    // A less important directory can't clear anything in the cache
+    let database = MimeDatabase::load_from(&[system, root.join("user/mime")]);
+    assert_eq!(database.mime_type_for_data(b"%PDF-1.7"), "application/pdf");
+
+    fs::remove_dir_all(root).ok();
+}
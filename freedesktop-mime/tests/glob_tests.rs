@@ -0,0 +1,102 @@
+use freedesktop_mime::GlobDatabase;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SYSTEM_GLOBS: &str = "# This file was automatically generated by the
+# update-mime-database command. DO NOT EDIT!
+80:text/html:*.html
+50:application/gzip:*.gz
+50:application/x-compressed-tar:*.tar.gz
+50:text/x-c++src:*.C:cs
+50:text/x-csrc:*.c
+50:text/x-makefile:Makefile
+50:text/x-makefile:[Mm]akefile.*
+40:text/x-log:*.log
+50:application/x-trash:*~
+50:application/x-trash:*.bak
+";
+
+#[test]
+fn test_mime_type_for_filename() {
+    let globs = GlobDatabase::new();
+    assert_eq!(globs.mime_type_for_filename("index.html"), None);
+
+    let mut globs = GlobDatabase::new();
+    globs.add_globs2(SYSTEM_GLOBS);
+    assert_eq!(globs.mime_type_for_filename("/srv/www/index.html"), Some("text/html"));
+    assert_eq!(globs.mime_type_for_filename("INDEX.HTML"), Some("text/html"));
+    // The longest pattern wins
+    assert_eq!(globs.mime_type_for_filename("backup.tar.gz"), Some("application/x-compressed-tar"));
+    assert_eq!(globs.mime_type_for_filename("notes.gz"), Some("application/gzip"));
+    // Case-sensitive patterns only match names with the same case
+    assert_eq!(globs.mime_type_for_filename("main.C"), Some("text/x-c++src"));
+    assert_eq!(globs.mime_types_for_filename("main.c"), ["text/x-csrc"]);
+    assert_eq!(globs.mime_type_for_filename("Makefile"), Some("text/x-makefile"));
+    assert_eq!(globs.mime_type_for_filename("makefile.am"), Some("text/x-makefile"));
+    assert_eq!(globs.mime_type_for_filename("notes.txt~"), Some("application/x-trash"));
+    assert_eq!(globs.mime_type_for_filename("README"), None);
+    assert_eq!(globs.mime_type_for_filename("/"), None);
+}
+
+#[test]
+fn test_weights_and_ties() {
+    let mut globs = GlobDatabase::new();
+    globs.add_globs2("40:text/x-log:*.log\n60:text/x-apache-log:access.log\n50:text/x-a:*.dup\n50:text/x-b:*.dup\n");
+    assert_eq!(globs.mime_type_for_filename("access.log"), Some("text/x-apache-log"));
+    assert_eq!(globs.mime_type_for_filename("error.log"), Some("text/x-log"));
+    // The name alone can't tell these apart
+    assert_eq!(globs.mime_types_for_filename("file.dup"), ["text/x-a", "text/x-b"]);
+
+    let glob = globs.globs().find(|glob| glob.pattern() == "access.log").unwrap();
+    assert_eq!((glob.mime_type(), glob.weight(), glob.is_case_sensitive()), ("text/x-apache-log", 60, false));
+}
+
+#[test]
+fn test_glob_patterns() {
+    let mut globs = GlobDatabase::new();
+    globs.add_globs2("50:text/x-a:*a*b*c\n50:text/x-set:[!0-9]?.[]x]\n50:text/x-uni:*ä[ö-ü]\n50:text/x-open:[ab\n");
+    assert_eq!(globs.mime_type_for_filename("xaaabbbxc"), Some("text/x-a"));
+    assert_eq!(globs.mime_type_for_filename("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaab"), None);
+    assert_eq!(globs.mime_type_for_filename("ab.]"), Some("text/x-set"));
+    assert_eq!(globs.mime_type_for_filename("ab.x"), Some("text/x-set"));
+    assert_eq!(globs.mime_type_for_filename("1b.x"), None);
+    assert_eq!(globs.mime_type_for_filename("Zeitungsständer.Äa"), None);
+    assert_eq!(globs.mime_type_for_filename("grüßä\u{fc}"), Some("text/x-uni"));
+    // An unterminated set is a literal '['
+    assert_eq!(globs.mime_type_for_filename("[AB"), Some("text/x-open"));
+
+    // Each type's pattern is kept once
+    globs.add_globs2("50:text/x-a:*a*b*c\n60:text/x-a:*a*b*c\n");
+    assert_eq!(globs.globs().filter(|glob| glob.mime_type() == "text/x-a").count(), 1);
+}
+
+#[test]
+fn test_layered_mime_dirs() {
+    let root = Path::new("/tmp/test_mime_globs");
+    fs::remove_dir_all(root).ok();
+    let user = root.join("user/mime");
+    let system = root.join("system/mime");
+    fs::create_dir_all(&user).unwrap();
+    fs::create_dir_all(&system).unwrap();
+    fs::write(system.join("globs2"), SYSTEM_GLOBS).unwrap();
+    // The user takes *.log away from text/x-log and gives .bak files a weight of their own
+    fs::write(
+        user.join("globs2"),
+        "50:text/x-log:__NOGLOBS__\n50:text/x-log:*.txtlog\n90:application/x-backup:*.bak\n",
+    )
+    .unwrap();
+
+    let dirs: Vec<PathBuf> = vec![user.clone(), root.join("missing"), system.clone()];
+    let globs = GlobDatabase::load_from(&dirs);
+    assert_eq!(globs.mime_type_for_filename("syslog.log"), None);
+    assert_eq!(globs.mime_type_for_filename("syslog.txtlog"), Some("text/x-log"));
+    assert_eq!(globs.mime_type_for_filename("data.bak"), Some("application/x-backup"));
+    assert_eq!(globs.mime_type_for_filename("page.html"), Some("text/html"));
+    assert!(globs.globs().all(|glob| glob.pattern() != "__NOGLOBS__"));
+
+    let system_only = GlobDatabase::from_path(system.join("globs2")).unwrap();
+    assert_eq!(system_only.mime_type_for_filename("syslog.log"), Some("text/x-log"));
+    assert!(GlobDatabase::from_path(root.join("missing/globs2")).is_err());
+
+    fs::remove_dir_all(root).ok();
+}
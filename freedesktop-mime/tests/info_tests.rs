@@ -0,0 +1,104 @@
+use freedesktop_mime::MimeTypeInfo;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MARKDOWN_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<mime-type xmlns="http://www.freedesktop.org/standards/shared-mime-info" type="text/markdown">
+  <!--Created automatically by update-mime-database. DO NOT EDIT!-->
+  <comment>Markdown document</comment>
+  <comment xml:lang="de">Markdown-Dokument</comment>
+  <comment xml:lang="pt_BR">Documento Markdown</comment>
+  <comment xml:lang="sr@latin">Markdaun dokument</comment>
+  <sub-class-of type="text/plain"/>
+  <glob pattern="*.md"/>
+  <alias type="text/x-markdown"/>
+</mime-type>
+"#;
+
+const PACKAGE_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns='http://www.freedesktop.org/standards/shared-mime-info'>
+  <mime-type type="application/pdf">
+    <comment>PDF document</comment>
+    <comment xml:lang="fr">document PDF</comment>
+    <acronym>PDF</acronym>
+    <expanded-acronym>Portable Document Format</expanded-acronym>
+    <generic-icon name="x-office-document"/>
+    <magic priority="50"><match type="string" value="%PDF-" offset="0"/></magic>
+  </mime-type>
+  <mime-type type="application/x-example">
+    <comment>Q&amp;A &lt;notes&gt; &#x263A;</comment>
+    <icon name='example-icon'/>
+  </mime-type>
+</mime-info>
+"#;
+
+#[test]
+fn test_parse_mime_type_xml() {
+    let types = MimeTypeInfo::parse(MARKDOWN_XML);
+    assert_eq!(types.len(), 1);
+    let markdown = &types[0];
+    assert_eq!(markdown.mime_type(), "text/markdown");
+    assert_eq!(markdown.comment(Some("C")), Some("Markdown document"));
+    assert_eq!(markdown.comment(Some("de_AT.UTF-8")), Some("Markdown-Dokument"));
+    assert_eq!(markdown.comment(Some("pt_BR")), Some("Documento Markdown"));
+    assert_eq!(markdown.comment(Some("pt_PT")), Some("Markdown document"));
+    assert_eq!(markdown.comment(Some("sr_RS@latin")), Some("Markdaun dokument"));
+    assert_eq!(markdown.aliases(), ["text/x-markdown"]);
+    assert_eq!(markdown.parents(), ["text/plain"]);
+    assert_eq!(markdown.icon(), "text-markdown");
+    assert_eq!(markdown.generic_icon(), "text-x-generic");
+    assert_eq!(markdown.acronym(None), None);
+
+    let types = MimeTypeInfo::parse(PACKAGE_XML);
+    assert_eq!(types.len(), 2);
+    let pdf = &types[0];
+    assert_eq!(pdf.comment(Some("fr_FR")), Some("document PDF"));
+    assert_eq!(pdf.acronym(Some("C")), Some("PDF"));
+    assert_eq!(pdf.expanded_acronym(Some("fr")), Some("Portable Document Format"));
+    assert_eq!(pdf.generic_icon(), "x-office-document");
+    assert_eq!(pdf.icon(), "application-pdf");
+    let example = &types[1];
+    assert_eq!(example.comment(Some("C")), Some("Q&A <notes> \u{263A}"));
+    assert_eq!(example.icon(), "example-icon");
+
+    assert!(MimeTypeInfo::parse("not xml").is_empty());
+}
+
+#[test]
+fn test_load_mime_type_info() {
+    let root = Path::new("/tmp/test_mime_info");
+    fs::remove_dir_all(root).ok();
+    let user = root.join("user/mime");
+    let system = root.join("system/mime");
+    fs::create_dir_all(user.join("packages")).unwrap();
+    fs::create_dir_all(system.join("text")).unwrap();
+    fs::create_dir_all(system.join("packages")).unwrap();
+    fs::write(system.join("text/markdown.xml"), MARKDOWN_XML).unwrap();
+    fs::write(system.join("aliases"), "text/x-markdown text/markdown\n").unwrap();
+    fs::write(system.join("packages/freedesktop.org.xml"), PACKAGE_XML).unwrap();
+    // A package of the user's adds a translation and overrides the icon
+    fs::write(
+        user.join("packages/custom.xml"),
+        r#"<mime-info><mime-type type="application/pdf"><comment xml:lang="nl">PDF-document</comment><generic-icon name="custom"/></mime-type></mime-info>"#,
+    )
+    .unwrap();
+    let dirs: Vec<PathBuf> = vec![user, root.join("missing"), system];
+
+    let markdown = MimeTypeInfo::load_from(&dirs, "text/markdown").unwrap();
+    assert_eq!(markdown.comment(Some("de")), Some("Markdown-Dokument"));
+    let alias = MimeTypeInfo::load_from(&dirs, "TEXT/X-MARKDOWN").unwrap();
+    assert_eq!(alias.mime_type(), "text/markdown");
+
+    // No per-type file, so the packages are merged
+    let pdf = MimeTypeInfo::load_from(&dirs, "application/pdf").unwrap();
+    assert_eq!(pdf.comment(Some("nl")), Some("PDF-document"));
+    assert_eq!(pdf.comment(Some("fr")), Some("document PDF"));
+    assert_eq!(pdf.comment(Some("C")), Some("PDF document"));
+    assert_eq!(pdf.generic_icon(), "custom");
+
+    assert!(MimeTypeInfo::load_from(&dirs, "image/png").is_none());
+    assert!(MimeTypeInfo::load_from(&dirs, "../mime").is_none());
+    assert!(MimeTypeInfo::load_from(&dirs, "text/../../aliases").is_none());
+
+    fs::remove_dir_all(root).ok();
+}
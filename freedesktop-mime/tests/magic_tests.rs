@@ -0,0 +1,129 @@
+use freedesktop_mime::{GlobDatabase, MagicDatabase, MimeDatabase};
+use std::fs;
+use std::path::Path;
+
+/// Build a matchlet line of a magic file
+fn matchlet(indent: &str, offset: usize, value: &[u8], extra: &[u8]) -> Vec<u8> {
+    let mut line = format!("{}>{}=", indent, offset).into_bytes();
+    line.extend((value.len() as u16).to_be_bytes());
+    line.extend(value);
+    line.extend(extra);
+    line.push(b'\n');
+    line
+}
+
+fn magic_file() -> Vec<u8> {
+    let mut magic = b"MIME-Magic\0\n".to_vec();
+    magic.extend(b"[90:application/pdf]\n");
+    magic.extend(matchlet("", 0, b"%PDF-", b""));
+    // The ZIP signature, with an OpenDocument mimetype member as a child rule
+    magic.extend(b"[70:application/vnd.oasis.opendocument.text]\n");
+    magic.extend(matchlet("", 0, b"PK\x03\x04", b""));
+    magic.extend(matchlet("1", 30, b"mimetypeapplication/vnd.oasis.opendocument.text", b""));
+    magic.extend(b"[40:application/zip]\n");
+    magic.extend(matchlet("", 0, b"PK\x03\x04", b""));
+    // Masked and ranged comparisons
+    magic.extend(b"[50:image/x-masked]\n");
+    magic.extend(matchlet("", 0, b"\xf0\x0f", b"&\xf0\x0f"));
+    magic.extend(b"[50:text/x-shebang]\n");
+    magic.extend(matchlet("", 0, b"python", b"+16"));
+    // Value with a newline in it, and a little-endian word
+    magic.extend(b"[50:application/x-newline]\n");
+    magic.extend(matchlet("", 0, b"A\nB", b""));
+    magic.extend(b"[50:application/x-word]\n");
+    magic.extend(matchlet("", 0, b"\x12\x34", b"~2"));
+    magic.extend(b"[20:application/x-ole-storage]\n");
+    magic.extend(matchlet("", 0, b"\xd0\xcf\x11\xe0", b""));
+    magic
+}
+
+#[test]
+fn test_magic_rules() {
+    let mut magic = MagicDatabase::new();
+    magic.add_magic(&magic_file()).unwrap();
+
+    assert_eq!(magic.mime_type_for_data(b"%PDF-1.7\n"), Some("application/pdf"));
+    let mut odt = b"PK\x03\x04".to_vec();
+    odt.resize(30, 0);
+    odt.extend(b"mimetypeapplication/vnd.oasis.opendocument.text");
+    assert_eq!(magic.mime_type_for_data(&odt), Some("application/vnd.oasis.opendocument.text"));
+    // The parent matches but the child doesn't, so the lower priority rule wins
+    assert_eq!(magic.mime_type_for_data(b"PK\x03\x04plain zip"), Some("application/zip"));
+    assert_eq!(magic.mime_type_for_data(b"\xf5\x5f"), Some("image/x-masked"));
+    assert_eq!(magic.mime_type_for_data(b"#!/usr/bin/env python3"), Some("text/x-shebang"));
+    assert_eq!(magic.mime_type_for_data(b"A\nB"), Some("application/x-newline"));
+    if cfg!(target_endian = "little") {
+        assert_eq!(magic.mime_type_for_data(b"\x34\x12"), Some("application/x-word"));
+    }
+    assert_eq!(magic.mime_type_for_data(b"hello"), None);
+    assert_eq!(magic.sniff(b"%PDF-").map(|rule| rule.priority()), Some(90));
+    assert_eq!(magic.max_extent(), 30 + 47);
+
+    assert!(magic.add_magic(b"not magic").is_err());
+    let mut truncated = b"MIME-Magic\0\n[50:text/x-a]\n".to_vec();
+    truncated.extend(&matchlet("", 0, b"abc", b"")[..8]);
+    assert!(MagicDatabase::new().add_magic(&truncated).is_err());
+}
+
+#[test]
+fn test_mime_type_for_file() {
+    let root = Path::new("/tmp/test_mime_magic");
+    fs::remove_dir_all(root).ok();
+    let mime = root.join("mime");
+    fs::create_dir_all(&mime).unwrap();
+    fs::write(mime.join("magic"), magic_file()).unwrap();
+    fs::write(
+        mime.join("globs2"),
+        "50:application/pdf:*.pdf\n50:application/msword:*.doc\n50:text/x-readme:*.doc\n50:application/x-gzip:*.dat\n50:text/x-data:*.dat\n",
+    )
+    .unwrap();
+    fs::write(mime.join("subclasses"), "application/msword application/x-ole-storage\n").unwrap();
+    fs::write(mime.join("aliases"), "application/x-msword application/msword\ntext/x-markdown text/markdown\n").unwrap();
+    let database = MimeDatabase::load_from(&[root.join("missing"), mime.clone()]);
+
+    let file = |name: &str, content: &[u8]| {
+        let path = root.join(name);
+        fs::write(&path, content).unwrap();
+        database.mime_type_for_file(path).unwrap()
+    };
+    // A single glob is trusted without reading the file
+    assert_eq!(file("fake.pdf", b"not a pdf"), "application/pdf");
+    // Conflicting globs are settled by the contents, preferring a glob that subclasses the sniffed type
+    assert_eq!(file("letter.doc", b"\xd0\xcf\x11\xe0rest"), "application/msword");
+    // Sniffed types win over unrelated globs
+    assert_eq!(file("report.dat", b"%PDF-1.4"), "application/pdf");
+    assert_eq!(file("unknown.dat", b"no rule matches"), "application/x-gzip");
+    assert_eq!(file("noext", b"%PDF-1.4"), "application/pdf");
+    assert_eq!(file("notes", "plain text é".as_bytes()), "text/plain");
+    assert_eq!(file("blob", b"\x00\x01\x02"), "application/octet-stream");
+    assert_eq!(file("empty", b""), "application/x-zerosize");
+    assert_eq!(database.mime_type_for_file(root).unwrap(), "inode/directory");
+    assert!(database.mime_type_for_file(root.join("missing")).is_err());
+
+    assert!(database.is_subclass("application/msword", "application/octet-stream"));
+    assert!(database.is_subclass("text/x-readme", "text/plain"));
+    assert!(!database.is_subclass("application/x-ole-storage", "application/msword"));
+
+    assert_eq!(database.unalias("text/x-markdown"), "text/markdown");
+    assert_eq!(database.unalias("text/markdown"), "text/markdown");
+    assert_eq!(database.aliases("application/msword"), vec!["application/x-msword"]);
+    assert_eq!(database.parents("application/x-msword"), vec!["application/x-ole-storage"]);
+    assert_eq!(database.ancestors("text/x-markdown"), vec!["text/plain"]);
+    assert!(database.is_subclass("application/x-msword", "application/msword"));
+
+    let parts = MimeDatabase::from_parts(GlobDatabase::new(), MagicDatabase::new(), &[("a/b", "c/d")]);
+    assert!(parts.is_subclass("a/b", "c/d"));
+    let chain = MimeDatabase::from_parts(
+        GlobDatabase::new(),
+        MagicDatabase::new(),
+        &[("text/x-c++src", "text/x-csrc"), ("text/x-csrc", "text/plain"), ("a/b", "c/d"), ("c/d", "a/b")],
+    );
+    assert_eq!(chain.parents("text/x-c++src"), vec!["text/x-csrc"]);
+    assert_eq!(chain.ancestors("text/x-c++src"), vec!["text/x-csrc", "text/plain"]);
+    assert_eq!(chain.ancestors("text/x-log"), vec!["text/plain"]);
+    assert_eq!(chain.ancestors("a/b"), vec!["c/d"]);
+    assert!(chain.ancestors("image/png").is_empty());
+    assert_eq!(parts.mime_type_for_data(b"text"), "text/plain");
+
+    fs::remove_dir_all(root).ok();
+}
@@ -0,0 +1,90 @@
+use freedesktop_mime::{MimeDatabase, MimePackages, MimeTypeInfo};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const NOTES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="application/x-example-notes">
+    <comment>Example notes</comment>
+    <comment xml:lang="de">Beispielnotizen</comment>
+    <generic-icon name="text-x-generic"/>
+    <sub-class-of type="text/plain"/>
+    <glob pattern="*.notes"/>
+    <glob pattern="NOTES" weight="80" case-sensitive="true"/>
+    <alias type="application/x-notes"/>
+  </mime-type>
+</mime-info>
+"#;
+
+#[test]
+fn test_install_and_uninstall_packages() {
+    let root = Path::new("/tmp/test_mime_packages");
+    fs::remove_dir_all(root).ok();
+    let dir = root.join("mime");
+    let packages = MimePackages::in_dir(&dir).update_tool(None);
+    assert!(packages.installed().is_empty());
+
+    let source = root.join("example-notes.xml");
+    fs::create_dir_all(root).unwrap();
+    fs::write(&source, NOTES_XML).unwrap();
+    // A cache from an earlier update would hide the regenerated files
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("mime.cache"), "stale").unwrap();
+
+    let installed = packages.install(&source).unwrap();
+    assert_eq!(installed, dir.join("packages/example-notes.xml"));
+    assert_eq!(packages.installed(), vec![installed]);
+    assert!(!dir.join("mime.cache").exists());
+
+    let database = MimeDatabase::load_from(std::slice::from_ref(&dir));
+    assert_eq!(database.mime_type_for_filename("todo.NOTES"), Some("application/x-example-notes"));
+    assert_eq!(database.mime_type_for_filename("NOTES"), Some("application/x-example-notes"));
+    assert_eq!(database.mime_type_for_filename("notes"), None);
+    assert_eq!(database.unalias("application/x-notes"), "application/x-example-notes");
+    assert!(database.is_subclass("application/x-example-notes", "text/plain"));
+
+    let info = MimeTypeInfo::load_from(std::slice::from_ref(&dir), "application/x-notes").unwrap();
+    assert_eq!(info.comment(Some("de")), Some("Beispielnotizen"));
+    assert_eq!(info.generic_icon(), "text-x-generic");
+    assert_eq!(info.globs().len(), 2);
+    assert!(dir.join("application/x-example-notes.xml").is_file());
+
+    let invalid = |result: io::Result<_>| result.map(|_| ()).unwrap_err().kind();
+    assert_eq!(invalid(packages.install_xml("notes.xml", NOTES_XML)), io::ErrorKind::InvalidInput);
+    assert_eq!(invalid(packages.install_xml("example-notes.txt", NOTES_XML)), io::ErrorKind::InvalidInput);
+    assert_eq!(invalid(packages.install_xml("../example-notes.xml", NOTES_XML)), io::ErrorKind::InvalidInput);
+    assert_eq!(invalid(packages.install_xml("example-empty.xml", "<mime-info/>")), io::ErrorKind::InvalidData);
+    assert_eq!(packages.installed().len(), 1);
+
+    assert!(packages.uninstall("example-notes.xml").unwrap());
+    assert!(!packages.uninstall("example-notes.xml").unwrap());
+    assert!(packages.installed().is_empty());
+    assert!(!dir.join("application/x-example-notes.xml").exists());
+    let database = MimeDatabase::load_from(std::slice::from_ref(&dir));
+    assert_eq!(database.mime_type_for_filename("todo.notes"), None);
+
+    fs::remove_dir_all(root).ok();
+}
+
+#[test]
+fn test_later_package_deletes_globs() {
+    let root = Path::new("/tmp/test_mime_packages_deleteall");
+    fs::remove_dir_all(root).ok();
+    let dir = root.join("mime");
+    let packages = MimePackages::in_dir(&dir).update_tool(None);
+    packages.install_xml("example-notes.xml", NOTES_XML).unwrap();
+    packages
+        .install_xml(
+            "example-override.xml",
+            r#"<mime-info><mime-type type="application/x-example-notes"><glob-deleteall/><glob pattern="*.note"/></mime-type></mime-info>"#,
+        )
+        .unwrap();
+
+    let database = MimeDatabase::load_from(std::slice::from_ref(&dir));
+    assert_eq!(database.mime_type_for_filename("todo.note"), Some("application/x-example-notes"));
+    assert_eq!(database.mime_type_for_filename("todo.notes"), None);
+    assert_eq!(database.mime_type_for_filename("NOTES"), None);
+
+    fs::remove_dir_all(root).ok();
+}
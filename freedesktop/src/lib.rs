@@ -8,7 +8,10 @@
 //! 
 //! - **`core`** (default) - XDG base directories and desktop environment detection
 //! - **`apps`** (default) - Desktop Entry parsing and application execution  
+//! - **`mime`** - File type detection with the shared-mime-info database (enables `core`)
 //! - **`cli`** - Command-line utilities (enables `apps`)
+//! - **`dbus`** - XDG desktop portals and other D-Bus services (enables `core`)
+//! - **`rayon`** - Parallel application discovery (enables `apps`)
 //! 
 //! ## Quick Start
 //! 
@@ -48,6 +51,17 @@
 //! # }
 //! ```
 //! 
+//! ### Opening Files and URLs
+//! 
+//! ```no_run
+//! # #[cfg(feature = "apps")]
+//! # {
+//! // Like xdg-open: the preferred application for the file's type or the URL's scheme
+//! freedesktop::open("report.pdf").unwrap();
+//! freedesktop::open("https://freedesktop.org").unwrap();
+//! # }
+//! ```
+//! 
 //! ## Feature Usage
 //! 
 //! ```toml
@@ -71,4 +85,9 @@ pub use freedesktop_core::*;
 // Re-export apps functionality
 #[cfg(feature = "apps")]
 #[cfg_attr(docsrs, doc(cfg(feature = "apps")))]
-pub use freedesktop_apps::*;
\ No newline at end of file
+pub use freedesktop_apps::*;
+
+// Re-export MIME functionality
+#[cfg(feature = "mime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mime")))]
+pub use freedesktop_mime::*;
\ No newline at end of file